@@ -1,11 +1,26 @@
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
 
 use bhtt::Histogram;
+use utilities::Dataset;
 
 const HISTOGRAM_SIZES: [usize; 6] = [8, 16, 32, 64, 128, 256];
 
+const QUANTILES: [f64; 7] = [0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99];
+
+/// Returns named datasets of 10000 values: real-world ping times and a synthetic dataset
+/// spread uniformly over [0; 1000).
+fn datasets() -> Vec<(&'static str, Dataset)> {
+    let pings = Dataset::from_file("utilities/testdata/pings.txt").unwrap();
+    // a low-discrepancy sequence based on the golden ratio
+    let uniform = (0..10_000)
+        .map(|i| (i as f64 * 0.618_033_988_749_895).fract() * 1000.0)
+        .collect();
+
+    vec![("pings", pings), ("uniform", Dataset::from_values(uniform))]
+}
+
 fn insert(c: &mut Criterion) {
-    let dataset = utilities::Dataset::from_file("utilities/testdata/pings.txt").unwrap();
+    let dataset = Dataset::from_file("utilities/testdata/pings.txt").unwrap();
 
     let mut group = c.benchmark_group("update_histogram_of_size_X_10000_times");
     for size in HISTOGRAM_SIZES.iter() {
@@ -21,8 +36,23 @@ fn insert(c: &mut Criterion) {
     group.finish();
 }
 
+fn insert_many(c: &mut Criterion) {
+    let dataset = Dataset::from_file("utilities/testdata/pings.txt").unwrap();
+
+    let mut group = c.benchmark_group("bulk_update_histogram_of_size_X_with_10000_values");
+    for size in HISTOGRAM_SIZES.iter() {
+        group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, &size| {
+            b.iter(|| {
+                let mut h = Histogram::new(size);
+                h.insert_many(black_box(dataset.values()).iter().copied());
+            });
+        });
+    }
+    group.finish();
+}
+
 fn from_iter(c: &mut Criterion) {
-    let dataset = utilities::Dataset::from_file("utilities/testdata/pings.txt").unwrap();
+    let dataset = Dataset::from_file("utilities/testdata/pings.txt").unwrap();
 
     let mut group = c.benchmark_group("create_histogram_of_size_X_from_10000_values");
     for size in HISTOGRAM_SIZES.iter() {
@@ -33,5 +63,84 @@ fn from_iter(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, insert, from_iter);
+fn quantile(c: &mut Criterion) {
+    let mut group = c.benchmark_group("query_7_quantiles_of_histogram_of_size_X");
+    for (name, dataset) in datasets() {
+        let values = dataset.values();
+        for size in HISTOGRAM_SIZES.iter() {
+            let h = Histogram::from_iter(*size, values);
+            group.bench_with_input(BenchmarkId::new(name, size), &h, |b, h| {
+                b.iter(|| {
+                    for q in QUANTILES {
+                        black_box(h.quantile(black_box(q)));
+                    }
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+fn count_less_than_or_equal_to(c: &mut Criterion) {
+    let mut group = c.benchmark_group("query_100_counts_of_histogram_of_size_X");
+    for (name, dataset) in datasets() {
+        let values = dataset.values();
+        // values spread evenly over the range of the dataset
+        let (min, max) = values
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &v| {
+                (min.min(v), max.max(v))
+            });
+        let points: Vec<f64> = (0..100)
+            .map(|i| min + (max - min) * i as f64 / 99.0)
+            .collect();
+
+        for size in HISTOGRAM_SIZES.iter() {
+            let h = Histogram::from_iter(*size, values);
+            group.bench_with_input(BenchmarkId::new(name, size), &h, |b, h| {
+                b.iter(|| {
+                    for &value in &points {
+                        black_box(h.count_less_than_or_equal_to(black_box(value)));
+                    }
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+fn merge(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merge_two_histograms_of_size_X");
+    for (name, dataset) in datasets() {
+        let values = dataset.values();
+        let (left, right) = values.split_at(values.len() / 2);
+        for size in HISTOGRAM_SIZES.iter() {
+            let (left, right) = (
+                Histogram::from_iter(*size, left),
+                Histogram::from_iter(*size, right),
+            );
+            group.bench_with_input(BenchmarkId::new(name, size), &right, |b, right| {
+                b.iter_batched(
+                    || left.clone(),
+                    |mut h| {
+                        h.merge(black_box(right));
+                        h
+                    },
+                    BatchSize::SmallInput,
+                );
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    insert,
+    insert_many,
+    from_iter,
+    quantile,
+    count_less_than_or_equal_to,
+    merge
+);
 criterion_main!(benches);
@@ -0,0 +1,29 @@
+#![no_main]
+
+use bhtt::Histogram;
+use libfuzzer_sys::fuzz_target;
+
+// Decode arbitrary bytes: the decoder must either reject them or return a histogram, which
+// is encoded and decoded again without changes. The size of a decoded histogram is untrusted,
+// so the operations deriving new histograms from it must not try to allocate that many bins.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(h) = Histogram::from_bytes(data) {
+        assert!(h.bins().len() <= h.size());
+
+        let decoded = Histogram::from_bytes(&h.to_bytes()).unwrap();
+        assert_eq!(decoded, h);
+
+        let (left, _) = h.split_at(0.0);
+        let _ = h.subtract(&left);
+        let _ = h.restrict(-1.0, 1.0);
+        let _: Histogram = [h.clone(), Histogram::new(5)].iter().sum();
+        let _ = Histogram::merge_all(5, [&h, &h]);
+
+        let mut other = Histogram::new(5);
+        other += &h;
+
+        let mut h = h;
+        h.scale_counts(0.5);
+        h.decay(0.5);
+    }
+});
@@ -0,0 +1,23 @@
+#![no_main]
+
+use bhtt::Histogram;
+use libfuzzer_sys::fuzz_target;
+
+// Insert arbitrary values (including NaN, infinities and extremely large or small values)
+// into a histogram of an arbitrary size.
+fuzz_target!(|input: (u8, Vec<f64>)| {
+    let (size, values) = input;
+    let mut h = Histogram::new(usize::from(size).max(1));
+
+    let mut count = 0;
+    for value in values {
+        if h.try_insert(value).is_ok() {
+            count += 1;
+        }
+    }
+
+    assert_eq!(h.count(), count);
+    assert!(h.bins().len() <= h.size());
+    assert!(h.bins().windows(2).all(|w| w[0].value() <= w[1].value()));
+    assert_eq!(h.bins().iter().map(|b| b.count()).sum::<u64>(), count);
+});
@@ -0,0 +1,26 @@
+#![no_main]
+
+use bhtt::Histogram;
+use libfuzzer_sys::fuzz_target;
+
+// Merge arbitrary valid histograms in both orders and into themselves.
+fuzz_target!(|input: (Histogram, Histogram)| {
+    let (a, b) = input;
+
+    let mut ab = a.clone();
+    ab.merge(&b);
+    let mut ba = b.clone();
+    ba.merge(&a);
+    let mut aa = a.clone();
+    aa.merge(&a);
+
+    assert_eq!(ab.count(), a.count() + b.count());
+    assert_eq!(ab.count(), ba.count());
+    assert_eq!(ab.min(), ba.min());
+    assert_eq!(ab.max(), ba.max());
+    assert_eq!(aa.count(), 2 * a.count());
+    for h in [&ab, &ba, &aa] {
+        assert!(h.bins().len() <= h.size());
+        assert!(h.bins().windows(2).all(|w| w[0].value() <= w[1].value()));
+    }
+});
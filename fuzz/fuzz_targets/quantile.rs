@@ -0,0 +1,25 @@
+#![no_main]
+
+use bhtt::Histogram;
+use libfuzzer_sys::fuzz_target;
+
+// Query an arbitrary valid histogram for quantiles and ranks of arbitrary values.
+fuzz_target!(|input: (Histogram, Vec<f64>)| {
+    let (h, values) = input;
+    let (Some(min_value), Some(max_value)) = (h.min(), h.max()) else {
+        assert_eq!(h.quantile(0.5), None);
+        return;
+    };
+
+    for value in values {
+        if (0.0..=1.0).contains(&value) {
+            let quantile = h.quantile(value).unwrap();
+            assert!(quantile >= min_value && quantile <= max_value);
+        }
+        if !value.is_nan() {
+            let rank = h.rank(value).unwrap();
+            assert!((0.0..=1.0).contains(&rank));
+            assert!(h.count_less_than_or_equal_to(value) <= h.count());
+        }
+    }
+});
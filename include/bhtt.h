@@ -0,0 +1,129 @@
+#ifndef BHTT_H
+#define BHTT_H
+
+/* This file is generated by cbindgen. Do not edit it manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The status returned by the functions of the C API.
+//
+// This type is only available when the `ffi` feature is enabled.
+typedef enum BhttStatus {
+  // The call succeeded.
+  BHTT_STATUS_OK = 0,
+  // A required pointer is NULL.
+  BHTT_STATUS_NULL_POINTER = 1,
+  // A value is NaN or infinite, a count is 0, a quantile is not in [0.0; 1.0], or
+  // histograms have different duration units.
+  BHTT_STATUS_INVALID_ARGUMENT = 2,
+  // The histogram is empty, so there is no result.
+  BHTT_STATUS_EMPTY = 3,
+  // The buffer is too small to fit the serialized histogram.
+  BHTT_STATUS_BUFFER_TOO_SMALL = 4,
+  // The call panicked, e.g. because of a bug. The histogram may be left in an
+  // unspecified (but valid) state.
+  BHTT_STATUS_PANIC = 5,
+} BhttStatus;
+
+// An opaque handle to a histogram, which is exposed to C (and C++) code, so that it can
+// use the same histograms as Rust code. The functions of the C API are declared in
+// `include/bhtt.h`, which is generated with cbindgen
+// (`cbindgen --config cbindgen.toml --output include/bhtt.h`).
+//
+// Handles are created with `bhtt_histogram_new` or `bhtt_histogram_from_bytes`, and must be
+// freed with `bhtt_histogram_free`. A handle must not be used from multiple threads
+// concurrently.
+//
+// Panics never unwind into the calling code: functions return `Panic` (or NULL) instead.
+//
+// This type is only available when the `ffi` feature is enabled.
+typedef struct BhttHistogram BhttHistogram;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Create a new empty histogram with (up to) the given number of bins.
+//
+// Returns NULL, if the size is 0, or if it is too large to be allocated.
+struct BhttHistogram *bhtt_histogram_new(size_t size);
+
+// Free a histogram. Freeing NULL is a no-op.
+//
+// # Safety
+//
+// The handle must be NULL or returned by this API, and must not be used after this call.
+void bhtt_histogram_free(struct BhttHistogram *h);
+
+// Insert a value, which is repeated `count` times.
+//
+// Returns `InvalidArgument`, if the value is NaN or infinite, or the count is 0.
+//
+// # Safety
+//
+// The handle must be NULL or a valid histogram.
+enum BhttStatus bhtt_histogram_insert(struct BhttHistogram *h, double value, uint64_t count);
+
+// Merge the histogram `src` into the histogram `dst`. Both handles may point to the same
+// histogram.
+//
+// Returns `InvalidArgument`, if the histograms have different duration units.
+//
+// # Safety
+//
+// Both handles must be NULL or valid histograms.
+enum BhttStatus bhtt_histogram_merge(struct BhttHistogram *dst, const struct BhttHistogram *src);
+
+// Estimate the `q`'th quantile and store it into `out`.
+//
+// Returns `InvalidArgument`, if `q` is not in the range [0.0; 1.0], or `Empty`, if
+// the histogram is empty.
+//
+// # Safety
+//
+// The handle must be NULL or a valid histogram, and `out` must be NULL or point to
+// a writable double.
+enum BhttStatus bhtt_histogram_quantile(const struct BhttHistogram *h, double q, double *out);
+
+// Returns the number of values in the histogram, or 0, if the handle is NULL.
+//
+// # Safety
+//
+// The handle must be NULL or a valid histogram.
+uint64_t bhtt_histogram_count(const struct BhttHistogram *h);
+
+// Serialize the histogram in the binary format into a buffer of `capacity` bytes, and
+// store the length of the serialized histogram into `len`.
+//
+// Returns `BufferTooSmall`, if the serialized histogram does not fit into the buffer, in
+// which case only `len` is set, so that the call can be repeated with a larger buffer.
+// `buf` may be NULL, if `capacity` is 0.
+//
+// # Safety
+//
+// The handle must be NULL or a valid histogram, `buf` must be NULL or point to
+// `capacity` writable bytes, and `len` must be NULL or point to a writable size_t.
+enum BhttStatus bhtt_histogram_to_bytes(const struct BhttHistogram *h,
+                                        uint8_t *buf,
+                                        size_t capacity,
+                                        size_t *len);
+
+// Deserialize a histogram from `len` bytes in the binary format (e.g. produced by
+// `bhtt_histogram_to_bytes` or `Histogram::to_bytes` in Rust).
+//
+// Returns NULL, if the bytes are not a valid histogram.
+//
+// # Safety
+//
+// `buf` must be NULL or point to `len` readable bytes.
+struct BhttHistogram *bhtt_histogram_from_bytes(const uint8_t *buf, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BHTT_H */
@@ -0,0 +1,206 @@
+use rkyv::rancor::{Fallible, Source};
+use rkyv::{Archive, Deserialize, Place, Serialize};
+
+use crate::bin::Bin;
+use crate::data::{ArchivedBinData, ArchivedHistogram, HistogramData, HistogramDataResolver};
+use crate::histogram::{uniform_quantile, Histogram};
+
+/// Histograms are archived as [`ArchivedHistogram`], which can be queried in place.
+impl Archive for Histogram {
+    type Archived = ArchivedHistogram;
+    type Resolver = HistogramDataResolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        HistogramData::from(self).resolve(resolver, out);
+    }
+}
+
+impl<S: Fallible + ?Sized> Serialize<S> for Histogram
+where
+    HistogramData: Serialize<S>,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        HistogramData::from(self).serialize(serializer)
+    }
+}
+
+impl<D: Fallible + ?Sized> Deserialize<Histogram, D> for ArchivedHistogram
+where
+    ArchivedHistogram: Deserialize<HistogramData, D>,
+    D::Error: Source,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<Histogram, D::Error> {
+        let data: HistogramData = Deserialize::deserialize(self, deserializer)?;
+
+        Histogram::try_from(data).map_err(D::Error::new)
+    }
+}
+
+impl From<ArchivedBinData> for Bin {
+    fn from(bin: ArchivedBinData) -> Self {
+        Bin::new(bin.value.to_native(), bin.count.to_native())
+    }
+}
+
+impl ArchivedHistogram {
+    /// Returns the size of the archived histogram.
+    ///
+    /// This is only available when the `rkyv` feature is enabled.
+    ///
+    /// ```
+    /// use bhtt::{ArchivedHistogram, Histogram};
+    ///
+    /// let h = Histogram::from_iter(5, (1..=100).map(|v| v as f64));
+    /// let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&h).unwrap();
+    ///
+    /// let archived = rkyv::access::<ArchivedHistogram, rkyv::rancor::Error>(&bytes).unwrap();
+    /// assert_eq!(archived.size(), 5);
+    /// assert_eq!(archived.count(), 100);
+    /// assert_eq!(archived.quantile(0.5), h.quantile(0.5));
+    ///
+    /// let h2: Histogram = rkyv::deserialize::<_, rkyv::rancor::Error>(archived).unwrap();
+    /// assert_eq!(h2.bins(), h.bins());
+    /// ```
+    pub fn size(&self) -> usize {
+        self.size.to_native() as usize
+    }
+
+    /// Returns the bins of the archived histogram in the ascending order.
+    pub fn bins(&self) -> impl Iterator<Item = Bin> + '_ {
+        self.bins.iter().map(|&bin| bin.into())
+    }
+
+    /// Returns the number of values in the archived histogram.
+    pub fn count(&self) -> u64 {
+        self.count.to_native()
+    }
+
+    /// Returns the minimum value in the archived histogram or `None` if it is empty.
+    pub fn min(&self) -> Option<f64> {
+        self.min_value.as_ref().map(|value| value.to_native())
+    }
+
+    /// Returns the maximum value in the archived histogram or `None` if it is empty.
+    pub fn max(&self) -> Option<f64> {
+        self.max_value.as_ref().map(|value| value.to_native())
+    }
+
+    /// Returns the sum of values in the archived histogram.
+    pub fn sum(&self) -> f64 {
+        self.sum.to_native()
+    }
+
+    /// Returns the arithmetic mean of values in the archived histogram or `None` if it is
+    /// empty.
+    pub fn mean(&self) -> Option<f64> {
+        match self.count() {
+            0 => None,
+            count => Some(self.sum() / count as f64),
+        }
+    }
+
+    /// Returns an approximated value of the `q`'th quantile of the values or `None`
+    /// if the archived histogram is empty (see [`Histogram::quantile`]).
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        assert!(
+            (0.0..=1.0).contains(&q),
+            "q must be in the range [0.0; 1.0]"
+        );
+
+        match (self.min(), self.max()) {
+            (Some(min_value), Some(max_value)) => Some(uniform_quantile(
+                self.bins.as_slice(),
+                self.count(),
+                min_value,
+                max_value,
+                q,
+                None,
+            )),
+            // histogram is empty
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rkyv::rancor::Error;
+
+    use super::*;
+    use crate::policy::{InfinityPolicy, NanPolicy};
+
+    #[test]
+    fn round_trip() {
+        let mut h = Histogram::new(16);
+        h.set_nan_policy(NanPolicy::Count);
+        h.set_infinity_policy(InfinityPolicy::Count);
+        for v in 0..1000 {
+            h.insert((v as f64).sqrt() * 1.1);
+        }
+        for value in [f64::NAN, f64::NEG_INFINITY, f64::INFINITY, f64::INFINITY] {
+            h.try_insert(value).unwrap();
+        }
+
+        let bytes = rkyv::to_bytes::<Error>(&h).unwrap();
+        let archived = rkyv::access::<ArchivedHistogram, Error>(&bytes).unwrap();
+        assert_eq!(archived.size(), h.size());
+        assert_eq!(archived.count(), h.count());
+        assert_eq!(archived.min(), h.min());
+        assert_eq!(archived.max(), h.max());
+        assert_eq!(archived.sum(), h.sum());
+        assert_eq!(archived.mean(), h.mean());
+        assert_eq!(archived.bins().collect::<Vec<_>>(), h.bins());
+        for q in [0.0, 0.1, 0.25, 0.5, 0.75, 0.9, 1.0] {
+            assert_eq!(archived.quantile(q), h.quantile(q));
+        }
+
+        let h2: Histogram = rkyv::deserialize::<_, Error>(archived).unwrap();
+        assert_eq!(h2.size(), h.size());
+        assert_eq!(h2.count(), h.count());
+        assert_eq!(h2.bins(), h.bins());
+        assert_eq!(h2.min(), h.min());
+        assert_eq!(h2.max(), h.max());
+        assert_eq!(h2.sum(), h.sum());
+        assert_eq!(h2.nan_count(), 1);
+        assert_eq!(h2.underflow_count(), 1);
+        assert_eq!(h2.overflow_count(), 2);
+    }
+
+    #[test]
+    fn empty() {
+        let h = Histogram::new(5);
+        let bytes = rkyv::to_bytes::<Error>(&h).unwrap();
+        let archived = rkyv::access::<ArchivedHistogram, Error>(&bytes).unwrap();
+
+        assert_eq!(archived.size(), 5);
+        assert_eq!(archived.count(), 0);
+        assert_eq!(archived.min(), None);
+        assert_eq!(archived.max(), None);
+        assert_eq!(archived.mean(), None);
+        assert_eq!(archived.quantile(0.5), None);
+        assert_eq!(archived.bins().count(), 0);
+    }
+
+    #[test]
+    fn nested() {
+        let histograms = vec![
+            Histogram::from_iter(5, [1.0, 2.0]),
+            Histogram::from_iter(5, [3.0]),
+        ];
+        let bytes = rkyv::to_bytes::<Error>(&histograms).unwrap();
+        let archived =
+            rkyv::access::<rkyv::vec::ArchivedVec<ArchivedHistogram>, Error>(&bytes).unwrap();
+
+        assert_eq!(archived.len(), 2);
+        assert_eq!(archived[0].count(), 2);
+        assert_eq!(archived[1].quantile(0.5), Some(3.0));
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        let h = Histogram::from_iter(5, [1.0, 2.0]);
+        let bytes = rkyv::to_bytes::<Error>(&h).unwrap();
+
+        assert!(rkyv::access::<ArchivedHistogram, Error>(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
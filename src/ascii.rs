@@ -0,0 +1,119 @@
+use crate::histogram::Histogram;
+
+impl Histogram {
+    /// Returns the distribution of values drawn as ASCII art, e.g. to eyeball it in a
+    /// terminal or in the output of a failed test. The range [min; max] of values is split
+    /// into `width` columns of equal width, and the estimated number of values in every
+    /// column (see [`to_buckets`](Histogram::to_buckets)) is drawn as a vertical bar of `#`
+    /// characters, whose height is proportional to the count (the highest bar is `height`
+    /// rows tall). Bars are followed by the value axis labeled with the minimum and maximum
+    /// values. Trailing whitespace is trimmed from every line.
+    ///
+    /// An empty string is returned, if the histogram is empty. Both `width` and `height`
+    /// must be greater than 0, or the function will panic.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(5, [(1.0, 1), (2.0, 4), (3.0, 1)]);
+    /// assert_eq!(
+    ///     h.render_ascii(4, 4),
+    ///     " ##\n ##\n####\n####\n----\n1  3"
+    /// );
+    /// ```
+    pub fn render_ascii(&self, width: usize, height: usize) -> String {
+        assert!(width > 0, "width must be greater than 0");
+        assert!(height > 0, "height must be greater than 0");
+
+        let (min_value, max_value) = match (self.min(), self.max()) {
+            (Some(min_value), Some(max_value)) => (min_value, max_value),
+            // histogram is empty
+            _ => return String::new(),
+        };
+
+        // the last boundary is the maximum value, so the extra bucket of greater values
+        // is always empty
+        let boundaries: Vec<f64> = (1..width)
+            .map(|i| min_value + (max_value - min_value) * (i as f64 / width as f64))
+            .chain(std::iter::once(max_value))
+            .collect();
+        let counts = &self.to_buckets(&boundaries)[..width];
+        let highest = counts.iter().copied().max().unwrap_or(0).max(1);
+
+        // non-empty columns are at least one row tall, so that they are not lost
+        let heights: Vec<usize> = counts
+            .iter()
+            .map(|&count| match count {
+                0 => 0,
+                count => ((count as f64 / highest as f64 * height as f64).round() as usize).max(1),
+            })
+            .collect();
+
+        let mut lines: Vec<String> = (1..=height)
+            .rev()
+            .map(|row| {
+                let line: String = heights
+                    .iter()
+                    .map(|&h| if h >= row { '#' } else { ' ' })
+                    .collect();
+                line.trim_end().to_string()
+            })
+            .collect();
+        lines.push("-".repeat(width));
+
+        let (min_label, max_label) = (format!("{}", min_value), format!("{}", max_value));
+        let padding = width
+            .saturating_sub(min_label.len() + max_label.len())
+            .max(1);
+        lines.push(format!("{}{}{}", min_label, " ".repeat(padding), max_label));
+
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_ascii() {
+        // squares of uniformly distributed values are skewed towards 0
+        let h = Histogram::from_iter(8, (0..1000).map(|v| (v as f64 / 100.0).powi(2)));
+        assert_eq!(
+            h.render_ascii(20, 5),
+            [
+                "#",
+                "#",
+                "##",
+                "####",
+                "####################",
+                "--------------------",
+                "0            99.8001",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(Histogram::new(5).render_ascii(10, 5), "");
+    }
+
+    #[test]
+    fn single_value() {
+        let h = Histogram::from_iter(5, [(2.5, 3)]);
+        assert_eq!(h.render_ascii(4, 2), "#\n#\n----\n2.5 2.5");
+    }
+
+    #[test]
+    #[should_panic(expected = "width must be greater than 0")]
+    fn zero_width() {
+        Histogram::from_iter(5, [1.0]).render_ascii(0, 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "height must be greater than 0")]
+    fn zero_height() {
+        Histogram::from_iter(5, [1.0]).render_ascii(5, 0);
+    }
+}
@@ -1,4 +1,6 @@
-use ordered_float::NotNan;
+use std::cmp::Ordering;
+
+use crate::error::Error;
 
 /// A histogram bin stored as a `(value, count)` pair.
 ///
@@ -32,10 +34,13 @@ use ordered_float::NotNan;
 /// assert!(reference < gt_by_count);
 /// assert_eq!(reference, equal);
 /// assert_eq!(equal, reference);
+///
+/// // positive and negative zeros are the same value
+/// assert_eq!(Bin::new(-0.0, 1), Bin::new(0.0, 1));
 /// ```
-#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord)]
+#[derive(Debug, Copy, Clone)]
 pub struct Bin {
-    value: NotNan<f64>,
+    value: f64,
     count: u64,
 }
 
@@ -55,27 +60,61 @@ impl Bin {
     /// assert_eq!(b.count(), 84);
     /// ```
     pub fn new(value: f64, count: u64) -> Bin {
-        assert!(!value.is_nan(), "value must not be NaN");
-        assert!(value.is_finite(), "value must be finite");
-        assert!(count > 0, "count must be greater than zero");
+        Bin::try_new(value, count).unwrap_or_else(|err| panic!("{}", err))
+    }
 
-        Bin {
-            value: NotNan::new(value).unwrap(),
-            count,
+    /// Returns a new Bin with the given value and count, or an error if the value is not
+    /// finite or the count is zero.
+    ///
+    /// Values can't be converted to bins via `TryFrom`, as `From` is already implemented,
+    /// and the standard library derives an infallible `TryFrom` from it.
+    ///
+    /// ```
+    /// use bhtt::{Bin, Error};
+    ///
+    /// assert_eq!(Bin::try_new(42.0, 84), Ok(Bin::new(42.0, 84)));
+    /// assert_eq!(Bin::try_new(f64::NAN, 1), Err(Error::NanValue));
+    /// assert_eq!(Bin::try_new(f64::INFINITY, 1), Err(Error::InfiniteValue));
+    /// assert_eq!(Bin::try_new(42.0, 0), Err(Error::ZeroCount));
+    /// ```
+    pub fn try_new(value: f64, count: u64) -> Result<Bin, Error> {
+        if value.is_nan() {
+            Err(Error::NanValue)
+        } else if value.is_infinite() {
+            Err(Error::InfiniteValue)
+        } else if count == 0 {
+            Err(Error::ZeroCount)
+        } else {
+            Ok(Bin {
+                value: normalize(value),
+                count,
+            })
         }
     }
 
     // Returns an empty Bin. This is only used internally in the algorithms for computing quantiles
     // and counts which operate on virtual bins.
     pub(crate) fn empty(value: f64) -> Bin {
+        debug_assert!(!value.is_nan());
+
         Bin {
-            value: NotNan::new(value).unwrap(),
+            value: normalize(value),
             count: 0,
         }
     }
 
     /// Returns a new Bin that is an approximation of two bins merged together.
     ///
+    /// The value is the weighted average of the values of the bins, which is computed by
+    /// moving the value of the heavier bin towards the value of the lighter one, rather than
+    /// by summing the products of values and counts. This way the result is always between
+    /// the two values, doesn't overflow for large values, and doesn't drift, when a bin with
+    /// a huge count (e.g. close to 2^53) repeatedly absorbs bins with small ones.
+    ///
+    /// Panics, if the sum of the counts overflows `u64` (in all builds). See
+    /// [`checked_merge`](Bin::checked_merge) and [`saturating_merge`](Bin::saturating_merge)
+    /// for alternatives.
+    ///
     /// ```
     /// use bhtt::Bin;
     ///
@@ -87,11 +126,61 @@ impl Bin {
     /// assert_eq!(b3.count(), 126);
     /// ```
     pub fn merge(left: &Bin, right: &Bin) -> Bin {
-        let count = left.count() + right.count();
-        let value = (left.value() * left.count() as f64 + right.value() * right.count() as f64)
-            / count as f64;
+        Bin::checked_merge(left, right).expect("count overflow")
+    }
 
-        Bin::new(value, count)
+    /// Returns a new Bin that is an approximation of two bins merged together (see
+    /// [`merge`](Bin::merge)), or `None` if the sum of the counts overflows `u64`.
+    ///
+    /// ```
+    /// use bhtt::Bin;
+    ///
+    /// let b1 = Bin::new(1.0, u64::MAX);
+    /// let b2 = Bin::new(2.0, 1);
+    /// assert_eq!(Bin::checked_merge(&b1, &b2), None);
+    /// ```
+    pub fn checked_merge(left: &Bin, right: &Bin) -> Option<Bin> {
+        let count = left.count().checked_add(right.count())?;
+
+        Some(Bin::merge_with_count(left, right, count))
+    }
+
+    /// Returns a new Bin that is an approximation of two bins merged together (see
+    /// [`merge`](Bin::merge)), whose count saturates at `u64::MAX`. The value is still the
+    /// weighted average of the two values.
+    ///
+    /// ```
+    /// use bhtt::Bin;
+    ///
+    /// let b1 = Bin::new(1.0, u64::MAX);
+    /// let b2 = Bin::new(2.0, 1);
+    /// assert_eq!(Bin::saturating_merge(&b1, &b2), Bin::new(1.0, u64::MAX));
+    /// ```
+    pub fn saturating_merge(left: &Bin, right: &Bin) -> Bin {
+        let count = left.count().saturating_add(right.count());
+
+        Bin::merge_with_count(left, right, count)
+    }
+
+    fn merge_with_count(left: &Bin, right: &Bin, count: u64) -> Bin {
+        let (heavy, light) = if left.count() >= right.count() {
+            (left, right)
+        } else {
+            (right, left)
+        };
+
+        // the weight of the lighter bin is at most 0.5, so the step is well-conditioned
+        let weight = light.count() as f64 / (heavy.count() as f64 + light.count() as f64);
+        let (from, to) = (heavy.value(), light.value());
+        let step = to - from;
+        let value = if step.is_finite() {
+            from + step * weight
+        } else {
+            // values of opposite signs, whose difference overflows
+            from * (1.0 - weight) + to * weight
+        };
+
+        Bin::new(value.clamp(from.min(to), from.max(to)), count)
     }
 
     /// Returns the value of the bin.
@@ -103,7 +192,7 @@ impl Bin {
     /// assert_eq!(b.value(), 42.0);
     /// ```
     pub fn value(&self) -> f64 {
-        self.value.into_inner()
+        self.value
     }
 
     /// Returns the count of the bin.
@@ -119,6 +208,61 @@ impl Bin {
     }
 }
 
+// values are never NaN and zeros are normalized, so the total order of floats agrees with
+// the IEEE 754 comparison of values, while bins can be compared without any conversions
+impl Ord for Bin {
+    fn cmp(&self, other: &Bin) -> Ordering {
+        self.value
+            .total_cmp(&other.value)
+            .then(self.count.cmp(&other.count))
+    }
+}
+
+impl PartialOrd for Bin {
+    fn partial_cmp(&self, other: &Bin) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Bin {
+    fn eq(&self, other: &Bin) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Bin {}
+
+/// Replace the negative zero with the positive one, which are equal, but are not the same
+/// value in the total order of floats.
+fn normalize(value: f64) -> f64 {
+    value + 0.0
+}
+
+/// A float ordered by [`f64::total_cmp`], which is used as a key for comparing the distances
+/// between bins (e.g. in `min_by_key()` or in a `BinaryHeap`). Distances are never NaN.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct TotalF64(pub(crate) f64);
+
+impl Ord for TotalF64 {
+    fn cmp(&self, other: &TotalF64) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl PartialOrd for TotalF64 {
+    fn partial_cmp(&self, other: &TotalF64) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for TotalF64 {
+    fn eq(&self, other: &TotalF64) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TotalF64 {}
+
 impl From<f32> for Bin {
     fn from(value: f32) -> Self {
         Bin::new(value as f64, 1)
@@ -129,6 +273,66 @@ impl From<f64> for Bin {
         Bin::new(value, 1)
     }
 }
+impl From<&f32> for Bin {
+    fn from(value: &f32) -> Self {
+        Bin::from(*value)
+    }
+}
+impl From<&f64> for Bin {
+    fn from(value: &f64) -> Self {
+        Bin::from(*value)
+    }
+}
+
+// integers are converted into bins with the count of 1, so that integer-valued data (e.g. byte
+// counts) can be inserted into histograms directly. 64-bit integers beyond 2^53 are rounded to
+// the nearest representable f64 value
+macro_rules! impl_from_integer {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Bin {
+                fn from(value: $t) -> Self {
+                    Bin::new(value as f64, 1)
+                }
+            }
+            impl From<&$t> for Bin {
+                fn from(value: &$t) -> Self {
+                    Bin::from(*value)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+/// Converts a `(value, count)` pair into a Bin, e.g. to load pre-aggregated data.
+///
+/// ```
+/// use bhtt::{Bin, Histogram};
+///
+/// assert_eq!(Bin::from((12.5, 40)), Bin::new(12.5, 40));
+///
+/// let mut h = Histogram::new(5);
+/// h.insert((12.5, 40));
+/// h.extend(vec![(1.0, 2), (3.0, 4)]);
+/// assert_eq!(h.count(), 46);
+/// ```
+impl From<(f64, u64)> for Bin {
+    fn from((value, count): (f64, u64)) -> Self {
+        Bin::new(value, count)
+    }
+}
+impl From<&(f64, u64)> for Bin {
+    fn from(pair: &(f64, u64)) -> Self {
+        Bin::from(*pair)
+    }
+}
+impl From<&Bin> for Bin {
+    fn from(bin: &Bin) -> Self {
+        *bin
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -150,22 +354,23 @@ mod tests {
     #[test]
     #[should_panic(expected = "value must not be NaN")]
     fn new_invalid_value_nan() {
-        Bin::new(std::f64::NAN, 84);
+        Bin::new(f64::NAN, 84);
     }
 
     #[test]
     #[should_panic(expected = "value must be finite")]
     fn new_invalid_value_positive_infinity() {
-        Bin::new(std::f64::INFINITY, 84);
+        Bin::new(f64::INFINITY, 84);
     }
 
     #[test]
     #[should_panic(expected = "value must be finite")]
     fn new_invalid_value_negative_infinity() {
-        Bin::new(std::f64::NEG_INFINITY, 84);
+        Bin::new(f64::NEG_INFINITY, 84);
     }
 
     #[test]
+    #[allow(clippy::nonminimal_bool)]
     fn ordering() {
         let reference = Bin::new(42.0, 84);
 
@@ -203,19 +408,99 @@ mod tests {
         assert_ne!(not_equal_by_count, reference);
     }
 
+    #[test]
+    fn total_ordering() {
+        let mut bins = [
+            Bin::new(1.0, 2),
+            Bin::new(-1.0, 1),
+            Bin::new(0.0, 3),
+            Bin::new(-0.0, 1),
+            Bin::new(f64::MAX, 1),
+            Bin::new(f64::MIN, 1),
+            Bin::new(1.0, 1),
+        ];
+        bins.sort();
+
+        let pairs: Vec<_> = bins.iter().map(|b| (b.value(), b.count())).collect();
+        assert_eq!(
+            pairs,
+            &[
+                (f64::MIN, 1),
+                (-1.0, 1),
+                (0.0, 1),
+                (0.0, 3),
+                (1.0, 1),
+                (1.0, 2),
+                (f64::MAX, 1)
+            ]
+        );
+        // the negative zero is normalized
+        assert!(bins[2].value().is_sign_positive());
+        assert_eq!(Bin::new(-0.0, 1).cmp(&Bin::new(0.0, 1)), Ordering::Equal);
+
+        assert!(TotalF64(0.5) < TotalF64(1.0));
+        assert_eq!(TotalF64(1.0), TotalF64(1.0));
+    }
+
     #[test]
     fn merge() {
         let left = Bin::new(42.0, 84);
         let right = Bin::new(84.0, 42);
-        let expected = Bin::new(
-            (42.0 * 84 as f64 + 84.0 * 42 as f64) / (84 + 42) as f64,
-            84 + 42,
-        );
+        let expected = Bin::new((42.0 * 84_f64 + 84.0 * 42_f64) / (84 + 42) as f64, 84 + 42);
 
         let actual = Bin::merge(&left, &right);
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn merge_stability() {
+        // the result is never outside of the merged values because of rounding
+        let left = Bin::new(-9.777197803337888e-292, 690707);
+        let right = Bin::new(-9.77719780333789e-292, 460552);
+        let merged = Bin::merge(&left, &right);
+        assert!(merged.value() >= right.value() && merged.value() <= left.value());
+        let merged = Bin::merge(&Bin::new(0.1, 3), &Bin::new(0.1, 7));
+        assert_eq!(merged.value(), 0.1);
+
+        // huge values don't overflow
+        let merged = Bin::merge(&Bin::new(f64::MAX, 1), &Bin::new(f64::MAX, 1));
+        assert_eq!(merged.value(), f64::MAX);
+        let merged = Bin::merge(&Bin::new(f64::MAX, 1), &Bin::new(-f64::MAX, 1));
+        assert_eq!(merged.value(), 0.0);
+
+        // absorbing many light bins doesn't make the value of a heavy bin drift
+        let mut heavy = Bin::new(1000.0, 1 << 53);
+        for _ in 0..10_000 {
+            heavy = Bin::merge(&heavy, &Bin::new(1000.0, 1));
+            heavy = Bin::merge(&Bin::new(1000.0 + 1e-9, 1), &heavy);
+        }
+        assert_eq!(heavy.value(), 1000.0);
+        assert_eq!(heavy.count(), (1 << 53) + 20_000);
+    }
+
+    #[test]
+    fn merge_overflow() {
+        let left = Bin::new(1.0, u64::MAX - 1);
+        let right = Bin::new(3.0, 1);
+        assert_eq!(
+            Bin::checked_merge(&left, &right).map(|b| b.count()),
+            Some(u64::MAX)
+        );
+        assert_eq!(Bin::saturating_merge(&left, &right).count(), u64::MAX);
+
+        let right = Bin::new(3.0, 2);
+        assert_eq!(Bin::checked_merge(&left, &right), None);
+        let merged = Bin::saturating_merge(&left, &right);
+        assert_eq!(merged.count(), u64::MAX);
+        assert!(merged.value() >= 1.0 && merged.value() < 3.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "count overflow")]
+    fn merge_overflow_panics() {
+        Bin::merge(&Bin::new(1.0, u64::MAX), &Bin::new(2.0, 1));
+    }
+
     #[test]
     #[should_panic(expected = "count must be greater than zero")]
     fn merge_invalid_count() {
@@ -236,15 +521,59 @@ mod tests {
         assert_eq!(b2.count(), 1);
     }
 
+    #[test]
+    fn from_integer() {
+        assert_eq!(Bin::from(42u8), Bin::new(42.0, 1));
+        assert_eq!(Bin::from(-42i16), Bin::new(-42.0, 1));
+        assert_eq!(Bin::from(&-42i32), Bin::new(-42.0, 1));
+        assert_eq!(Bin::from(u32::MAX), Bin::new(u32::MAX as f64, 1));
+        assert_eq!(Bin::from(i64::MIN), Bin::new(-(2f64.powi(63)), 1));
+        assert_eq!(Bin::from(&u64::MAX), Bin::new(2f64.powi(64), 1));
+        assert_eq!(Bin::from(7usize), Bin::new(7.0, 1));
+        assert_eq!(Bin::from(-7isize), Bin::new(-7.0, 1));
+    }
+
+    #[test]
+    fn from_pair() {
+        assert_eq!(Bin::from((42.0, 84)), Bin::new(42.0, 84));
+        assert_eq!(Bin::from(&(-7.5, 1)), Bin::new(-7.5, 1));
+        assert_eq!(Bin::from(&Bin::new(1.0, 2)), Bin::new(1.0, 2));
+        assert_eq!(Bin::from(&1.5f64), Bin::new(1.5, 1));
+        assert_eq!(Bin::from(&1.5f32), Bin::new(1.5, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "count must be greater than zero")]
+    fn from_pair_zero_count() {
+        let _ = Bin::from((42.0, 0));
+    }
+
     #[test]
     #[should_panic(expected = "value must not be NaN")]
     fn from_nan() {
-        let _ = Bin::from(std::f64::NAN);
+        let _ = Bin::from(f64::NAN);
     }
 
     #[test]
     #[should_panic(expected = "value must be finite")]
     fn from_infinite() {
-        let _ = Bin::from(std::f64::INFINITY);
+        let _ = Bin::from(f64::INFINITY);
+    }
+
+    #[test]
+    fn try_new() {
+        let cases = [
+            (42.0, 84, Ok(Bin::new(42.0, 84))),
+            (-7.5, 1, Ok(Bin::new(-7.5, 1))),
+            (f64::NAN, 1, Err(Error::NanValue)),
+            (f64::NAN, 0, Err(Error::NanValue)),
+            (f64::INFINITY, 1, Err(Error::InfiniteValue)),
+            (f64::NEG_INFINITY, 1, Err(Error::InfiniteValue)),
+            (42.0, 0, Err(Error::ZeroCount)),
+        ];
+
+        for (value, count, expected) in cases {
+            assert_eq!(Bin::try_new(value, count), expected);
+        }
     }
 }
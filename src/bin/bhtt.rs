@@ -0,0 +1,523 @@
+//! A command-line tool, which summarizes numbers read from a file (or stdin), one per line,
+//! with a histogram: it prints the count, the minimum and the maximum values, estimates
+//! of the requested quantiles, and the distribution of values drawn as ASCII art.
+//!
+//! In the watch mode, numbers are read from stdin continuously, and the estimates of
+//! quantiles are printed periodically, e.g. `my_app | bhtt --watch p50,p99`.
+//!
+//! This binary is only built when the `cli` feature is enabled.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bhtt::{Histogram, RotatingHistogram};
+
+const USAGE: &str = "\
+Usage: bhtt [OPTIONS] [FILE]
+       bhtt --watch LIST [OPTIONS]
+
+Summarize numbers read from FILE (or stdin, if FILE is - or missing), one per line.
+Empty lines and lines starting with # are skipped.
+
+In the watch mode, numbers are read from stdin continuously, and the estimates of
+the quantiles in LIST are printed every second (or as set by --interval or --lines),
+until stdin is closed. Invalid lines are reported and skipped.
+
+Options:
+  -s, --size N            number of bins of the histogram [default: 64]
+  -q, --quantiles LIST    comma-separated quantiles, e.g. p50,p99.9 or 0.5,0.999
+                          [default: p50,p90,p99]
+  -w, --width N           width of the chart in columns [default: 60]
+      --height N          height of the chart in rows (0 disables the chart) [default: 10]
+      --watch LIST        watch stdin and print the quantiles in LIST periodically
+      --interval SECS     print every SECS seconds in the watch mode [default: 1]
+      --lines N           print after every N numbers in the watch mode instead
+      --window SECS       only account for the numbers read in the last SECS seconds
+                          in the watch mode [default: all numbers]
+  -h, --help              print this help";
+
+/// The number of sub-windows of the window of the watch mode (see
+/// [`RotatingHistogram::sliding`]).
+const WINDOW_PRECISION: usize = 10;
+
+/// Parsed command-line options.
+#[derive(Debug, PartialEq)]
+struct Options {
+    size: usize,
+    quantiles: Vec<(String, f64)>,
+    width: usize,
+    height: usize,
+    path: Option<String>,
+    watch: bool,
+    interval: Duration,
+    lines: Option<usize>,
+    window: Option<Duration>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            size: 64,
+            quantiles: parse_quantiles("p50,p90,p99").unwrap(),
+            width: 60,
+            height: 10,
+            path: None,
+            watch: false,
+            interval: Duration::from_secs(1),
+            lines: None,
+            window: None,
+        }
+    }
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("bhtt: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if options.watch {
+        if let Err(err) = watch(read_lines(), &options, &mut io::stdout().lock()) {
+            eprintln!("bhtt: {}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let result = match options.path.as_deref() {
+        None | Some("-") => read_histogram(io::stdin().lock(), options.size),
+        Some(path) => File::open(path)
+            .map_err(|err| format!("{}: {}", path, err))
+            .and_then(|file| read_histogram(BufReader::new(file), options.size)),
+    };
+    match result {
+        Ok(h) => print!("{}", summary(&h, &options)),
+        Err(err) => {
+            eprintln!("bhtt: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Parse command-line arguments. Returns `None`, if the help was requested.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // both `--size 64` and `--size=64` are accepted
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value)),
+            _ => (arg.clone(), None),
+        };
+        let mut value = || {
+            inline_value
+                .map(str::to_string)
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("missing value of {}", flag))
+        };
+
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-s" | "--size" => options.size = parse_positive(&value()?, "size")?,
+            "-q" | "--quantiles" => options.quantiles = parse_quantiles(&value()?)?,
+            "-w" | "--width" => options.width = parse_positive(&value()?, "width")?,
+            "--watch" => {
+                options.quantiles = parse_quantiles(&value()?)?;
+                options.watch = true;
+            }
+            "--interval" => options.interval = parse_seconds(&value()?, "interval")?,
+            "--lines" => options.lines = Some(parse_positive(&value()?, "lines")?),
+            "--window" => options.window = Some(parse_seconds(&value()?, "window")?),
+            "--height" => {
+                let value = value()?;
+                options.height = value
+                    .parse()
+                    .map_err(|_| format!("invalid height: {}", value))?;
+            }
+            _ if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unknown option: {}", flag));
+            }
+            _ => match options.path {
+                None => options.path = Some(arg),
+                Some(_) => return Err(format!("unexpected argument: {}", arg)),
+            },
+        }
+    }
+
+    if options.watch && options.path.is_some() {
+        return Err("the watch mode only reads stdin".into());
+    }
+
+    Ok(Some(options))
+}
+
+/// Parse a number, which must be greater than 0.
+fn parse_positive(value: &str, name: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("{} must be a positive integer: {}", name, value)),
+    }
+}
+
+/// Parse a duration in seconds, which must be greater than 0.
+fn parse_seconds(value: &str, name: &str) -> Result<Duration, String> {
+    match value.parse::<f64>() {
+        Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(Duration::from_secs_f64(secs)),
+        _ => Err(format!(
+            "{} must be a positive number of seconds: {}",
+            name, value
+        )),
+    }
+}
+
+/// Parse a comma-separated list of quantiles, given either as percentiles (`p99.9`) or
+/// fractions (`0.999`). The labels are kept as written, so that they can be printed.
+fn parse_quantiles(list: &str) -> Result<Vec<(String, f64)>, String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(|label| {
+            let q = match label.strip_prefix('p') {
+                Some(percentile) => percentile.parse::<f64>().map(|p| p / 100.0),
+                None => label.parse::<f64>(),
+            };
+            match q {
+                Ok(q) if (0.0..=1.0).contains(&q) => Ok((label.to_string(), q)),
+                _ => Err(format!("invalid quantile: {}", label)),
+            }
+        })
+        .collect()
+}
+
+/// Build a histogram of the numbers read from a reader, one per line.
+fn read_histogram(reader: impl BufRead, size: usize) -> Result<Histogram, String> {
+    let mut h = Histogram::new(size);
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| err.to_string())?;
+        if let Some(value) = parse_line(&line, i + 1)? {
+            h.insert(value);
+        }
+    }
+
+    Ok(h)
+}
+
+/// Parse a line of input. Returns `None`, if the line is empty or is a comment.
+fn parse_line(line: &str, lineno: usize) -> Result<Option<f64>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    match line.parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok(Some(value)),
+        _ => Err(format!("line {}: invalid number: {}", lineno, line)),
+    }
+}
+
+/// Returns the summary of a histogram, which is printed to stdout.
+fn summary(h: &Histogram, options: &Options) -> String {
+    let fmt = |value: Option<f64>| value.map_or("-".to_string(), |v| v.to_string());
+
+    let mut lines = vec![
+        format!("count: {}", h.count()),
+        format!("min: {}", fmt(h.min())),
+        format!("max: {}", fmt(h.max())),
+        format!("mean: {}", fmt(h.mean())),
+    ];
+    for (label, q) in &options.quantiles {
+        lines.push(format!("{}: {}", label, fmt(h.quantile(*q))));
+    }
+    if options.height > 0 && h.count() > 0 {
+        lines.push(String::new());
+        lines.push(h.render_ascii(options.width, options.height));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// The numbers accounted for in the watch mode.
+enum Values {
+    All(Box<Histogram>),
+    Window(RotatingHistogram),
+}
+
+impl Values {
+    fn new(options: &Options) -> Values {
+        match options.window {
+            None => Values::All(Box::new(Histogram::new(options.size))),
+            Some(window) => Values::Window(RotatingHistogram::sliding(
+                options.size,
+                window,
+                WINDOW_PRECISION,
+            )),
+        }
+    }
+
+    fn insert(&mut self, value: f64, now: Instant) {
+        match self {
+            Values::All(h) => h.insert(value),
+            Values::Window(h) => h.insert_at(value, now),
+        }
+    }
+
+    fn snapshot(&mut self, now: Instant) -> Histogram {
+        match self {
+            Values::All(h) => (**h).clone(),
+            Values::Window(h) => h.snapshot_at(now),
+        }
+    }
+}
+
+/// Read lines of stdin in a separate thread, so that the watch mode can print estimates
+/// periodically, even when no new lines arrive.
+fn read_lines() -> Receiver<io::Result<String>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let is_err = line.is_err();
+            if sender.send(line).is_err() || is_err {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+/// Insert the numbers received from a channel of lines and print the estimates of
+/// quantiles periodically, until the channel is closed.
+fn watch(
+    lines: Receiver<io::Result<String>>,
+    options: &Options,
+    out: &mut impl Write,
+) -> Result<(), String> {
+    let mut values = Values::new(options);
+    let mut lineno = 0;
+    let mut pending = 0;
+    let mut next_report = Instant::now() + options.interval;
+    let mut report = |values: &mut Values| -> Result<(), String> {
+        let h = values.snapshot(Instant::now());
+        writeln!(out, "{}", watch_line(&h, options))
+            .and_then(|_| out.flush())
+            .map_err(|err| err.to_string())
+    };
+
+    loop {
+        let received = match options.lines {
+            Some(_) => lines.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            None => lines.recv_timeout(next_report.saturating_duration_since(Instant::now())),
+        };
+        match received {
+            Ok(line) => {
+                lineno += 1;
+                match parse_line(&line.map_err(|err| err.to_string())?, lineno) {
+                    Ok(Some(value)) => {
+                        values.insert(value, Instant::now());
+                        pending += 1;
+                    }
+                    Ok(None) => {}
+                    Err(err) => eprintln!("bhtt: {}", err),
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        match options.lines {
+            Some(n) if pending >= n => {
+                report(&mut values)?;
+                pending = 0;
+            }
+            Some(_) => {}
+            None if Instant::now() >= next_report => {
+                report(&mut values)?;
+                next_report = Instant::now() + options.interval;
+            }
+            None => {}
+        }
+    }
+
+    // the final estimates, once stdin is closed
+    report(&mut values)
+}
+
+/// Returns a line of the watch mode, e.g. `count=100 p50=12.5 p99=40`.
+fn watch_line(h: &Histogram, options: &Options) -> String {
+    let mut fields = vec![format!("count={}", h.count())];
+    for (label, q) in &options.quantiles {
+        let value = h.quantile(*q).map_or("-".to_string(), |v| v.to_string());
+        fields.push(format!("{}={}", label, value));
+    }
+
+    fields.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Option<Options>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!(args(&[]), Ok(Some(Options::default())));
+        assert_eq!(args(&["-q", "0.5", "--help"]), Ok(None));
+
+        let options = args(&[
+            "-s",
+            "32",
+            "--quantiles=p50,p99.9,0.25",
+            "--height",
+            "0",
+            "-",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(options.size, 32);
+        assert_eq!(
+            options.quantiles,
+            [
+                ("p50".to_string(), 0.5),
+                ("p99.9".to_string(), 99.9 / 100.0),
+                ("0.25".to_string(), 0.25),
+            ]
+        );
+        assert_eq!(options.height, 0);
+        assert_eq!(options.path.as_deref(), Some("-"));
+
+        assert_eq!(
+            args(&["--size", "0"]),
+            Err("size must be a positive integer: 0".into())
+        );
+        assert_eq!(args(&["-w"]), Err("missing value of -w".into()));
+        assert_eq!(args(&["-q", "p101"]), Err("invalid quantile: p101".into()));
+        assert_eq!(args(&["--bins", "5"]), Err("unknown option: --bins".into()));
+        assert_eq!(
+            args(&["a.txt", "b.txt"]),
+            Err("unexpected argument: b.txt".into())
+        );
+
+        let options = args(&["--watch", "p50,p99", "--lines=100", "--window", "0.5"])
+            .unwrap()
+            .unwrap();
+        assert!(options.watch);
+        assert_eq!(options.quantiles.len(), 2);
+        assert_eq!(options.lines, Some(100));
+        assert_eq!(options.window, Some(Duration::from_millis(500)));
+        assert_eq!(
+            args(&["--interval", "0"]),
+            Err("interval must be a positive number of seconds: 0".into())
+        );
+        assert_eq!(
+            args(&["--watch", "p50", "a.txt"]),
+            Err("the watch mode only reads stdin".into())
+        );
+    }
+
+    #[test]
+    fn input() {
+        let input = "# pings\n1\n\n 2.5 \n4\n";
+        let h = read_histogram(input.as_bytes(), 8).unwrap();
+        assert_eq!(h, Histogram::from_iter(8, [1.0, 2.5, 4.0]));
+
+        let err = read_histogram("1\n2\nabc\n".as_bytes(), 8).unwrap_err();
+        assert_eq!(err, "line 3: invalid number: abc");
+        let err = read_histogram("NaN\n".as_bytes(), 8).unwrap_err();
+        assert_eq!(err, "line 1: invalid number: NaN");
+    }
+
+    #[test]
+    fn summaries() {
+        let options = Options {
+            quantiles: parse_quantiles("p50,p100").unwrap(),
+            width: 4,
+            height: 2,
+            ..Options::default()
+        };
+
+        let h = Histogram::from_iter(8, [(1.0, 1), (2.0, 4), (3.0, 1)]);
+        assert_eq!(
+            summary(&h, &options),
+            "count: 6\nmin: 1\nmax: 3\nmean: 2\np50: 2\np100: 3\n\n ##\n####\n----\n1  3\n"
+        );
+
+        assert_eq!(
+            summary(&Histogram::new(8), &options),
+            "count: 0\nmin: -\nmax: -\nmean: -\np50: -\np100: -\n"
+        );
+    }
+
+    #[test]
+    fn watch_lines() {
+        let options = args(&["--watch", "p50,p100", "--lines", "2"])
+            .unwrap()
+            .unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        for line in ["1", "# comment", "3", "abc", "5", "7", "9"] {
+            sender.send(Ok(line.to_string())).unwrap();
+        }
+        drop(sender);
+
+        let mut out = Vec::new();
+        watch(receiver, &options, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "count=2 p50=2 p100=3\ncount=4 p50=4 p100=7\ncount=5 p50=5 p100=9\n"
+        );
+    }
+
+    #[test]
+    fn watch_interval() {
+        let options = args(&["--watch", "p50", "--interval", "0.01"])
+            .unwrap()
+            .unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        sender.send(Ok("42".to_string())).unwrap();
+        let stdin = thread::spawn(move || {
+            // estimates are printed, even if no new lines arrive
+            thread::sleep(Duration::from_millis(50));
+            drop(sender);
+        });
+
+        let mut out = Vec::new();
+        watch(receiver, &options, &mut out).unwrap();
+        stdin.join().unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.lines().count() > 1);
+        assert!(out.lines().all(|line| line == "count=1 p50=42"));
+    }
+
+    #[test]
+    fn window() {
+        let options = args(&["--watch", "p50", "--window", "10"])
+            .unwrap()
+            .unwrap();
+        let start = Instant::now();
+
+        let mut values = Values::new(&options);
+        values.insert(1.0, start);
+        values.insert(2.0, start + Duration::from_secs(5));
+        assert_eq!(values.snapshot(start + Duration::from_secs(5)).count(), 2);
+
+        // the first number is evicted, once it is more than 10 seconds old
+        assert_eq!(values.snapshot(start + Duration::from_secs(11)).count(), 1);
+        assert_eq!(values.snapshot(start + Duration::from_secs(16)).count(), 0);
+    }
+}
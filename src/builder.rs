@@ -0,0 +1,256 @@
+use crate::bin::Bin;
+use crate::duration::DurationUnit;
+use crate::histogram::Histogram;
+use crate::policy::{InfinityPolicy, MergePolicy, NanPolicy};
+
+/// A builder of histograms, which captures the configuration of histograms
+/// (such as their size) before they are created.
+///
+/// ```
+/// use bhtt::{HistogramBuilder, NanPolicy};
+///
+/// let builder = HistogramBuilder::new(5).nan_policy(NanPolicy::Ignore);
+///
+/// let h1 = builder.build();
+/// assert_eq!(h1.size(), 5);
+/// assert_eq!(h1.count(), 0);
+///
+/// let h2 = builder.collect_from(vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2]);
+/// assert_eq!(h2.size(), 5);
+/// assert_eq!(h2.count(), 10);
+/// ```
+#[derive(Debug, Clone)]
+pub struct HistogramBuilder {
+    size: usize,
+    nan_policy: NanPolicy,
+    infinity_policy: InfinityPolicy,
+    min_max_tracking: bool,
+    min_gap: f64,
+    merge_policy: MergePolicy,
+    duration_unit: DurationUnit,
+}
+
+impl HistogramBuilder {
+    /// Create a new HistogramBuilder of histograms with the given number of bins.
+    ///
+    /// ```
+    /// use bhtt::HistogramBuilder;
+    ///
+    /// let h = HistogramBuilder::new(5).build();
+    /// assert_eq!(h.size(), 5);
+    /// ```
+    pub fn new(size: usize) -> HistogramBuilder {
+        assert!(size > 0, "histogram size must be greater than 0");
+
+        HistogramBuilder {
+            size,
+            nan_policy: NanPolicy::default(),
+            infinity_policy: InfinityPolicy::default(),
+            min_max_tracking: true,
+            min_gap: 0.0,
+            merge_policy: MergePolicy::default(),
+            duration_unit: DurationUnit::default(),
+        }
+    }
+
+    /// Set the policy of handling NaN values (see [`Histogram::set_nan_policy`]).
+    ///
+    /// ```
+    /// use bhtt::{HistogramBuilder, NanPolicy};
+    ///
+    /// let h = HistogramBuilder::new(5).nan_policy(NanPolicy::Count).build();
+    /// assert_eq!(h.nan_policy(), NanPolicy::Count);
+    /// ```
+    pub fn nan_policy(mut self, policy: NanPolicy) -> HistogramBuilder {
+        self.nan_policy = policy;
+        self
+    }
+
+    /// Set the policy of handling infinite values (see [`Histogram::set_infinity_policy`]).
+    ///
+    /// ```
+    /// use bhtt::{HistogramBuilder, InfinityPolicy};
+    ///
+    /// let h = HistogramBuilder::new(5).infinity_policy(InfinityPolicy::Clamp).build();
+    /// assert_eq!(h.infinity_policy(), InfinityPolicy::Clamp);
+    /// ```
+    pub fn infinity_policy(mut self, policy: InfinityPolicy) -> HistogramBuilder {
+        self.infinity_policy = policy;
+        self
+    }
+
+    /// Enable or disable tracking of the exact minimum and maximum values (see
+    /// [`Histogram::set_min_max_tracking`]).
+    ///
+    /// ```
+    /// use bhtt::HistogramBuilder;
+    ///
+    /// let h = HistogramBuilder::new(5).min_max_tracking(false).build();
+    /// assert!(!h.min_max_tracking());
+    /// ```
+    pub fn min_max_tracking(mut self, enabled: bool) -> HistogramBuilder {
+        self.min_max_tracking = enabled;
+        self
+    }
+
+    /// Set the minimum distance between values of adjacent bins (see
+    /// [`Histogram::set_min_gap`]).
+    ///
+    /// ```
+    /// use bhtt::HistogramBuilder;
+    ///
+    /// let h = HistogramBuilder::new(5).min_gap(0.5).build();
+    /// assert_eq!(h.min_gap(), 0.5);
+    /// ```
+    pub fn min_gap(mut self, gap: f64) -> HistogramBuilder {
+        self.min_gap = gap;
+        self
+    }
+
+    /// Set the policy of measuring the distance between bins (see
+    /// [`Histogram::set_merge_policy`]).
+    ///
+    /// ```
+    /// use bhtt::{HistogramBuilder, MergePolicy};
+    ///
+    /// let h = HistogramBuilder::new(5).merge_policy(MergePolicy::Relative).build();
+    /// assert_eq!(h.merge_policy(), MergePolicy::Relative);
+    /// ```
+    pub fn merge_policy(mut self, policy: MergePolicy) -> HistogramBuilder {
+        self.merge_policy = policy;
+        self
+    }
+
+    /// Set the unit, in which durations are recorded (see
+    /// [`Histogram::set_duration_unit`]).
+    ///
+    /// ```
+    /// use bhtt::{DurationUnit, HistogramBuilder};
+    ///
+    /// let h = HistogramBuilder::new(5).duration_unit(DurationUnit::Microseconds).build();
+    /// assert_eq!(h.duration_unit(), DurationUnit::Microseconds);
+    /// ```
+    pub fn duration_unit(mut self, unit: DurationUnit) -> HistogramBuilder {
+        self.duration_unit = unit;
+        self
+    }
+
+    /// Returns a new empty Histogram.
+    ///
+    /// ```
+    /// use bhtt::HistogramBuilder;
+    ///
+    /// let h = HistogramBuilder::new(5).build();
+    /// assert_eq!(h.size(), 5);
+    /// assert_eq!(h.count(), 0);
+    /// ```
+    pub fn build(&self) -> Histogram {
+        let mut h = Histogram::new(self.size);
+        h.set_nan_policy(self.nan_policy);
+        h.set_infinity_policy(self.infinity_policy);
+        h.set_min_max_tracking(self.min_max_tracking);
+        h.set_min_gap(self.min_gap);
+        h.set_merge_policy(self.merge_policy);
+        h.set_duration_unit(self.duration_unit);
+
+        h
+    }
+
+    /// Returns a new Histogram with all values (or bins) from an iterator inserted.
+    ///
+    /// ```
+    /// use bhtt::HistogramBuilder;
+    ///
+    /// let h = HistogramBuilder::new(5).collect_from((1..=100).map(|v| v as f64 / 10.0));
+    /// assert_eq!(h.size(), 5);
+    /// assert_eq!(h.count(), 100);
+    /// assert_eq!(h.min(), Some(0.1));
+    /// assert_eq!(h.max(), Some(10.0));
+    /// ```
+    pub fn collect_from(&self, iter: impl IntoIterator<Item = impl Into<Bin>>) -> Histogram {
+        let mut h = self.build();
+        h.extend(iter);
+
+        h
+    }
+}
+
+impl Default for HistogramBuilder {
+    /// Returns a builder of histograms of the default size (see [`Histogram::DEFAULT_SIZE`]).
+    fn default() -> Self {
+        HistogramBuilder::new(Histogram::DEFAULT_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new() {
+        let h = HistogramBuilder::new(5).build();
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.min(), None);
+        assert_eq!(h.max(), None);
+        assert_eq!(h.bins(), &[]);
+        assert_eq!(h.nan_policy(), NanPolicy::default());
+        assert_eq!(h.infinity_policy(), InfinityPolicy::default());
+    }
+
+    #[test]
+    fn options() {
+        let builder = HistogramBuilder::new(5)
+            .nan_policy(NanPolicy::Count)
+            .infinity_policy(InfinityPolicy::Count)
+            .min_max_tracking(false)
+            .min_gap(0.5)
+            .merge_policy(MergePolicy::CountWeighted)
+            .duration_unit(DurationUnit::Seconds);
+
+        let mut h = builder.build();
+        assert_eq!(h.nan_policy(), NanPolicy::Count);
+        assert_eq!(h.infinity_policy(), InfinityPolicy::Count);
+        assert!(!h.min_max_tracking());
+        assert_eq!(h.min_gap(), 0.5);
+        assert_eq!(h.merge_policy(), MergePolicy::CountWeighted);
+        assert_eq!(h.duration_unit(), DurationUnit::Seconds);
+
+        let h2 = builder.collect_from(vec![1.0, 2.0]);
+        assert_eq!(h2.nan_policy(), NanPolicy::Count);
+        assert_eq!(h2.infinity_policy(), InfinityPolicy::Count);
+
+        h.try_insert(f64::NAN).unwrap();
+        h.try_insert(f64::INFINITY).unwrap();
+        assert_eq!(h.nan_count(), 1);
+        assert_eq!(h.overflow_count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram size must be greater than 0")]
+    fn new_invalid_size() {
+        HistogramBuilder::new(0);
+    }
+
+    #[test]
+    fn default() {
+        let h = HistogramBuilder::default().build();
+        assert_eq!(h.size(), Histogram::DEFAULT_SIZE);
+    }
+
+    #[test]
+    fn collect_from() {
+        let values = vec![
+            1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2, -6.0, -6.6, 0.5, 0.5, 2.625,
+        ];
+
+        let h = HistogramBuilder::new(5).collect_from(values.iter().copied());
+        let expected = Histogram::from_iter(5, &values);
+
+        assert_eq!(h.size(), expected.size());
+        assert_eq!(h.count(), expected.count());
+        assert_eq!(h.min(), expected.min());
+        assert_eq!(h.max(), expected.max());
+        assert_eq!(h.bins(), expected.bins());
+    }
+}
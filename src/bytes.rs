@@ -0,0 +1,780 @@
+use std::io::{self, Read, Write};
+
+use crate::data::{BinData, HistogramData};
+use crate::duration::DurationUnit;
+use crate::error::Error;
+use crate::histogram::Histogram;
+use crate::policy::{CountPolicy, InfinityPolicy, MergePolicy, NanPolicy};
+
+// Versions of the binary format. Data written by older versions must always remain readable,
+// so a new version must be added whenever the format is changed, and the decoder must
+// migrate data of all previous versions.
+//
+// Version 1: the data of the histogram.
+// Version 2: the data of the histogram followed by its settings.
+// Version 3: the count policy is added to the settings.
+
+/// The version of the binary format, which is written by [`Histogram::to_bytes`].
+const VERSION: u8 = 3;
+
+/// The maximum number of bins, for which space is reserved before they are decoded, so that
+/// a corrupted number of bins can't cause a huge allocation.
+const MAX_RESERVED_BINS: u64 = 4096;
+
+/// The alphabet of the standard base64 encoding (RFC 4648).
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl Histogram {
+    /// Returns the histogram encoded in a compact binary format.
+    ///
+    /// The format starts with a version byte, which is followed by little-endian fields:
+    /// the size (`u64`), the number of bins (`u64`), the `(value: f64, count: u64)` pairs
+    /// of bins, the minimum and the maximum values (each is a presence byte optionally
+    /// followed by an `f64`), the sum of values (`f64`), the numbers of NaN, underflowed
+    /// and overflowed values (`u64` each), and the settings of the histogram: the NaN
+    /// policy, the infinity policy, min/max tracking (a byte each), the minimum gap (`f64`),
+    /// the merge policy, the duration unit and the count policy (a byte each).
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(5, (1..=100).map(|v| v as f64));
+    /// let bytes = h.to_bytes();
+    ///
+    /// let h2 = Histogram::from_bytes(&bytes).unwrap();
+    /// assert_eq!(h2.bins(), h.bins());
+    /// assert_eq!(h2.min(), Some(1.0));
+    /// assert_eq!(h2.max(), Some(100.0));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(81 + 16 * self.bins().len());
+        self.write_to(&mut bytes)
+            .expect("writing to a Vec can't fail");
+
+        bytes
+    }
+
+    /// Write the histogram encoded in the binary format (see
+    /// [`to_bytes`](Histogram::to_bytes)) to a writer, e.g. to pipe it through a compressor
+    /// or to send it over a socket. Fields are written one by one without an intermediate
+    /// buffer, so wrap unbuffered writers (e.g. files) in a [`BufWriter`](io::BufWriter).
+    ///
+    /// Returns an error, if the writer fails.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(5, (1..=100).map(|v| v as f64));
+    ///
+    /// let mut buf = Vec::new();
+    /// h.write_to(&mut buf).unwrap();
+    /// assert_eq!(buf, h.to_bytes());
+    /// ```
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&[VERSION])?;
+        writer.write_all(&(self.size() as u64).to_le_bytes())?;
+        writer.write_all(&(self.bins().len() as u64).to_le_bytes())?;
+        for bin in self.bins() {
+            writer.write_all(&bin.value().to_le_bytes())?;
+            writer.write_all(&bin.count().to_le_bytes())?;
+        }
+        for value in [self.min(), self.max()] {
+            match value {
+                Some(value) => {
+                    writer.write_all(&[1])?;
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+                None => writer.write_all(&[0])?,
+            }
+        }
+        writer.write_all(&self.sum().to_le_bytes())?;
+        for count in [
+            self.nan_count(),
+            self.underflow_count(),
+            self.overflow_count(),
+        ] {
+            writer.write_all(&count.to_le_bytes())?;
+        }
+
+        let nan_policy = match self.nan_policy() {
+            NanPolicy::Error => 0,
+            NanPolicy::Ignore => 1,
+            NanPolicy::Count => 2,
+        };
+        let infinity_policy = match self.infinity_policy() {
+            InfinityPolicy::Error => 0,
+            InfinityPolicy::Clamp => 1,
+            InfinityPolicy::Count => 2,
+        };
+        writer.write_all(&[nan_policy, infinity_policy, self.min_max_tracking() as u8])?;
+        writer.write_all(&self.min_gap().to_le_bytes())?;
+        let merge_policy = match self.merge_policy() {
+            MergePolicy::Absolute => 0,
+            MergePolicy::Relative => 1,
+            MergePolicy::CountWeighted => 2,
+        };
+        let duration_unit = match self.duration_unit() {
+            DurationUnit::Seconds => 0,
+            DurationUnit::Milliseconds => 1,
+            DurationUnit::Microseconds => 2,
+            DurationUnit::Nanoseconds => 3,
+        };
+        let count_policy = match self.count_policy() {
+            CountPolicy::Saturating => 0,
+            CountPolicy::Checked => 1,
+        };
+        writer.write_all(&[merge_policy, duration_unit, count_policy])
+    }
+
+    /// Create a new Histogram from bytes produced by [`to_bytes`](Histogram::to_bytes).
+    /// Bytes written by all previous versions of the format are accepted too: settings,
+    /// which were not encoded by older versions, are set to their default values.
+    ///
+    /// Returns an error, if the bytes can't be decoded or the decoded data is not
+    /// consistent (e.g. bins are not sorted).
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// assert!(Histogram::from_bytes(&[]).is_err());
+    /// assert!(Histogram::from_bytes(&[1, 2, 3]).is_err());
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> Result<Histogram, Error> {
+        let mut reader = Reader { inner: bytes };
+        let h = match reader.histogram() {
+            Ok(h) => h,
+            Err(DecodeError::Format(err)) => return Err(err),
+            // reading from a slice only fails at the end of input, which is handled above
+            Err(DecodeError::Io(err)) => return Err(Error::InvalidFormat(err.to_string())),
+        };
+        if !reader.inner.is_empty() {
+            return Err(Error::InvalidFormat("unexpected trailing bytes".into()));
+        }
+
+        Ok(h)
+    }
+
+    /// Create a new Histogram by reading it in the binary format (see
+    /// [`to_bytes`](Histogram::to_bytes)) from a reader, e.g. from a decompressor or
+    /// a socket. Exactly one encoded histogram is read, so several histograms can be read
+    /// from the same stream one after another. Fields are read one by one, so wrap
+    /// unbuffered readers (e.g. files) in a [`BufReader`](io::BufReader).
+    ///
+    /// Returns an error, if the reader fails, or an error of the
+    /// [`InvalidData`](io::ErrorKind::InvalidData) kind, if the data can't be decoded
+    /// (see [`from_bytes`](Histogram::from_bytes)). The inner error of the latter is
+    /// an [`Error`].
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h1 = Histogram::from_iter(5, [1.0, 2.0, 3.0]);
+    /// let h2 = Histogram::from_iter(5, [4.0, 5.0]);
+    ///
+    /// let mut buf = Vec::new();
+    /// h1.write_to(&mut buf).unwrap();
+    /// h2.write_to(&mut buf).unwrap();
+    ///
+    /// let mut reader = &buf[..];
+    /// assert_eq!(Histogram::read_from(&mut reader).unwrap(), h1);
+    /// assert_eq!(Histogram::read_from(&mut reader).unwrap(), h2);
+    /// assert!(Histogram::read_from(&mut reader).is_err());
+    /// ```
+    pub fn read_from<R: Read>(reader: R) -> io::Result<Histogram> {
+        Reader { inner: reader }
+            .histogram()
+            .map_err(|err| match err {
+                DecodeError::Format(err) => io::Error::new(io::ErrorKind::InvalidData, err),
+                DecodeError::Io(err) => err,
+            })
+    }
+
+    /// Returns the histogram encoded in the binary format (see
+    /// [`to_bytes`](Histogram::to_bytes)) as a base64 string (the standard alphabet with
+    /// padding), e.g. to store it in a text column of a database or an HTTP header.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(5, [1.0, 2.0, 3.0]);
+    /// let s = h.to_base64();
+    ///
+    /// let h2 = Histogram::from_base64(&s).unwrap();
+    /// assert_eq!(h2.bins(), h.bins());
+    /// ```
+    pub fn to_base64(&self) -> String {
+        base64_encode(&self.to_bytes())
+    }
+
+    /// Create a new Histogram from a base64 string produced by
+    /// [`to_base64`](Histogram::to_base64). Returns an error, if the string is not valid
+    /// base64 or if the decoded bytes can't be decoded as a histogram.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// assert!(Histogram::from_base64("not base64!").is_err());
+    /// ```
+    pub fn from_base64(s: &str) -> Result<Histogram, Error> {
+        Histogram::from_bytes(&base64_decode(s)?)
+    }
+}
+
+fn unexpected_end() -> Error {
+    Error::InvalidFormat("unexpected end of input".into())
+}
+
+fn invalid_tag(name: &str, tag: u8) -> Error {
+    Error::InvalidFormat(format!("invalid {} {}", name, tag))
+}
+
+/// An error, which occurred while decoding a histogram: either the input is not valid, or
+/// it can't be read.
+enum DecodeError {
+    Format(Error),
+    Io(io::Error),
+}
+
+impl From<Error> for DecodeError {
+    fn from(err: Error) -> Self {
+        DecodeError::Format(err)
+    }
+}
+
+/// A reader of the fields of the binary format.
+struct Reader<R> {
+    inner: R,
+}
+
+impl<R: Read> Reader<R> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let mut bytes = [0; N];
+        self.inner.read_exact(&mut bytes).map_err(|err| {
+            if err.kind() == io::ErrorKind::UnexpectedEof {
+                DecodeError::Format(unexpected_end())
+            } else {
+                DecodeError::Io(err)
+            }
+        })?;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        self.take::<1>().map(|bytes| bytes[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    fn f64(&mut self) -> Result<f64, DecodeError> {
+        self.take().map(f64::from_le_bytes)
+    }
+
+    /// Decode a histogram (all versions).
+    fn histogram(&mut self) -> Result<Histogram, DecodeError> {
+        let version = self.u8()?;
+        if version == 0 || version > VERSION {
+            return Err(Error::InvalidFormat(format!("unsupported version {}", version)).into());
+        }
+
+        let mut h = Histogram::try_from(self.data()?)?;
+        if version >= 2 {
+            self.settings(&mut h, version)?;
+        }
+
+        Ok(h)
+    }
+
+    /// Decode the data of a histogram (all versions).
+    fn data(&mut self) -> Result<HistogramData, DecodeError> {
+        let size = self.u64()?;
+        let len = self.u64()?;
+        let mut bins = Vec::with_capacity(len.min(MAX_RESERVED_BINS) as usize);
+        for _ in 0..len {
+            bins.push(BinData {
+                value: self.f64()?,
+                count: self.u64()?,
+            });
+        }
+        let count = bins
+            .iter()
+            .try_fold(0u64, |total, bin| total.checked_add(bin.count))
+            .ok_or_else(|| Error::InvalidFormat("the total count is too large".into()))?;
+
+        Ok(HistogramData {
+            size,
+            count,
+            bins,
+            min_value: self.optional_f64()?,
+            max_value: self.optional_f64()?,
+            sum: self.f64()?,
+            nan_count: self.u64()?,
+            underflow_count: self.u64()?,
+            overflow_count: self.u64()?,
+        })
+    }
+
+    /// Decode the settings of a histogram (version 2 and newer) and apply them.
+    fn settings(&mut self, h: &mut Histogram, version: u8) -> Result<(), DecodeError> {
+        let nan_policy = match self.u8()? {
+            0 => NanPolicy::Error,
+            1 => NanPolicy::Ignore,
+            2 => NanPolicy::Count,
+            tag => return Err(invalid_tag("NaN policy", tag).into()),
+        };
+        let infinity_policy = match self.u8()? {
+            0 => InfinityPolicy::Error,
+            1 => InfinityPolicy::Clamp,
+            2 => InfinityPolicy::Count,
+            tag => return Err(invalid_tag("infinity policy", tag).into()),
+        };
+        let min_max_tracking = match self.u8()? {
+            0 => false,
+            1 => true,
+            tag => return Err(invalid_tag("min/max tracking", tag).into()),
+        };
+        let min_gap = self.f64()?;
+        if !(min_gap.is_finite() && min_gap >= 0.0) {
+            return Err(
+                Error::InvalidFormat("minimum gap must be finite and non-negative".into()).into(),
+            );
+        }
+        let merge_policy = match self.u8()? {
+            0 => MergePolicy::Absolute,
+            1 => MergePolicy::Relative,
+            2 => MergePolicy::CountWeighted,
+            tag => return Err(invalid_tag("merge policy", tag).into()),
+        };
+        let duration_unit = match self.u8()? {
+            0 => DurationUnit::Seconds,
+            1 => DurationUnit::Milliseconds,
+            2 => DurationUnit::Microseconds,
+            3 => DurationUnit::Nanoseconds,
+            tag => return Err(invalid_tag("duration unit", tag).into()),
+        };
+        let count_policy = match version {
+            2 => CountPolicy::default(),
+            _ => match self.u8()? {
+                0 => CountPolicy::Saturating,
+                1 => CountPolicy::Checked,
+                tag => return Err(invalid_tag("count policy", tag).into()),
+            },
+        };
+
+        h.set_nan_policy(nan_policy);
+        h.set_infinity_policy(infinity_policy);
+        if !min_max_tracking {
+            h.set_min_max_tracking(false);
+        }
+        h.set_merge_policy(merge_policy);
+        h.set_min_gap(min_gap);
+        h.restore_duration_unit(duration_unit);
+        h.set_count_policy(count_policy);
+
+        Ok(())
+    }
+
+    fn optional_f64(&mut self) -> Result<Option<f64>, DecodeError> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => self.f64().map(Some),
+            tag => Err(invalid_tag("presence byte", tag).into()),
+        }
+    }
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | ((byte as u32) << (16 - 8 * i))
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(BASE64_ALPHABET[((group >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+
+    s
+}
+
+fn base64_decode(s: &str) -> Result<Vec<u8>, Error> {
+    let invalid = || Error::InvalidFormat("invalid base64 string".into());
+
+    let s = s.as_bytes();
+    if !s.len().is_multiple_of(4) {
+        return Err(invalid());
+    }
+    let padding = s.iter().rev().take_while(|&&c| c == b'=').count();
+    if padding > 2 {
+        return Err(invalid());
+    }
+
+    let mut bytes = Vec::with_capacity(s.len() / 4 * 3);
+    for (n, chunk) in s.chunks(4).enumerate() {
+        let is_last = (n + 1) * 4 == s.len();
+        let len = if is_last { 4 - padding } else { 4 };
+
+        let mut group = 0u32;
+        for (i, &c) in chunk[..len].iter().enumerate() {
+            let sextet = BASE64_ALPHABET
+                .iter()
+                .position(|&a| a == c)
+                .ok_or_else(invalid)?;
+            group |= (sextet as u32) << (18 - 6 * i);
+        }
+        for i in 0..len - 1 {
+            bytes.push((group >> (16 - 8 * i)) as u8);
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bin::Bin;
+    use crate::policy::{CountPolicy, InfinityPolicy, NanPolicy};
+
+    #[test]
+    fn round_trip() {
+        let mut h = Histogram::new(8);
+        h.set_nan_policy(NanPolicy::Count);
+        h.set_infinity_policy(InfinityPolicy::Count);
+        for v in 0..1000 {
+            h.insert((v as f64).sin() * 100.0);
+        }
+        for value in [f64::NAN, f64::NEG_INFINITY, f64::INFINITY] {
+            h.try_insert(value).unwrap();
+        }
+
+        for h in [Histogram::new(3), h] {
+            let bytes = h.to_bytes();
+            let expected_len = match h.bins().len() {
+                0 => 65,
+                len => 81 + 16 * len,
+            };
+            assert_eq!(bytes.len(), expected_len);
+
+            let h2 = Histogram::from_bytes(&bytes).unwrap();
+            assert_eq!(h2.size(), h.size());
+            assert_eq!(h2.count(), h.count());
+            assert_eq!(h2.bins(), h.bins());
+            assert_eq!(h2.min(), h.min());
+            assert_eq!(h2.max(), h.max());
+            assert_eq!(h2.sum(), h.sum());
+            assert_eq!(h2.nan_count(), h.nan_count());
+            assert_eq!(h2.underflow_count(), h.underflow_count());
+            assert_eq!(h2.overflow_count(), h.overflow_count());
+
+            let h3 = Histogram::from_base64(&h.to_base64()).unwrap();
+            assert_eq!(h3.bins(), h.bins());
+        }
+    }
+
+    fn version_1_bytes() -> Vec<u8> {
+        [
+            vec![1],                     // version
+            3u64.to_le_bytes().to_vec(), // size
+            1u64.to_le_bytes().to_vec(), // the number of bins
+            1.0f64.to_le_bytes().to_vec(),
+            2u64.to_le_bytes().to_vec(),
+            vec![1], // min_value
+            1.0f64.to_le_bytes().to_vec(),
+            vec![1], // max_value
+            1.0f64.to_le_bytes().to_vec(),
+            2.0f64.to_le_bytes().to_vec(), // sum
+            [0u64.to_le_bytes(); 3].concat(),
+        ]
+        .concat()
+    }
+
+    fn version_2_bytes() -> Vec<u8> {
+        let mut bytes = version_1_bytes();
+        bytes[0] = 2;
+        bytes.extend_from_slice(&[2, 1, 1]); // NaN policy, infinity policy, min/max tracking
+        bytes.extend_from_slice(&0.5f64.to_le_bytes()); // min gap
+        bytes.extend_from_slice(&[2, 1]); // merge policy, duration unit
+        bytes
+    }
+
+    // formats must never change, as they are read from persisted data
+    #[test]
+    fn stable_format() {
+        let mut h = Histogram::new(3);
+        h.set_nan_policy(NanPolicy::Count);
+        h.set_infinity_policy(InfinityPolicy::Clamp);
+        h.set_min_gap(0.5);
+        h.set_merge_policy(MergePolicy::CountWeighted);
+        h.set_duration_unit(DurationUnit::Milliseconds);
+        h.set_count_policy(CountPolicy::Checked);
+        h.insert((1.0, 2));
+
+        let mut expected = version_2_bytes();
+        expected[0] = 3;
+        expected.push(1); // count policy
+
+        assert_eq!(h.to_bytes(), expected);
+
+        let h2 = Histogram::from_bytes(&expected).unwrap();
+        assert_eq!(h2.bins(), &[Bin::new(1.0, 2)]);
+        assert_eq!(h2.nan_policy(), NanPolicy::Count);
+        assert_eq!(h2.infinity_policy(), InfinityPolicy::Clamp);
+        assert!(h2.min_max_tracking());
+        assert_eq!(h2.min_gap(), 0.5);
+        assert_eq!(h2.merge_policy(), MergePolicy::CountWeighted);
+        assert_eq!(h2.duration_unit(), DurationUnit::Milliseconds);
+        assert_eq!(h2.count_policy(), CountPolicy::Checked);
+    }
+
+    #[test]
+    fn version_2() {
+        let h = Histogram::from_bytes(&version_2_bytes()).unwrap();
+        assert_eq!(h.bins(), &[Bin::new(1.0, 2)]);
+        assert_eq!(h.nan_policy(), NanPolicy::Count);
+        assert_eq!(h.merge_policy(), MergePolicy::CountWeighted);
+
+        // the count policy was not encoded, so the default one is used
+        assert_eq!(h.count_policy(), CountPolicy::default());
+
+        // version 2 data followed by the count policy is rejected
+        let mut bytes = version_2_bytes();
+        bytes.push(1);
+        assert!(Histogram::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn version_1() {
+        let h = Histogram::from_bytes(&version_1_bytes()).unwrap();
+        assert_eq!(h.size(), 3);
+        assert_eq!(h.bins(), &[Bin::new(1.0, 2)]);
+        assert_eq!(h.min(), Some(1.0));
+        assert_eq!(h.max(), Some(1.0));
+        assert_eq!(h.sum(), 2.0);
+
+        // settings were not encoded, so the default ones are used
+        let defaults = Histogram::new(3);
+        assert_eq!(h.nan_policy(), defaults.nan_policy());
+        assert_eq!(h.infinity_policy(), defaults.infinity_policy());
+        assert_eq!(h.min_max_tracking(), defaults.min_max_tracking());
+        assert_eq!(h.min_gap(), defaults.min_gap());
+        assert_eq!(h.merge_policy(), defaults.merge_policy());
+        assert_eq!(h.duration_unit(), defaults.duration_unit());
+        assert_eq!(h.count_policy(), defaults.count_policy());
+
+        // version 1 data followed by settings is rejected
+        let mut bytes = version_1_bytes();
+        bytes.extend_from_slice(&[0, 0, 1]);
+        assert!(Histogram::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let mut h = Histogram::new(5);
+        h.set_min_max_tracking(false);
+        h.set_nan_policy(NanPolicy::Ignore);
+        h.set_merge_policy(MergePolicy::Relative);
+        h.set_duration_unit(DurationUnit::Seconds);
+        h.set_count_policy(CountPolicy::Checked);
+        h.insert_many([1.0, 2.0, 3.0]);
+
+        let h2 = Histogram::from_bytes(&h.to_bytes()).unwrap();
+        assert!(!h2.min_max_tracking());
+        assert_eq!(h2.nan_policy(), NanPolicy::Ignore);
+        assert_eq!(h2.merge_policy(), MergePolicy::Relative);
+        assert_eq!(h2.duration_unit(), DurationUnit::Seconds);
+        assert_eq!(h2.count_policy(), CountPolicy::Checked);
+        assert_eq!(h2.bins(), h.bins());
+        assert_eq!(h2.min(), Some(1.0));
+    }
+
+    #[test]
+    fn from_bytes_invalid() {
+        let bytes = Histogram::from_iter(3, [1.0, 2.0]).to_bytes();
+
+        // truncated input and trailing bytes
+        for len in 0..bytes.len() {
+            assert!(Histogram::from_bytes(&bytes[..len]).is_err());
+        }
+        assert!(Histogram::from_bytes(&[bytes.clone(), vec![0]].concat()).is_err());
+
+        let corrupt = |offset: usize, byte: u8| {
+            let mut bytes = bytes.clone();
+            bytes[offset] = byte;
+            Histogram::from_bytes(&bytes)
+        };
+        assert_eq!(
+            corrupt(0, 42).unwrap_err(),
+            Error::InvalidFormat("unsupported version 42".into())
+        );
+        // the number of bins
+        assert!(corrupt(9, 3).is_err());
+        assert!(corrupt(16, 0xff).is_err());
+        // the count of the first bin
+        assert!(corrupt(25, 0).is_err());
+        // the presence byte of the minimum value
+        assert!(corrupt(49, 2).is_err());
+        assert_eq!(
+            corrupt(0, 4).unwrap_err(),
+            Error::InvalidFormat("unsupported version 4".into())
+        );
+        assert!(corrupt(0, 0).is_err());
+
+        // settings
+        let settings = bytes.len() - 14;
+        for (offset, byte) in [(0, 3), (1, 3), (2, 2), (11, 3), (12, 4), (13, 2)] {
+            assert!(corrupt(settings + offset, byte).is_err());
+        }
+        let mut bytes = bytes.clone();
+        bytes[settings + 3..settings + 11].copy_from_slice(&(-1.0f64).to_le_bytes());
+        assert!(Histogram::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn from_bytes_huge_size() {
+        // the size is only an upper bound of the number of bins, so a huge one is accepted, but
+        // it must not be used to reserve memory for bins up front
+        let mut bytes = Histogram::from_iter(5, [1.0, 2.0, 3.0]).to_bytes();
+        bytes[1..9].copy_from_slice(&(1u64 << 44).to_le_bytes());
+        let mut h = Histogram::from_bytes(&bytes).unwrap();
+        assert_eq!(h.size(), 1 << 44);
+        assert_eq!(h.count(), 3);
+
+        let (left, right) = h.split_at(2.0);
+        assert_eq!((left.count(), right.count()), (2, 1));
+        assert_eq!(h.restrict(1.5, 2.5).count(), 1);
+        assert_eq!(h.subtract(&left).bins(), right.bins());
+
+        let sum: Histogram = [h.clone(), Histogram::new(5)].iter().sum();
+        assert_eq!(sum.count(), 3);
+        assert_eq!(Histogram::merge_all(5, [&h, &h]).count(), 6);
+
+        let mut other = Histogram::new(5);
+        other += &h;
+        assert_eq!(other.size(), 1 << 44);
+        assert_eq!(other.count(), 3);
+
+        h.scale_counts(2.0);
+        assert_eq!(h.count(), 6);
+        h.decay(0.5);
+        assert_eq!(h.count(), 3);
+    }
+
+    // A reader, which returns at most one byte per read, like a slow socket.
+    struct ByteByByte<'a>(&'a [u8]);
+
+    impl Read for ByteByByte<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            (&self.0[..self.0.len().min(1)]).read(buf).inspect(|&n| {
+                self.0 = &self.0[n..];
+            })
+        }
+    }
+
+    // A writer, which fails after the given number of bytes.
+    struct Limited(usize);
+
+    impl Write for Limited {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > self.0 {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "limit reached"));
+            }
+            self.0 -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn streaming() {
+        let h = Histogram::from_iter(8, (0..1000).map(|v| (v as f64).sqrt()));
+        let bytes = h.to_bytes();
+
+        let mut buf = Vec::new();
+        h.write_to(&mut buf).unwrap();
+        assert_eq!(buf, bytes);
+
+        let h2 = Histogram::read_from(ByteByByte(&bytes)).unwrap();
+        assert_eq!(h2, h);
+
+        // the rest of the stream is not consumed
+        let stream = [bytes.clone(), vec![42]].concat();
+        let mut reader = &stream[..];
+        assert_eq!(Histogram::read_from(&mut reader).unwrap(), h);
+        assert_eq!(reader, &[42]);
+
+        assert_eq!(
+            h.write_to(Limited(10)).unwrap_err().kind(),
+            io::ErrorKind::WriteZero
+        );
+        assert!(h.write_to(Limited(bytes.len())).is_ok());
+    }
+
+    #[test]
+    fn read_from_invalid() {
+        let bytes = Histogram::from_iter(3, [1.0, 2.0]).to_bytes();
+
+        for len in 0..bytes.len() {
+            let err = Histogram::read_from(&bytes[..len]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(
+                err.into_inner().unwrap().downcast_ref::<Error>(),
+                Some(&Error::InvalidFormat("unexpected end of input".into()))
+            );
+        }
+
+        // a corrupted number of bins doesn't cause a huge allocation
+        let mut corrupted = bytes.clone();
+        corrupted[9..17].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            Histogram::read_from(&corrupted[..]).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        // errors of the reader are passed through
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::ConnectionReset, "reset"))
+            }
+        }
+        assert_eq!(
+            Histogram::read_from(Failing).unwrap_err().kind(),
+            io::ErrorKind::ConnectionReset
+        );
+    }
+
+    #[test]
+    fn base64() {
+        let cases: [(&[u8], &str); 7] = [
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"fooba", "Zm9vYmE="),
+            (b"foobar", "Zm9vYmFy"),
+        ];
+        for (bytes, s) in cases {
+            assert_eq!(base64_encode(bytes), s);
+            assert_eq!(base64_decode(s).unwrap(), bytes);
+        }
+
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(base64_decode(&base64_encode(&all)).unwrap(), all);
+
+        for s in ["Zg=", "Zg===", "Z===", "Zm9v!A==", "Zg==Zg==", "=Zg="] {
+            assert!(base64_decode(s).is_err(), "{}", s);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::bin::Bin;
+use crate::histogram::Histogram;
+
+impl Histogram {
+    /// Create a new Histogram of the given size from a list of t-digest centroids, i.e.
+    /// `(mean, weight)` pairs. Centroids don't have to be sorted.
+    ///
+    /// Weights are rounded to whole counts in a way that preserves the total weight (rounded
+    /// to the nearest integer), and centroids, which end up with a count of 0, are skipped.
+    /// Means must not be NaN or infinite, and weights must be finite and non-negative, or the
+    /// function will panic.
+    ///
+    /// As t-digests don't generally keep the exact minimum and maximum values, the means of
+    /// the outermost centroids are used instead.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let centroids = vec![(1.0, 2.0), (5.5, 3.0), (10.0, 1.0)];
+    /// let h = Histogram::from_centroids(5, centroids);
+    ///
+    /// assert_eq!(h.count(), 6);
+    /// assert_eq!(h.min(), Some(1.0));
+    /// assert_eq!(h.max(), Some(10.0));
+    /// ```
+    pub fn from_centroids(
+        size: usize,
+        centroids: impl IntoIterator<Item = (f64, f64)>,
+    ) -> Histogram {
+        let mut h = Histogram::new(size);
+
+        let (mut total_weight, mut total_count) = (0.0, 0);
+        for (mean, weight) in centroids {
+            assert!(
+                weight.is_finite() && weight >= 0.0,
+                "weight must be finite and non-negative"
+            );
+
+            // round the cumulative weight rather than individual ones, so that rounding
+            // errors don't accumulate
+            total_weight += weight;
+            let count = (total_weight.round() as u64).saturating_sub(total_count);
+            total_count += count;
+
+            if count > 0 {
+                h.insert(Bin::new(mean, count));
+            }
+        }
+
+        h
+    }
+
+    /// Returns the bins of the histogram as a list of t-digest centroids, i.e. `(mean, weight)`
+    /// pairs sorted by the mean, e.g. to merge the histogram with t-digests produced elsewhere.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(5, [(1.0, 2), (5.0, 1), (10.0, 3)]);
+    ///
+    /// assert_eq!(h.to_centroids(), vec![(1.0, 2.0), (5.0, 1.0), (10.0, 3.0)]);
+    /// ```
+    pub fn to_centroids(&self) -> Vec<(f64, f64)> {
+        self.bins()
+            .iter()
+            .map(|bin| (bin.value(), bin.count() as f64))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_centroids() {
+        let h = Histogram::from_centroids(5, vec![]);
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.bins(), &[]);
+
+        let h = Histogram::from_centroids(5, vec![(3.0, 1.0), (-1.0, 2.0), (7.0, 4.0)]);
+        assert_eq!(h.count(), 7);
+        assert_eq!(
+            h.bins(),
+            &[Bin::new(-1.0, 2), Bin::new(3.0, 1), Bin::new(7.0, 4)]
+        );
+        assert_eq!(h.sum(), 29.0);
+    }
+
+    #[test]
+    fn from_centroids_fractional_weights() {
+        let centroids = vec![(1.0, 0.4), (2.0, 0.4), (3.0, 0.4), (4.0, 1.3), (5.0, 0.0)];
+        let h = Histogram::from_centroids(5, centroids);
+
+        // the total weight of 2.5 is rounded to 3
+        assert_eq!(h.count(), 3);
+        assert_eq!(h.bins(), &[Bin::new(2.0, 1), Bin::new(4.0, 2)]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let h = Histogram::from_iter(16, (0..1000).map(|v| (v * v) as f64));
+        let h2 = Histogram::from_centroids(16, h.to_centroids());
+
+        assert_eq!(h2.count(), h.count());
+        assert_eq!(h2.bins(), h.bins());
+    }
+
+    #[test]
+    #[should_panic(expected = "weight must be finite and non-negative")]
+    fn from_centroids_negative_weight() {
+        Histogram::from_centroids(5, vec![(1.0, -1.0)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "value must not be NaN")]
+    fn from_centroids_nan_mean() {
+        Histogram::from_centroids(5, vec![(f64::NAN, 1.0)]);
+    }
+}
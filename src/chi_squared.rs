@@ -0,0 +1,147 @@
+use statrs::distribution::{ChiSquared, ContinuousCDF};
+
+use crate::distance::common_buckets;
+use crate::histogram::Histogram;
+
+/// The result of Pearson's chi-squared test of homogeneity of two histograms (see
+/// [`Histogram::chi_squared_test`]).
+///
+/// This is only available when the `statrs` feature is enabled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChiSquaredTest {
+    statistic: f64,
+    degrees_of_freedom: usize,
+    p_value: f64,
+}
+
+impl ChiSquaredTest {
+    /// Returns the chi-squared statistic.
+    pub fn statistic(&self) -> f64 {
+        self.statistic
+    }
+
+    /// Returns the number of degrees of freedom, which is the number of non-empty buckets
+    /// minus one.
+    pub fn degrees_of_freedom(&self) -> usize {
+        self.degrees_of_freedom
+    }
+
+    /// Returns the p-value, i.e. the probability to get a statistic at least as large as
+    /// this one, if both histograms were built from samples of the same distribution.
+    pub fn p_value(&self) -> f64 {
+        self.p_value
+    }
+}
+
+impl Histogram {
+    /// Returns the result of Pearson's chi-squared test of homogeneity, which checks if
+    /// the values in this and the `other` histograms are samples of the same distribution,
+    /// or `None` if either of the histograms is empty. A small p-value (e.g. less than 0.05)
+    /// means that the distributions are likely to be different.
+    ///
+    /// The counts of values of both histograms are estimated in the same `buckets`, which
+    /// split the range of values of both histograms into equal intervals (see
+    /// [`to_buckets`](Histogram::to_buckets)). Buckets, which are empty in both histograms,
+    /// are ignored. As with any chi-squared test, the result is only reliable if the counts
+    /// in most of the buckets are not too small (e.g. at least 5). The number of buckets must
+    /// be greater than 0, or the function will panic.
+    ///
+    /// This is only available when the `statrs` feature is enabled.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let a = Histogram::from_iter(32, (0..1000).map(|v| (v % 100) as f64));
+    /// let b = Histogram::from_iter(32, (0..2000).map(|v| (v % 100) as f64));
+    /// let c = Histogram::from_iter(32, (0..1000).map(|v| (v % 100) as f64 * 1.2));
+    ///
+    /// assert!(a.chi_squared_test(&b, 10).unwrap().p_value() > 0.9);
+    /// assert!(a.chi_squared_test(&c, 10).unwrap().p_value() < 0.001);
+    /// ```
+    pub fn chi_squared_test(&self, other: &Histogram, buckets: usize) -> Option<ChiSquaredTest> {
+        assert!(buckets > 0, "number of buckets must be greater than 0");
+
+        let (counts1, counts2) = common_buckets(self, other, buckets)?;
+
+        // samples may have different sizes, so the counts are scaled accordingly (see
+        // "Numerical Recipes", 14.3.1)
+        let (total1, total2) = (self.count() as f64, other.count() as f64);
+        let (scale1, scale2) = ((total2 / total1).sqrt(), (total1 / total2).sqrt());
+
+        let mut statistic = 0.0;
+        let mut non_empty = 0;
+        for (count1, count2) in counts1.into_iter().zip(counts2) {
+            if count1 + count2 > 0 {
+                let (count1, count2) = (count1 as f64, count2 as f64);
+                statistic += (scale1 * count1 - scale2 * count2).powi(2) / (count1 + count2);
+                non_empty += 1;
+            }
+        }
+
+        let degrees_of_freedom = non_empty - 1;
+        let p_value = match degrees_of_freedom {
+            // there is only one non-empty bucket, so the distributions can't be told apart
+            0 => 1.0,
+            df => ChiSquared::new(df as f64)
+                .expect("degrees of freedom are positive")
+                .sf(statistic),
+        };
+
+        Some(ChiSquaredTest {
+            statistic,
+            degrees_of_freedom,
+            p_value,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chi_squared_test() {
+        // the same distribution, but twice as many values
+        let h1 = Histogram::from_iter(32, (0..1000).map(|v| (v % 100) as f64));
+        let h2 = Histogram::from_iter(32, (0..2000).map(|v| (v % 100) as f64));
+        let test = h1.chi_squared_test(&h2, 10).unwrap();
+        assert_eq!(test.degrees_of_freedom(), 9);
+        assert!(test.statistic() < 1.0);
+        assert!(test.p_value() > 0.99);
+        assert_eq!(h2.chi_squared_test(&h1, 10), Some(test));
+
+        let test = h1.chi_squared_test(&h1, 10).unwrap();
+        assert_eq!(test.statistic(), 0.0);
+        assert_eq!(test.p_value(), 1.0);
+
+        // values are shifted
+        let mut h3 = h1.clone();
+        h3.map_values(1.0, 10.0);
+        let test = h1.chi_squared_test(&h3, 10).unwrap();
+        assert!(test.statistic() > 100.0);
+        assert!(test.p_value() < 1e-6);
+    }
+
+    #[test]
+    fn chi_squared_test_special_cases() {
+        let h1 = Histogram::from_iter(5, [(1.0, 10)]);
+        let h2 = Histogram::from_iter(5, [(1.0, 20)]);
+        assert_eq!(
+            h1.chi_squared_test(&h2, 10),
+            Some(ChiSquaredTest {
+                statistic: 0.0,
+                degrees_of_freedom: 0,
+                p_value: 1.0
+            })
+        );
+
+        assert_eq!(h1.chi_squared_test(&Histogram::new(5), 10), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "number of buckets must be greater than 0")]
+    fn chi_squared_test_no_buckets() {
+        let h = Histogram::from_iter(5, [1.0]);
+        h.chi_squared_test(&h, 0);
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use prometheus::core::{Collector, Desc};
+use prometheus::proto;
+
+use crate::expose::PrometheusExport;
+use crate::histogram::Histogram;
+use crate::registry::Registry;
+
+/// A Prometheus collector, which exports histograms of a [`Registry`] with the same name
+/// as a family of metrics, so that quantiles estimated by histograms can be exposed via
+/// the standard scrape endpoint of the prometheus crate.
+///
+/// Every histogram with the name of the collector and exactly the label names of
+/// the collector is exported as a metric of the family. Other histograms are ignored.
+/// Histograms are exported either as classic Prometheus histograms or as summaries (see
+/// [`PrometheusExport`]). See [`Registry::expose`] for an alternative, which renders
+/// histograms in the text format without the prometheus crate.
+///
+/// This type is only available when the `prometheus` feature is enabled.
+///
+/// ```
+/// use std::sync::Arc;
+/// use bhtt::{Key, PrometheusCollector, PrometheusExport, Registry};
+/// use prometheus::{Encoder, TextEncoder};
+///
+/// let registry = Arc::new(Registry::new(64));
+/// let collector = PrometheusCollector::new(
+///     Arc::clone(&registry),
+///     "request_latency_seconds",
+///     "Latency of requests",
+///     &["method"],
+///     PrometheusExport::Quantiles(vec![0.5, 0.99]),
+/// )
+/// .unwrap();
+///
+/// let prometheus_registry = prometheus::Registry::new();
+/// prometheus_registry.register(Box::new(collector)).unwrap();
+///
+/// for value in 1..=100 {
+///     let key = Key::new("request_latency_seconds").with_label("method", "GET");
+///     registry.record(key, value as f64 / 1000.0);
+/// }
+///
+/// let mut buf = Vec::new();
+/// TextEncoder::new()
+///     .encode(&prometheus_registry.gather(), &mut buf)
+///     .unwrap();
+/// let text = String::from_utf8(buf).unwrap();
+/// assert!(text.contains(r#"request_latency_seconds{method="GET",quantile="0.99"}"#));
+/// assert!(text.contains(r#"request_latency_seconds_count{method="GET"} 100"#));
+/// ```
+#[derive(Debug)]
+pub struct PrometheusCollector {
+    registry: Arc<Registry>,
+    desc: Desc,
+    export: PrometheusExport,
+}
+
+impl PrometheusCollector {
+    /// Create a new PrometheusCollector of histograms with the given name and label names
+    /// in the registry.
+    ///
+    /// Returns an error, if the name or the label names are not valid Prometheus names.
+    /// Bucket bounds and quantiles must be valid (see [`PrometheusExport`]), or
+    /// the function will panic.
+    pub fn new(
+        registry: Arc<Registry>,
+        name: &str,
+        help: &str,
+        label_names: &[&str],
+        export: PrometheusExport,
+    ) -> prometheus::Result<PrometheusCollector> {
+        export.validate();
+
+        let mut label_names: Vec<String> = label_names.iter().map(|&n| n.to_string()).collect();
+        label_names.sort();
+        let desc = Desc::new(name.into(), help.into(), label_names, HashMap::new())?;
+
+        Ok(PrometheusCollector {
+            registry,
+            desc,
+            export,
+        })
+    }
+
+    /// Returns the metric of a histogram.
+    fn metric(&self, labels: &[(String, String)], h: &Histogram) -> proto::Metric {
+        let label_pairs = labels
+            .iter()
+            .map(|(name, value)| {
+                let mut pair = proto::LabelPair::default();
+                pair.set_name(name.clone());
+                pair.set_value(value.clone());
+                pair
+            })
+            .collect();
+
+        let mut metric = proto::Metric::default();
+        metric.set_label(label_pairs);
+        match &self.export {
+            PrometheusExport::Buckets(bounds) => {
+                let mut cumulative_count = 0;
+                let buckets = bounds
+                    .iter()
+                    .zip(h.to_buckets(bounds))
+                    .map(|(&bound, count)| {
+                        cumulative_count += count;
+                        let mut bucket = proto::Bucket::default();
+                        bucket.set_upper_bound(bound);
+                        bucket.set_cumulative_count(cumulative_count);
+                        bucket
+                    })
+                    .collect();
+
+                let mut histogram = proto::Histogram::default();
+                histogram.set_sample_count(h.count());
+                histogram.set_sample_sum(h.sum());
+                histogram.set_bucket(buckets);
+                metric.set_histogram(histogram);
+            }
+            PrometheusExport::Quantiles(quantiles) => {
+                let quantiles = quantiles
+                    .iter()
+                    .map(|&q| {
+                        let mut quantile = proto::Quantile::default();
+                        quantile.set_quantile(q);
+                        quantile.set_value(h.quantile(q).unwrap_or(f64::NAN));
+                        quantile
+                    })
+                    .collect();
+
+                let mut summary = proto::Summary::default();
+                summary.set_sample_count(h.count());
+                summary.set_sample_sum(h.sum());
+                summary.set_quantile(quantiles);
+                metric.set_summary(summary);
+            }
+        }
+
+        metric
+    }
+}
+
+impl Collector for PrometheusCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.desc]
+    }
+
+    fn collect(&self) -> Vec<proto::MetricFamily> {
+        // both the label names of the collector and the labels of keys are sorted
+        let metrics: Vec<proto::Metric> = self
+            .registry
+            .snapshot_all()
+            .iter()
+            .filter(|(key, _)| {
+                key.name() == self.desc.fq_name
+                    && key
+                        .labels()
+                        .iter()
+                        .map(|(name, _)| name)
+                        .eq(self.desc.variable_labels.iter())
+            })
+            .map(|(key, h)| self.metric(key.labels(), h))
+            .collect();
+        if metrics.is_empty() {
+            return Vec::new();
+        }
+
+        let mut family = proto::MetricFamily::default();
+        family.set_name(self.desc.fq_name.clone());
+        family.set_help(self.desc.help.clone());
+        family.set_field_type(match self.export {
+            PrometheusExport::Buckets(_) => proto::MetricType::HISTOGRAM,
+            PrometheusExport::Quantiles(_) => proto::MetricType::SUMMARY,
+        });
+        family.set_metric(metrics);
+
+        vec![family]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prometheus::{Encoder, TextEncoder};
+
+    use super::*;
+    use crate::registry::Key;
+
+    fn gather(collector: PrometheusCollector) -> String {
+        let registry = prometheus::Registry::new();
+        registry.register(Box::new(collector)).unwrap();
+
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&registry.gather(), &mut buf)
+            .unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    fn test_registry() -> Arc<Registry> {
+        let registry = Arc::new(Registry::new(64));
+        for value in 1..=100 {
+            registry.record("latency", value as f64);
+            registry.record(
+                Key::new("latency").with_label("method", "GET"),
+                value as f64,
+            );
+            registry.record(
+                Key::new("latency")
+                    .with_label("method", "POST")
+                    .with_label("code", "200"),
+                value as f64 * 2.0,
+            );
+            registry.record("other", value as f64);
+        }
+
+        registry
+    }
+
+    #[test]
+    fn buckets() {
+        let collector = PrometheusCollector::new(
+            test_registry(),
+            "latency",
+            "Latency of requests",
+            &["method", "code"],
+            PrometheusExport::Buckets(vec![50.0, 100.0, 150.0]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            gather(collector),
+            [
+                "# HELP latency Latency of requests",
+                "# TYPE latency histogram",
+                r#"latency_bucket{code="200",method="POST",le="50"} 25"#,
+                r#"latency_bucket{code="200",method="POST",le="100"} 50"#,
+                r#"latency_bucket{code="200",method="POST",le="150"} 75"#,
+                r#"latency_bucket{code="200",method="POST",le="+Inf"} 100"#,
+                r#"latency_sum{code="200",method="POST"} 10100"#,
+                r#"latency_count{code="200",method="POST"} 100"#,
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn quantiles() {
+        let collector = PrometheusCollector::new(
+            test_registry(),
+            "latency",
+            "Latency of requests",
+            &[],
+            PrometheusExport::Quantiles(vec![0.0, 0.5, 1.0]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            gather(collector),
+            [
+                "# HELP latency Latency of requests",
+                "# TYPE latency summary",
+                r#"latency{quantile="0"} 1"#,
+                r#"latency{quantile="0.5"} 50.5"#,
+                r#"latency{quantile="1"} 100"#,
+                "latency_sum 5050",
+                "latency_count 100",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn no_histograms() {
+        let collector = PrometheusCollector::new(
+            Arc::new(Registry::new(64)),
+            "latency",
+            "Latency of requests",
+            &["method"],
+            PrometheusExport::Quantiles(vec![0.5]),
+        )
+        .unwrap();
+
+        assert!(collector.collect().is_empty());
+        assert_eq!(collector.desc().len(), 1);
+    }
+
+    #[test]
+    fn invalid_name() {
+        let result = PrometheusCollector::new(
+            Arc::new(Registry::new(64)),
+            "invalid name",
+            "help",
+            &[],
+            PrometheusExport::Quantiles(vec![0.5]),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "bucket bounds must be sorted in strictly ascending order")]
+    fn unsorted_buckets() {
+        let _ = PrometheusCollector::new(
+            Arc::new(Registry::new(64)),
+            "latency",
+            "help",
+            &[],
+            PrometheusExport::Buckets(vec![2.0, 1.0]),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "quantiles must be in the range [0.0; 1.0]")]
+    fn invalid_quantiles() {
+        let _ = PrometheusCollector::new(
+            Arc::new(Registry::new(64)),
+            "latency",
+            "help",
+            &[],
+            PrometheusExport::Quantiles(vec![1.5]),
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::bin::Bin;
+use crate::histogram::Histogram;
+
+/// A histogram that can be updated concurrently from many threads.
+///
+/// Inserts are spread across a number of independent sub-histograms (shards), each of which
+/// is protected by its own lock, so that threads rarely contend with each other. Shards are
+/// merged together lazily when the histogram is queried (see [`snapshot`](Self::snapshot)).
+///
+/// This type is only available when the `sync` feature is enabled.
+///
+/// ```
+/// use bhtt::ConcurrentHistogram;
+///
+/// let h = ConcurrentHistogram::new(64);
+/// std::thread::scope(|s| {
+///     for t in 0..4 {
+///         let h = &h;
+///         s.spawn(move || {
+///             for i in 0..1000 {
+///                 h.insert((t * 1000 + i) as f64);
+///             }
+///         });
+///     }
+/// });
+///
+/// assert_eq!(h.count(), 4000);
+/// assert_eq!(h.min(), Some(0.0));
+/// assert_eq!(h.max(), Some(3999.0));
+/// ```
+#[derive(Debug)]
+pub struct ConcurrentHistogram {
+    size: usize,
+    shards: Vec<Mutex<Histogram>>,
+}
+
+impl ConcurrentHistogram {
+    /// Create a new ConcurrentHistogram with the given number of bins. The number of
+    /// shards is equal to the available parallelism of the system.
+    ///
+    /// ```
+    /// use bhtt::ConcurrentHistogram;
+    ///
+    /// let h = ConcurrentHistogram::new(64);
+    /// assert_eq!(h.size(), 64);
+    /// ```
+    pub fn new(size: usize) -> ConcurrentHistogram {
+        let shards = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+        ConcurrentHistogram::with_shards(size, shards)
+    }
+
+    /// Create a new ConcurrentHistogram with the given number of bins and shards.
+    ///
+    /// ```
+    /// use bhtt::ConcurrentHistogram;
+    ///
+    /// let h = ConcurrentHistogram::with_shards(64, 8);
+    /// assert_eq!(h.size(), 64);
+    /// assert_eq!(h.shards(), 8);
+    /// ```
+    pub fn with_shards(size: usize, shards: usize) -> ConcurrentHistogram {
+        assert!(size > 0, "histogram size must be greater than 0");
+        assert!(shards > 0, "number of shards must be greater than 0");
+
+        ConcurrentHistogram {
+            size,
+            shards: (0..shards)
+                .map(|_| Mutex::new(Histogram::new(size)))
+                .collect(),
+        }
+    }
+
+    /// Returns the size of the histogram.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the number of shards of the histogram.
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Update the histogram by inserting a new value (or bin).
+    ///
+    /// ```
+    /// use bhtt::{Bin, ConcurrentHistogram};
+    ///
+    /// let h = ConcurrentHistogram::new(5);
+    /// h.insert(42.0);
+    /// h.insert(Bin::new(-7.5, 10));
+    ///
+    /// assert_eq!(h.count(), 11);
+    /// ```
+    pub fn insert<T: Into<Bin>>(&self, value: T) {
+        // convert the value before locking the shard, so that invalid values
+        // can't poison it
+        let bin = value.into();
+        self.lock_shard().insert(bin);
+    }
+
+    /// Merge another histogram into this one.
+    ///
+    /// ```
+    /// use bhtt::{ConcurrentHistogram, Histogram};
+    ///
+    /// let h = ConcurrentHistogram::new(5);
+    /// h.merge(&Histogram::from_iter(5, &[1.0, -7.6, 0.0, 5.8, 4.3, 2.1, 11.6]));
+    ///
+    /// assert_eq!(h.count(), 7);
+    /// ```
+    pub fn merge(&self, other: &Histogram) {
+        self.lock_shard().merge(other);
+    }
+
+    /// Returns a Histogram with all shards merged together.
+    ///
+    /// Each query method of ConcurrentHistogram that needs the bins takes a snapshot
+    /// internally, so prefer taking a snapshot explicitly when running several queries.
+    ///
+    /// ```
+    /// use bhtt::ConcurrentHistogram;
+    ///
+    /// let h = ConcurrentHistogram::new(5);
+    /// for value in vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+    ///     h.insert(value);
+    /// }
+    ///
+    /// let snapshot = h.snapshot();
+    /// assert_eq!(snapshot.size(), 5);
+    /// assert_eq!(snapshot.count(), 10);
+    /// assert_eq!(snapshot.quantile(0.0), Some(-5.4));
+    /// assert_eq!(snapshot.quantile(1.0), Some(10.0));
+    /// ```
+    pub fn snapshot(&self) -> Histogram {
+        let shards: Vec<Histogram> = self
+            .shards
+            .iter()
+            .map(|shard| lock(shard).clone())
+            .collect();
+
+        Histogram::merge_all(self.size, shards)
+    }
+
+    /// Returns the total number of values in the histogram.
+    pub fn count(&self) -> u64 {
+        self.shards.iter().map(|shard| lock(shard).count()).sum()
+    }
+
+    /// Returns the (exact) minimum value or `None` if the histogram is empty.
+    pub fn min(&self) -> Option<f64> {
+        self.shards
+            .iter()
+            .filter_map(|shard| lock(shard).min())
+            .reduce(f64::min)
+    }
+
+    /// Returns the (exact) maximum value or `None` if the histogram is empty.
+    pub fn max(&self) -> Option<f64> {
+        self.shards
+            .iter()
+            .filter_map(|shard| lock(shard).max())
+            .reduce(f64::max)
+    }
+
+    /// Returns the (exact) sum of all values in the histogram.
+    pub fn sum(&self) -> f64 {
+        self.snapshot().sum()
+    }
+
+    /// Returns the (exact) arithmetic mean of the values or `None` if the histogram is empty.
+    pub fn mean(&self) -> Option<f64> {
+        self.snapshot().mean()
+    }
+
+    /// Returns an approximated value of the `q`'th quantile of the values or `None`
+    /// if the histogram is empty (see [`Histogram::quantile`]).
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        self.snapshot().quantile(q)
+    }
+
+    /// Returns an estimate of the fraction of values in the histogram that are less
+    /// than or equal to `value` (see [`Histogram::rank`]).
+    pub fn rank(&self, value: f64) -> Option<f64> {
+        self.snapshot().rank(value)
+    }
+
+    /// Returns an estimate of the number of values in the histogram that are less
+    /// than or equal to `value` (see [`Histogram::count_less_than_or_equal_to`]).
+    pub fn count_less_than_or_equal_to(&self, value: f64) -> u64 {
+        self.snapshot().count_less_than_or_equal_to(value)
+    }
+
+    /// Lock the shard of the current thread. If it's busy (e.g. because a snapshot is being
+    /// taken), try other shards first instead of waiting.
+    fn lock_shard(&self) -> MutexGuard<'_, Histogram> {
+        let home = shard_index() % self.shards.len();
+        (0..self.shards.len())
+            .map(|i| &self.shards[(home + i) % self.shards.len()])
+            .find_map(|shard| shard.try_lock().ok())
+            .unwrap_or_else(|| lock(&self.shards[home]))
+    }
+}
+
+/// Lock a shard. Histograms are never left in an inconsistent state by a panic, so
+/// poisoned locks are simply ignored.
+fn lock(shard: &Mutex<Histogram>) -> MutexGuard<'_, Histogram> {
+    shard.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Returns the index of the shard of the current thread. Threads are assigned
+/// to shards in a round-robin fashion.
+fn shard_index() -> usize {
+    static NEXT_SHARD_INDEX: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static SHARD_INDEX: Cell<Option<usize>> = const { Cell::new(None) };
+    }
+
+    SHARD_INDEX.with(|index| match index.get() {
+        Some(index) => index,
+        None => {
+            let new_index = NEXT_SHARD_INDEX.fetch_add(1, Ordering::Relaxed);
+            index.set(Some(new_index));
+            new_index
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new() {
+        let h = ConcurrentHistogram::with_shards(5, 3);
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.shards(), 3);
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.min(), None);
+        assert_eq!(h.max(), None);
+        assert_eq!(h.mean(), None);
+        assert_eq!(h.quantile(0.5), None);
+        assert_eq!(h.snapshot().bins(), &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram size must be greater than 0")]
+    fn new_invalid_size() {
+        ConcurrentHistogram::new(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "number of shards must be greater than 0")]
+    fn new_invalid_shards() {
+        ConcurrentHistogram::with_shards(5, 0);
+    }
+
+    #[test]
+    fn insert_concurrently() {
+        let h = ConcurrentHistogram::with_shards(32, 4);
+        std::thread::scope(|s| {
+            for t in 0..8 {
+                let h = &h;
+                s.spawn(move || {
+                    for i in 0..1000 {
+                        h.insert((i * 8 + t) as f64);
+                    }
+                });
+            }
+        });
+
+        let expected = Histogram::from_iter(32, (0..8000).map(|v| v as f64));
+        let snapshot = h.snapshot();
+
+        assert_eq!(h.count(), 8000);
+        assert_eq!(h.min(), Some(0.0));
+        assert_eq!(h.max(), Some(7999.0));
+        assert_eq!(h.sum(), expected.sum());
+        assert_eq!(snapshot.size(), 32);
+        assert_eq!(snapshot.count(), 8000);
+        for q in [0.1, 0.25, 0.5, 0.75, 0.9] {
+            assert_relative_eq!(
+                h.quantile(q).unwrap(),
+                expected.quantile(q).unwrap(),
+                max_relative = 0.05
+            );
+        }
+    }
+
+    #[test]
+    fn insert_invalid_value() {
+        let h = ConcurrentHistogram::with_shards(5, 1);
+        h.insert(42.0);
+
+        let result = std::panic::catch_unwind(|| h.insert(f64::NAN));
+        assert!(result.is_err());
+
+        // the histogram is still usable
+        h.insert(-42.0);
+        assert_eq!(h.count(), 2);
+    }
+
+    #[test]
+    fn merge() {
+        let h = ConcurrentHistogram::with_shards(5, 2);
+        h.insert(42.0);
+        h.merge(&Histogram::from_iter(5, [1.0, -7.6, 0.0]));
+
+        assert_eq!(h.count(), 4);
+        assert_eq!(h.min(), Some(-7.6));
+        assert_eq!(h.max(), Some(42.0));
+        assert_eq!(h.rank(42.0), Some(1.0));
+        assert_eq!(h.count_less_than_or_equal_to(-10.0), 0);
+    }
+}
@@ -0,0 +1,174 @@
+use std::mem;
+
+use crate::bin::Bin;
+use crate::error::Error;
+use crate::histogram::{Bins, Histogram};
+use crate::sum::CompensatedSum;
+
+/// The maximum size of restored histograms: bins of larger histograms (and the extra slot,
+/// which is reserved for updates) can't be allocated.
+pub(crate) const MAX_SIZE: usize = isize::MAX as usize / mem::size_of::<Bin>() - 1;
+
+/// The data of a histogram in the form, which is serialized. Settings of histograms
+/// (e.g. policies) are not serialized.
+///
+/// The order of fields and their types define the serialization format: non-self-describing
+/// formats (e.g. bincode or postcard) rely on them, so they must never be changed.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    rkyv(archived = ArchivedHistogram)
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HistogramData {
+    pub(crate) size: u64,
+    pub(crate) count: u64,
+    pub(crate) bins: Vec<BinData>,
+    pub(crate) min_value: Option<f64>,
+    pub(crate) max_value: Option<f64>,
+    pub(crate) sum: f64,
+    pub(crate) nan_count: u64,
+    pub(crate) underflow_count: u64,
+    pub(crate) overflow_count: u64,
+}
+
+/// A bin in the form, which is serialized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    rkyv(derive(Clone, Copy))
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BinData {
+    pub(crate) value: f64,
+    pub(crate) count: u64,
+}
+
+impl From<&Histogram> for HistogramData {
+    fn from(h: &Histogram) -> Self {
+        HistogramData {
+            size: h.size() as u64,
+            count: h.count(),
+            bins: h
+                .bins()
+                .iter()
+                .map(|bin| BinData {
+                    value: bin.value(),
+                    count: bin.count(),
+                })
+                .collect(),
+            min_value: h.min(),
+            max_value: h.max(),
+            sum: h.sum(),
+            nan_count: h.nan_count(),
+            underflow_count: h.underflow_count(),
+            overflow_count: h.overflow_count(),
+        }
+    }
+}
+
+impl TryFrom<HistogramData> for Histogram {
+    type Error = Error;
+
+    /// Restore a histogram from its serialized data. Returns an error, if the data is not
+    /// consistent, so that corrupted data can't be used to create a broken histogram.
+    fn try_from(data: HistogramData) -> Result<Self, Self::Error> {
+        let invalid = |reason: &str| Err(Error::InvalidFormat(reason.into()));
+
+        let size = match usize::try_from(data.size) {
+            Ok(size) if size > MAX_SIZE => return invalid("the histogram size is too large"),
+            Ok(size) if size > 0 => size,
+            Err(_) => return invalid("the histogram size is too large"),
+            _ => return invalid("histogram size must be greater than 0"),
+        };
+        if data.bins.len() > size {
+            return invalid("the number of bins exceeds the histogram size");
+        }
+
+        let bins = data
+            .bins
+            .iter()
+            .map(|bin| Bin::try_new(bin.value, bin.count))
+            .collect::<Result<Bins, Error>>()?;
+        if bins
+            .windows(2)
+            .any(|pair| pair[0].value() > pair[1].value())
+        {
+            return invalid("bins must be sorted in the ascending order");
+        }
+        if bins
+            .iter()
+            .try_fold(0u64, |total, bin| total.checked_add(bin.count()))
+            != Some(data.count)
+        {
+            return invalid("the count does not match the counts of bins");
+        }
+        if !data.sum.is_finite() {
+            return invalid("the sum must be finite");
+        }
+
+        match (bins.first(), bins.last(), data.min_value, data.max_value) {
+            (None, None, None, None) => {}
+            (Some(first), Some(last), Some(min_value), Some(max_value))
+                if min_value <= first.value() && max_value >= last.value() => {}
+            _ => return invalid("min and max values do not match the bins"),
+        }
+
+        let mut sum = CompensatedSum::default();
+        sum.add(data.sum);
+
+        let mut h = Histogram::from_raw_parts(size, bins, data.min_value, data.max_value, sum);
+        h.set_special_counts(data.nan_count, data.underflow_count, data.overflow_count);
+
+        Ok(h)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let h = Histogram::from_iter(5, (0..100).map(|v| v as f64));
+        let data = HistogramData::from(&h);
+        assert_eq!(data.size, 5);
+        assert_eq!(data.count, 100);
+        assert_eq!(data.bins.len(), 5);
+
+        let h2 = Histogram::try_from(data.clone()).unwrap();
+        assert_eq!(h2.bins(), h.bins());
+        assert_eq!(h2.min(), h.min());
+        assert_eq!(h2.max(), h.max());
+        assert_eq!(h2.sum(), h.sum());
+        assert_eq!(HistogramData::from(&h2), data);
+
+        let empty = HistogramData::from(&Histogram::new(3));
+        assert_eq!(Histogram::try_from(empty).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn invalid() {
+        let valid = HistogramData::from(&Histogram::from_iter(3, [1.0, 2.0]));
+        let cases: Vec<fn(&mut HistogramData)> = vec![
+            |data| data.size = 0,
+            |data| data.size = 1,
+            |data| data.size = u64::MAX,
+            |data| data.count = 3,
+            |data| data.bins[0].count = 0,
+            |data| data.bins[0].value = f64::NAN,
+            |data| data.bins.swap(0, 1),
+            |data| data.min_value = Some(1.5),
+            |data| data.max_value = None,
+            |data| data.sum = f64::INFINITY,
+            |data| data.bins.clear(),
+        ];
+        for case in cases {
+            let mut data = valid.clone();
+            case(&mut data);
+            assert!(Histogram::try_from(data).is_err());
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+
+use crate::bin::Bin;
+use crate::histogram::Histogram;
+
+/// A DDSketch-style sketch, which counts values in logarithmically sized buckets, used to
+/// exchange data with DDSketch implementations (e.g. the ones in Datadog client libraries).
+///
+/// A positive value `v` is counted in the bucket with the index `ceil(log(v, gamma))`, where
+/// `gamma = (1 + relative_accuracy) / (1 - relative_accuracy)`, i.e. the bucket with the index
+/// `i` covers the interval (`gamma^(i - 1)`; `gamma^i`]. Negative values are counted in a
+/// separate store of buckets by their absolute value, and zeros are counted separately too.
+///
+/// ```
+/// use bhtt::{DDSketch, Histogram};
+///
+/// let mut sketch = DDSketch::new(0.01);
+/// for value in 1..=1000 {
+///     sketch.insert(value as f64);
+/// }
+///
+/// let h = Histogram::from(&sketch);
+/// assert_eq!(h.count(), 1000);
+/// assert!((h.quantile(0.5).unwrap() - 500.0).abs() < 10.0);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DDSketch {
+    gamma: f64,
+    positive: BTreeMap<i32, u64>,
+    negative: BTreeMap<i32, u64>,
+    zero_count: u64,
+}
+
+impl DDSketch {
+    /// Create a new empty DDSketch with the given relative accuracy. The relative accuracy
+    /// must be in the range (0.0; 1.0), or the function will panic.
+    ///
+    /// ```
+    /// use bhtt::DDSketch;
+    ///
+    /// let sketch = DDSketch::new(0.01);
+    /// assert!((sketch.relative_accuracy() - 0.01).abs() < 1e-12);
+    /// assert_eq!(sketch.count(), 0);
+    /// ```
+    pub fn new(relative_accuracy: f64) -> DDSketch {
+        assert!(
+            relative_accuracy > 0.0 && relative_accuracy < 1.0,
+            "relative accuracy must be in the range (0.0; 1.0)"
+        );
+
+        DDSketch::with_gamma((1.0 + relative_accuracy) / (1.0 - relative_accuracy))
+    }
+
+    /// Create a new empty DDSketch with the given base of the logarithmic bucket boundaries
+    /// (as it is specified in the index mapping of serialized DDSketches). `gamma` must be
+    /// finite and greater than 1.0, or the function will panic.
+    ///
+    /// ```
+    /// use bhtt::DDSketch;
+    ///
+    /// let sketch = DDSketch::with_gamma(1.02);
+    /// assert_eq!(sketch.gamma(), 1.02);
+    /// ```
+    pub fn with_gamma(gamma: f64) -> DDSketch {
+        assert!(
+            gamma.is_finite() && gamma > 1.0,
+            "gamma must be finite and greater than 1.0"
+        );
+
+        DDSketch {
+            gamma,
+            positive: BTreeMap::new(),
+            negative: BTreeMap::new(),
+            zero_count: 0,
+        }
+    }
+
+    /// Returns the base of the logarithmic bucket boundaries.
+    pub fn gamma(&self) -> f64 {
+        self.gamma
+    }
+
+    /// Returns the relative accuracy of the sketch.
+    pub fn relative_accuracy(&self) -> f64 {
+        (self.gamma - 1.0) / (self.gamma + 1.0)
+    }
+
+    /// Returns the number of values in the sketch.
+    pub fn count(&self) -> u64 {
+        self.positive.values().sum::<u64>() + self.negative.values().sum::<u64>() + self.zero_count
+    }
+
+    /// Returns the number of zeros in the sketch.
+    pub fn zero_count(&self) -> u64 {
+        self.zero_count
+    }
+
+    /// Returns the non-empty buckets of positive values as `(index, count)` pairs in the
+    /// ascending order of indices.
+    pub fn positive_buckets(&self) -> impl Iterator<Item = (i32, u64)> + '_ {
+        self.positive.iter().map(|(&index, &count)| (index, count))
+    }
+
+    /// Returns the non-empty buckets of negative values as `(index, count)` pairs in the
+    /// ascending order of indices (i.e. the descending order of values).
+    pub fn negative_buckets(&self) -> impl Iterator<Item = (i32, u64)> + '_ {
+        self.negative.iter().map(|(&index, &count)| (index, count))
+    }
+
+    /// Add `count` to the bucket of positive values with the given index.
+    ///
+    /// ```
+    /// use bhtt::DDSketch;
+    ///
+    /// let mut sketch = DDSketch::new(0.01);
+    /// sketch.add_positive(230, 5);
+    /// sketch.add_positive(230, 2);
+    ///
+    /// assert_eq!(sketch.positive_buckets().collect::<Vec<_>>(), vec![(230, 7)]);
+    /// ```
+    pub fn add_positive(&mut self, index: i32, count: u64) {
+        if count > 0 {
+            *self.positive.entry(index).or_insert(0) += count;
+        }
+    }
+
+    /// Add `count` to the bucket of negative values with the given index.
+    pub fn add_negative(&mut self, index: i32, count: u64) {
+        if count > 0 {
+            *self.negative.entry(index).or_insert(0) += count;
+        }
+    }
+
+    /// Add `count` to the number of zeros.
+    pub fn add_zero(&mut self, count: u64) {
+        self.zero_count += count;
+    }
+
+    /// Update the sketch by inserting a new value. The value must not be NaN or infinite,
+    /// or the function will panic.
+    pub fn insert(&mut self, value: f64) {
+        self.insert_many(value, 1);
+    }
+
+    /// Returns the index of the bucket, which a (positive) value is counted in.
+    pub fn index(&self, value: f64) -> i32 {
+        (value.ln() / self.gamma.ln()).ceil() as i32
+    }
+
+    /// Returns the value, which represents the bucket with the given index. The relative
+    /// difference between it and any value in the bucket does not exceed the relative accuracy.
+    pub fn value(&self, index: i32) -> f64 {
+        2.0 * self.gamma.powi(index) / (1.0 + self.gamma)
+    }
+
+    fn insert_many(&mut self, value: f64, count: u64) {
+        assert!(!value.is_nan(), "value must not be NaN");
+        assert!(value.is_finite(), "value must be finite");
+
+        if value > 0.0 {
+            self.add_positive(self.index(value), count);
+        } else if value < 0.0 {
+            self.add_negative(self.index(-value), count);
+        } else {
+            self.add_zero(count);
+        }
+    }
+}
+
+impl From<&DDSketch> for Histogram {
+    /// Create a new Histogram of the default size (see [`Histogram::DEFAULT_SIZE`]) from a
+    /// DDSketch. Every non-empty bucket becomes a bin, which value is the value representing
+    /// the bucket (see [`DDSketch::value`]), and these bins are then merged down to the size
+    /// of the histogram.
+    fn from(sketch: &DDSketch) -> Self {
+        let negative = sketch
+            .negative
+            .iter()
+            .rev()
+            .map(|(&index, &count)| Bin::new(-sketch.value(index), count));
+        let zero = Some(sketch.zero_count)
+            .filter(|&count| count > 0)
+            .map(|count| Bin::new(0.0, count));
+        let positive = sketch
+            .positive
+            .iter()
+            .map(|(&index, &count)| Bin::new(sketch.value(index), count));
+
+        Histogram::from_sorted_iter(
+            Histogram::DEFAULT_SIZE,
+            negative.chain(zero).chain(positive),
+        )
+    }
+}
+
+impl Histogram {
+    /// Returns a best-effort DDSketch representation of the histogram with the given relative
+    /// accuracy: the count of every bin is added to the bucket, which the value of the bin
+    /// belongs to. As bins are weighted averages of values, the accuracy guarantee of the
+    /// DDSketch doesn't hold for the original values. The relative accuracy must be in the
+    /// range (0.0; 1.0), or the function will panic.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(5, [-2.0, 0.0, 1.0, 100.0, 100.0]);
+    /// let sketch = h.to_ddsketch(0.01);
+    ///
+    /// assert_eq!(sketch.count(), 5);
+    /// assert_eq!(sketch.zero_count(), 1);
+    /// assert_eq!(sketch.negative_buckets().count(), 1);
+    /// assert_eq!(sketch.positive_buckets().count(), 2);
+    /// ```
+    pub fn to_ddsketch(&self, relative_accuracy: f64) -> DDSketch {
+        let mut sketch = DDSketch::new(relative_accuracy);
+        for bin in self.bins() {
+            sketch.insert_many(bin.value(), bin.count());
+        }
+
+        sketch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new() {
+        let sketch = DDSketch::new(0.02);
+        assert_relative_eq!(sketch.gamma(), 1.02 / 0.98);
+        assert_relative_eq!(sketch.relative_accuracy(), 0.02);
+        assert_eq!(sketch.count(), 0);
+        assert_eq!(sketch.zero_count(), 0);
+        assert_eq!(sketch.positive_buckets().count(), 0);
+        assert_eq!(sketch.negative_buckets().count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "relative accuracy must be in the range (0.0; 1.0)")]
+    fn new_invalid_accuracy() {
+        DDSketch::new(1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "gamma must be finite and greater than 1.0")]
+    fn with_gamma_invalid() {
+        DDSketch::with_gamma(0.5);
+    }
+
+    #[test]
+    fn index_and_value() {
+        let sketch = DDSketch::new(0.01);
+        for value in [1e-300, 1e-5, 0.5, 1.0, 3.0, 1234.5, 1e300] {
+            let index = sketch.index(value);
+            let lower = sketch.gamma().powi(index - 1);
+            let upper = sketch.gamma().powi(index);
+            assert!(lower < value && value <= upper * (1.0 + 1e-12));
+            assert_relative_eq!(sketch.value(index), value, max_relative = 0.01);
+        }
+    }
+
+    #[test]
+    fn insert() {
+        let mut sketch = DDSketch::new(0.01);
+        for value in [-3.0, -3.0, 0.0, 2.0, 2.0, 2.0, 50.0] {
+            sketch.insert(value);
+        }
+        sketch.add_positive(0, 0);
+
+        assert_eq!(sketch.count(), 7);
+        assert_eq!(sketch.zero_count(), 1);
+        assert_eq!(
+            sketch.negative_buckets().collect::<Vec<_>>(),
+            vec![(sketch.index(3.0), 2)]
+        );
+        assert_eq!(
+            sketch.positive_buckets().collect::<Vec<_>>(),
+            vec![(sketch.index(2.0), 3), (sketch.index(50.0), 1)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "value must be finite")]
+    fn insert_infinite() {
+        DDSketch::new(0.01).insert(f64::INFINITY);
+    }
+
+    #[test]
+    fn into_histogram() {
+        let mut sketch = DDSketch::new(0.01);
+        for value in [-3.0, -3.0, -0.5, 0.0, 2.0, 2.0, 2.0, 50.0] {
+            sketch.insert(value);
+        }
+
+        let h = Histogram::from(&sketch);
+        assert_eq!(h.count(), 8);
+        let expected = [(-3.0, 2), (-0.5, 1), (0.0, 1), (2.0, 3), (50.0, 1)];
+        assert_eq!(h.bins().len(), expected.len());
+        for (bin, (value, count)) in h.bins().iter().zip(expected) {
+            assert_relative_eq!(bin.value(), value, max_relative = 0.01);
+            assert_eq!(bin.count(), count);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let h = Histogram::from_iter(32, (-500..1000).map(|v| v as f64));
+        let h2 = Histogram::from(&h.to_ddsketch(0.001));
+
+        assert_eq!(h2.count(), h.count());
+        for (bin, expected) in h2.bins().iter().zip(h.bins()) {
+            assert_relative_eq!(bin.value(), expected.value(), max_relative = 0.001);
+            assert_eq!(bin.count(), expected.count());
+        }
+    }
+}
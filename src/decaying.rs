@@ -0,0 +1,373 @@
+use std::time::{Duration, Instant};
+
+use crate::weighted::WeightedHistogram;
+
+/// How the weights of a [`DecayingHistogram`] decrease.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Decay {
+    /// Weights are halved every `half_life` units of time.
+    HalfLife {
+        half_life: Duration,
+        last_update: Instant,
+    },
+    /// Weights are multiplied by a factor on every insert.
+    PerInsert(f64),
+}
+
+/// A variant of [`WeightedHistogram`], in which the weights of bins decrease
+/// exponentially over time (or on every insert), so that recent values dominate
+/// the estimates of quantiles.
+///
+/// The exact minimum and maximum values are not tracked, as they would be dominated by
+/// old outliers. Instead, the values of the outermost bins are used as the boundaries
+/// of the distribution.
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use bhtt::DecayingHistogram;
+///
+/// let start = Instant::now();
+/// let mut h = DecayingHistogram::with_start(64, Duration::from_secs(60), start);
+/// for _ in 0..100 {
+///     h.insert_at(100.0, start);
+/// }
+///
+/// // ten minutes later the old values are practically forgotten
+/// for _ in 0..100 {
+///     h.insert_at(10.0, start + Duration::from_secs(600));
+/// }
+/// assert_eq!(h.median(), Some(10.0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct DecayingHistogram {
+    histogram: WeightedHistogram,
+    decay: Decay,
+}
+
+impl DecayingHistogram {
+    /// Create a new DecayingHistogram with the given number of bins, in which weights
+    /// are halved every `half_life` units of time starting from now.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use bhtt::DecayingHistogram;
+    ///
+    /// let h = DecayingHistogram::new(5, Duration::from_secs(60));
+    /// assert_eq!(h.size(), 5);
+    /// assert_eq!(h.count(), 0.0);
+    /// ```
+    pub fn new(size: usize, half_life: Duration) -> DecayingHistogram {
+        DecayingHistogram::with_start(size, half_life, Instant::now())
+    }
+
+    /// Create a new DecayingHistogram like [`DecayingHistogram::new`], but with the time
+    /// starting at the given instant.
+    pub fn with_start(size: usize, half_life: Duration, start: Instant) -> DecayingHistogram {
+        assert!(
+            half_life > Duration::ZERO,
+            "half-life must be greater than 0"
+        );
+
+        DecayingHistogram::with_decay(
+            size,
+            Decay::HalfLife {
+                half_life,
+                last_update: start,
+            },
+        )
+    }
+
+    /// Create a new DecayingHistogram with the given number of bins, in which weights
+    /// are multiplied by `factor` before every insert. `factor` must be in the range
+    /// (0.0; 1.0], or the function will panic.
+    ///
+    /// ```
+    /// use bhtt::DecayingHistogram;
+    ///
+    /// let mut h = DecayingHistogram::per_insert(5, 0.5);
+    /// h.insert(1.0);
+    /// h.insert(2.0);
+    /// assert_eq!(h.count(), 1.5);
+    /// ```
+    pub fn per_insert(size: usize, factor: f64) -> DecayingHistogram {
+        assert_factor(factor);
+
+        DecayingHistogram::with_decay(size, Decay::PerInsert(factor))
+    }
+
+    fn with_decay(size: usize, decay: Decay) -> DecayingHistogram {
+        DecayingHistogram {
+            histogram: WeightedHistogram::new(size),
+            decay,
+        }
+    }
+
+    /// Returns the size of the histogram.
+    pub fn size(&self) -> usize {
+        self.histogram.size()
+    }
+
+    /// Returns the total (decayed) weight of values in the histogram.
+    pub fn count(&self) -> f64 {
+        self.histogram.count()
+    }
+
+    /// Returns the value of the leftmost bin or `None` if the histogram is empty.
+    pub fn min(&self) -> Option<f64> {
+        self.histogram.min()
+    }
+
+    /// Returns the value of the rightmost bin or `None` if the histogram is empty.
+    pub fn max(&self) -> Option<f64> {
+        self.histogram.max()
+    }
+
+    /// Returns the weighted mean of values or `None` if the histogram is empty.
+    ///
+    /// ```
+    /// use bhtt::DecayingHistogram;
+    ///
+    /// let mut h = DecayingHistogram::per_insert(5, 0.5);
+    /// assert_eq!(h.mean(), None);
+    ///
+    /// h.insert(1.0);
+    /// h.insert(4.0);
+    /// assert_eq!(h.mean(), Some(3.0));
+    /// ```
+    pub fn mean(&self) -> Option<f64> {
+        self.histogram.mean()
+    }
+
+    /// Returns an approximated value of the `q`'th quantile of the values or `None`
+    /// if the histogram is empty. `q` must be in the range [0.0; 1.0], or the function
+    /// will panic.
+    ///
+    /// ```
+    /// use bhtt::DecayingHistogram;
+    ///
+    /// let mut h = DecayingHistogram::per_insert(5, 1.0);
+    /// for value in vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+    ///     h.insert(value);
+    /// }
+    ///
+    /// assert_eq!(h.quantile(0.0), Some(-5.4));
+    /// assert_eq!(h.quantile(0.5), Some(4.75));
+    /// // the value of the rightmost bin rather than the exact maximum
+    /// assert_eq!(h.quantile(1.0), Some(8.725));
+    /// ```
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        self.histogram.quantile(q)
+    }
+
+    /// Returns an approximated value of the median or `None` if the histogram is empty.
+    pub fn median(&self) -> Option<f64> {
+        self.quantile(0.5)
+    }
+
+    /// Insert a new value into the histogram, decaying the existing weights first.
+    pub fn insert(&mut self, value: f64) {
+        self.insert_at(value, Instant::now());
+    }
+
+    /// Insert a new value into the histogram as observed at the given instant, decaying
+    /// the existing weights first. `value` must be finite, or the function will panic.
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use bhtt::DecayingHistogram;
+    ///
+    /// let start = Instant::now();
+    /// let mut h = DecayingHistogram::with_start(5, Duration::from_secs(10), start);
+    /// h.insert_at(1.0, start);
+    /// h.insert_at(2.0, start + Duration::from_secs(10));
+    /// assert_eq!(h.count(), 1.5);
+    /// ```
+    pub fn insert_at(&mut self, value: f64, now: Instant) {
+        self.insert_weighted_at(value, 1.0, now);
+    }
+
+    /// Insert a new value with the given weight into the histogram, decaying the existing
+    /// weights first (see [`WeightedHistogram::insert_weighted`]).
+    pub fn insert_weighted(&mut self, value: f64, weight: f64) {
+        self.insert_weighted_at(value, weight, Instant::now());
+    }
+
+    /// Insert a new value with the given weight into the histogram as observed at the given
+    /// instant, decaying the existing weights first.
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use bhtt::DecayingHistogram;
+    ///
+    /// let start = Instant::now();
+    /// let mut h = DecayingHistogram::with_start(5, Duration::from_secs(10), start);
+    /// h.insert_weighted_at(1.0, 4.0, start);
+    /// h.insert_weighted_at(2.0, 0.5, start + Duration::from_secs(20));
+    /// assert_eq!(h.count(), 1.5);
+    /// ```
+    pub fn insert_weighted_at(&mut self, value: f64, weight: f64, now: Instant) {
+        match self.decay {
+            Decay::HalfLife { .. } => self.decay_to(now),
+            Decay::PerInsert(factor) => self.decay(factor),
+        }
+
+        self.histogram.insert_weighted(value, weight);
+    }
+
+    /// Multiply the weights of all bins by `factor`, which must be in the range
+    /// (0.0; 1.0], or the function will panic.
+    ///
+    /// ```
+    /// use bhtt::DecayingHistogram;
+    ///
+    /// let mut h = DecayingHistogram::per_insert(5, 1.0);
+    /// h.insert(1.0);
+    /// h.insert(2.0);
+    ///
+    /// h.decay(0.25);
+    /// assert_eq!(h.count(), 0.5);
+    /// ```
+    pub fn decay(&mut self, factor: f64) {
+        assert_factor(factor);
+
+        self.histogram.scale(factor);
+    }
+
+    /// Decay the weights of all bins according to the time elapsed since the last update.
+    /// Does nothing for histograms that decay on every insert.
+    pub fn decay_to(&mut self, now: Instant) {
+        if let Decay::HalfLife {
+            half_life,
+            ref mut last_update,
+        } = self.decay
+        {
+            if now <= *last_update {
+                return;
+            }
+
+            let elapsed = now - *last_update;
+            *last_update = now;
+
+            let factor = 0.5f64.powf(elapsed.as_secs_f64() / half_life.as_secs_f64());
+            if factor > 0.0 {
+                self.decay(factor);
+            } else {
+                self.histogram.clear();
+            }
+        }
+    }
+}
+
+fn assert_factor(factor: f64) {
+    assert!(
+        factor > 0.0 && factor <= 1.0,
+        "decay factor must be in the range (0.0; 1.0]"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new() {
+        let h = DecayingHistogram::new(5, Duration::from_secs(1));
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.count(), 0.0);
+        assert_eq!(h.min(), None);
+        assert_eq!(h.max(), None);
+        assert_eq!(h.mean(), None);
+        assert_eq!(h.quantile(0.5), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram size must be greater than 0")]
+    fn new_invalid_size() {
+        DecayingHistogram::new(0, Duration::from_secs(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "half-life must be greater than 0")]
+    fn new_invalid_half_life() {
+        DecayingHistogram::new(5, Duration::ZERO);
+    }
+
+    #[test]
+    #[should_panic(expected = "decay factor must be in the range (0.0; 1.0]")]
+    fn per_insert_invalid_factor() {
+        DecayingHistogram::per_insert(5, 1.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "value must not be NaN")]
+    fn insert_nan() {
+        DecayingHistogram::per_insert(5, 0.5).insert(f64::NAN);
+    }
+
+    #[test]
+    fn insert_without_decay() {
+        let values = [1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2];
+        let mut h = DecayingHistogram::per_insert(5, 1.0);
+        for value in values {
+            h.insert(value);
+        }
+
+        let mut expected = WeightedHistogram::new(5);
+        for value in values {
+            expected.insert(value);
+        }
+        assert_eq!(h.count(), expected.count());
+        for i in 0..=10 {
+            let q = i as f64 / 10.0;
+            assert_eq!(h.quantile(q), expected.quantile(q));
+        }
+    }
+
+    #[test]
+    fn decay_over_time() {
+        let start = Instant::now();
+        let mut h = DecayingHistogram::with_start(5, Duration::from_secs(10), start);
+        h.insert_at(1.0, start);
+        h.insert_at(2.0, start);
+        assert_eq!(h.count(), 2.0);
+
+        // no time has passed
+        h.decay_to(start);
+        assert_eq!(h.count(), 2.0);
+
+        h.decay_to(start + Duration::from_secs(20));
+        assert_relative_eq!(h.count(), 0.5);
+
+        // weights underflow eventually
+        h.decay_to(start + Duration::from_secs(1_000_000));
+        assert_eq!(h.count(), 0.0);
+        assert_eq!(h.median(), None);
+    }
+
+    #[test]
+    fn quantile_favours_recent_values() {
+        let mut h = DecayingHistogram::per_insert(16, 0.99);
+        for i in 0..1000 {
+            h.insert(if i < 500 { 100.0 } else { 10.0 });
+        }
+
+        assert_relative_eq!(h.median().unwrap(), 10.0);
+        assert_relative_eq!(h.quantile(0.0).unwrap(), 10.0);
+        assert!(h.quantile(1.0).unwrap() > 10.0);
+    }
+
+    #[test]
+    fn quantile() {
+        let mut h = DecayingHistogram::per_insert(5, 1.0);
+        for value in [1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+            h.insert(value);
+        }
+
+        let mut previous = f64::NEG_INFINITY;
+        for i in 0..=100 {
+            let value = h.quantile(i as f64 / 100.0).unwrap();
+            assert!(value >= previous);
+            previous = value;
+        }
+    }
+}
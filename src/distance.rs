@@ -0,0 +1,284 @@
+use crate::histogram::Histogram;
+
+impl Histogram {
+    /// Returns the earth mover's distance (the 1st Wasserstein distance) between the
+    /// distributions of values in this and the `other` histograms, or `None` if either of them
+    /// is empty. The distance is the minimum "work" (an amount of probability mass times the
+    /// distance it's moved by) needed to transform one distribution into the other one. It's
+    /// measured in the units of values, e.g. shifting all values by `d` results in
+    /// the distance of `d`.
+    ///
+    /// The distance is computed exactly on the binned representation of the distributions,
+    /// i.e. every bin is treated as a point mass, which is proportional to its count. This
+    /// makes it more robust than comparing quantiles or the Kolmogorov-Smirnov statistic,
+    /// as it takes into account both how much and how far the distribution has changed.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h1 = Histogram::from_iter(5, [(1.0, 1), (2.0, 1)]);
+    /// let h2 = Histogram::from_iter(5, [(1.0, 1), (4.0, 1)]);
+    ///
+    /// // half of the mass is moved by 2.0
+    /// assert_eq!(h1.emd(&h2), Some(1.0));
+    /// assert_eq!(h1.emd(&h1), Some(0.0));
+    /// assert_eq!(h1.emd(&Histogram::new(5)), None);
+    /// ```
+    pub fn emd(&self, other: &Histogram) -> Option<f64> {
+        if self.is_empty() || other.is_empty() {
+            return None;
+        }
+
+        // the distance is the area between the cumulative distribution functions, which are
+        // step functions changing at the values of bins
+        let (total1, total2) = (self.count() as f64, other.count() as f64);
+        let (mut bins1, mut bins2) = (
+            self.bins().iter().peekable(),
+            other.bins().iter().peekable(),
+        );
+        let (mut cumulative1, mut cumulative2) = (0u64, 0u64);
+        let mut previous: Option<f64> = None;
+        let mut distance = 0.0;
+
+        loop {
+            let value = match (bins1.peek(), bins2.peek()) {
+                (Some(bin1), Some(bin2)) => bin1.value().min(bin2.value()),
+                (Some(bin1), None) => bin1.value(),
+                (None, Some(bin2)) => bin2.value(),
+                (None, None) => break,
+            };
+
+            if let Some(previous) = previous {
+                let difference = cumulative1 as f64 / total1 - cumulative2 as f64 / total2;
+                distance += difference.abs() * (value - previous);
+            }
+            while let Some(bin) = bins1.next_if(|bin| bin.value() == value) {
+                cumulative1 += bin.count();
+            }
+            while let Some(bin) = bins2.next_if(|bin| bin.value() == value) {
+                cumulative2 += bin.count();
+            }
+            previous = Some(value);
+        }
+
+        Some(distance)
+    }
+
+    /// Returns an estimate of the Kullback-Leibler divergence (in nats) of the distribution
+    /// of values in the `other` histogram from the distribution of values in this one, or
+    /// `None` if either of them is empty. The divergence is 0.0 for identical distributions,
+    /// and it grows as they drift apart, e.g. when the distribution of values in production
+    /// (`other`) drifts from the one observed at training time (`self`).
+    ///
+    /// Both distributions are discretized by estimating the counts of values in the same
+    /// buckets (see [`to_buckets`](Histogram::to_buckets)): the range of values of both
+    /// histograms is split into equal intervals, one per bin of the larger histogram.
+    /// `smoothing` is a pseudo-count added to every bucket (additive smoothing), so that
+    /// buckets, which are empty in the `other` histogram, don't make the divergence infinite.
+    /// It must be finite and non-negative, or the function will panic. Without smoothing the
+    /// divergence is infinite, if such buckets are not empty in this histogram.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let training = Histogram::from_iter(32, (0..1000).map(|v| v as f64));
+    /// let production = Histogram::from_iter(32, (0..1000).map(|v| v as f64 * 1.5));
+    ///
+    /// assert_eq!(training.kl_divergence(&training, 0.0), Some(0.0));
+    /// assert!(training.kl_divergence(&production, 0.5).unwrap() > 0.1);
+    ///
+    /// // values greater than 1000.0 are never observed at training time
+    /// assert_eq!(production.kl_divergence(&training, 0.0), Some(f64::INFINITY));
+    /// ```
+    pub fn kl_divergence(&self, other: &Histogram, smoothing: f64) -> Option<f64> {
+        let (p, q) = common_distributions(self, other, smoothing)?;
+
+        Some(
+            p.iter()
+                .zip(&q)
+                .filter(|(&p, _)| p > 0.0)
+                .map(|(&p, &q)| p * (p / q).ln())
+                .sum::<f64>()
+                // rounding errors must not make the divergence negative
+                .max(0.0),
+        )
+    }
+
+    /// Returns an estimate of the cross-entropy (in nats) of the distribution of values in
+    /// the `other` histogram relative to the distribution of values in this one, or `None`
+    /// if either of them is empty. Both distributions are discretized and smoothed the same
+    /// way as by [`kl_divergence`](Histogram::kl_divergence), so the cross-entropy is equal
+    /// to the entropy of the discretized distribution of this histogram plus the divergence. `smoothing` must be finite and
+    /// non-negative, or the function will panic.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h1 = Histogram::from_iter(2, [(0.0, 1), (1.0, 1)]);
+    /// let h2 = Histogram::from_iter(2, [(0.0, 1), (1.0, 3)]);
+    ///
+    /// // the entropy of a fair coin is ln(2)
+    /// assert_eq!(h1.cross_entropy(&h1, 0.0), Some(2f64.ln()));
+    /// assert!(h1.cross_entropy(&h2, 0.0).unwrap() > 2f64.ln());
+    /// ```
+    pub fn cross_entropy(&self, other: &Histogram, smoothing: f64) -> Option<f64> {
+        let (p, q) = common_distributions(self, other, smoothing)?;
+
+        Some(
+            p.iter()
+                .zip(&q)
+                .filter(|(&p, _)| p > 0.0)
+                .map(|(&p, &q)| -p * q.ln())
+                .sum(),
+        )
+    }
+}
+
+/// Returns the estimated counts of values of both histograms in the same `n` buckets, which
+/// split the range of values of both histograms into equal intervals, or `None` if either
+/// of the histograms is empty.
+pub(crate) fn common_buckets(
+    h1: &Histogram,
+    h2: &Histogram,
+    n: usize,
+) -> Option<(Vec<u64>, Vec<u64>)> {
+    let low = h1.min()?.min(h2.min()?);
+    let high = h1.max()?.max(h2.max()?);
+
+    // the last boundary is the upper end of the range, so the extra buckets of greater
+    // values are always empty
+    let boundaries: Vec<f64> = (1..n)
+        .map(|i| low + (high - low) * (i as f64 / n as f64))
+        .chain(std::iter::once(high))
+        .collect();
+
+    Some((
+        h1.to_buckets(&boundaries)[..n].to_vec(),
+        h2.to_buckets(&boundaries)[..n].to_vec(),
+    ))
+}
+
+/// Returns the probabilities of values of both histograms to be in the same buckets (see
+/// `common_buckets()`), after `smoothing` is added to the count of every bucket.
+fn common_distributions(
+    h1: &Histogram,
+    h2: &Histogram,
+    smoothing: f64,
+) -> Option<(Vec<f64>, Vec<f64>)> {
+    assert!(
+        smoothing.is_finite() && smoothing >= 0.0,
+        "smoothing must be finite and non-negative"
+    );
+
+    let (counts1, counts2) = common_buckets(h1, h2, h1.size().max(h2.size()))?;
+    let probabilities = |counts: Vec<u64>| {
+        let total = counts.iter().sum::<u64>() as f64 + smoothing * counts.len() as f64;
+        counts
+            .into_iter()
+            .map(|count| (count as f64 + smoothing) / total)
+            .collect()
+    };
+
+    Some((probabilities(counts1), probabilities(counts2)))
+}
+
+#[cfg(test)]
+mod tests {
+    use utilities::pseudo_random_integers;
+
+    use super::*;
+
+    #[test]
+    fn emd() {
+        let h1 = Histogram::from_iter(32, pseudo_random_integers(1, 10000));
+        assert_eq!(h1.emd(&h1), Some(0.0));
+
+        // shifting all values moves all the mass by the same distance
+        let mut h2 = h1.clone();
+        h2.map_values(1.0, 2.5);
+        assert_relative_eq!(h1.emd(&h2).unwrap(), 2.5, max_relative = 1e-12);
+        assert_eq!(h1.emd(&h2), h2.emd(&h1));
+
+        // samples of the same distribution are close (within 2% of the range of values)
+        let h3 = Histogram::from_iter(32, pseudo_random_integers(2, 10000));
+        assert!(h1.emd(&h3).unwrap() < 20.0);
+
+        // scaling values changes the distribution
+        let mut h4 = h1.clone();
+        h4.map_values(2.0, 0.0);
+        assert_relative_eq!(
+            h1.emd(&h4).unwrap(),
+            h1.mean().unwrap(),
+            max_relative = 1e-9
+        );
+    }
+
+    #[test]
+    fn emd_point_masses() {
+        let h1 = Histogram::from_iter(5, [(0.0, 1)]);
+        let h2 = Histogram::from_iter(5, [(3.0, 2)]);
+        assert_eq!(h1.emd(&h2), Some(3.0));
+
+        let h3 = Histogram::from_iter(5, [(0.0, 1), (3.0, 1)]);
+        assert_eq!(h1.emd(&h3), Some(1.5));
+        assert_eq!(h3.emd(&h2), Some(1.5));
+
+        assert_eq!(Histogram::new(5).emd(&Histogram::new(5)), None);
+        assert_eq!(Histogram::new(5).emd(&h1), None);
+    }
+
+    #[test]
+    fn kl_divergence() {
+        let h1 = Histogram::from_iter(32, pseudo_random_integers(1, 10000));
+        assert_eq!(h1.kl_divergence(&h1, 0.0), Some(0.0));
+        assert_eq!(h1.kl_divergence(&h1, 1.0), Some(0.0));
+
+        // samples of the same distribution are close
+        let h2 = Histogram::from_iter(32, pseudo_random_integers(2, 10000));
+        assert!(h1.kl_divergence(&h2, 0.5).unwrap() < 0.01);
+
+        // the divergence grows as the distributions drift apart, and it's not symmetric
+        let mut h3 = h1.clone();
+        h3.map_values(1.0, 100.0);
+        let mut h4 = h1.clone();
+        h4.map_values(1.0, 300.0);
+        let (d3, d4) = (
+            h1.kl_divergence(&h3, 0.5).unwrap(),
+            h1.kl_divergence(&h4, 0.5).unwrap(),
+        );
+        assert!(0.1 < d3 && d3 < d4);
+        assert_ne!(h1.kl_divergence(&h4, 0.5), h4.kl_divergence(&h1, 0.5));
+        assert_eq!(h1.kl_divergence(&h4, 0.0), Some(f64::INFINITY));
+
+        assert_eq!(h1.kl_divergence(&Histogram::new(5), 0.5), None);
+    }
+
+    #[test]
+    fn cross_entropy() {
+        let h1 = Histogram::from_iter(32, pseudo_random_integers(1, 10000));
+        let mut h2 = h1.clone();
+        h2.map_values(1.0, 100.0);
+        assert!(h1.cross_entropy(&h2, 0.5).unwrap() > h1.cross_entropy(&h1, 0.5).unwrap());
+
+        // the cross-entropy is the entropy plus the divergence, if both histograms have the
+        // same range of values, and thus are discretized the same way
+        let h3 = Histogram::from_iter(4, [(0.0, 4), (1.0, 1), (2.0, 2), (3.0, 1)]);
+        let h4 = Histogram::from_iter(4, [(0.0, 1), (1.0, 3), (2.0, 1), (3.0, 3)]);
+        let entropy = h3.cross_entropy(&h3, 0.5).unwrap();
+        assert!(entropy > 0.0);
+        assert_relative_eq!(
+            h3.cross_entropy(&h4, 0.5).unwrap(),
+            entropy + h3.kl_divergence(&h4, 0.5).unwrap(),
+            max_relative = 1e-12
+        );
+
+        assert_eq!(Histogram::new(5).cross_entropy(&h1, 0.5), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "smoothing must be finite and non-negative")]
+    fn kl_divergence_invalid_smoothing() {
+        let h = Histogram::from_iter(5, [1.0]);
+        h.kl_divergence(&h, -1.0);
+    }
+}
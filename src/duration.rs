@@ -0,0 +1,189 @@
+use std::time::Duration;
+
+use crate::histogram::Histogram;
+
+/// Defines the unit, in which [`Histogram::insert_duration`] records durations as values
+/// of a histogram.
+///
+/// ```
+/// use std::time::Duration;
+/// use bhtt::{DurationUnit, Histogram};
+///
+/// let mut h = Histogram::new(5);
+/// h.set_duration_unit(DurationUnit::Milliseconds);
+/// h.insert_duration(Duration::from_micros(1500));
+///
+/// assert_eq!(h.max(), Some(1.5));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DurationUnit {
+    /// Durations are recorded as (fractional) seconds.
+    Seconds,
+    /// Durations are recorded as (fractional) milliseconds.
+    Milliseconds,
+    /// Durations are recorded as (fractional) microseconds.
+    Microseconds,
+    /// Durations are recorded as whole nanoseconds.
+    #[default]
+    Nanoseconds,
+}
+
+impl DurationUnit {
+    /// Returns the number of nanoseconds in one unit.
+    fn nanos(self) -> f64 {
+        match self {
+            DurationUnit::Seconds => 1e9,
+            DurationUnit::Milliseconds => 1e6,
+            DurationUnit::Microseconds => 1e3,
+            DurationUnit::Nanoseconds => 1.0,
+        }
+    }
+
+    /// Converts a duration into a value in this unit.
+    pub(crate) fn to_value(self, duration: Duration) -> f64 {
+        duration.as_nanos() as f64 / self.nanos()
+    }
+
+    /// Converts a value in this unit into a duration rounded to whole nanoseconds. Negative
+    /// values are clamped to zero, and values too large to be represented are clamped to
+    /// [`Duration::MAX`].
+    pub(crate) fn to_duration(self, value: f64) -> Duration {
+        let nanos = (value * self.nanos()).round().max(0.0);
+        Duration::try_from_secs_f64(nanos / 1e9).unwrap_or(Duration::MAX)
+    }
+}
+
+impl Histogram {
+    /// Update the histogram by inserting a new duration, which is converted into a value
+    /// in the duration unit of the histogram (see [`Histogram::set_duration_unit`]).
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::new(5);
+    /// h.insert_duration(Duration::from_millis(250));
+    ///
+    /// assert_eq!(h.count(), 1);
+    /// assert_eq!(h.max(), Some(250_000_000.0));
+    /// ```
+    pub fn insert_duration(&mut self, duration: Duration) {
+        self.insert(self.duration_unit().to_value(duration));
+    }
+
+    /// Returns an approximated value of the `q`'th quantile of the values converted back
+    /// into a duration (see [`Histogram::quantile`]) or `None` if the histogram is empty.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use bhtt::{DurationUnit, Histogram};
+    ///
+    /// let mut h = Histogram::new(64);
+    /// h.set_duration_unit(DurationUnit::Microseconds);
+    /// for ms in 1..=100 {
+    ///     h.insert_duration(Duration::from_millis(ms));
+    /// }
+    ///
+    /// assert_eq!(h.quantile_duration(0.0), Some(Duration::from_millis(1)));
+    /// assert_eq!(h.quantile_duration(1.0), Some(Duration::from_millis(100)));
+    /// ```
+    pub fn quantile_duration(&self, q: f64) -> Option<Duration> {
+        self.quantile(q)
+            .map(|value| self.duration_unit().to_duration(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        let duration = Duration::new(1, 500_250_125);
+        let cases = [
+            (DurationUnit::Seconds, 1.500250125),
+            (DurationUnit::Milliseconds, 1500.250125),
+            (DurationUnit::Microseconds, 1500250.125),
+            (DurationUnit::Nanoseconds, 1500250125.0),
+        ];
+
+        for (unit, value) in cases {
+            assert_relative_eq!(unit.to_value(duration), value);
+            assert_eq!(unit.to_duration(value), duration);
+        }
+    }
+
+    #[test]
+    fn to_duration_out_of_range() {
+        assert_eq!(DurationUnit::Seconds.to_duration(-1.0), Duration::ZERO);
+        assert_eq!(DurationUnit::Seconds.to_duration(1e300), Duration::MAX);
+    }
+
+    #[test]
+    fn insert_duration() {
+        let mut h = Histogram::new(5);
+        assert_eq!(h.duration_unit(), DurationUnit::Nanoseconds);
+        assert_eq!(h.quantile_duration(0.5), None);
+
+        h.set_duration_unit(DurationUnit::Milliseconds);
+        for ms in [10, 20, 30] {
+            h.insert_duration(Duration::from_millis(ms));
+        }
+        assert_eq!(h.count(), 3);
+        assert_eq!(h.min(), Some(10.0));
+        assert_eq!(h.max(), Some(30.0));
+        assert_eq!(h.quantile_duration(0.5), Some(Duration::from_millis(20)));
+    }
+
+    #[test]
+    #[should_panic(expected = "the duration unit of a non-empty histogram can't be changed")]
+    fn set_duration_unit_non_empty() {
+        let mut h = Histogram::new(5);
+        h.insert_duration(Duration::from_millis(10));
+
+        // setting the same unit is a no-op
+        h.set_duration_unit(DurationUnit::Nanoseconds);
+        h.set_duration_unit(DurationUnit::Milliseconds);
+    }
+
+    #[test]
+    fn merge_duration_unit() {
+        let mut h1 = Histogram::new(5);
+        h1.set_duration_unit(DurationUnit::Milliseconds);
+        h1.insert_duration(Duration::from_millis(10));
+        let mut h2 = Histogram::new(5);
+        h2.set_duration_unit(DurationUnit::Milliseconds);
+        h2.insert_duration(Duration::from_millis(30));
+
+        let h = Histogram::merge_all(5, [&h1, &h2]);
+        assert_eq!(h.duration_unit(), DurationUnit::Milliseconds);
+        assert_eq!(h.max(), Some(30.0));
+
+        let h: Histogram = [&h1, &h2].into_iter().sum();
+        assert_eq!(h.duration_unit(), DurationUnit::Milliseconds);
+
+        h1 += &h2;
+        assert_eq!(h1.quantile_duration(1.0), Some(Duration::from_millis(30)));
+    }
+
+    #[test]
+    #[should_panic(expected = "histograms must have the same duration unit")]
+    fn merge_duration_unit_mismatch() {
+        let mut h1 = Histogram::new(5);
+        h1.set_duration_unit(DurationUnit::Milliseconds);
+        h1.insert_duration(Duration::from_millis(10));
+
+        let mut h2 = Histogram::new(5);
+        h2.insert_duration(Duration::from_millis(30));
+        h1.merge(&h2);
+    }
+
+    #[test]
+    #[should_panic(expected = "histograms must have the same duration unit")]
+    fn merge_all_duration_unit_mismatch() {
+        let mut h1 = Histogram::new(5);
+        h1.set_duration_unit(DurationUnit::Seconds);
+
+        let _: Histogram = [h1, Histogram::new(5)].into_iter().sum();
+    }
+}
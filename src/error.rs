@@ -0,0 +1,74 @@
+use std::fmt;
+
+/// An error returned by fallible operations on histograms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// A NaN value was passed where a number was expected.
+    NanValue,
+    /// An infinite value was passed where a finite number was expected.
+    InfiniteValue,
+    /// A bin with the count of zero was passed where a non-empty bin was expected.
+    ZeroCount,
+    /// A serialized histogram could not be parsed.
+    InvalidFormat(String),
+    /// Parts of a histogram are not consistent (see [`Histogram::from_parts`]).
+    ///
+    /// [`Histogram::from_parts`]: crate::Histogram::from_parts
+    InvalidParts(String),
+    /// A histogram is not a later snapshot of the same cumulative histogram (see
+    /// [`Histogram::delta_since`]).
+    ///
+    /// [`Histogram::delta_since`]: crate::Histogram::delta_since
+    InvalidSnapshot(String),
+    /// A bounded queue of values is full.
+    Full,
+    /// The receiving side of a queue of values has stopped.
+    Closed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NanValue => write!(f, "value must not be NaN"),
+            Error::InfiniteValue => write!(f, "value must be finite"),
+            Error::ZeroCount => write!(f, "count must be greater than zero"),
+            Error::InvalidFormat(reason) => write!(f, "invalid format: {}", reason),
+            Error::InvalidParts(reason) => write!(f, "invalid parts: {}", reason),
+            Error::InvalidSnapshot(reason) => write!(f, "invalid snapshot: {}", reason),
+            Error::Full => write!(f, "queue is full"),
+            Error::Closed => write!(f, "queue is closed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        assert_eq!(Error::NanValue.to_string(), "value must not be NaN");
+        assert_eq!(Error::InfiniteValue.to_string(), "value must be finite");
+        assert_eq!(
+            Error::ZeroCount.to_string(),
+            "count must be greater than zero"
+        );
+        assert_eq!(
+            Error::InvalidFormat("unexpected end of input".to_string()).to_string(),
+            "invalid format: unexpected end of input"
+        );
+        assert_eq!(
+            Error::InvalidParts("bins must be sorted".to_string()).to_string(),
+            "invalid parts: bins must be sorted"
+        );
+        assert_eq!(
+            Error::InvalidSnapshot("the count decreased from 2 to 1".to_string()).to_string(),
+            "invalid snapshot: the count decreased from 2 to 1"
+        );
+        assert_eq!(Error::Full.to_string(), "queue is full");
+        assert_eq!(Error::Closed.to_string(), "queue is closed");
+    }
+}
@@ -0,0 +1,356 @@
+use crate::sketch::Sketch;
+
+/// The default seed of the random number generator of [`ExactSample`].
+const DEFAULT_SEED: u64 = 0x5eed_b477;
+
+/// A baseline for accuracy studies, which keeps a bounded uniform random sample (a
+/// reservoir) of raw values, and answers queries exactly over the sample.
+///
+/// As long as no more than `capacity` values are inserted, all of them are kept, and
+/// quantiles and ranks are exact, so the estimates of other sketches can be compared
+/// against the ground truth in-process (e.g. in tests). Once the reservoir is full, every
+/// value seen so far is kept with equal probability (reservoir sampling, "algorithm R"),
+/// so quantiles of the sample are unbiased estimates. The minimum and maximum values are
+/// always tracked exactly.
+///
+/// The sample is kept sorted, so inserting a value takes `O(capacity)` time in the worst
+/// case: this type is meant for tests and offline studies rather than for production use.
+/// Sampling is driven by a deterministic pseudo-random number generator, so results are
+/// reproducible for a given seed.
+///
+/// ```
+/// use bhtt::{ExactSample, Histogram, Sketch};
+///
+/// let mut exact = ExactSample::new(10000);
+/// let mut h = Histogram::new(64);
+/// for value in 1..=10000 {
+///     exact.insert(value as f64);
+///     h.insert(value as f64);
+/// }
+///
+/// assert!(exact.is_exact());
+/// assert_eq!(exact.quantile(0.5), Some(5000.5));
+/// let error = (h.quantile(0.5).unwrap() - exact.quantile(0.5).unwrap()).abs();
+/// assert!(error < 50.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExactSample {
+    capacity: usize,
+    values: Vec<f64>,
+    count: u64,
+    min_max: Option<(f64, f64)>,
+    rng: SplitMix64,
+}
+
+impl ExactSample {
+    /// Create a new empty ExactSample, which keeps up to `capacity` values. The capacity
+    /// must be greater than 0, or the function will panic.
+    pub fn new(capacity: usize) -> ExactSample {
+        ExactSample::with_seed(capacity, DEFAULT_SEED)
+    }
+
+    /// Create a new empty ExactSample (see [`new`](ExactSample::new)), whose random number
+    /// generator is seeded with the given value.
+    pub fn with_seed(capacity: usize, seed: u64) -> ExactSample {
+        assert!(capacity > 0, "capacity must be greater than 0");
+
+        ExactSample {
+            capacity,
+            values: Vec::new(),
+            count: 0,
+            min_max: None,
+            rng: SplitMix64(seed),
+        }
+    }
+
+    /// Returns the maximum number of values in the sample.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of values inserted into the sample (some of which may have been
+    /// dropped from it).
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns true, if all inserted values are kept, i.e. queries are exact.
+    pub fn is_exact(&self) -> bool {
+        self.count <= self.capacity as u64
+    }
+
+    /// Returns the values in the sample sorted in ascending order.
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// Returns the minimum value inserted into the sample or `None` if it is empty.
+    pub fn min(&self) -> Option<f64> {
+        self.min_max.map(|(min_value, _)| min_value)
+    }
+
+    /// Returns the maximum value inserted into the sample or `None` if it is empty.
+    pub fn max(&self) -> Option<f64> {
+        self.min_max.map(|(_, max_value)| max_value)
+    }
+
+    /// Insert a new value. Once the sample is full, the value replaces a random value in
+    /// the sample with the probability of `capacity / count`. The value must not be NaN or
+    /// infinite, or the function will panic.
+    pub fn insert(&mut self, value: f64) {
+        assert!(!value.is_nan(), "value must not be NaN");
+        assert!(value.is_finite(), "value must be finite");
+
+        self.count += 1;
+        self.track_min_max(value, value);
+        if self.values.len() < self.capacity {
+            self.insert_sorted(value);
+        } else {
+            let i = self.rng.below(self.count);
+            if i < self.capacity as u64 {
+                // the sample is sorted, but any index is equally likely to be replaced
+                self.values.remove(i as usize);
+                self.insert_sorted(value);
+            }
+        }
+    }
+
+    /// Merge another sample into this one. If both samples fit into the capacity of this
+    /// one, all values are kept. Otherwise, the number of values taken from either sample is
+    /// proportional to the number of values inserted into it (on average), so that all
+    /// inserted values remain equally likely to be kept.
+    pub fn merge(&mut self, other: &ExactSample) {
+        if let Some((min_value, max_value)) = other.min_max {
+            self.track_min_max(min_value, max_value);
+        }
+
+        if self.values.len() + other.values.len() <= self.capacity
+            && self.is_exact()
+            && other.is_exact()
+        {
+            self.count += other.count;
+            self.values.extend_from_slice(&other.values);
+            self.values.sort_by(f64::total_cmp);
+            return;
+        }
+
+        // draw the number of values taken from either sample as if the result was sampled
+        // from all inserted values, then take random subsets of that size
+        let mut left = std::mem::take(&mut self.values);
+        let mut right = other.values.clone();
+        let len = self.capacity.min(left.len() + right.len());
+        let (mut left_count, mut right_count) = (self.count, other.count);
+        let mut from_left = 0;
+        for _ in 0..len {
+            if self.rng.below(left_count + right_count) < left_count {
+                from_left += 1;
+                left_count -= 1;
+            } else {
+                right_count -= 1;
+            }
+        }
+        let from_left = from_left.clamp(len.saturating_sub(right.len()), left.len());
+
+        self.shuffle_prefix(&mut left, from_left);
+        self.shuffle_prefix(&mut right, len - from_left);
+        self.values.extend_from_slice(&left[..from_left]);
+        self.values.extend_from_slice(&right[..len - from_left]);
+        self.values.sort_by(f64::total_cmp);
+        self.count += other.count;
+    }
+
+    /// Returns the `q`'th quantile of the sample or `None` if it is empty. The quantile is
+    /// interpolated linearly between the closest ranks, so that the median of an even number
+    /// of values is the mean of the middle ones. The exact minimum and maximum values are
+    /// returned for 0.0 and 1.0. `q` must be in the range [0.0; 1.0], or the function will
+    /// panic.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        assert!(
+            (0.0..=1.0).contains(&q),
+            "q must be in the range [0.0; 1.0]"
+        );
+
+        let (min_value, max_value) = self.min_max?;
+        if q == 0.0 {
+            return Some(min_value);
+        } else if q == 1.0 {
+            return Some(max_value);
+        }
+
+        let position = q * (self.values.len() - 1) as f64;
+        let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+        let fraction = position - lower as f64;
+
+        Some(self.values[lower] + (self.values[upper] - self.values[lower]) * fraction)
+    }
+
+    /// Returns the fraction of values in the sample, which are less than or equal to
+    /// `value`, or `None` if the sample is empty. The value must not be NaN, or the function
+    /// will panic.
+    pub fn rank(&self, value: f64) -> Option<f64> {
+        assert!(!value.is_nan(), "value must not be NaN");
+
+        match self.values.len() {
+            0 => None,
+            len => Some(self.values.partition_point(|&v| v <= value) as f64 / len as f64),
+        }
+    }
+
+    /// Move `n` random values of the slice to its beginning.
+    fn shuffle_prefix(&mut self, values: &mut [f64], n: usize) {
+        for i in 0..n {
+            let j = i + self.rng.below((values.len() - i) as u64) as usize;
+            values.swap(i, j);
+        }
+    }
+
+    fn insert_sorted(&mut self, value: f64) {
+        let i = self.values.partition_point(|&v| v <= value);
+        self.values.insert(i, value);
+    }
+
+    fn track_min_max(&mut self, min_value: f64, max_value: f64) {
+        let (self_min, self_max) = self.min_max.unwrap_or((min_value, max_value));
+        self.min_max = Some((self_min.min(min_value), self_max.max(max_value)));
+    }
+}
+
+impl Sketch for ExactSample {
+    fn insert(&mut self, value: f64) {
+        ExactSample::insert(self, value);
+    }
+
+    fn merge(&mut self, other: &ExactSample) {
+        ExactSample::merge(self, other);
+    }
+
+    fn count(&self) -> u64 {
+        ExactSample::count(self)
+    }
+
+    fn min(&self) -> Option<f64> {
+        ExactSample::min(self)
+    }
+
+    fn max(&self) -> Option<f64> {
+        ExactSample::max(self)
+    }
+
+    fn quantile(&self, q: f64) -> Option<f64> {
+        ExactSample::quantile(self, q)
+    }
+
+    fn rank(&self, value: f64) -> Option<f64> {
+        ExactSample::rank(self, value)
+    }
+}
+
+/// The SplitMix64 pseudo-random number generator, which is good enough for sampling and
+/// doesn't require a dependency.
+#[derive(Debug, Clone)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a random number in the range [0; n).
+    fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact() {
+        let mut sample = ExactSample::new(100);
+        assert_eq!(sample.quantile(0.5), None);
+        assert_eq!(sample.rank(0.0), None);
+
+        for value in [5.0, 1.0, 4.0, 2.0, 3.0] {
+            sample.insert(value);
+        }
+        assert!(sample.is_exact());
+        assert_eq!(sample.values(), &[1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(sample.quantile(0.5), Some(3.0));
+        assert_eq!(sample.quantile(0.25), Some(2.0));
+        assert_eq!(sample.quantile(0.1), Some(1.4));
+        assert_eq!(sample.rank(3.0), Some(0.6));
+        assert_eq!(sample.rank(0.0), Some(0.0));
+    }
+
+    #[test]
+    fn reservoir() {
+        let mut sample = ExactSample::new(1000);
+        for value in 0..100_000 {
+            sample.insert(value as f64);
+        }
+
+        assert!(!sample.is_exact());
+        assert_eq!(sample.count(), 100_000);
+        assert_eq!(sample.values().len(), 1000);
+        assert!(sample.values().windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(sample.min(), Some(0.0));
+        assert_eq!(sample.max(), Some(99_999.0));
+
+        // the sample is uniform, so its quantiles are close to the true ones
+        for q in [0.1, 0.5, 0.9] {
+            let error = (sample.quantile(q).unwrap() - q * 99_999.0).abs();
+            assert!(error < 5000.0, "q = {}: error {}", q, error);
+        }
+
+        // the same seed gives the same sample
+        let mut other = ExactSample::new(1000);
+        for value in 0..100_000 {
+            other.insert(value as f64);
+        }
+        assert_eq!(sample.values(), other.values());
+    }
+
+    #[test]
+    fn merge() {
+        let mut left = ExactSample::new(10);
+        let mut right = ExactSample::new(10);
+        for value in 0..5 {
+            left.insert(value as f64);
+            right.insert((value + 5) as f64);
+        }
+
+        // both samples fit, so the result is exact
+        let mut exact = left.clone();
+        exact.merge(&right);
+        assert!(exact.is_exact());
+        assert_eq!(exact.values().len(), 10);
+        assert_eq!(exact.quantile(0.5), Some(4.5));
+
+        // values are drawn proportionally to the number of values they represent
+        let mut small = ExactSample::with_seed(1000, 1);
+        let mut large = ExactSample::with_seed(1000, 2);
+        for value in 0..10_000 {
+            small.insert(-1.0 - value as f64);
+        }
+        for value in 0..90_000 {
+            large.insert(value as f64);
+        }
+        small.merge(&large);
+        assert_eq!(small.count(), 100_000);
+        assert_eq!(small.values().len(), 1000);
+        assert_eq!(small.min(), Some(-10_000.0));
+        let negative = small.rank(-0.5).unwrap();
+        assert!((negative - 0.1).abs() < 0.03, "{}", negative);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity must be greater than 0")]
+    fn zero_capacity() {
+        ExactSample::new(0);
+    }
+}
@@ -0,0 +1,286 @@
+use std::fmt::Write;
+
+use crate::histogram::Histogram;
+use crate::registry::{Key, Registry};
+
+/// How histograms are exported to Prometheus (see [`Registry::expose`] and
+/// `PrometheusCollector`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrometheusExport {
+    /// Classic Prometheus histograms with buckets of the given upper bounds, which must
+    /// be finite and sorted in strictly ascending order. The estimated cumulative counts
+    /// of values in buckets are exported (see [`Histogram::to_buckets`]).
+    Buckets(Vec<f64>),
+    /// Estimates of the given quantiles, which must be in the range [0.0; 1.0] (see
+    /// [`Histogram::quantile`]).
+    Quantiles(Vec<f64>),
+}
+
+impl PrometheusExport {
+    /// Panic, if bucket bounds or quantiles are not valid.
+    pub(crate) fn validate(&self) {
+        match self {
+            PrometheusExport::Buckets(bounds) => {
+                assert!(
+                    bounds.iter().all(|bound| bound.is_finite()),
+                    "bucket bounds must be finite"
+                );
+                assert!(
+                    bounds.windows(2).all(|w| w[0] < w[1]),
+                    "bucket bounds must be sorted in strictly ascending order"
+                );
+            }
+            PrometheusExport::Quantiles(quantiles) => {
+                assert!(
+                    quantiles.iter().all(|q| (0.0..=1.0).contains(q)),
+                    "quantiles must be in the range [0.0; 1.0]"
+                );
+            }
+        }
+    }
+}
+
+impl Registry {
+    /// Returns all histograms in the registry rendered in the OpenMetrics text format,
+    /// which is also understood by Prometheus, e.g. to serve it from a scrape endpoint.
+    ///
+    /// Histograms with the same name form a metric family, and the labels of their keys
+    /// become the labels of metrics. Histograms are rendered either as classic histograms
+    /// (`_bucket`, `_sum` and `_count` samples), or as gauges with a `quantile` label (see
+    /// [`PrometheusExport`]). Quantiles of empty histograms are rendered as `NaN`.
+    /// Histograms, whose names or label names are not valid metric and label names (e.g.
+    /// `request latency`), are skipped.
+    ///
+    /// Bucket bounds and quantiles must be valid (see [`PrometheusExport`]), or
+    /// the function will panic.
+    ///
+    /// ```
+    /// use bhtt::{Key, PrometheusExport, Registry};
+    ///
+    /// let registry = Registry::new(64);
+    /// for value in 1..=100 {
+    ///     registry.record(Key::new("latency").with_label("method", "GET"), value as f64);
+    /// }
+    ///
+    /// let text = registry.expose(&PrometheusExport::Quantiles(vec![0.5, 0.99]));
+    /// assert_eq!(
+    ///     text.lines().collect::<Vec<_>>(),
+    ///     [
+    ///         "# TYPE latency gauge",
+    ///         r#"latency{method="GET",quantile="0.5"} 50.5"#,
+    ///         r#"latency{method="GET",quantile="0.99"} 99.5"#,
+    ///         "# EOF",
+    ///     ]
+    /// );
+    /// ```
+    pub fn expose(&self, export: &PrometheusExport) -> String {
+        export.validate();
+
+        let mut s = String::new();
+        let mut family = None;
+        for (key, h) in self.snapshot_all() {
+            let valid = is_valid_name(key.name(), true)
+                && key.labels().iter().all(|(name, _)| {
+                    is_valid_name(name, false) && name != "le" && name != "quantile"
+                });
+            if !valid {
+                continue;
+            }
+
+            // keys are sorted, so all histograms of a family follow each other
+            if family.as_deref() != Some(key.name()) {
+                let metric_type = match export {
+                    PrometheusExport::Buckets(_) => "histogram",
+                    PrometheusExport::Quantiles(_) => "gauge",
+                };
+                writeln!(s, "# TYPE {} {}", key.name(), metric_type).unwrap();
+                family = Some(key.name().to_string());
+            }
+            write_metric(&mut s, &key, &h, export);
+        }
+        s.push_str("# EOF\n");
+
+        s
+    }
+}
+
+/// Write the samples of a histogram.
+fn write_metric(s: &mut String, key: &Key, h: &Histogram, export: &PrometheusExport) {
+    let name = key.name();
+    match export {
+        PrometheusExport::Buckets(bounds) => {
+            let mut cumulative_count = 0;
+            for (&bound, count) in bounds.iter().zip(h.to_buckets(bounds)) {
+                cumulative_count += count;
+                let labels = labels(key, Some(("le", &format_value(bound))));
+                writeln!(s, "{}_bucket{} {}", name, labels, cumulative_count).unwrap();
+            }
+            let labels_inf = labels(key, Some(("le", "+Inf")));
+            writeln!(s, "{}_bucket{} {}", name, labels_inf, h.count()).unwrap();
+
+            let labels = labels(key, None);
+            writeln!(s, "{}_sum{} {}", name, labels, format_value(h.sum())).unwrap();
+            writeln!(s, "{}_count{} {}", name, labels, h.count()).unwrap();
+        }
+        PrometheusExport::Quantiles(quantiles) => {
+            for &q in quantiles {
+                let labels = labels(key, Some(("quantile", &format_value(q))));
+                let value = h.quantile(q).unwrap_or(f64::NAN);
+                writeln!(s, "{}{} {}", name, labels, format_value(value)).unwrap();
+            }
+        }
+    }
+}
+
+/// Returns the labels of a key and an extra label (e.g. `le`) rendered in the text format,
+/// or an empty string, if there are no labels.
+fn labels(key: &Key, extra: Option<(&str, &str)>) -> String {
+    let labels: Vec<String> = key
+        .labels()
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .chain(extra)
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+        .collect();
+
+    match labels.is_empty() {
+        true => String::new(),
+        false => format!("{{{}}}", labels.join(",")),
+    }
+}
+
+/// Returns a label value with backslashes, double quotes and line feeds escaped.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+/// Returns a number rendered in the text format.
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".into()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.into()
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+/// Returns true, if the name is a valid metric name (or a label name, which can't contain
+/// colons).
+fn is_valid_name(name: &str, is_metric: bool) -> bool {
+    let valid_char = |c: char| c.is_ascii_alphabetic() || c == '_' || (is_metric && c == ':');
+    let mut chars = name.chars();
+
+    chars.next().is_some_and(valid_char) && chars.all(|c| valid_char(c) || c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets() {
+        let registry = Registry::new(64);
+        for value in 1..=100 {
+            registry.record("latency", value as f64);
+            registry.record(
+                Key::new("latency").with_label("method", "GET"),
+                value as f64 * 2.0,
+            );
+            registry.record(Key::new("size").with_label("path", "/a\"b\\c\n"), 1.0);
+        }
+
+        assert_eq!(
+            registry.expose(&PrometheusExport::Buckets(vec![50.0, 100.5])),
+            [
+                "# TYPE latency histogram",
+                r#"latency_bucket{le="50.0"} 50"#,
+                r#"latency_bucket{le="100.5"} 100"#,
+                r#"latency_bucket{le="+Inf"} 100"#,
+                "latency_sum 5050.0",
+                "latency_count 100",
+                r#"latency_bucket{method="GET",le="50.0"} 25"#,
+                r#"latency_bucket{method="GET",le="100.5"} 50"#,
+                r#"latency_bucket{method="GET",le="+Inf"} 100"#,
+                r#"latency_sum{method="GET"} 10100.0"#,
+                r#"latency_count{method="GET"} 100"#,
+                "# TYPE size histogram",
+                r#"size_bucket{path="/a\"b\\c\n",le="50.0"} 100"#,
+                r#"size_bucket{path="/a\"b\\c\n",le="100.5"} 100"#,
+                r#"size_bucket{path="/a\"b\\c\n",le="+Inf"} 100"#,
+                r#"size_sum{path="/a\"b\\c\n"} 100.0"#,
+                r#"size_count{path="/a\"b\\c\n"} 100"#,
+                "# EOF",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn quantiles() {
+        let registry = Registry::new(64);
+        registry.record("latency", 42.0);
+        registry.merge("empty", &Histogram::new(64));
+
+        assert_eq!(
+            registry.expose(&PrometheusExport::Quantiles(vec![0.0, 1.0])),
+            [
+                "# TYPE empty gauge",
+                r#"empty{quantile="0.0"} NaN"#,
+                r#"empty{quantile="1.0"} NaN"#,
+                "# TYPE latency gauge",
+                r#"latency{quantile="0.0"} 42.0"#,
+                r#"latency{quantile="1.0"} 42.0"#,
+                "# EOF",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn invalid_names() {
+        let registry = Registry::new(64);
+        for key in [
+            Key::new("request latency"),
+            Key::new("1latency"),
+            Key::new(""),
+            Key::new("latency").with_label("le", "1"),
+            Key::new("latency").with_label("a:b", "1"),
+        ] {
+            registry.record(key, 1.0);
+        }
+        registry.record(Key::new("valid:name_1").with_label("_label1", "x"), 1.0);
+
+        let s = registry.expose(&PrometheusExport::Quantiles(vec![0.5]));
+        assert_eq!(
+            s,
+            "# TYPE valid:name_1 gauge\nvalid:name_1{_label1=\"x\",quantile=\"0.5\"} 1.0\n# EOF\n"
+        );
+
+        assert_eq!(
+            Registry::new(64).expose(&PrometheusExport::Buckets(vec![])),
+            "# EOF\n"
+        );
+    }
+
+    #[test]
+    fn format_values() {
+        assert_eq!(format_value(1.0), "1.0");
+        assert_eq!(format_value(0.25), "0.25");
+        assert_eq!(format_value(-1e-9), "-1e-9");
+        assert_eq!(format_value(f64::INFINITY), "+Inf");
+        assert_eq!(format_value(f64::NEG_INFINITY), "-Inf");
+        assert_eq!(format_value(f64::NAN), "NaN");
+    }
+
+    #[test]
+    #[should_panic(expected = "bucket bounds must be finite")]
+    fn infinite_bucket_bound() {
+        Registry::new(64).expose(&PrometheusExport::Buckets(vec![1.0, f64::INFINITY]));
+    }
+}
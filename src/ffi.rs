@@ -0,0 +1,386 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::bin::Bin;
+use crate::histogram::Histogram;
+
+/// An opaque handle to a histogram, which is exposed to C (and C++) code, so that it can
+/// use the same histograms as Rust code. The functions of the C API are declared in
+/// `include/bhtt.h`, which is generated with cbindgen
+/// (`cbindgen --config cbindgen.toml --output include/bhtt.h`).
+///
+/// Handles are created with `bhtt_histogram_new` or `bhtt_histogram_from_bytes`, and must be
+/// freed with `bhtt_histogram_free`. A handle must not be used from multiple threads
+/// concurrently.
+///
+/// Panics never unwind into the calling code: functions return `Panic` (or NULL) instead.
+///
+/// This type is only available when the `ffi` feature is enabled.
+#[derive(Debug)]
+pub struct BhttHistogram {
+    inner: Histogram,
+}
+
+/// The status returned by the functions of the C API.
+///
+/// This type is only available when the `ffi` feature is enabled.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BhttStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A required pointer is NULL.
+    NullPointer = 1,
+    /// A value is NaN or infinite, a count is 0, a quantile is not in [0.0; 1.0], or
+    /// histograms have different duration units.
+    InvalidArgument = 2,
+    /// The histogram is empty, so there is no result.
+    Empty = 3,
+    /// The buffer is too small to fit the serialized histogram.
+    BufferTooSmall = 4,
+    /// The call panicked, e.g. because of a bug. The histogram may be left in an
+    /// unspecified (but valid) state.
+    Panic = 5,
+}
+
+/// Create a new empty histogram with (up to) the given number of bins.
+///
+/// Returns NULL, if the size is 0, or if it is too large to be allocated.
+#[no_mangle]
+pub extern "C" fn bhtt_histogram_new(size: usize) -> *mut BhttHistogram {
+    catch_panic(ptr::null_mut(), || {
+        if size == 0 {
+            return ptr::null_mut();
+        }
+
+        match Histogram::try_new(size) {
+            Some(h) => into_handle(h),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+/// Free a histogram. Freeing NULL is a no-op.
+///
+/// # Safety
+///
+/// The handle must be NULL or returned by this API, and must not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn bhtt_histogram_free(h: *mut BhttHistogram) {
+    catch_panic((), || {
+        if !h.is_null() {
+            drop(Box::from_raw(h));
+        }
+    })
+}
+
+/// Insert a value, which is repeated `count` times.
+///
+/// Returns `InvalidArgument`, if the value is NaN or infinite, or the count is 0.
+///
+/// # Safety
+///
+/// The handle must be NULL or a valid histogram.
+#[no_mangle]
+pub unsafe extern "C" fn bhtt_histogram_insert(
+    h: *mut BhttHistogram,
+    value: f64,
+    count: u64,
+) -> BhttStatus {
+    catch_panic(BhttStatus::Panic, || {
+        let Some(h) = h.as_mut() else {
+            return BhttStatus::NullPointer;
+        };
+        match Bin::try_new(value, count) {
+            Ok(bin) if value.is_finite() => {
+                h.inner.insert(bin);
+                BhttStatus::Ok
+            }
+            _ => BhttStatus::InvalidArgument,
+        }
+    })
+}
+
+/// Merge the histogram `src` into the histogram `dst`. Both handles may point to the same
+/// histogram.
+///
+/// Returns `InvalidArgument`, if the histograms have different duration units.
+///
+/// # Safety
+///
+/// Both handles must be NULL or valid histograms.
+#[no_mangle]
+pub unsafe extern "C" fn bhtt_histogram_merge(
+    dst: *mut BhttHistogram,
+    src: *const BhttHistogram,
+) -> BhttStatus {
+    catch_panic(BhttStatus::Panic, || {
+        if dst.is_null() || src.is_null() {
+            return BhttStatus::NullPointer;
+        }
+        if (*dst).inner.duration_unit() != (*src).inner.duration_unit() {
+            return BhttStatus::InvalidArgument;
+        }
+
+        // the histograms can't be borrowed mutably and immutably at the same time
+        if ptr::eq(dst, src) {
+            let other = (*src).inner.clone();
+            (*dst).inner.merge(&other);
+        } else {
+            (*dst).inner.merge(&(*src).inner);
+        }
+        BhttStatus::Ok
+    })
+}
+
+/// Estimate the `q`'th quantile and store it into `out`.
+///
+/// Returns `InvalidArgument`, if `q` is not in the range [0.0; 1.0], or `Empty`, if
+/// the histogram is empty.
+///
+/// # Safety
+///
+/// The handle must be NULL or a valid histogram, and `out` must be NULL or point to
+/// a writable double.
+#[no_mangle]
+pub unsafe extern "C" fn bhtt_histogram_quantile(
+    h: *const BhttHistogram,
+    q: f64,
+    out: *mut f64,
+) -> BhttStatus {
+    catch_panic(BhttStatus::Panic, || {
+        let (Some(h), false) = (h.as_ref(), out.is_null()) else {
+            return BhttStatus::NullPointer;
+        };
+        if !(0.0..=1.0).contains(&q) {
+            return BhttStatus::InvalidArgument;
+        }
+
+        match h.inner.quantile(q) {
+            Some(value) => {
+                *out = value;
+                BhttStatus::Ok
+            }
+            None => BhttStatus::Empty,
+        }
+    })
+}
+
+/// Returns the number of values in the histogram, or 0, if the handle is NULL.
+///
+/// # Safety
+///
+/// The handle must be NULL or a valid histogram.
+#[no_mangle]
+pub unsafe extern "C" fn bhtt_histogram_count(h: *const BhttHistogram) -> u64 {
+    catch_panic(0, || h.as_ref().map_or(0, |h| h.inner.count()))
+}
+
+/// Serialize the histogram in the binary format into a buffer of `capacity` bytes, and
+/// store the length of the serialized histogram into `len`.
+///
+/// Returns `BufferTooSmall`, if the serialized histogram does not fit into the buffer, in
+/// which case only `len` is set, so that the call can be repeated with a larger buffer.
+/// `buf` may be NULL, if `capacity` is 0.
+///
+/// # Safety
+///
+/// The handle must be NULL or a valid histogram, `buf` must be NULL or point to
+/// `capacity` writable bytes, and `len` must be NULL or point to a writable size_t.
+#[no_mangle]
+pub unsafe extern "C" fn bhtt_histogram_to_bytes(
+    h: *const BhttHistogram,
+    buf: *mut u8,
+    capacity: usize,
+    len: *mut usize,
+) -> BhttStatus {
+    catch_panic(BhttStatus::Panic, || {
+        let (Some(h), false) = (h.as_ref(), len.is_null()) else {
+            return BhttStatus::NullPointer;
+        };
+
+        let bytes = h.inner.to_bytes();
+        *len = bytes.len();
+        if bytes.len() > capacity {
+            return BhttStatus::BufferTooSmall;
+        }
+        if buf.is_null() {
+            return BhttStatus::NullPointer;
+        }
+
+        ptr::copy_nonoverlapping(bytes.as_ptr(), buf, bytes.len());
+        BhttStatus::Ok
+    })
+}
+
+/// Deserialize a histogram from `len` bytes in the binary format (e.g. produced by
+/// `bhtt_histogram_to_bytes` or `Histogram::to_bytes` in Rust).
+///
+/// Returns NULL, if the bytes are not a valid histogram.
+///
+/// # Safety
+///
+/// `buf` must be NULL or point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bhtt_histogram_from_bytes(
+    buf: *const u8,
+    len: usize,
+) -> *mut BhttHistogram {
+    catch_panic(ptr::null_mut(), || {
+        if buf.is_null() {
+            return ptr::null_mut();
+        }
+
+        match Histogram::from_bytes(slice::from_raw_parts(buf, len)) {
+            Ok(h) => into_handle(h),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+/// Returns a new handle, which owns a histogram.
+fn into_handle(inner: Histogram) -> *mut BhttHistogram {
+    Box::into_raw(Box::new(BhttHistogram { inner }))
+}
+
+/// Call `f` and return its result, or `default`, if it panics, as unwinding across the
+/// boundary of an `extern "C"` function aborts the process.
+fn catch_panic<T>(default: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::MAX_SIZE;
+    use crate::duration::DurationUnit;
+
+    #[test]
+    fn histogram() {
+        unsafe {
+            let h = bhtt_histogram_new(5);
+            for value in [1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+                assert_eq!(bhtt_histogram_insert(h, value, 1), BhttStatus::Ok);
+            }
+            assert_eq!(bhtt_histogram_insert(h, 42.0, 3), BhttStatus::Ok);
+            assert_eq!(bhtt_histogram_count(h), 13);
+
+            let other = bhtt_histogram_new(5);
+            assert_eq!(bhtt_histogram_insert(other, -10.0, 1), BhttStatus::Ok);
+            assert_eq!(bhtt_histogram_merge(h, other), BhttStatus::Ok);
+            assert_eq!(bhtt_histogram_merge(other, other), BhttStatus::Ok);
+            assert_eq!(bhtt_histogram_count(h), 14);
+            assert_eq!(bhtt_histogram_count(other), 2);
+
+            let mut value = 0.0;
+            assert_eq!(bhtt_histogram_quantile(h, 0.0, &mut value), BhttStatus::Ok);
+            assert_eq!(value, -10.0);
+            assert_eq!(bhtt_histogram_quantile(h, 1.0, &mut value), BhttStatus::Ok);
+            assert_eq!(value, 42.0);
+
+            bhtt_histogram_free(h);
+            bhtt_histogram_free(other);
+            bhtt_histogram_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn serialization() {
+        unsafe {
+            let h = into_handle(Histogram::from_iter(8, [1.0, 2.0, 3.0]));
+            let expected = (*h).inner.to_bytes();
+
+            // the length is reported, if the buffer is too small
+            let mut len = 0;
+            let status = bhtt_histogram_to_bytes(h, ptr::null_mut(), 0, &mut len);
+            assert_eq!(status, BhttStatus::BufferTooSmall);
+            assert_eq!(len, expected.len());
+
+            let mut buf = vec![0; len + 10];
+            let status = bhtt_histogram_to_bytes(h, buf.as_mut_ptr(), buf.len(), &mut len);
+            assert_eq!(status, BhttStatus::Ok);
+            assert_eq!(&buf[..len], &expected[..]);
+
+            let decoded = bhtt_histogram_from_bytes(buf.as_ptr(), len);
+            assert!(!decoded.is_null());
+            assert_eq!((*decoded).inner, (*h).inner);
+
+            // trailing bytes are not a valid histogram
+            assert!(bhtt_histogram_from_bytes(buf.as_ptr(), buf.len()).is_null());
+            assert!(bhtt_histogram_from_bytes(ptr::null(), 0).is_null());
+
+            bhtt_histogram_free(decoded);
+            bhtt_histogram_free(h);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        unsafe {
+            assert!(bhtt_histogram_new(0).is_null());
+            assert!(bhtt_histogram_new(usize::MAX).is_null());
+            assert!(bhtt_histogram_new(MAX_SIZE + 1).is_null());
+            // sizes, which don't overflow, but still can't be allocated
+            assert!(bhtt_histogram_new(MAX_SIZE).is_null());
+            assert!(bhtt_histogram_new(1 << 50).is_null());
+
+            let h = bhtt_histogram_new(5);
+            let mut value = 0.0;
+            assert_eq!(
+                bhtt_histogram_quantile(h, 0.5, &mut value),
+                BhttStatus::Empty
+            );
+            assert_eq!(
+                bhtt_histogram_quantile(h, 1.5, &mut value),
+                BhttStatus::InvalidArgument
+            );
+            for (value, count) in [(f64::NAN, 1), (f64::INFINITY, 1), (1.0, 0)] {
+                assert_eq!(
+                    bhtt_histogram_insert(h, value, count),
+                    BhttStatus::InvalidArgument
+                );
+            }
+            assert_eq!(bhtt_histogram_count(h), 0);
+
+            assert_eq!(
+                bhtt_histogram_insert(ptr::null_mut(), 1.0, 1),
+                BhttStatus::NullPointer
+            );
+            assert_eq!(
+                bhtt_histogram_merge(h, ptr::null()),
+                BhttStatus::NullPointer
+            );
+            assert_eq!(
+                bhtt_histogram_quantile(h, 0.5, ptr::null_mut()),
+                BhttStatus::NullPointer
+            );
+            assert_eq!(
+                bhtt_histogram_to_bytes(h, ptr::null_mut(), 0, ptr::null_mut()),
+                BhttStatus::NullPointer
+            );
+            assert_eq!(bhtt_histogram_count(ptr::null()), 0);
+
+            // histograms of different duration units can't be merged
+            let mut inner = Histogram::new(5);
+            inner.set_duration_unit(DurationUnit::Milliseconds);
+            let other = into_handle(inner);
+            assert_eq!(bhtt_histogram_merge(h, other), BhttStatus::InvalidArgument);
+
+            bhtt_histogram_free(other);
+            bhtt_histogram_free(h);
+        }
+    }
+
+    #[test]
+    fn panics() {
+        assert_eq!(
+            catch_panic(BhttStatus::Panic, || BhttStatus::Ok),
+            BhttStatus::Ok
+        );
+        assert_eq!(
+            catch_panic(BhttStatus::Panic, || -> BhttStatus { panic!("oops") }),
+            BhttStatus::Panic
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use std::cmp::Ordering;
+
+use crate::bin::{Bin, TotalF64};
+use crate::histogram::{uniform_quantile, Bins, Histogram};
+use crate::sum::CompensatedSum;
+
+/// A variant of [`Histogram`] with the number of bins fixed at compile time. Bins are
+/// stored inline in an array, so that updates never allocate memory.
+///
+/// ```
+/// use bhtt::FixedHistogram;
+///
+/// let mut h = FixedHistogram::<5>::new();
+/// for value in vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+///     h.insert(value);
+/// }
+///
+/// assert_eq!(h.count(), 10);
+/// assert_eq!(h.quantile(0.0), Some(-5.4));
+/// assert_eq!(h.quantile(0.5), Some(4.75));
+/// assert_eq!(h.quantile(1.0), Some(10.0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FixedHistogram<const N: usize> {
+    bins: [Bin; N],
+    len: usize,
+    min_value: Option<f64>,
+    max_value: Option<f64>,
+    sum: CompensatedSum,
+}
+
+impl<const N: usize> FixedHistogram<N> {
+    /// Create a new empty FixedHistogram. `N` must be greater than 0, or the function
+    /// will panic.
+    ///
+    /// ```
+    /// use bhtt::FixedHistogram;
+    ///
+    /// let h = FixedHistogram::<16>::new();
+    /// assert_eq!(h.size(), 16);
+    /// assert_eq!(h.count(), 0);
+    /// ```
+    pub fn new() -> FixedHistogram<N> {
+        assert!(N > 0, "histogram size must be greater than 0");
+
+        FixedHistogram {
+            bins: [Bin::empty(0.0); N],
+            len: 0,
+            min_value: None,
+            max_value: None,
+            sum: CompensatedSum::default(),
+        }
+    }
+
+    /// Returns the size of the histogram.
+    pub fn size(&self) -> usize {
+        N
+    }
+
+    /// Returns the bins of the histogram in the ascending order.
+    pub fn bins(&self) -> &[Bin] {
+        &self.bins[..self.len]
+    }
+
+    /// Returns the number of values in the histogram.
+    pub fn count(&self) -> u64 {
+        self.bins().iter().map(|bin| bin.count()).sum()
+    }
+
+    /// Returns the minimum value in the histogram or `None` if the histogram is empty.
+    pub fn min(&self) -> Option<f64> {
+        self.min_value
+    }
+
+    /// Returns the maximum value in the histogram or `None` if the histogram is empty.
+    pub fn max(&self) -> Option<f64> {
+        self.max_value
+    }
+
+    /// Returns the sum of values in the histogram.
+    pub fn sum(&self) -> f64 {
+        self.sum.value()
+    }
+
+    /// Returns the arithmetic mean of values or `None` if the histogram is empty.
+    pub fn mean(&self) -> Option<f64> {
+        match self.count() {
+            0 => None,
+            count => Some(self.sum() / count as f64),
+        }
+    }
+
+    /// Returns an approximated value of the `q`'th quantile of the values or `None`
+    /// if the histogram is empty. `q` must be in the range [0.0; 1.0], or the function
+    /// will panic (see [`Histogram::quantile`]).
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        assert!(
+            (0.0..=1.0).contains(&q),
+            "q must be in the range [0.0; 1.0]"
+        );
+
+        match (self.min_value, self.max_value) {
+            (Some(min_value), Some(max_value)) => Some(uniform_quantile(
+                self.bins(),
+                self.count(),
+                min_value,
+                max_value,
+                q,
+                None,
+            )),
+            // histogram is empty
+            _ => None,
+        }
+    }
+
+    /// Returns an approximated value of the median or `None` if the histogram is empty.
+    pub fn median(&self) -> Option<f64> {
+        self.quantile(0.5)
+    }
+
+    /// Insert a new value (or bin) into the histogram.
+    ///
+    /// ```
+    /// use bhtt::{Bin, FixedHistogram};
+    ///
+    /// let mut h = FixedHistogram::<2>::new();
+    /// h.insert(1.0);
+    /// h.insert(Bin::new(2.0, 3));
+    /// h.insert(10.0);
+    ///
+    /// assert_eq!(h.bins(), &[Bin::new(1.75, 4), Bin::new(10.0, 1)]);
+    /// ```
+    pub fn insert<T: Into<Bin>>(&mut self, value: T) {
+        let bin = value.into();
+        let pos = self.bins().partition_point(|b| b <= &bin);
+
+        if self.len < N {
+            self.bins.copy_within(pos..self.len, pos + 1);
+            self.bins[pos] = bin;
+            self.len += 1;
+        } else {
+            self.insert_and_merge(pos, bin);
+        }
+
+        let value = bin.value();
+        self.min_value = Some(self.min_value.map_or(value, |min| min.min(value)));
+        self.max_value = Some(self.max_value.map_or(value, |max| max.max(value)));
+        self.sum.add(value * bin.count() as f64);
+    }
+
+    /// Insert a bin at the given position into a full histogram and merge the two closest
+    /// bins, without ever storing N + 1 bins at the same time.
+    fn insert_and_merge(&mut self, pos: usize, bin: Bin) {
+        let old = self.bins;
+        // the list of bins as if the new bin was inserted at `pos`
+        let virtual_bin = |i: usize| match i.cmp(&pos) {
+            Ordering::Less => old[i],
+            Ordering::Equal => bin,
+            Ordering::Greater => old[i - 1],
+        };
+
+        // find the closest pair the same way Histogram does: by the distance between values,
+        // then by the total count, and then by the position in the list
+        let right = (1..=N)
+            .min_by_key(|&i| {
+                let (l, r) = (virtual_bin(i - 1), virtual_bin(i));
+                (TotalF64(r.value() - l.value()), l.count() + r.count())
+            })
+            .unwrap();
+
+        for i in 0..N {
+            self.bins[i] = match i.cmp(&(right - 1)) {
+                Ordering::Less => virtual_bin(i),
+                Ordering::Equal => Bin::merge(&virtual_bin(right - 1), &virtual_bin(right)),
+                Ordering::Greater => virtual_bin(i + 1),
+            };
+        }
+    }
+}
+
+impl<const N: usize> Default for FixedHistogram<N> {
+    fn default() -> Self {
+        FixedHistogram::new()
+    }
+}
+
+impl<const N: usize> From<&FixedHistogram<N>> for Histogram {
+    /// Convert a FixedHistogram into a (heap-allocated) Histogram of the same size.
+    ///
+    /// ```
+    /// use bhtt::{FixedHistogram, Histogram};
+    ///
+    /// let mut fixed = FixedHistogram::<5>::new();
+    /// fixed.insert(42.0);
+    ///
+    /// let h = Histogram::from(&fixed);
+    /// assert_eq!(h.size(), 5);
+    /// assert_eq!(h.bins(), fixed.bins());
+    /// ```
+    fn from(fixed: &FixedHistogram<N>) -> Self {
+        let mut bins = Bins::with_capacity(N + 1);
+        bins.extend_from_slice(fixed.bins());
+
+        Histogram::from_raw_parts(N, bins, fixed.min_value, fixed.max_value, fixed.sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new() {
+        let h = FixedHistogram::<5>::default();
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.bins(), &[]);
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.min(), None);
+        assert_eq!(h.max(), None);
+        assert_eq!(h.sum(), 0.0);
+        assert_eq!(h.mean(), None);
+        assert_eq!(h.quantile(0.5), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram size must be greater than 0")]
+    fn new_invalid_size() {
+        FixedHistogram::<0>::new();
+    }
+
+    #[test]
+    #[should_panic(expected = "q must be in the range [0.0; 1.0]")]
+    fn quantile_not_in_range() {
+        FixedHistogram::<5>::new().quantile(1.5);
+    }
+
+    #[test]
+    fn insert_matches_histogram() {
+        let values: Vec<f64> = (0..1000)
+            .map(|i| ((i * 7919) % 1009) as f64 / 10.0)
+            .collect();
+
+        let mut fixed = FixedHistogram::<16>::new();
+        let mut h = Histogram::new(16);
+        for value in values.iter() {
+            fixed.insert(*value);
+            h.insert(*value);
+            assert_eq!(fixed.bins(), h.bins());
+        }
+
+        assert_eq!(fixed.count(), h.count());
+        assert_eq!(fixed.min(), h.min());
+        assert_eq!(fixed.max(), h.max());
+        assert_eq!(fixed.sum(), h.sum());
+        for i in 0..=10 {
+            let q = i as f64 / 10.0;
+            assert_eq!(fixed.quantile(q), h.quantile(q));
+        }
+    }
+
+    #[test]
+    fn insert_single_bin() {
+        let mut h = FixedHistogram::<1>::new();
+        h.insert(1.0);
+        h.insert(3.0);
+        h.insert(Bin::new(5.0, 2));
+
+        assert_eq!(h.bins(), &[Bin::new(3.5, 4)]);
+        assert_eq!(h.min(), Some(1.0));
+        assert_eq!(h.max(), Some(5.0));
+        assert_eq!(h.mean(), Some(3.5));
+        assert_eq!(h.median(), Some(3.5));
+    }
+
+    #[test]
+    fn into_histogram() {
+        let mut fixed = FixedHistogram::<3>::new();
+        for value in [1.0, 2.0, 3.0, 4.0] {
+            fixed.insert(value);
+        }
+
+        let mut h = Histogram::from(&fixed);
+        assert_eq!(h.bins(), fixed.bins());
+        assert_eq!(h.min(), Some(1.0));
+        assert_eq!(h.max(), Some(4.0));
+        assert_eq!(h.sum(), 10.0);
+
+        h.insert(5.0);
+        assert_eq!(h.count(), 5);
+    }
+}
@@ -0,0 +1,221 @@
+use std::ops::RangeBounds;
+
+use crate::histogram::{uniform_quantile, Histogram};
+
+/// An immutable snapshot of a [`Histogram`] with precomputed cumulative counts of bins,
+/// which answers quantile and count queries in O(log n) rather than O(n) time.
+///
+/// Freezing a histogram takes O(n) time, so it pays off when a histogram is queried
+/// many times without being updated in between.
+///
+/// ```
+/// use bhtt::{FrozenHistogram, Histogram};
+///
+/// let h = Histogram::from_iter(5, vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2]);
+/// let frozen = FrozenHistogram::from(h.clone());
+///
+/// for q in [0.0, 0.25, 0.5, 0.75, 0.99, 1.0] {
+///     assert_eq!(frozen.quantile(q), h.quantile(q));
+/// }
+/// assert_eq!(frozen.count_less_than_or_equal_to(5.0), 5);
+/// assert_eq!(frozen.histogram().count(), 10);
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrozenHistogram {
+    histogram: Histogram,
+    /// The `i`'th element is the total count of the first `i` bins.
+    prefix_counts: Vec<u64>,
+}
+
+impl FrozenHistogram {
+    /// Freeze a histogram, precomputing the cumulative counts of its bins.
+    pub fn new(histogram: Histogram) -> FrozenHistogram {
+        let prefix_counts = std::iter::once(0)
+            .chain(histogram.bins().iter().scan(0, |total, bin| {
+                *total += bin.count();
+                Some(*total)
+            }))
+            .collect();
+
+        FrozenHistogram {
+            histogram,
+            prefix_counts,
+        }
+    }
+
+    /// Returns the underlying histogram.
+    pub fn histogram(&self) -> &Histogram {
+        &self.histogram
+    }
+
+    /// Returns the underlying histogram, so that it can be updated again.
+    ///
+    /// ```
+    /// use bhtt::{FrozenHistogram, Histogram};
+    ///
+    /// let frozen = FrozenHistogram::new(Histogram::from_iter(5, &[1.0, 2.0]));
+    ///
+    /// let mut h = frozen.into_histogram();
+    /// h.insert(3.0);
+    /// assert_eq!(h.count(), 3);
+    /// ```
+    pub fn into_histogram(self) -> Histogram {
+        self.histogram
+    }
+
+    /// Returns an approximated value of the `q`'th quantile of the values or `None`
+    /// if the histogram is empty (see [`Histogram::quantile`]).
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        assert!(
+            (0.0..=1.0).contains(&q),
+            "q must be in the range [0.0; 1.0]"
+        );
+
+        let h = &self.histogram;
+        match (h.min(), h.max()) {
+            (Some(min_value), Some(max_value)) => Some(uniform_quantile(
+                h.bins(),
+                h.count(),
+                min_value,
+                max_value,
+                q,
+                Some(&self.prefix_counts),
+            )),
+            // histogram is empty
+            _ => None,
+        }
+    }
+
+    /// Returns an approximated value of the `p`'th percentile of the values or `None`
+    /// if the histogram is empty (see [`Histogram::percentile`]).
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        assert!(
+            (0.0..=100.0).contains(&p),
+            "p must be in the range [0.0; 100.0]"
+        );
+
+        self.quantile(p / 100.0)
+    }
+
+    /// Returns an approximated value of the median or `None` if the histogram is empty.
+    pub fn median(&self) -> Option<f64> {
+        self.quantile(0.5)
+    }
+
+    /// Returns an estimate of the number of values in the histogram that are less than
+    /// or equal to `value` (see [`Histogram::count_less_than_or_equal_to`]).
+    pub fn count_less_than_or_equal_to(&self, value: f64) -> u64 {
+        assert!(!value.is_nan(), "value must not be NaN");
+
+        self.count_up_to(value).round() as u64
+    }
+
+    /// Returns an estimate of the number of values in the histogram that are greater
+    /// than `value` (see [`Histogram::count_greater_than`]).
+    pub fn count_greater_than(&self, value: f64) -> u64 {
+        assert!(!value.is_nan(), "value must not be NaN");
+
+        self.histogram
+            .count_down_to(value, false, Some(&self.prefix_counts))
+            .round() as u64
+    }
+
+    /// Returns an estimate of the number of values in the histogram that fall into
+    /// the given `range` (see [`Histogram::count_in_range`]).
+    ///
+    /// ```
+    /// use bhtt::{FrozenHistogram, Histogram};
+    ///
+    /// let h = Histogram::from_iter(5, vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2]);
+    /// let frozen = FrozenHistogram::new(h);
+    ///
+    /// assert_eq!(frozen.count_in_range(..), 10);
+    /// assert_eq!(frozen.count_in_range(0.0..=5.0), 2);
+    /// ```
+    pub fn count_in_range(&self, range: impl RangeBounds<f64>) -> u64 {
+        self.histogram
+            .estimate_count_in_range(range, Some(&self.prefix_counts))
+    }
+
+    /// Returns an estimate of the fraction of values in the histogram that are less
+    /// than or equal to `value` or `None` if the histogram is empty (see
+    /// [`Histogram::rank`]).
+    pub fn rank(&self, value: f64) -> Option<f64> {
+        assert!(!value.is_nan(), "value must not be NaN");
+
+        match self.histogram.count() {
+            0 => None,
+            total_count => Some(self.count_up_to(value) / total_count as f64),
+        }
+    }
+
+    fn count_up_to(&self, value: f64) -> f64 {
+        self.histogram
+            .count_up_to(value, true, Some(&self.prefix_counts))
+    }
+}
+
+impl From<Histogram> for FrozenHistogram {
+    fn from(histogram: Histogram) -> Self {
+        FrozenHistogram::new(histogram)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let frozen = FrozenHistogram::new(Histogram::new(5));
+        assert_eq!(frozen.quantile(0.5), None);
+        assert_eq!(frozen.median(), None);
+        assert_eq!(frozen.rank(1.0), None);
+        assert_eq!(frozen.count_less_than_or_equal_to(1.0), 0);
+        assert_eq!(frozen.count_greater_than(1.0), 0);
+        assert_eq!(frozen.count_in_range(..), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "q must be in the range [0.0; 1.0]")]
+    fn quantile_not_in_range() {
+        FrozenHistogram::new(Histogram::new(5)).quantile(-0.1);
+    }
+
+    #[test]
+    #[should_panic(expected = "value must not be NaN")]
+    fn count_less_than_or_equal_to_nan() {
+        FrozenHistogram::new(Histogram::new(5)).count_less_than_or_equal_to(f64::NAN);
+    }
+
+    #[test]
+    fn matches_histogram() {
+        let values = (0..10_000).map(|i| ((i * 7919) % 10007) as f64 / 10.0);
+        for size in [1, 2, 5, 64, 256] {
+            let h = Histogram::from_iter(size, values.clone());
+            let frozen = FrozenHistogram::from(h.clone());
+
+            for i in 0..=1000 {
+                let q = i as f64 / 1000.0;
+                assert_eq!(frozen.quantile(q), h.quantile(q), "q = {}", q);
+                assert_eq!(frozen.percentile(q * 100.0), h.percentile(q * 100.0));
+            }
+            for i in -10..=1010 {
+                let value = i as f64;
+                assert_eq!(
+                    frozen.count_less_than_or_equal_to(value),
+                    h.count_less_than_or_equal_to(value)
+                );
+                assert_eq!(
+                    frozen.count_greater_than(value),
+                    h.count_greater_than(value)
+                );
+                assert_eq!(
+                    frozen.count_in_range(value..value + 100.0),
+                    h.count_in_range(value..value + 100.0)
+                );
+                assert_eq!(frozen.rank(value), h.rank(value));
+            }
+        }
+    }
+}
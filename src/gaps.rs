@@ -0,0 +1,236 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::bin::{Bin, TotalF64};
+use crate::policy::MergePolicy;
+
+/// The distance between labels of adjacent bins after relabeling.
+const LABEL_STEP: u64 = 1 << 32;
+
+/// The label of the leftmost bin after relabeling. Labels are assigned from the middle
+/// of the range, so that there is room for new bins on both sides.
+const FIRST_LABEL: u64 = 1 << 62;
+
+/// A pair of adjacent bins. Pairs are ordered the same way `find_closest_bins()` compares
+/// them: by the distance between bins (according to the merge policy), then by the total count, and then by the position
+/// in the list (the labels of bins increase from left to right).
+type Pair = Reverse<(TotalF64, u64, u64, u64)>;
+
+/// A priority queue of gaps between adjacent bins, which allows finding the closest pair of
+/// bins in O(log n) time and is updated incrementally as bins are inserted and merged.
+///
+/// Bins can't be identified by their positions in the list, as all the bins to the right of
+/// an inserted bin are shifted. Instead, every bin is assigned a label, and labels increase
+/// from left to right, so that the position of a bin can be found by a binary search. Pairs
+/// of bins are never removed from the queue eagerly: whenever either of the two bins changes,
+/// the pair becomes stale and is skipped once it reaches the top of the queue.
+///
+/// The structure is only kept up to date by [`Gaps::insert`] and [`Gaps::merge`]. Any other
+/// change to the list of bins must be followed by [`Gaps::invalidate`], and the structure
+/// will be rebuilt from scratch on the next insert.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Gaps {
+    valid: bool,
+    policy: MergePolicy,
+    labels: Vec<u64>,
+    pairs: BinaryHeap<Pair>,
+}
+
+impl Gaps {
+    /// Mark the structure as out of sync with the list of bins.
+    pub(crate) fn invalidate(&mut self) {
+        if self.valid {
+            self.valid = false;
+            self.labels.clear();
+            self.pairs.clear();
+        }
+    }
+
+    /// Change the way the distance between bins is measured.
+    pub(crate) fn set_policy(&mut self, policy: MergePolicy) {
+        self.invalidate();
+        self.policy = policy;
+    }
+
+    /// Returns the number of bytes of heap memory used by the structure.
+    pub(crate) fn memory_usage(&self) -> usize {
+        self.labels.capacity() * std::mem::size_of::<u64>()
+            + self.pairs.capacity() * std::mem::size_of::<Pair>()
+    }
+
+    /// Update the structure after a bin has been inserted into the list at position `pos`.
+    pub(crate) fn insert(&mut self, bins: &[Bin], pos: usize) {
+        if !self.valid {
+            self.rebuild(bins);
+            return;
+        }
+
+        let low = if pos == 0 { 0 } else { self.labels[pos - 1] };
+        let label = match self.labels.get(pos) {
+            // appending and prepending are common (e.g. monotonic values), so the remaining
+            // space is not halved at either end of the list
+            Some(&high) if pos == 0 && high > LABEL_STEP => high - LABEL_STEP,
+            None if u64::MAX - low > LABEL_STEP => low + LABEL_STEP,
+            Some(&high) if high - low > 1 => low + (high - low) / 2,
+            _ => {
+                // no free label is left between the neighbours
+                self.rebuild(bins);
+                return;
+            }
+        };
+        self.labels.insert(pos, label);
+
+        if pos > 0 {
+            self.push_pair(bins, pos - 1);
+        }
+        if pos + 1 < bins.len() {
+            self.push_pair(bins, pos);
+        }
+        self.compact(bins);
+    }
+
+    /// Returns the positions of the closest pair of bins. The structure is rebuilt first, if
+    /// it is out of sync with the list of bins.
+    pub(crate) fn closest(&mut self, bins: &[Bin]) -> (usize, usize) {
+        debug_assert!(bins.len() > 1);
+        if !self.valid {
+            self.rebuild(bins);
+        }
+
+        loop {
+            let top = self
+                .pairs
+                .peek()
+                .expect("there is at least one pair of bins");
+            if let Some(left) = Self::position(self.policy, bins, &self.labels, top) {
+                return (left, left + 1);
+            }
+
+            // the pair is stale
+            self.pairs.pop();
+        }
+    }
+
+    /// Update the structure after the bin at position `left` has been merged with its
+    /// right neighbour.
+    pub(crate) fn merge(&mut self, bins: &[Bin], left: usize) {
+        self.labels.remove(left + 1);
+
+        if left > 0 {
+            self.push_pair(bins, left - 1);
+        }
+        if left + 1 < bins.len() {
+            self.push_pair(bins, left);
+        }
+    }
+
+    /// Rebuild the structure from scratch.
+    fn rebuild(&mut self, bins: &[Bin]) {
+        self.valid = true;
+        self.labels.clear();
+        self.labels
+            .extend((0..bins.len() as u64).map(|i| FIRST_LABEL + i * LABEL_STEP));
+        self.pairs = (1..bins.len())
+            .map(|right| Self::pair(self.policy, bins, &self.labels, right - 1))
+            .collect();
+    }
+
+    /// Drop stale pairs, if there are too many of them.
+    fn compact(&mut self, bins: &[Bin]) {
+        if self.pairs.len() > 4 * bins.len() + 16 {
+            let (policy, labels) = (self.policy, &self.labels);
+            self.pairs
+                .retain(|pair| Self::position(policy, bins, labels, pair).is_some());
+        }
+    }
+
+    /// Returns the position of the left bin of a pair or `None` if the pair is stale.
+    fn position(policy: MergePolicy, bins: &[Bin], labels: &[u64], pair: &Pair) -> Option<usize> {
+        let Reverse((_, _, left_label, _)) = pair;
+        match labels.binary_search(left_label) {
+            Ok(left)
+                if left + 1 < labels.len() && Self::pair(policy, bins, labels, left) == *pair =>
+            {
+                Some(left)
+            }
+            _ => None,
+        }
+    }
+
+    fn push_pair(&mut self, bins: &[Bin], left: usize) {
+        self.pairs
+            .push(Self::pair(self.policy, bins, &self.labels, left));
+    }
+
+    fn pair(policy: MergePolicy, bins: &[Bin], labels: &[u64], left: usize) -> Pair {
+        let right = left + 1;
+        Reverse((
+            TotalF64(policy.distance(&bins[left], &bins[right])),
+            bins[left].count() + bins[right].count(),
+            labels[left],
+            labels[right],
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn closest_by_scan(bins: &[Bin]) -> (usize, usize) {
+        let right = (1..bins.len())
+            .min_by_key(|&i| {
+                (
+                    TotalF64(bins[i].value() - bins[i - 1].value()),
+                    bins[i - 1].count() + bins[i].count(),
+                )
+            })
+            .unwrap();
+
+        (right - 1, right)
+    }
+
+    #[test]
+    fn insert_and_merge() {
+        let mut bins = vec![Bin::new(0.0, 1), Bin::new(1000.0, 1)];
+        let mut gaps = Gaps::default();
+        gaps.insert(&bins, 1);
+        assert_eq!(gaps.closest(&bins), (0, 1));
+
+        // keep inserting bins right after the leftmost one, until labels between the two
+        // run out and the structure has to be rebuilt
+        for i in 1..100 {
+            let bin = Bin::new(1000.0 / (i as f64 + 1.0), i);
+            bins.insert(1, bin);
+            gaps.insert(&bins, 1);
+            assert_eq!(gaps.closest(&bins), closest_by_scan(&bins));
+        }
+        assert!(gaps.labels.windows(2).all(|pair| pair[0] < pair[1]));
+
+        while bins.len() > 1 {
+            let (left, right) = gaps.closest(&bins);
+            assert_eq!((left, right), closest_by_scan(&bins));
+
+            bins[left] = Bin::merge(&bins[left], &bins[right]);
+            bins.remove(right);
+            gaps.merge(&bins, left);
+        }
+        assert_eq!(gaps.labels.len(), 1);
+    }
+
+    #[test]
+    fn invalidate() {
+        let bins = vec![Bin::new(0.0, 1), Bin::new(1.0, 1), Bin::new(3.0, 1)];
+        let mut gaps = Gaps::default();
+        gaps.insert(&bins, 2);
+        assert_eq!(gaps.closest(&bins), (0, 1));
+
+        gaps.invalidate();
+        assert!(gaps.labels.is_empty());
+        assert!(gaps.pairs.is_empty());
+
+        let bins = vec![Bin::new(0.0, 1), Bin::new(2.0, 1), Bin::new(3.0, 1)];
+        gaps.insert(&bins, 0);
+        assert_eq!(gaps.closest(&bins), (1, 2));
+    }
+}
@@ -0,0 +1,206 @@
+//! Generation of random valid bins and histograms for property testing and fuzzing.
+//!
+//! Generated values are limited to [-MAX_ABS_VALUE; MAX_ABS_VALUE] and counts to
+//! [1; MAX_COUNT], so that weighted averages of bins and sums of counts of generated
+//! histograms can't overflow, no matter how many of them are merged in a test.
+
+use crate::bin::Bin;
+use crate::histogram::Histogram;
+
+/// The maximum absolute value of generated bins.
+const MAX_ABS_VALUE: f64 = 1e12;
+
+/// The maximum count of generated bins.
+const MAX_COUNT: u64 = 1 << 20;
+
+/// The maximum size of generated histograms.
+const MAX_SIZE: usize = 64;
+
+/// The maximum number of bins inserted into generated histograms (by proptest).
+#[cfg(feature = "proptest")]
+const MAX_BINS: usize = 256;
+
+/// Bins are generated with finite values in [-1e12; 1e12] and counts in [1; 2^20].
+/// Non-finite input values are rejected.
+///
+/// This is only available when the `arbitrary` feature is enabled.
+///
+/// ```
+/// use arbitrary::{Arbitrary, Unstructured};
+/// use bhtt::Bin;
+///
+/// let bytes: Vec<u8> = (0..16).collect();
+/// let bin = Bin::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+/// assert!(bin.value().abs() <= 1e12);
+/// assert!(bin.count() >= 1);
+/// ```
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Bin {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Bin> {
+        let value = f64::arbitrary(u)?;
+        if !value.is_finite() {
+            return Err(arbitrary::Error::IncorrectFormat);
+        }
+        let count = u.int_in_range(1..=MAX_COUNT)?;
+
+        Ok(Bin::new(value % MAX_ABS_VALUE, count))
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        arbitrary::size_hint::and(f64::size_hint(depth), u64::size_hint(depth))
+    }
+}
+
+/// Histograms of up to 64 bins are generated by inserting arbitrary bins (see the
+/// implementation for [`Bin`]) until the input is exhausted, so that they respect all
+/// invariants of histograms.
+///
+/// This is only available when the `arbitrary` feature is enabled.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Histogram {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Histogram> {
+        let mut h = Histogram::new(u.int_in_range(1..=MAX_SIZE)?);
+        for bin in u.arbitrary_iter::<Bin>()? {
+            h.insert(bin?);
+        }
+
+        Ok(h)
+    }
+}
+
+/// Bins are generated with finite values in [-1e12; 1e12] (half of them small integers, so
+/// that equal values are common) and counts in [1; 2^20].
+///
+/// This is only available when the `proptest` feature is enabled.
+///
+/// ```
+/// use bhtt::{Bin, Histogram};
+/// use proptest::prelude::*;
+///
+/// proptest!(|(a: Histogram, b: Bin)| {
+///     let mut h = a.clone();
+///     h.insert(b);
+///     prop_assert_eq!(h.count(), a.count() + b.count());
+/// });
+/// ```
+#[cfg(feature = "proptest")]
+impl proptest::arbitrary::Arbitrary for Bin {
+    type Parameters = ();
+    type Strategy = proptest::strategy::BoxedStrategy<Bin>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        use proptest::prelude::*;
+
+        let value = prop_oneof![
+            -MAX_ABS_VALUE..=MAX_ABS_VALUE,
+            (-100i32..=100).prop_map(f64::from),
+        ];
+        (value, 1..=MAX_COUNT)
+            .prop_map(|(value, count)| Bin::new(value, count))
+            .boxed()
+    }
+}
+
+/// Histograms of up to 64 bins are generated by inserting up to 256 arbitrary bins (see
+/// the implementation for [`Bin`]), so that they respect all invariants of histograms.
+///
+/// This is only available when the `proptest` feature is enabled.
+#[cfg(feature = "proptest")]
+impl proptest::arbitrary::Arbitrary for Histogram {
+    type Parameters = ();
+    type Strategy = proptest::strategy::BoxedStrategy<Histogram>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        use proptest::prelude::*;
+
+        (
+            1..=MAX_SIZE,
+            proptest::collection::vec(any::<Bin>(), 0..=MAX_BINS),
+        )
+            .prop_map(|(size, bins)| {
+                let mut h = Histogram::new(size);
+                h.insert_many(bins);
+                h
+            })
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "proptest")]
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[cfg(feature = "proptest")]
+    proptest! {
+        #[test]
+        fn invariants(h: Histogram) {
+            let bins = h.bins();
+            prop_assert!(bins.len() <= h.size());
+            prop_assert!(bins.windows(2).all(|w| w[0].value() <= w[1].value()));
+            prop_assert_eq!(bins.iter().map(|b| b.count()).sum::<u64>(), h.count());
+            if let (Some(min_value), Some(max_value)) = (h.min(), h.max()) {
+                prop_assert!(min_value <= bins[0].value());
+                prop_assert!(max_value >= bins[bins.len() - 1].value());
+            }
+        }
+
+        #[test]
+        fn merge_associativity(a: Histogram, b: Histogram, c: Histogram) {
+            // bins depend on the order of merges, but totals and extremes do not
+            let mut left = a.clone();
+            left.merge(&b);
+            left.merge(&c);
+            let mut bc = b.clone();
+            bc.merge(&c);
+            let mut right = a.clone();
+            right.merge(&bc);
+
+            prop_assert_eq!(left.count(), right.count());
+            prop_assert_eq!(left.count(), a.count() + b.count() + c.count());
+            prop_assert_eq!(left.min(), right.min());
+            prop_assert_eq!(left.max(), right.max());
+            prop_assert!(left.bins().len() <= left.size());
+            prop_assert!(right.bins().len() <= right.size());
+        }
+
+        #[test]
+        fn monotone_cdf(h: Histogram, mut values: Vec<f64>) {
+            prop_assume!(!h.is_empty());
+            values.retain(|v| v.is_finite());
+            values.sort_by(f64::total_cmp);
+
+            let ranks: Vec<f64> = values.iter().map(|&v| h.rank(v).unwrap()).collect();
+            prop_assert!(ranks.windows(2).all(|w| w[0] <= w[1]));
+            prop_assert!(ranks.iter().all(|r| (0.0..=1.0).contains(r)));
+
+            let quantiles: Vec<f64> = (0..=100).map(|i| h.quantile(i as f64 / 100.0).unwrap()).collect();
+            prop_assert!(quantiles.windows(2).all(|w| w[0] <= w[1]));
+            prop_assert!(quantiles[0] >= h.min().unwrap());
+            prop_assert!(quantiles[100] <= h.max().unwrap());
+        }
+
+        #[test]
+        fn bytes_round_trip(h: Histogram) {
+            let decoded = Histogram::from_bytes(&h.to_bytes()).unwrap();
+            prop_assert_eq!(decoded, h);
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let bytes: Vec<u8> = (0..=255).cycle().take(4096).collect();
+        let h = <Histogram as Arbitrary>::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+        assert!(h.bins().len() <= h.size());
+        assert!(h.bins().windows(2).all(|w| w[0].value() <= w[1].value()));
+
+        // non-finite values are rejected
+        let nan = [0xff; 16];
+        assert!(<Bin as Arbitrary>::arbitrary(&mut Unstructured::new(&nan)).is_err());
+    }
+}
@@ -0,0 +1,422 @@
+use crate::sketch::Sketch;
+
+/// A Greenwald-Khanna quantile summary, which answers quantile queries with a deterministic
+/// bound on the rank error.
+///
+/// Unlike the histogram (or a t-digest), whose errors depend on the distribution of values,
+/// the summary guarantees, that the rank of the value returned for the `q`'th quantile
+/// differs from `q * count` by at most `epsilon * count`, e.g. with `epsilon` of 0.001 the
+/// reported p99 is between the true p98.9 and p99.1. The summary keeps
+/// `O(1 / epsilon * log(epsilon * count))` tuples in the worst case.
+///
+/// The summary stores tuples of a value `v`, the difference `g` between the lowest possible
+/// ranks of `v` and of the previous value, and the difference `delta` between the highest
+/// and the lowest possible ranks of `v`. The summary is periodically compressed by merging
+/// adjacent tuples while `g + delta <= 2 * epsilon * count`, which maintains the bound. The
+/// minimum and maximum values are always kept exactly.
+///
+/// ```
+/// use bhtt::{GKSketch, Sketch};
+///
+/// let mut sketch = GKSketch::new(0.001);
+/// for value in (1..=100_000).rev() {
+///     sketch.insert(value as f64);
+/// }
+///
+/// // the rank error is at most 0.001 * 100000 = 100
+/// let p99 = sketch.quantile(0.99).unwrap();
+/// assert!((p99 - 99_000.0).abs() <= 100.0);
+/// assert!(sketch.len() < 10_000);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct GKSketch {
+    epsilon: f64,
+    tuples: Vec<Tuple>,
+    count: u64,
+    inserted: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Tuple {
+    value: f64,
+    g: u64,
+    delta: u64,
+}
+
+impl GKSketch {
+    /// Create a new empty GKSketch with the given maximum rank error (as a fraction of the
+    /// number of values). `epsilon` must be in the range (0.0; 1.0), or the function will
+    /// panic.
+    ///
+    /// ```
+    /// use bhtt::GKSketch;
+    ///
+    /// let sketch = GKSketch::new(0.01);
+    /// assert_eq!(sketch.epsilon(), 0.01);
+    /// assert_eq!(sketch.count(), 0);
+    /// ```
+    pub fn new(epsilon: f64) -> GKSketch {
+        assert!(
+            epsilon > 0.0 && epsilon < 1.0,
+            "epsilon must be in the range (0.0; 1.0)"
+        );
+
+        GKSketch {
+            epsilon,
+            tuples: Vec::new(),
+            count: 0,
+            inserted: 0,
+        }
+    }
+
+    /// Returns the maximum rank error (as a fraction of the number of values).
+    pub fn epsilon(&self) -> f64 {
+        self.epsilon
+    }
+
+    /// Returns the number of values in the sketch.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the number of tuples stored in the sketch.
+    pub fn len(&self) -> usize {
+        self.tuples.len()
+    }
+
+    /// Returns true, if the sketch is empty.
+    pub fn is_empty(&self) -> bool {
+        self.tuples.is_empty()
+    }
+
+    /// Returns the minimum value in the sketch or `None` if the sketch is empty.
+    pub fn min(&self) -> Option<f64> {
+        self.tuples.first().map(|t| t.value)
+    }
+
+    /// Returns the maximum value in the sketch or `None` if the sketch is empty.
+    pub fn max(&self) -> Option<f64> {
+        self.tuples.last().map(|t| t.value)
+    }
+
+    /// Insert a new value. The value must not be NaN or infinite, or the function will panic.
+    pub fn insert(&mut self, value: f64) {
+        assert!(!value.is_nan(), "value must not be NaN");
+        assert!(value.is_finite(), "value must be finite");
+
+        // the new value can be anywhere between the ranks of its neighbours
+        let i = self.tuples.partition_point(|t| t.value <= value);
+        let delta = match self.tuples.get(i) {
+            Some(next) if i > 0 => next.g + next.delta - 1,
+            _ => 0,
+        };
+        self.tuples.insert(i, Tuple { value, g: 1, delta });
+        self.count += 1;
+
+        self.inserted += 1;
+        if self.inserted >= self.compress_interval() {
+            self.compress();
+        }
+    }
+
+    /// Merge another sketch into this one. The rank bounds of tuples of either sketch are
+    /// combined with the bounds of the neighbouring tuples of the other one, so that the
+    /// result keeps the maximum rank error of the less accurate sketch.
+    ///
+    /// ```
+    /// use bhtt::GKSketch;
+    ///
+    /// let mut left = GKSketch::new(0.01);
+    /// let mut right = GKSketch::new(0.01);
+    /// for value in 0..1000 {
+    ///     left.insert(value as f64);
+    ///     right.insert((value + 1000) as f64);
+    /// }
+    ///
+    /// left.merge(&right);
+    /// assert_eq!(left.count(), 2000);
+    /// assert!((left.quantile(0.5).unwrap() - 1000.0).abs() <= 20.0);
+    /// ```
+    pub fn merge(&mut self, other: &GKSketch) {
+        if other.is_empty() {
+            return;
+        }
+
+        let left = rank_bounds(&self.tuples);
+        let right = rank_bounds(&other.tuples);
+        let (left_count, right_count) = (self.count, other.count);
+
+        // order tuples by value, ones of this sketch go first on ties
+        let mut merged = Vec::with_capacity(self.tuples.len() + other.tuples.len());
+        let (mut i, mut j) = (0, 0);
+        while i < left.len() || j < right.len() {
+            let from_left = j == right.len() || (i < left.len() && left[i].0 <= right[j].0);
+            let (value, rmin, rmax) = if from_left {
+                // tuples up to `j` of the other sketch precede this one, the rest follow
+                let (value, rmin, rmax) = left[i];
+                i += 1;
+                (
+                    value,
+                    rmin + j.checked_sub(1).map_or(0, |k| right[k].1),
+                    rmax + right.get(j).map_or(right_count, |t| t.2 - 1),
+                )
+            } else {
+                let (value, rmin, rmax) = right[j];
+                j += 1;
+                (
+                    value,
+                    rmin + i.checked_sub(1).map_or(0, |k| left[k].1),
+                    rmax + left.get(i).map_or(left_count, |t| t.2 - 1),
+                )
+            };
+            merged.push((value, rmin, rmax));
+        }
+
+        let mut previous = 0;
+        self.tuples = merged
+            .into_iter()
+            .map(|(value, rmin, rmax)| {
+                let g = rmin - previous;
+                previous = rmin;
+                Tuple {
+                    value,
+                    g,
+                    delta: rmax - rmin,
+                }
+            })
+            .collect();
+        self.count += other.count;
+        self.epsilon = self.epsilon.max(other.epsilon);
+        self.compress();
+    }
+
+    /// Returns the `q`'th quantile or `None` if the sketch is empty. The rank of the returned
+    /// value differs from `q * count` by at most `epsilon * count`. `q` must be in the range
+    /// [0.0; 1.0], or the function will panic.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        assert!(
+            (0.0..=1.0).contains(&q),
+            "q must be in the range [0.0; 1.0]"
+        );
+
+        let rank = (q * self.count as f64).ceil().max(1.0);
+        rank_bounds(&self.tuples)
+            .into_iter()
+            .min_by(|a, b| {
+                let error = |(_, rmin, rmax): &(f64, u64, u64)| {
+                    (rank - *rmin as f64).max(*rmax as f64 - rank)
+                };
+                error(a).total_cmp(&error(b))
+            })
+            .map(|(value, _, _)| value)
+    }
+
+    /// Returns an estimate of the fraction of values less than or equal to `value` or `None`
+    /// if the sketch is empty. The estimate differs from the true fraction by at most
+    /// `epsilon`. The value must not be NaN, or the function will panic.
+    pub fn rank(&self, value: f64) -> Option<f64> {
+        assert!(!value.is_nan(), "value must not be NaN");
+
+        if self.is_empty() {
+            return None;
+        }
+
+        // the number of values is between the lowest rank of the last tuple <= value and
+        // the highest rank of the next tuple (exclusive)
+        let bounds = rank_bounds(&self.tuples);
+        let i = bounds.partition_point(|t| t.0 <= value);
+        let rank = match (i.checked_sub(1).map(|k| bounds[k]), bounds.get(i)) {
+            (None, _) => 0.0,
+            (Some(_), None) => self.count as f64,
+            (Some((_, rmin, _)), Some((_, _, rmax))) => (rmin + rmax - 1) as f64 / 2.0,
+        };
+
+        Some(rank / self.count as f64)
+    }
+
+    /// Merge adjacent tuples, as long as the error bound holds. The first and the last
+    /// tuples are never merged, so that the minimum and maximum values are kept.
+    fn compress(&mut self) {
+        self.inserted = 0;
+        if self.tuples.len() < 3 {
+            return;
+        }
+
+        let threshold = (2.0 * self.epsilon * self.count as f64).floor() as u64;
+        let mut compressed: Vec<Tuple> = Vec::with_capacity(self.tuples.len());
+        compressed.push(*self.tuples.last().unwrap());
+        for tuple in self.tuples[1..self.tuples.len() - 1].iter().rev() {
+            let next = compressed.last_mut().unwrap();
+            if tuple.g + next.g + next.delta <= threshold {
+                next.g += tuple.g;
+            } else {
+                compressed.push(*tuple);
+            }
+        }
+        compressed.push(self.tuples[0]);
+        compressed.reverse();
+
+        self.tuples = compressed;
+    }
+
+    fn compress_interval(&self) -> u64 {
+        ((1.0 / (2.0 * self.epsilon)).floor() as u64).max(1)
+    }
+}
+
+/// Returns the values of tuples with the lowest and the highest possible ranks of them.
+fn rank_bounds(tuples: &[Tuple]) -> Vec<(f64, u64, u64)> {
+    let mut rmin = 0;
+    tuples
+        .iter()
+        .map(|t| {
+            rmin += t.g;
+            (t.value, rmin, rmin + t.delta)
+        })
+        .collect()
+}
+
+impl Sketch for GKSketch {
+    fn insert(&mut self, value: f64) {
+        GKSketch::insert(self, value);
+    }
+
+    fn merge(&mut self, other: &GKSketch) {
+        GKSketch::merge(self, other);
+    }
+
+    fn count(&self) -> u64 {
+        GKSketch::count(self)
+    }
+
+    fn min(&self) -> Option<f64> {
+        GKSketch::min(self)
+    }
+
+    fn max(&self) -> Option<f64> {
+        GKSketch::max(self)
+    }
+
+    fn quantile(&self, q: f64) -> Option<f64> {
+        GKSketch::quantile(self, q)
+    }
+
+    fn rank(&self, value: f64) -> Option<f64> {
+        GKSketch::rank(self, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utilities::pseudo_random_values;
+
+    use super::*;
+
+    // Check that the rank error of quantiles is within the bound.
+    fn check_bound(sketch: &GKSketch, sorted: &[f64]) {
+        let max_error = sketch.epsilon() * sorted.len() as f64;
+        for i in 0..=1000 {
+            let q = i as f64 / 1000.0;
+            let value = sketch.quantile(q).unwrap();
+            let rank = sorted.partition_point(|&v| v <= value) as f64;
+            let expected = (q * sorted.len() as f64).ceil().max(1.0);
+            assert!(
+                (rank - expected).abs() <= max_error,
+                "q = {}: rank {} != {}",
+                q,
+                rank,
+                expected
+            );
+
+            let error = sketch.rank(sorted[i * (sorted.len() - 1) / 1000]).unwrap()
+                - (i * (sorted.len() - 1) / 1000 + 1) as f64 / sorted.len() as f64;
+            assert!(error.abs() <= sketch.epsilon(), "rank error {}", error);
+        }
+    }
+
+    #[test]
+    fn empty() {
+        let sketch = GKSketch::new(0.01);
+        assert!(sketch.is_empty());
+        assert_eq!(sketch.min(), None);
+        assert_eq!(sketch.max(), None);
+        assert_eq!(sketch.quantile(0.5), None);
+        assert_eq!(sketch.rank(1.0), None);
+    }
+
+    #[test]
+    fn exact() {
+        // compression can't merge anything, while the threshold is less than 2
+        let mut sketch = GKSketch::new(0.01);
+        for value in [3.0, 1.0, 5.0, 2.0, 4.0] {
+            sketch.insert(value);
+        }
+
+        assert_eq!(sketch.len(), 5);
+        assert_eq!(sketch.quantile(0.0), Some(1.0));
+        assert_eq!(sketch.quantile(0.4), Some(2.0));
+        assert_eq!(sketch.quantile(0.5), Some(3.0));
+        assert_eq!(sketch.quantile(1.0), Some(5.0));
+        assert_eq!(sketch.rank(0.0), Some(0.0));
+        assert_eq!(sketch.rank(3.0), Some(0.6));
+        assert_eq!(sketch.rank(3.5), Some(0.6));
+        assert_eq!(sketch.rank(5.0), Some(1.0));
+    }
+
+    #[test]
+    fn error_bound() {
+        let values = pseudo_random_values(42, 100_000);
+        let mut sorted = values.clone();
+        sorted.sort_by(f64::total_cmp);
+
+        for epsilon in [0.01, 0.001] {
+            let mut sketch = GKSketch::new(epsilon);
+            values.iter().for_each(|&v| sketch.insert(v));
+
+            assert_eq!(sketch.count(), values.len() as u64);
+            assert_eq!(sketch.min(), sorted.first().copied());
+            assert_eq!(sketch.max(), sorted.last().copied());
+            assert!(sketch.len() < values.len() / 10, "{}", sketch.len());
+            check_bound(&sketch, &sorted);
+        }
+
+        // sorted input is the worst case for many summaries
+        let mut sketch = GKSketch::new(0.001);
+        sorted.iter().for_each(|&v| sketch.insert(v));
+        check_bound(&sketch, &sorted);
+    }
+
+    #[test]
+    fn merge() {
+        let values = pseudo_random_values(7, 50_000);
+        let mut sorted = values.clone();
+        sorted.sort_by(f64::total_cmp);
+
+        // interleaved values of many sketches
+        let mut sketches = vec![GKSketch::new(0.005); 8];
+        for (i, &value) in values.iter().enumerate() {
+            sketches[i % 8].insert(value);
+        }
+        let mut merged = GKSketch::new(0.001);
+        for sketch in &sketches {
+            merged.merge(sketch);
+        }
+
+        assert_eq!(merged.epsilon(), 0.005);
+        assert_eq!(merged.count(), values.len() as u64);
+        assert_eq!(merged.min(), sorted.first().copied());
+        assert_eq!(merged.max(), sorted.last().copied());
+        check_bound(&merged, &sorted);
+
+        // merging an empty sketch changes nothing
+        let expected = merged.clone();
+        merged.merge(&GKSketch::new(0.1));
+        assert_eq!(merged, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "epsilon must be in the range (0.0; 1.0)")]
+    fn invalid_epsilon() {
+        GKSketch::new(0.0);
+    }
+}
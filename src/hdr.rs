@@ -0,0 +1,125 @@
+use hdrhistogram::Counter;
+
+use crate::bin::Bin;
+use crate::histogram::Histogram;
+
+/// The number of significant decimal digits of histograms created by converting from a
+/// [`Histogram`].
+const SIGNIFICANT_FIGURES: u8 = 3;
+
+/// The highest value, which an auto-resizing `hdrhistogram::Histogram` can track.
+const HIGHEST_TRACKABLE_VALUE: u64 = i64::MAX as u64 / 2;
+
+impl<T: Counter> From<&hdrhistogram::Histogram<T>> for Histogram {
+    /// Create a new Histogram of the default size (see [`Histogram::DEFAULT_SIZE`]) from
+    /// an `hdrhistogram::Histogram`. Every group of equivalent recorded values becomes a bin,
+    /// which value is the median of the group, and these bins are then merged down to the
+    /// size of the histogram.
+    ///
+    /// This is only available when the `hdrhistogram` feature is enabled.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut hdr = hdrhistogram::Histogram::<u64>::new(3).unwrap();
+    /// for value in 1..=1000 {
+    ///     hdr.record(value).unwrap();
+    /// }
+    ///
+    /// let h = Histogram::from(&hdr);
+    /// assert_eq!(h.count(), 1000);
+    /// assert_eq!(h.min(), Some(1.0));
+    /// assert_eq!(h.max(), Some(1000.0));
+    /// ```
+    fn from(hdr: &hdrhistogram::Histogram<T>) -> Self {
+        Histogram::from_sorted_iter(
+            Histogram::DEFAULT_SIZE,
+            hdr.iter_recorded().map(|v| {
+                Bin::new(
+                    hdr.median_equivalent(v.value_iterated_to()) as f64,
+                    v.count_at_value().as_u64(),
+                )
+            }),
+        )
+    }
+}
+
+impl From<&Histogram> for hdrhistogram::Histogram<u64> {
+    /// Create a new auto-resizing `hdrhistogram::Histogram` with 3 significant figures from
+    /// a Histogram. The count of every bin is recorded at the value of the bin rounded to
+    /// the nearest integer. Values, which can't be represented by an `hdrhistogram::Histogram`
+    /// (i.e. negative ones and ones greater than `i64::MAX / 2`), are clamped.
+    ///
+    /// This is only available when the `hdrhistogram` feature is enabled.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(64, (1..=1000).map(|v| v as f64));
+    ///
+    /// let hdr = hdrhistogram::Histogram::<u64>::from(&h);
+    /// assert_eq!(hdr.len(), 1000);
+    /// assert!((hdr.mean() - 500.5).abs() < 1.0);
+    /// ```
+    fn from(h: &Histogram) -> Self {
+        let mut hdr = hdrhistogram::Histogram::new(SIGNIFICANT_FIGURES)
+            .expect("3 significant figures are supported");
+        for bin in h.bins() {
+            // float to integer casts saturate, so negative values become 0
+            let value = (bin.value().round() as u64).min(HIGHEST_TRACKABLE_VALUE);
+            hdr.record_n(value, bin.count())
+                .expect("auto-resizing histogram can track values up to i64::MAX / 2");
+        }
+
+        hdr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_hdr() {
+        let hdr = hdrhistogram::Histogram::<u32>::new(3).unwrap();
+        let h = Histogram::from(&hdr);
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.bins(), &[]);
+
+        let mut hdr = hdrhistogram::Histogram::<u32>::new(3).unwrap();
+        hdr.record_n(5, 3).unwrap();
+        hdr.record_n(1_000_000, 2).unwrap();
+        let h = Histogram::from(&hdr);
+        assert_eq!(h.count(), 5);
+        assert_eq!(h.bins().len(), 2);
+        assert_eq!(h.bins()[0], Bin::new(5.0, 3));
+        assert_eq!(h.bins()[1].count(), 2);
+        assert_relative_eq!(h.bins()[1].value(), 1_000_000.0, max_relative = 0.001);
+    }
+
+    #[test]
+    fn into_hdr() {
+        let h = Histogram::from_iter(5, [-10.0, 0.4, 2.6, 2.6, 1e300]);
+        let hdr = hdrhistogram::Histogram::<u64>::from(&h);
+        assert_eq!(hdr.len(), 5);
+        assert_eq!(hdr.count_at(0), 2);
+        assert_eq!(hdr.count_at(3), 2);
+        assert!(hdr.equivalent(hdr.max(), HIGHEST_TRACKABLE_VALUE));
+    }
+
+    #[test]
+    fn round_trip() {
+        let h = Histogram::from_iter(64, (1..=10_000).map(|v| v as f64));
+        let hdr = hdrhistogram::Histogram::<u64>::from(&h);
+        let h2 = Histogram::from(&hdr);
+
+        assert_eq!(h2.count(), h.count());
+        for q in [0.1, 0.25, 0.5, 0.75, 0.9] {
+            assert_relative_eq!(
+                h2.quantile(q).unwrap(),
+                h.quantile(q).unwrap(),
+                max_relative = 0.01
+            );
+        }
+    }
+}
@@ -1,22 +1,100 @@
-use std::borrow::Borrow;
+use std::borrow::{Borrow, Cow};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Bound, Range, RangeBounds};
 
-use ordered_float::OrderedFloat;
-use superslice::*;
+use crate::bin::{Bin, TotalF64};
+use crate::duration::DurationUnit;
+use crate::error::Error;
+use crate::gaps::Gaps;
+use crate::policy::{CountPolicy, InfinityPolicy, MergePolicy, NanPolicy};
+use crate::sum::CompensatedSum;
 
-use crate::bin::Bin;
+/// The number of bins stored inline (i.e. without a separate heap allocation) when
+/// the `smallvec` feature is enabled. One extra slot is reserved for bins, which are
+/// temporarily added during histogram updates.
+#[cfg(feature = "smallvec")]
+const INLINE_BINS: usize = 32 + 1;
+
+/// The storage of bins of a histogram.
+#[cfg(feature = "smallvec")]
+pub(crate) type Bins = smallvec::SmallVec<[Bin; INLINE_BINS]>;
+#[cfg(not(feature = "smallvec"))]
+pub(crate) type Bins = Vec<Bin>;
+
+/// The exact minimum and maximum values of a histogram.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Extrema {
+    /// Tracking is enabled, but there are no values in the histogram yet.
+    Empty,
+    /// The exact minimum and maximum values.
+    Tracked(f64, f64),
+    /// Tracking is disabled: nothing is stored, and the values of the outermost bins are
+    /// used instead.
+    Disabled,
+}
+
+impl Extrema {
+    /// Returns the state of tracking for the exact minimum and maximum values, which are
+    /// either both known, or both unknown (if the histogram is empty).
+    fn new(min_value: Option<f64>, max_value: Option<f64>) -> Extrema {
+        match (min_value, max_value) {
+            (Some(min_value), Some(max_value)) => Extrema::Tracked(min_value, max_value),
+            _ => Extrema::Empty,
+        }
+    }
+
+    /// Returns the exact minimum and maximum values, if they are tracked.
+    fn get(self) -> Option<(f64, f64)> {
+        match self {
+            Extrema::Tracked(min_value, max_value) => Some((min_value, max_value)),
+            _ => None,
+        }
+    }
+
+    /// Returns the state of an empty histogram with the same setting of tracking.
+    fn cleared(self) -> Extrema {
+        match self {
+            Extrema::Disabled => Extrema::Disabled,
+            _ => Extrema::Empty,
+        }
+    }
+}
 
 /// A fixed-size ordered list of bins that is a compact approximate representation
 /// of a numerical data distribution. Typical operations on the constructed histograms
 /// include approximations of quantiles and counts.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Histogram {
     size: usize,
-    bins: Vec<Bin>,
-    min_value: Option<f64>,
-    max_value: Option<f64>,
+    bins: Bins,
+    extrema: Extrema,
+    min_gap: f64,
+    merge_policy: MergePolicy,
+    duration_unit: DurationUnit,
+    count: u64,
+    count_policy: CountPolicy,
+    sum: CompensatedSum,
+    nan_policy: NanPolicy,
+    nan_count: u64,
+    infinity_policy: InfinityPolicy,
+    underflow_count: u64,
+    overflow_count: u64,
+    gaps: Gaps,
 }
 
 impl Histogram {
+    /// The size of histograms created via [`Default`] or [`FromIterator`].
+    pub const DEFAULT_SIZE: usize = 64;
+
+    /// The minimum size of histograms, for which the gaps between adjacent bins are kept in
+    /// a priority queue. Scanning the bins is faster for smaller histograms.
+    const GAPS_MIN_SIZE: usize = 64;
+
+    /// The (maximum) number of values buffered by [`Histogram::insert_many`] at a time.
+    const INSERT_MANY_BATCH_SIZE: usize = 4096;
+
     /// Create a new Histogram with the given number of bins.
     ///
     /// The larger the size of the histogram, the more accurate approximations
@@ -32,18 +110,47 @@ impl Histogram {
     pub fn new(size: usize) -> Histogram {
         assert!(size > 0, "histogram size must be greater than 0");
 
+        // reserve one extra slot for bins, which are temporarily added during
+        // histogram updates. This will allow us to avoid unnecessary memory
+        // allocations
+        Histogram::with_capacity(size, size + 1)
+    }
+
+    /// Same as `new()`, but returns `None` instead of aborting, if the memory for bins can't
+    /// be allocated, e.g. if the size is passed by a foreign caller.
+    #[cfg(any(feature = "ffi", feature = "python"))]
+    pub(crate) fn try_new(size: usize) -> Option<Histogram> {
+        assert!(size > 0, "histogram size must be greater than 0");
+
+        let mut h = Histogram::with_capacity(size, 0);
+        h.bins.try_reserve(size.checked_add(1)?).ok()?;
+        Some(h)
+    }
+
+    /// Create a new empty Histogram of the given size, whose storage of bins initially
+    /// has room for `capacity` bins.
+    fn with_capacity(size: usize, capacity: usize) -> Histogram {
         Histogram {
             size,
-            // reserve one extra slot for bins, which are temporarily added during
-            // histogram updates. This will allow us to avoid unnecessary memory
-            // allocations
-            bins: Vec::with_capacity(size + 1),
-            min_value: None,
-            max_value: None,
+            bins: Bins::with_capacity(capacity),
+            extrema: Extrema::Empty,
+            min_gap: 0.0,
+            merge_policy: MergePolicy::default(),
+            duration_unit: DurationUnit::default(),
+            count: 0,
+            count_policy: CountPolicy::default(),
+            sum: CompensatedSum::default(),
+            nan_policy: NanPolicy::default(),
+            nan_count: 0,
+            infinity_policy: InfinityPolicy::default(),
+            underflow_count: 0,
+            overflow_count: 0,
+            gaps: Gaps::default(),
         }
     }
 
-    /// Create a new Histogram of the given size from an iterable.
+    /// Create a new Histogram of the given size from an iterable of values (or bins, or
+    /// `(value, count)` pairs).
     ///
     /// ```
     /// use bhtt::Histogram;
@@ -53,17 +160,165 @@ impl Histogram {
     /// assert_eq!(h.count(), 10);
     /// assert_eq!(h.min(), Some(-5.4));
     /// assert_eq!(h.max(), Some(10.0));
+    ///
+    /// let h = Histogram::from_iter(5, &[(1.0, 10), (2.5, 40)]);
+    /// assert_eq!(h.count(), 50);
+    ///
+    /// // integers are converted into floating point values
+    /// let h = Histogram::from_iter(5, vec![1u64, 512, 4096]);
+    /// assert_eq!(h.max(), Some(4096.0));
+    /// ```
+    pub fn from_iter(size: usize, iter: impl IntoIterator<Item = impl Into<Bin>>) -> Histogram {
+        let mut h = Histogram::new(size);
+
+        for v in iter {
+            h.insert(v);
+        }
+
+        h
+    }
+
+    /// Create a new Histogram of the given size from an iterable of values (or bins, or
+    /// `(value, count)` pairs) sorted in the ascending order. The function will panic if
+    /// the values are not sorted.
+    ///
+    /// This is faster than [`from_iter`](Histogram::from_iter), as new bins are always
+    /// appended to the end of the list, and the histogram is compacted in batches rather
+    /// than after every value. The resulting bins may slightly differ from the ones
+    /// produced by [`from_iter`](Histogram::from_iter).
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_sorted_iter(5, vec![-5.4, -2.1, 0.0, 1.0, 4.3, 5.2, 7.8, 8.5, 8.6, 10.0]);
+    /// assert_eq!(h.size(), 5);
+    /// assert_eq!(h.count(), 10);
+    /// assert_eq!(h.min(), Some(-5.4));
+    /// assert_eq!(h.max(), Some(10.0));
     /// ```
-    pub fn from_iter(size: usize, iter: impl IntoIterator<Item = impl Borrow<f64>>) -> Histogram {
+    pub fn from_sorted_iter(
+        size: usize,
+        iter: impl IntoIterator<Item = impl Into<Bin>>,
+    ) -> Histogram {
         let mut h = Histogram::new(size);
 
+        let mut previous = f64::NEG_INFINITY;
         for v in iter {
-            h.insert(*v.borrow());
+            let bin = v.into();
+            assert!(
+                bin.value() >= previous,
+                "values must be sorted in the ascending order"
+            );
+            previous = bin.value();
+
+            if let Some(bin) = h.admit(bin) {
+                h.push_sorted_bin(bin);
+                h.track_min_max(bin.value());
+                h.count += bin.count();
+                h.sum.add(bin.value() * bin.count() as f64);
+            }
         }
+        h.shrink();
+        h.release_excess_capacity();
 
         h
     }
 
+    /// Create a new Histogram from its parts (see [`into_parts`](Histogram::into_parts)),
+    /// e.g. to restore it from a custom serialization format, or to ingest bins computed
+    /// by another system: the size, the bins sorted in the ascending order, and the exact
+    /// minimum and maximum values (both must be `None` if there are no bins). If there are
+    /// more bins than `size`, the closest ones are merged. The sum of values is estimated
+    /// from the bins.
+    ///
+    /// Returns an error, if the size is 0, if bins are not sorted, or if the minimum and
+    /// maximum values are not finite or do not match the bins.
+    ///
+    /// ```
+    /// use bhtt::{Bin, Histogram};
+    ///
+    /// let bins = vec![Bin::new(1.0, 2), Bin::new(3.5, 1)];
+    /// let h = Histogram::from_parts(5, bins.clone(), Some(0.5), Some(4.0)).unwrap();
+    /// assert_eq!(h.bins(), bins);
+    /// assert_eq!(h.min(), Some(0.5));
+    /// assert_eq!(h.sum(), 5.5);
+    ///
+    /// assert_eq!(h.into_parts(), (5, bins.clone(), Some(0.5), Some(4.0)));
+    /// assert!(Histogram::from_parts(5, bins, Some(1.5), Some(4.0)).is_err());
+    /// ```
+    pub fn from_parts(
+        size: usize,
+        bins: Vec<Bin>,
+        min_value: Option<f64>,
+        max_value: Option<f64>,
+    ) -> Result<Histogram, Error> {
+        let invalid = |reason: &str| Err(Error::InvalidParts(reason.into()));
+
+        if size == 0 {
+            return invalid("histogram size must be greater than 0");
+        }
+        if bins
+            .windows(2)
+            .any(|pair| pair[0].value() > pair[1].value())
+        {
+            return invalid("bins must be sorted in the ascending order");
+        }
+        match (bins.first(), bins.last(), min_value, max_value) {
+            (None, None, None, None) => {}
+            (Some(first), Some(last), Some(min_value), Some(max_value))
+                if min_value.is_finite()
+                    && max_value.is_finite()
+                    && min_value <= first.value()
+                    && max_value >= last.value() => {}
+            _ => return invalid("min and max values do not match the bins"),
+        }
+        let count = bins
+            .iter()
+            .try_fold(0u64, |total, bin| total.checked_add(bin.count()));
+        let count = match count {
+            Some(count) => count,
+            None => return invalid("the total count is too large"),
+        };
+
+        let mut sum = CompensatedSum::default();
+        for bin in &bins {
+            sum.add(bin.value() * bin.count() as f64);
+        }
+
+        let mut h = Histogram::from_raw_parts(size, Bins::new(), min_value, max_value, sum);
+        h.bins = bins.into_iter().collect();
+        h.count = count;
+        h.shrink();
+        h.release_excess_capacity();
+
+        Ok(h)
+    }
+
+    /// Returns the parts of the histogram, which it can be restored from with
+    /// [`from_parts`](Histogram::from_parts): the size, the bins, and the exact minimum and
+    /// maximum values. Settings of the histogram (e.g. policies) and the numbers of NaN,
+    /// underflowed and overflowed values are not included.
+    ///
+    /// ```
+    /// use bhtt::{Bin, Histogram};
+    ///
+    /// let h = Histogram::from_iter(5, [1.0, 2.0]);
+    /// assert_eq!(
+    ///     h.into_parts(),
+    ///     (5, vec![Bin::new(1.0, 1), Bin::new(2.0, 1)], Some(1.0), Some(2.0))
+    /// );
+    /// ```
+    pub fn into_parts(self) -> (usize, Vec<Bin>, Option<f64>, Option<f64>) {
+        let (min_value, max_value) = self.extrema.get().unzip();
+
+        (
+            self.size,
+            self.bins.into_iter().collect(),
+            min_value,
+            max_value,
+        )
+    }
+
     /// Returns the size of the histogram.
     ///
     /// ```
@@ -92,6 +347,63 @@ impl Histogram {
         &self.bins
     }
 
+    /// Returns estimates of the boundaries of bins, e.g. to export the histogram to
+    /// a system, which expects buckets with explicit boundaries rather than bin values.
+    /// The `i`'th bin is estimated to span the interval between the `i`'th and the
+    /// `i + 1`'th boundaries, so there is one more boundary than there are bins (or none,
+    /// if the histogram is empty).
+    ///
+    /// Inner boundaries are the midpoints between the values of adjacent bins, and the
+    /// outermost boundaries are the exact minimum and maximum values.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(5, [(1.0, 2), (2.0, 1), (4.0, 3)]);
+    /// assert_eq!(h.bin_edges(), vec![1.0, 1.5, 3.0, 4.0]);
+    ///
+    /// assert!(Histogram::new(5).bin_edges().is_empty());
+    /// ```
+    pub fn bin_edges(&self) -> Vec<f64> {
+        match (self.min(), self.max()) {
+            (Some(min_value), Some(max_value)) => std::iter::once(min_value)
+                .chain(
+                    self.bins
+                        .windows(2)
+                        .map(|pair| (pair[0].value() + pair[1].value()) / 2.0),
+                )
+                .chain(std::iter::once(max_value))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the number of bytes of memory used by the histogram: the size of the
+    /// struct itself and the heap memory allocated for its bins (and auxiliary data
+    /// structures used to speed up updates of large histograms).
+    ///
+    /// ```
+    /// use std::mem::size_of;
+    /// use bhtt::{Bin, Histogram};
+    ///
+    /// let h = Histogram::new(64);
+    /// assert!(h.memory_usage() >= size_of::<Histogram>() + 64 * size_of::<Bin>());
+    /// ```
+    pub fn memory_usage(&self) -> usize {
+        #[cfg(feature = "smallvec")]
+        let heap_bins = if self.bins.spilled() {
+            self.bins.capacity()
+        } else {
+            0
+        };
+        #[cfg(not(feature = "smallvec"))]
+        let heap_bins = self.bins.capacity();
+
+        std::mem::size_of::<Histogram>()
+            + heap_bins * std::mem::size_of::<Bin>()
+            + self.gaps.memory_usage()
+    }
+
     /// Returns the total number of values in the histogram.
     ///
     /// ```
@@ -105,10 +417,176 @@ impl Histogram {
     /// assert_eq!(h.count(), 10);
     /// ```
     pub fn count(&self) -> u64 {
-        self.bins.iter().map(|bin| bin.count()).sum()
+        self.count
+    }
+
+    /// Returns `true` if the histogram contains no values. NaN and infinite values counted
+    /// separately (see [`try_insert`](Histogram::try_insert)) are not taken into account.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::new(5);
+    /// assert!(h.is_empty());
+    ///
+    /// h.insert(1.0);
+    /// assert!(!h.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the number of NaN values passed to [`try_insert`](Histogram::try_insert)
+    /// with [`NanPolicy::Count`]. These values are not counted by [`count`](Histogram::count).
+    pub fn nan_count(&self) -> u64 {
+        self.nan_count
+    }
+
+    /// Returns the policy of handling NaN values by [`try_insert`](Histogram::try_insert).
+    pub fn nan_policy(&self) -> NanPolicy {
+        self.nan_policy
+    }
+
+    /// Set the policy of handling NaN values by [`try_insert`](Histogram::try_insert).
+    pub fn set_nan_policy(&mut self, policy: NanPolicy) {
+        self.nan_policy = policy;
+    }
+
+    /// Returns the number of negative infinite values passed to
+    /// [`try_insert`](Histogram::try_insert) with [`InfinityPolicy::Count`]. These values are
+    /// not counted by [`count`](Histogram::count).
+    pub fn underflow_count(&self) -> u64 {
+        self.underflow_count
+    }
+
+    /// Returns the number of positive infinite values passed to
+    /// [`try_insert`](Histogram::try_insert) with [`InfinityPolicy::Count`]. These values are
+    /// not counted by [`count`](Histogram::count).
+    pub fn overflow_count(&self) -> u64 {
+        self.overflow_count
+    }
+
+    /// Returns the policy of handling infinite values by [`try_insert`](Histogram::try_insert).
+    pub fn infinity_policy(&self) -> InfinityPolicy {
+        self.infinity_policy
+    }
+
+    /// Set the policy of handling infinite values by [`try_insert`](Histogram::try_insert).
+    pub fn set_infinity_policy(&mut self, policy: InfinityPolicy) {
+        self.infinity_policy = policy;
+    }
+
+    /// Returns `true` if the exact minimum and maximum values are tracked.
+    pub fn min_max_tracking(&self) -> bool {
+        self.extrema != Extrema::Disabled
+    }
+
+    /// Enable or disable tracking of the exact minimum and maximum values.
+    ///
+    /// Histograms, which do not track the exact minimum and maximum values, use the values
+    /// of the outermost bins instead. Estimates of interior quantiles are not affected much,
+    /// but estimates close to the tails of the distribution become less accurate. Once
+    /// disabled, tracking can only be re-enabled for an empty histogram, or the function
+    /// will panic.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::new(2);
+    /// h.set_min_max_tracking(false);
+    /// h.insert_many(vec![1.0, 2.0, 3.0, 10.0]);
+    ///
+    /// assert_eq!(h.min(), Some(2.0));
+    /// assert_eq!(h.max(), Some(10.0));
+    /// ```
+    pub fn set_min_max_tracking(&mut self, enabled: bool) {
+        if !enabled {
+            self.extrema = Extrema::Disabled;
+        } else if self.extrema == Extrema::Disabled {
+            assert!(
+                self.bins.is_empty(),
+                "min/max tracking can only be enabled for an empty histogram"
+            );
+            self.extrema = Extrema::Empty;
+        }
+    }
+
+    /// Returns the minimum distance between values of adjacent bins.
+    pub fn min_gap(&self) -> f64 {
+        self.min_gap
+    }
+
+    /// Set the minimum distance between values of adjacent bins. `gap` must be finite and
+    /// non-negative, or the function will panic.
+    ///
+    /// Adjacent bins, which are closer to each other than `gap`, are merged eagerly, even if
+    /// the histogram is not full yet. This prevents long runs of nearly identical values from
+    /// taking up most of the bins, so that they are still available for rare outliers.
+    /// The default value of 0.0 disables eager merges.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::new(5);
+    /// h.set_min_gap(0.5);
+    /// h.insert_many(vec![1.0, 1.1, 1.2, 1.3, 10.0]);
+    ///
+    /// assert_eq!(h.bins().len(), 2);
+    /// assert_eq!(h.count(), 5);
+    /// ```
+    pub fn set_min_gap(&mut self, gap: f64) {
+        assert!(
+            gap.is_finite() && gap >= 0.0,
+            "minimum gap must be finite and non-negative"
+        );
+
+        self.min_gap = gap;
+        self.shrink();
+    }
+
+    /// Returns the policy of measuring the distance between bins.
+    pub fn merge_policy(&self) -> MergePolicy {
+        self.merge_policy
     }
 
-    /// Returns the (exact) minimum value or `None` if the histogram is empty.
+    /// Set the policy of measuring the distance between bins (see [`MergePolicy`]), which
+    /// determines the pair of bins to be merged when the histogram is full. Only future
+    /// updates are affected.
+    pub fn set_merge_policy(&mut self, policy: MergePolicy) {
+        self.merge_policy = policy;
+        self.gaps.set_policy(policy);
+    }
+
+    /// Returns the policy of handling overflows of the total count.
+    pub fn count_policy(&self) -> CountPolicy {
+        self.count_policy
+    }
+
+    /// Set the policy of handling overflows of the total count (see [`CountPolicy`]). Only
+    /// future updates are affected.
+    pub fn set_count_policy(&mut self, policy: CountPolicy) {
+        self.count_policy = policy;
+    }
+
+    /// Returns the unit, in which durations are recorded.
+    pub fn duration_unit(&self) -> DurationUnit {
+        self.duration_unit
+    }
+
+    /// Set the unit, in which durations are recorded (see [`Histogram::insert_duration`]).
+    /// Values already in the histogram are not converted, so the unit of a non-empty
+    /// histogram can't be changed, or the function will panic.
+    pub fn set_duration_unit(&mut self, unit: DurationUnit) {
+        assert!(
+            unit == self.duration_unit || self.is_empty(),
+            "the duration unit of a non-empty histogram can't be changed"
+        );
+
+        self.duration_unit = unit;
+    }
+
+    /// Returns the (exact) minimum value or `None` if the histogram is empty. If min/max
+    /// tracking is disabled, the value of the leftmost bin is returned instead.
     ///
     /// ```
     /// use bhtt::Histogram;
@@ -121,10 +599,15 @@ impl Histogram {
     /// assert_eq!(h.min(), Some(-5.4));
     /// ```
     pub fn min(&self) -> Option<f64> {
-        self.min_value
+        match self.extrema {
+            Extrema::Tracked(min_value, _) => Some(min_value),
+            Extrema::Empty => None,
+            Extrema::Disabled => self.bins.first().map(|bin| bin.value()),
+        }
     }
 
-    /// Returns the (exact) maximum value or `None` if the histogram is empty.
+    /// Returns the (exact) maximum value or `None` if the histogram is empty. If min/max
+    /// tracking is disabled, the value of the rightmost bin is returned instead.
     ///
     /// ```
     /// use bhtt::Histogram;
@@ -137,7 +620,49 @@ impl Histogram {
     /// assert_eq!(h.max(), Some(10.0));
     /// ```
     pub fn max(&self) -> Option<f64> {
-        self.max_value
+        match self.extrema {
+            Extrema::Tracked(_, max_value) => Some(max_value),
+            Extrema::Empty => None,
+            Extrema::Disabled => self.bins.last().map(|bin| bin.value()),
+        }
+    }
+
+    /// Returns the (exact) sum of all values in the histogram.
+    ///
+    /// The sum is tracked using compensated summation, so it does not accumulate
+    /// rounding errors even for large numbers of values.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::new(5);
+    /// for value in vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+    ///     h.insert(value);
+    /// }
+    ///
+    /// assert_eq!(h.sum(), 37.9);
+    /// ```
+    pub fn sum(&self) -> f64 {
+        self.sum.value()
+    }
+
+    /// Returns the (exact) arithmetic mean of the values or `None` if the histogram is empty.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::new(5);
+    /// for value in vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+    ///     h.insert(value);
+    /// }
+    ///
+    /// assert_eq!(h.mean(), Some(3.79));
+    /// ```
+    pub fn mean(&self) -> Option<f64> {
+        match self.count() {
+            0 => None,
+            count => Some(self.sum() / count as f64),
+        }
     }
 
     /// Returns an approximated value of the `q`'th quantile of the values or `None`
@@ -162,48 +687,74 @@ impl Histogram {
             "q must be in the range [0.0; 1.0]"
         );
 
+        match (self.min(), self.max()) {
+            (Some(min_value), Some(max_value)) => Some(uniform_quantile(
+                &self.bins, self.count, min_value, max_value, q, None,
+            )),
+            // histogram is empty
+            _ => None,
+        }
+    }
+
+    /// Returns a conservative bracket `(lower, upper)` for the value of the `q`'th quantile
+    /// of the values or `None` if the histogram is empty, e.g. to act on the worst case
+    /// rather than on the point estimate returned by [`quantile`](Histogram::quantile).
+    /// `q` must be in the range [0.0; 1.0], or the function will panic.
+    ///
+    /// The bracket is formed by the values of the bins enclosing the bin, which the value of
+    /// the quantile belongs to (or the minimum and maximum values for the outermost bins):
+    /// bins are only ever merged with adjacent ones, so the values merged into a bin are
+    /// expected to be between the values of its neighbours. The bracket of the minimum
+    /// (`q = 0.0`) and the maximum (`q = 1.0`) values is exact, as they are tracked exactly.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(5, [(1.0, 1), (2.0, 2), (3.0, 4), (4.0, 2), (5.0, 1)]);
+    /// assert_eq!(h.quantile_bounds(0.5), Some((2.0, 4.0)));
+    /// assert_eq!(h.quantile_bounds(0.1), Some((1.0, 2.0)));
+    /// assert_eq!(h.quantile_bounds(1.0), Some((5.0, 5.0)));
+    /// ```
+    pub fn quantile_bounds(&self, q: f64) -> Option<(f64, f64)> {
+        assert!(
+            (0.0..=1.0).contains(&q),
+            "q must be in the range [0.0; 1.0]"
+        );
+
+        let (min_value, max_value) = (self.min()?, self.max()?);
         if q == 0.0 {
-            self.min()
+            return Some((min_value, min_value));
         } else if q == 1.0 {
-            self.max()
-        } else {
-            let total_count = self.count();
-            match total_count {
-                0 => None,
-                _ => {
-                    // Algorithm 4: Uniform procedure (from the paper mentioned in the description)
-                    //
-                    // To estimate the value of a given quantile we first find a pair of bins in
-                    // the histogram that enclose the target cumulative count. Then we use the
-                    // difference between the target count and the cumulative count up to the left
-                    // bin in the pair to form a (quadratic) equation, that allows us to calculate
-                    // the target value based on its proximity to the right bin.
-
-                    let qth_count = self.count() as f64 * q;
-                    let (i, up_to_qth_count) = self.index_of_cumulative_count_less_than(qth_count);
-
-                    let (left_bin, right_bin) = self.get_bordering_bins(i);
-                    let (left_value, left_count) = (left_bin.value(), left_bin.count() as f64);
-                    let (right_value, right_count) = (right_bin.value(), right_bin.count() as f64);
-
-                    let d = qth_count - up_to_qth_count;
-                    let a = right_count - left_count;
-                    if a == 0.0 {
-                        Some(left_value + (right_value - left_value) * d / left_count)
-                    } else {
-                        let b = 2.0 * left_count;
-                        let c = -2.0 * d;
-                        let z = (-b + (b.powi(2) - 4.0 * a * c).sqrt()) / (2.0 * a);
-
-                        Some(left_value + (right_value - left_value) * z)
-                    }
-                }
-            }
+            return Some((max_value, max_value));
         }
+
+        // find the first bin, for which the cumulative count reaches the rank of the quantile
+        let rank = self.count() as f64 * q;
+        let mut cumulative_count = 0;
+        let i = self
+            .bins
+            .iter()
+            .position(|bin| {
+                cumulative_count += bin.count();
+                cumulative_count as f64 >= rank
+            })
+            .unwrap_or(self.bins.len() - 1);
+
+        let lower = match i {
+            0 => min_value,
+            i => self.bins[i - 1].value(),
+        };
+        let upper = match self.bins.get(i + 1) {
+            Some(bin) => bin.value(),
+            None => max_value,
+        };
+
+        Some((lower.max(min_value), upper.min(max_value)))
     }
 
-    /// Returns an estimate of the number of values in the histogram that are less
-    /// than or equal to `value`.
+    /// Returns an approximated value of the `p`'th percentile of the values or `None`
+    /// if the histogram is empty. `p` must be in the range [0.0; 100.0], or the function
+    /// will panic.
     ///
     /// ```
     /// use bhtt::Histogram;
@@ -212,60 +763,323 @@ impl Histogram {
     /// for value in vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
     ///     h.insert(value);
     /// }
-    /// assert_eq!(h.count_less_than_or_equal_to(-7.4), 0);
-    /// assert_eq!(h.count_less_than_or_equal_to(5.0), 5);
-    /// assert_eq!(h.count_less_than_or_equal_to(13.0), 10);
+    ///
+    /// assert_eq!(h.percentile(0.0), Some(-5.4));
+    /// assert_eq!(h.percentile(50.0), Some(4.75));
+    /// assert_eq!(h.percentile(100.0), Some(10.0));
     /// ```
-    pub fn count_less_than_or_equal_to(&self, value: f64) -> u64 {
-        assert!(!value.is_nan(), "value must not be NaN");
-
-        let total_count = self.count();
-        if total_count == 0 || value < self.min().unwrap_or(f64::NAN) {
-            // histogram is empty, or the interval (-inf; value] does not intersect
-            // with the interval [min; max]
-            0
-        } else if value >= self.max().unwrap_or(f64::NAN) {
-            // the interval (-inf; value] includes all the values in the histogram
-            total_count
-        } else {
-            // Algorithm 3: Sum Procedure (from the paper mentioned in the description)
-            //
-            // In order to estimate the number of values in the histogram that are less than or
-            // equal to the given value we need to find a pair of bins, which would be adjacent to
-            // the (value, count) bin if we were to insert it to the histogram. The resulting count
-            // will be equal to the sum of the following components:
-            //
-            // 1) sum of counts of the bins preceding the left neighbour
-            // 2) one half of left neighbour's count
-            // 3) count of values between the left neighbour and the (value, count) bin
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        assert!(
+            (0.0..=100.0).contains(&p),
+            "p must be in the range [0.0; 100.0]"
+        );
 
-            // find the position of the bin if we were to insert it to the histogram
-            let pos = self.bins.upper_bound(&Bin::empty(value));
+        self.quantile(p / 100.0)
+    }
 
-            // calculate the sum of counts of the bins preceding the left neighbour of that bin
-            let left = pos.saturating_sub(1);
-            let count_up_to_left: u64 = self.bins[..left].iter().map(|bin| bin.count()).sum();
+    /// Returns an approximated value of the median of the values or `None` if the
+    /// histogram is empty.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::new(5);
+    /// for value in vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+    ///     h.insert(value);
+    /// }
+    ///
+    /// assert_eq!(h.median(), Some(4.75));
+    /// ```
+    pub fn median(&self) -> Option<f64> {
+        self.quantile(0.5)
+    }
 
-            // determine the bordering bins
-            let (left_bin, right_bin) = self.get_bordering_bins(pos);
-            let (left_value, left_count) = (left_bin.value(), left_bin.count() as f64);
-            let (right_value, right_count) = (right_bin.value(), right_bin.count() as f64);
+    /// Returns an estimate of the mode (i.e. the most probable value) of the values or
+    /// `None` if the histogram is empty.
+    ///
+    /// The mode is estimated as the value of the bin with the highest density, which is
+    /// the count of the bin divided by its estimated width. This way a narrow cluster of
+    /// values is preferred over a wide bin that just happens to have a larger count.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::new(5);
+    /// for value in vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+    ///     h.insert(value);
+    /// }
+    ///
+    /// assert_eq!(h.mode(), Some(8.725));
+    /// ```
+    pub fn mode(&self) -> Option<f64> {
+        let edges = self.bin_edges();
 
-            // estimate the count of values between the left neighbour and the (value, count) bins
-            let count_left_to_value = if right_value - left_value <= 0.0 {
-                0.0
-            } else {
-                let proximity_to_right = (value - left_value) / (right_value - left_value);
-                let count = left_count + (right_count - left_count) * proximity_to_right;
+        self.bins
+            .iter()
+            .zip(edges.windows(2))
+            .map(|(bin, edges)| (bin.value(), bin.count() as f64 / (edges[1] - edges[0])))
+            .fold(None, |mode, (value, density)| match mode {
+                // in case of a tie the leftmost bin wins
+                Some((_, mode_density)) if mode_density >= density => mode,
+                _ => Some((value, density)),
+            })
+            .map(|(value, _)| value)
+    }
 
-                (left_count + count) / 2.0 * proximity_to_right
-            };
+    /// Returns an approximated value of the interquartile range (i.e. the difference
+    /// between the 3rd and the 1st quartiles) of the values or `None` if the histogram is
+    /// empty.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(8, (1..=8).map(|v| v as f64));
+    /// assert_eq!(h.iqr(), Some(4.0));
+    /// ```
+    pub fn iqr(&self) -> Option<f64> {
+        Some(self.quantile(0.75)? - self.quantile(0.25)?)
+    }
+
+    /// Returns Tukey's fences `(Q1 - k * IQR, Q3 + k * IQR)` or `None` if the histogram is
+    /// empty. Values outside the fences are considered to be outliers: `k` is usually 1.5
+    /// for outliers, and 3.0 for "far out" values. `k` must be finite and non-negative, or
+    /// the function will panic.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(8, (1..=8).map(|v| v as f64));
+    /// assert_eq!(h.outlier_fences(1.5), Some((-3.5, 12.5)));
+    /// assert_eq!(h.outlier_fences(0.0), Some((2.5, 6.5)));
+    /// ```
+    pub fn outlier_fences(&self, k: f64) -> Option<(f64, f64)> {
+        assert!(
+            k.is_finite() && k >= 0.0,
+            "k must be finite and non-negative"
+        );
+
+        let (q1, q3) = (self.quantile(0.25)?, self.quantile(0.75)?);
+        let iqr = q3 - q1;
+
+        Some((q1 - k * iqr, q3 + k * iqr))
+    }
+
+    /// Returns an estimate of the number of values in the histogram that are less
+    /// than or equal to `value`.
+    ///
+    /// If the exact minimum and maximum values are tracked (see
+    /// [`set_min_max_tracking`](Histogram::set_min_max_tracking)), the estimate accounts for
+    /// them: the minimum value is always counted, and a value less than the maximum never
+    /// includes it. Otherwise, the estimate is only based on the bins.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::new(5);
+    /// for value in vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+    ///     h.insert(value);
+    /// }
+    /// assert_eq!(h.count_less_than_or_equal_to(-7.4), 0);
+    /// assert_eq!(h.count_less_than_or_equal_to(5.0), 5);
+    /// assert_eq!(h.count_less_than_or_equal_to(13.0), 10);
+    /// ```
+    pub fn count_less_than_or_equal_to(&self, value: f64) -> u64 {
+        assert!(!value.is_nan(), "value must not be NaN");
+
+        self.count_up_to(value, true, None).round() as u64
+    }
+
+    /// Returns an estimate of the number of values in the histogram that are greater
+    /// than `value`. The estimate mirrors
+    /// [`count_less_than_or_equal_to`](Histogram::count_less_than_or_equal_to): the counts of
+    /// bins are summed up from the right rather than from the left, so the two estimates are
+    /// rounded independently and do not necessarily add up to [`count`](Histogram::count).
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::new(5);
+    /// for value in vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+    ///     h.insert(value);
+    /// }
+    /// assert_eq!(h.count_greater_than(-7.4), 10);
+    /// assert_eq!(h.count_greater_than(5.0), 5);
+    /// assert_eq!(h.count_greater_than(10.0), 0);
+    /// ```
+    pub fn count_greater_than(&self, value: f64) -> u64 {
+        assert!(!value.is_nan(), "value must not be NaN");
+
+        self.count_down_to(value, false, None).round() as u64
+    }
+
+    /// Returns an estimate of the number of values in the histogram that fall into
+    /// the given `range`. Both inclusive and exclusive bounds are supported, as well
+    /// as unbounded ranges. Range bounds must not be NaN, or the function will panic.
+    ///
+    /// The estimate is computed from the unrounded cumulative counts at both bounds,
+    /// so it is generally more accurate than a difference of two
+    /// [`count_less_than_or_equal_to`](Histogram::count_less_than_or_equal_to) calls.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::new(5);
+    /// for value in vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+    ///     h.insert(value);
+    /// }
+    /// assert_eq!(h.count_in_range(..), 10);
+    /// assert_eq!(h.count_in_range(-5.4..=10.0), 10);
+    /// assert_eq!(h.count_in_range(-5.4..10.0), 9);
+    /// assert_eq!(h.count_in_range(0.0..=5.0), 2);
+    /// assert_eq!(h.count_in_range(11.0..), 0);
+    /// ```
+    pub fn count_in_range(&self, range: impl RangeBounds<f64>) -> u64 {
+        self.estimate_count_in_range(range, None)
+    }
+
+    /// Returns an estimate of the fraction of values in the histogram that are less
+    /// than or equal to `value` (i.e. the inverse of [`quantile`](Histogram::quantile)),
+    /// or `None` if the histogram is empty. The result is in the range [0.0; 1.0].
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::new(5);
+    /// for value in vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+    ///     h.insert(value);
+    /// }
+    /// assert_eq!(h.rank(-7.4), Some(0.0));
+    /// assert_eq!(h.rank(4.75), Some(0.5));
+    /// assert_eq!(h.rank(13.0), Some(1.0));
+    /// ```
+    pub fn rank(&self, value: f64) -> Option<f64> {
+        assert!(!value.is_nan(), "value must not be NaN");
 
-            // add up all partial counts and round to the nearest integer number
-            (count_up_to_left as f64 + left_count / 2.0 + count_left_to_value).round() as u64
+        match self.count() {
+            0 => None,
+            total_count => Some(self.count_up_to(value, true, None) / total_count as f64),
         }
     }
 
+    /// Returns estimates of the number of values in fixed buckets defined by their upper
+    /// `boundaries`, e.g. to export the histogram to a system, which only supports buckets
+    /// with fixed boundaries. The `i`'th element of the result is the number of values in
+    /// the interval (`boundaries[i - 1]`; `boundaries[i]`], and the last extra element is
+    /// the number of values greater than the last boundary, so that the counts always add
+    /// up to [`count`](Histogram::count).
+    ///
+    /// Boundaries must be sorted in ascending order and must not be NaN, or the function
+    /// will panic.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(64, (1..=1000).map(|v| v as f64));
+    /// let buckets = h.to_buckets(&[100.0, 500.0, 1000.0]);
+    ///
+    /// assert_eq!(buckets, vec![100, 400, 500, 0]);
+    /// ```
+    pub fn to_buckets(&self, boundaries: &[f64]) -> Vec<u64> {
+        assert!(
+            boundaries.iter().all(|boundary| !boundary.is_nan()),
+            "value must not be NaN"
+        );
+        assert!(
+            boundaries.windows(2).all(|pair| pair[0] <= pair[1]),
+            "boundaries must be sorted in ascending order"
+        );
+
+        let prefix_counts: Vec<u64> = std::iter::once(0)
+            .chain(self.bins.iter().scan(0, |total, bin| {
+                *total += bin.count();
+                Some(*total)
+            }))
+            .collect();
+
+        let mut previous = 0;
+        let mut buckets: Vec<u64> = boundaries
+            .iter()
+            .map(|&boundary| {
+                // rounding must not make the cumulative count decrease
+                let cumulative = (self
+                    .count_up_to(boundary, true, Some(&prefix_counts))
+                    .round() as u64)
+                    .clamp(previous, self.count());
+                let count = cumulative - previous;
+                previous = cumulative;
+
+                count
+            })
+            .collect();
+        buckets.push(self.count() - previous);
+
+        buckets
+    }
+
+    /// Returns `n` points `(value, density)` of the estimated probability density function
+    /// of values, which are evenly spaced across the range [min; max], e.g. to plot the
+    /// distribution. The density is the derivative of the cumulative count estimated by
+    /// [`count_in_range`](Histogram::count_in_range) divided by the total count, i.e. it's
+    /// interpolated linearly between the counts of adjacent bins.
+    ///
+    /// An empty vector is returned, if the histogram is empty. `n` must be greater than 0,
+    /// or the function will panic. If `n` is 1, the only point is at the minimum value.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(5, [(0.0, 1), (1.0, 2), (2.0, 1)]);
+    /// assert_eq!(
+    ///     h.pdf_points(5),
+    ///     vec![(0.0, 0.25), (0.5, 0.375), (1.0, 0.5), (1.5, 0.375), (2.0, 0.25)]
+    /// );
+    /// ```
+    pub fn pdf_points(&self, n: usize) -> Vec<(f64, f64)> {
+        assert!(n > 0, "number of points must be greater than 0");
+
+        let total_count = self.count() as f64;
+        self.evenly_spaced_values(n)
+            .into_iter()
+            .map(|value| (value, self.density_at(value) / total_count))
+            .collect()
+    }
+
+    /// Returns `n` points `(value, fraction)` of the estimated cumulative distribution
+    /// function of values (see [`rank`](Histogram::rank)), which are evenly spaced across
+    /// the range [min; max], e.g. to plot the distribution.
+    ///
+    /// An empty vector is returned, if the histogram is empty. `n` must be greater than 0,
+    /// or the function will panic. If `n` is 1, the only point is at the minimum value.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(5, [(0.0, 1), (1.0, 2), (2.0, 1)]);
+    /// assert_eq!(
+    ///     h.cdf_points(3),
+    ///     vec![(0.0, 0.25), (1.0, 0.5), (2.0, 1.0)]
+    /// );
+    /// ```
+    pub fn cdf_points(&self, n: usize) -> Vec<(f64, f64)> {
+        assert!(n > 0, "number of points must be greater than 0");
+
+        let prefix_counts: Vec<u64> = std::iter::once(0)
+            .chain(self.bins.iter().scan(0, |total, bin| {
+                *total += bin.count();
+                Some(*total)
+            }))
+            .collect();
+
+        let total_count = self.count() as f64;
+        self.evenly_spaced_values(n)
+            .into_iter()
+            .map(|value| {
+                let count = self.count_up_to(value, true, Some(&prefix_counts));
+                (value, count / total_count)
+            })
+            .collect()
+    }
+
     /// Update the histogram by inserting a new value.
     ///
     /// ```
@@ -285,13 +1099,112 @@ impl Histogram {
         // insert the new bin preserving the ascending order. If the total number of bins exceeds
         // the configured size, the histogram is shrunk by merging two closest bins to restore
         // the invariant
-        let bin = value.into();
-        self.bins.insert(self.bins.upper_bound(&bin), bin);
-        self.shrink();
+        let bin = match self.admit(value.into()) {
+            Some(bin) => bin,
+            None => return,
+        };
+        self.insert_bin(bin);
         self.track_min_max(bin.value());
+        self.count += bin.count();
+        self.sum.add(bin.value() * bin.count() as f64);
+    }
+
+    /// Update the histogram by inserting a new value, or handle it according to the NaN
+    /// (see [`NanPolicy`]) or the infinity (see [`InfinityPolicy`]) policy of the histogram,
+    /// if the value is NaN or infinite. Unlike [`insert`](Histogram::insert), this function
+    /// never panics.
+    ///
+    /// ```
+    /// use bhtt::{Error, Histogram, NanPolicy};
+    ///
+    /// let mut h = Histogram::new(5);
+    /// assert_eq!(h.try_insert(1.0), Ok(()));
+    /// assert_eq!(h.try_insert(f64::NAN), Err(Error::NanValue));
+    ///
+    /// h.set_nan_policy(NanPolicy::Ignore);
+    /// assert_eq!(h.try_insert(f64::NAN), Ok(()));
+    /// assert_eq!(h.count(), 1);
+    /// assert_eq!(h.nan_count(), 0);
+    /// ```
+    pub fn try_insert(&mut self, value: f64) -> Result<(), Error> {
+        if value.is_nan() {
+            return match self.nan_policy {
+                NanPolicy::Error => Err(Error::NanValue),
+                NanPolicy::Ignore => Ok(()),
+                NanPolicy::Count => {
+                    self.nan_count += 1;
+                    Ok(())
+                }
+            };
+        }
+
+        let value = if value.is_infinite() {
+            match self.infinity_policy {
+                InfinityPolicy::Error => return Err(Error::InfiniteValue),
+                InfinityPolicy::Clamp => {
+                    let bound = if value > 0.0 { self.max() } else { self.min() };
+                    bound.ok_or(Error::InfiniteValue)?
+                }
+                InfinityPolicy::Count => {
+                    if value > 0.0 {
+                        self.overflow_count += 1;
+                    } else {
+                        self.underflow_count += 1;
+                    }
+                    return Ok(());
+                }
+            }
+        } else {
+            value
+        };
+
+        self.insert(value);
+        Ok(())
+    }
+
+    /// Update the histogram by inserting many values (or bins) at once.
+    ///
+    /// Values are buffered in batches, which are sorted and merged with the bins of
+    /// the histogram in one pass. The histogram is then shrunk once per batch, which is
+    /// considerably faster than inserting values one by one. The resulting bins may
+    /// slightly differ from the ones produced by [`insert`](Histogram::insert).
+    ///
+    /// ```
+    /// use bhtt::{Bin, Histogram};
+    ///
+    /// let mut h = Histogram::new(5);
+    /// h.insert_many(vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2]);
+    /// h.insert_many(vec![Bin::new(-7.5, 10), Bin::new(12.0, 5)]);
+    ///
+    /// assert_eq!(h.size(), 5);
+    /// assert_eq!(h.count(), 25);
+    /// assert_eq!(h.min(), Some(-7.5));
+    /// assert_eq!(h.max(), Some(12.0));
+    /// ```
+    pub fn insert_many<T: Into<Bin>>(&mut self, values: impl IntoIterator<Item = T>) {
+        let mut values = values.into_iter().map(Into::into).peekable();
+        // avoid allocating a large buffer for a small number of values
+        let mut batch = Vec::with_capacity(Self::INSERT_MANY_BATCH_SIZE.min(values.size_hint().0));
+
+        while values.peek().is_some() {
+            batch.clear();
+            for bin in values.by_ref().take(Self::INSERT_MANY_BATCH_SIZE) {
+                if let Some(bin) = self.admit(bin) {
+                    self.track_min_max(bin.value());
+                    self.count += bin.count();
+                    self.sum.add(bin.value() * bin.count() as f64);
+                    batch.push(bin);
+                }
+            }
+
+            batch.sort_unstable();
+            self.union_bins(&batch);
+            self.shrink();
+        }
     }
 
-    /// Merge the histogram with another one (in-place).
+    /// Merge the histogram with another one (in-place). Both histograms must have the same
+    /// duration unit (see [`Histogram::set_duration_unit`]), or the function will panic.
     ///
     /// ```
     /// use bhtt::Histogram;
@@ -311,9 +1224,32 @@ impl Histogram {
     /// assert_eq!(h1.max(), Some(11.6));
     /// ```
     pub fn merge(&mut self, other: &Histogram) {
-        for bin in other.bins() {
-            self.insert(*bin);
+        assert_eq!(
+            self.duration_unit, other.duration_unit,
+            "histograms must have the same duration unit"
+        );
+
+        // instead of inserting the bins one by one, compute a union of the two sorted lists
+        // of bins in one pass, and then shrink the histogram once to restore the invariant
+        let bins = self.admit_all(other);
+        self.union_bins(&bins);
+        self.shrink();
+        if let Cow::Owned(bins) = bins {
+            for bin in bins {
+                self.count += bin.count();
+                self.sum.add(bin.value() * bin.count() as f64);
+            }
+        } else {
+            self.count += other.count;
+            self.sum.merge(&other.sum);
         }
+        self.nan_count = self.count_policy.add(self.nan_count, other.nan_count);
+        self.underflow_count = self
+            .count_policy
+            .add(self.underflow_count, other.underflow_count);
+        self.overflow_count = self
+            .count_policy
+            .add(self.overflow_count, other.overflow_count);
 
         if let Some(min_value) = other.min() {
             self.track_min_max(min_value);
@@ -323,465 +1259,3870 @@ impl Histogram {
         }
     }
 
-    /// Keep track of the minimum and the maximum values (this will allow us to have more accurate quantile approximations).
-    fn track_min_max(&mut self, value: f64) {
-        self.min_value
-            .replace(self.min_value.map_or(
-                value,
-                |current| if value < current { value } else { current },
-            ));
-        self.max_value
-            .replace(self.max_value.map_or(
-                value,
-                |current| if value > current { value } else { current },
-            ));
-    }
+    /// Merge many histograms together into a new histogram of the given size.
+    ///
+    /// Unlike merging histograms pairwise, the bins of all histograms are combined into a
+    /// single sorted stream, so that the result does not depend on the order of histograms.
+    /// Bins of the stream are buffered, and the buffer is compacted down to `size` bins
+    /// every time it reaches `2 * size` bins (and once more at the end), so that memory
+    /// usage stays bounded regardless of the number of histograms.
+    ///
+    /// The new histogram has the duration unit of the merged histograms, which must all be
+    /// the same (see [`Histogram::set_duration_unit`]), or the function will panic.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let histograms = vec![
+    ///     Histogram::from_iter(5, &[1.0, 0.0, -5.4, -2.1, 8.5]),
+    ///     Histogram::from_iter(5, &[10.0, 8.6, 4.3, 7.8, 5.2]),
+    ///     Histogram::from_iter(5, &[1.0, -7.6, 0.0, 5.8, 4.3, 2.1, 11.6]),
+    /// ];
+    ///
+    /// let h = Histogram::merge_all(5, histograms);
+    /// assert_eq!(h.size(), 5);
+    /// assert_eq!(h.count(), 17);
+    /// assert_eq!(h.min(), Some(-7.6));
+    /// assert_eq!(h.max(), Some(11.6));
+    /// ```
+    pub fn merge_all(
+        size: usize,
+        histograms: impl IntoIterator<Item = impl Borrow<Histogram>>,
+    ) -> Histogram {
+        assert!(size > 0, "histogram size must be greater than 0");
 
-    /// Merge two closest bins until the histogram shrinks back to the fixed size.
-    fn shrink(&mut self) {
-        while self.bins.len() > self.size {
-            let (left, right) = self.find_closest_bins();
-            self.bins[left] = Bin::merge(&self.bins[left], &self.bins[right]);
-            self.bins.remove(right);
+        let histograms: Vec<_> = histograms.into_iter().collect();
+        let histograms: Vec<&Histogram> = histograms.iter().map(|h| h.borrow()).collect();
+        let bins = histograms
+            .iter()
+            .map(|other| other.bins.len())
+            .sum::<usize>();
+        let mut h = Histogram::with_capacity(size, bins.min(size) + 1);
+        if let Some(first) = histograms.first() {
+            assert!(
+                histograms
+                    .iter()
+                    .all(|other| other.duration_unit == first.duration_unit),
+                "histograms must have the same duration unit"
+            );
+            h.duration_unit = first.duration_unit;
+        }
+        let total = histograms
+            .iter()
+            .try_fold(0u64, |total, other| total.checked_add(other.count));
+        if total.is_none() {
+            // the total count saturates, which depends on the order of histograms anyway
+            for other in histograms {
+                h.merge(other);
+            }
+            return h;
         }
-    }
-
-    /// Find a pair of bins that are closest to each other.
-    fn find_closest_bins(&self) -> (usize, usize) {
-        let right_index = (1..self.bins.len())
-            .min_by_key(|i| {
-                (
-                    // distance between values is considered first
-                    OrderedFloat((self.bins[*i].value() - self.bins[*i - 1].value()).abs()),
-                    // if distances are equal, a pair of bins with smaller total count is preferred
-                    self.bins[i - 1].count() + self.bins[*i].count(),
-                )
-            })
-            .unwrap_or(self.bins.len() - 1);
 
-        (right_index - 1, right_index)
-    }
+        for other in &histograms {
+            h.count += other.count;
+            h.sum.merge(&other.sum);
+            h.nan_count = h.count_policy.add(h.nan_count, other.nan_count);
+            h.underflow_count = h.count_policy.add(h.underflow_count, other.underflow_count);
+            h.overflow_count = h.count_policy.add(h.overflow_count, other.overflow_count);
+            if let Some(min_value) = other.min() {
+                h.track_min_max(min_value);
+            }
+            if let Some(max_value) = other.max() {
+                h.track_min_max(max_value);
+            }
+        }
 
-    fn index_of_cumulative_count_less_than(&self, target_count: f64) -> (usize, f64) {
-        self.bins
+        // k-way merge of sorted lists of bins. Bins are consumed in the ascending order, so
+        // that the order of bins in the combined stream (and, hence, the result) does not
+        // depend on the order of histograms
+        let mut heads: BinaryHeap<_> = histograms
             .iter()
-            .zip(std::iter::once(&Bin::empty(0.0)).chain(&self.bins))
-            .map(|(l, r)| (l.count() + r.count()) as f64 / 2.0)
-            .scan(0.0, |partial_count, next_count| {
-                *partial_count += next_count;
-                Some(*partial_count)
-            })
             .enumerate()
-            .take_while(|(_, partial_count)| target_count > *partial_count)
-            .last()
-            .map_or((0, 0.0), |(i, sum)| (i + 1, sum))
-    }
-
-    fn get_bordering_bins(&self, i: usize) -> (Bin, Bin) {
-        if i == 0 {
-            (
-                Bin::empty(self.min_value.unwrap()),
-                *self.bins.first().unwrap(),
-            )
-        } else if i == self.bins.len() {
-            (
-                *self.bins.last().unwrap(),
-                Bin::empty(self.max_value.unwrap()),
-            )
-        } else {
-            (self.bins[i - 1], self.bins[i])
+            .filter(|(_, other)| !other.bins.is_empty())
+            .map(|(i, other)| Reverse((other.bins[0], i, 0)))
+            .collect();
+        while let Some(Reverse((bin, i, j))) = heads.pop() {
+            h.push_sorted_bin(bin);
+            if let Some(next_bin) = histograms[i].bins.get(j + 1) {
+                heads.push(Reverse((*next_bin, i, j + 1)));
+            }
         }
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        h.shrink();
+        h.release_excess_capacity();
 
-    fn histogram_from_parts(
-        size: usize,
-        bins: Vec<Bin>,
-        min_value: Option<f64>,
+        h
+    }
+
+    /// Remove all values from the histogram (including the NaN and infinity counts), so that
+    /// it can be reused, e.g. for the next reporting interval. The size and the settings of
+    /// the histogram are preserved, and so is the allocated memory.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::from_iter(5, vec![1.0, 2.0, 3.0]);
+    /// h.clear();
+    ///
+    /// assert!(h.is_empty());
+    /// assert_eq!(h.size(), 5);
+    /// assert_eq!(h.min(), None);
+    /// assert_eq!(h.quantile(0.5), None);
+    /// ```
+    pub fn clear(&mut self) {
+        self.bins.clear();
+        self.extrema = self.extrema.cleared();
+        self.count = 0;
+        self.sum = CompensatedSum::default();
+        self.nan_count = 0;
+        self.underflow_count = 0;
+        self.overflow_count = 0;
+        self.gaps.invalidate();
+    }
+
+    /// Change the size of the histogram. If the new size is smaller, the closest bins are
+    /// merged until the histogram fits, and the memory held by extra bins is released.
+    /// Otherwise, more bins will be kept after future updates. `size` must be greater
+    /// than 0, or the function will panic.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::from_iter(512, (0..10_000).map(|v| v as f64));
+    /// h.resize(32);
+    ///
+    /// assert_eq!(h.size(), 32);
+    /// assert_eq!(h.bins().len(), 32);
+    /// assert_eq!(h.count(), 10_000);
+    /// ```
+    pub fn resize(&mut self, size: usize) {
+        assert!(size > 0, "histogram size must be greater than 0");
+
+        if size >= self.size {
+            self.grow(size);
+        } else {
+            self.size = size;
+            self.shrink();
+            self.release_excess_capacity();
+        }
+    }
+
+    /// Returns a copy of the histogram with a different size (see [`Histogram::resize`]).
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(512, (0..10_000).map(|v| v as f64));
+    /// let summary = h.resized(32);
+    ///
+    /// assert_eq!(h.bins().len(), 512);
+    /// assert_eq!(summary.bins().len(), 32);
+    /// assert_eq!(summary.min(), Some(0.0));
+    /// assert_eq!(summary.max(), Some(9999.0));
+    /// ```
+    pub fn resized(&self, size: usize) -> Histogram {
+        let mut h = self.clone();
+        h.resize(size);
+
+        h
+    }
+
+    /// Returns the bins of the histogram merged down to at most `n` bins (the closest bins are
+    /// merged first), e.g. to render a small chart. The histogram itself is not changed.
+    /// `n` must be greater than 0, or the function will panic.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(64, (0..1000).map(|v| v as f64));
+    /// let sparkline = h.compressed(8);
+    ///
+    /// assert_eq!(sparkline.len(), 8);
+    /// assert_eq!(sparkline.iter().map(|bin| bin.count()).sum::<u64>(), 1000);
+    /// assert_eq!(h.bins().len(), 64);
+    /// ```
+    pub fn compressed(&self, n: usize) -> Vec<Bin> {
+        assert!(n > 0, "number of bins must be greater than 0");
+
+        let mut h = Histogram::new(n);
+        h.set_merge_policy(self.merge_policy);
+        h.bins.extend_from_slice(&self.bins);
+        h.shrink();
+
+        h.bins.into_iter().collect()
+    }
+
+    /// Returns a new histogram approximating the distribution of values, which are in this
+    /// histogram, but not in `other`. This is meant for cumulative histograms: if `other`
+    /// is an earlier snapshot of this histogram, the result covers the values inserted since.
+    ///
+    /// The bins of the result are the bins of this histogram with the counts reduced by
+    /// the number of values of `other` (estimated with the Sum procedure) within the range
+    /// of each bin, while the total count, the sum, and the NaN and infinity counts are
+    /// subtracted exactly. Counts never go below zero. The settings of this histogram are
+    /// preserved.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::new(64);
+    /// h.extend((0..100).map(|v| v as f64));
+    /// let before = h.clone();
+    ///
+    /// h.extend((1000..1100).map(|v| v as f64));
+    /// let delta = h.subtract(&before);
+    ///
+    /// assert_eq!(delta.count(), 100);
+    /// assert_eq!(delta.sum(), 104950.0);
+    /// assert!(delta.quantile(0.0).unwrap() >= 1000.0);
+    /// ```
+    pub fn subtract(&self, other: &Histogram) -> Histogram {
+        let total = self.count.saturating_sub(other.count);
+
+        // cumulative counts of the difference at the right boundary of each bin, which are
+        // kept non-decreasing and capped, so that all counts are non-negative and add up to
+        // the exact total
+        let mut bins = Bins::with_capacity(self.bins_capacity());
+        let (mut up_to_bin, mut previous) = (0, 0);
+        for (i, bin) in self.bins.iter().enumerate() {
+            up_to_bin += bin.count();
+            let other_up_to_bin = match self.bins.get(i + 1) {
+                Some(next) => other.count_less_than_or_equal_to((bin.value() + next.value()) / 2.0),
+                None => other.count,
+            };
+
+            let current = up_to_bin
+                .saturating_sub(other_up_to_bin)
+                .clamp(previous, total);
+            if current > previous {
+                bins.push(Bin::new(bin.value(), current - previous));
+            }
+            previous = current;
+        }
+
+        let mut h = self.clone();
+        h.sum.subtract(&other.sum);
+        h.nan_count = self.nan_count.saturating_sub(other.nan_count);
+        h.underflow_count = self.underflow_count.saturating_sub(other.underflow_count);
+        h.overflow_count = self.overflow_count.saturating_sub(other.overflow_count);
+        h.replace_bins(bins);
+
+        h
+    }
+
+    /// Returns a new histogram approximating the distribution of values inserted between
+    /// two snapshots of a cumulative histogram: `earlier` and this one (see
+    /// [`subtract`](Histogram::subtract)), e.g. to compute the distribution of values
+    /// observed between two scrapes of a monitoring endpoint.
+    ///
+    /// Returns an error, if `earlier` can't be an earlier snapshot of this histogram, i.e.
+    /// if any of its counts (the total count and the numbers of NaN, underflowed and
+    /// overflowed values) is greater than the matching count of this histogram, or if its
+    /// minimum value is less than the minimum value of this histogram (or its maximum value
+    /// is greater than the maximum value of this histogram). This usually means that
+    /// the histogram has been reset (e.g. the process has been restarted) in between.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::new(64);
+    /// h.extend((0..100).map(|v| v as f64));
+    /// let earlier = h.clone();
+    ///
+    /// h.extend((1000..1100).map(|v| v as f64));
+    /// let delta = h.delta_since(&earlier).unwrap();
+    /// assert_eq!(delta.count(), 100);
+    ///
+    /// // the histogram has been reset
+    /// let h = Histogram::from_iter(64, [1.0, 2.0]);
+    /// assert!(h.delta_since(&earlier).is_err());
+    /// ```
+    pub fn delta_since(&self, earlier: &Histogram) -> Result<Histogram, Error> {
+        let invalid = |reason: String| Err(Error::InvalidSnapshot(reason));
+
+        for (name, earlier_count, count) in [
+            ("count", earlier.count, self.count),
+            ("NaN count", earlier.nan_count, self.nan_count),
+            (
+                "underflow count",
+                earlier.underflow_count,
+                self.underflow_count,
+            ),
+            (
+                "overflow count",
+                earlier.overflow_count,
+                self.overflow_count,
+            ),
+        ] {
+            if earlier_count > count {
+                return invalid(format!(
+                    "the {} decreased from {} to {}",
+                    name, earlier_count, count
+                ));
+            }
+        }
+        if let (Some(earlier_min), Some(min_value)) = (earlier.min(), self.min()) {
+            if earlier_min < min_value {
+                return invalid(format!(
+                    "the minimum value increased from {} to {}",
+                    earlier_min, min_value
+                ));
+            }
+        }
+        if let (Some(earlier_max), Some(max_value)) = (earlier.max(), self.max()) {
+            if earlier_max > max_value {
+                return invalid(format!(
+                    "the maximum value decreased from {} to {}",
+                    earlier_max, max_value
+                ));
+            }
+        }
+
+        Ok(self.subtract(earlier))
+    }
+
+    /// Multiply the counts of all bins (as well as the NaN and infinity counts) by `factor`,
+    /// which must be finite and non-negative, or the function will panic. This allows
+    /// reweighting a histogram, e.g. to account for the sampling rate of values.
+    ///
+    /// Counts remain integer: the cumulative counts of bins are scaled and rounded to the
+    /// nearest integer, so that the total count is the rounded scaled total count, and
+    /// rounding errors do not accumulate. Bins, whose counts are rounded down to zero, are
+    /// removed. The sum of values is scaled exactly.
+    ///
+    /// ```
+    /// use bhtt::{Bin, Histogram};
+    ///
+    /// // values sampled at the rate of 10%
+    /// let mut h = Histogram::from_iter(5, vec![(1.0, 3), (2.0, 5), (3.0, 2)]);
+    /// h.scale_counts(10.0);
+    /// assert_eq!(h.count(), 100);
+    ///
+    /// h.scale_counts(0.25);
+    /// assert_eq!(h.count(), 25);
+    /// assert_eq!(h.bins(), &[Bin::new(1.0, 8), Bin::new(2.0, 12), Bin::new(3.0, 5)]);
+    /// ```
+    pub fn scale_counts(&mut self, factor: f64) {
+        assert!(
+            factor.is_finite() && factor >= 0.0,
+            "scale factor must be finite and non-negative"
+        );
+
+        self.scale_counts_by(factor, f64::round);
+    }
+
+    /// Multiply the counts of all bins (as well as the NaN and infinity counts) by `factor`,
+    /// which must be in the range (0.0; 1.0], or the function will panic. This allows
+    /// implementing custom aging schedules, e.g. halving the counts once a minute.
+    ///
+    /// Unlike [`scale_counts`](Histogram::scale_counts), the scaled cumulative counts of bins
+    /// are rounded down, so that repeated decay always makes progress, and bins, whose
+    /// counts drop to zero, are removed. The sum of values is recomputed from the remaining
+    /// bins.
+    ///
+    /// ```
+    /// use bhtt::{Bin, Histogram};
+    ///
+    /// let mut h = Histogram::from_iter(5, vec![(1.0, 1), (2.0, 10)]);
+    /// h.decay(0.5);
+    /// assert_eq!(h.bins(), &[Bin::new(2.0, 5)]);
+    ///
+    /// // the histogram is eventually emptied
+    /// for _ in 0..10 {
+    ///     h.decay(0.9);
+    /// }
+    /// assert!(h.is_empty());
+    /// ```
+    pub fn decay(&mut self, factor: f64) {
+        assert!(
+            factor > 0.0 && factor <= 1.0,
+            "decay factor must be in the range (0.0; 1.0]"
+        );
+
+        self.scale_counts_by(factor, f64::floor);
+        self.sum = CompensatedSum::default();
+        for bin in self.bins.iter() {
+            self.sum.add(bin.value() * bin.count() as f64);
+        }
+    }
+
+    /// Multiply the counts by `factor`, rounding the scaled cumulative counts of bins with
+    /// the given function.
+    fn scale_counts_by(&mut self, factor: f64, round: fn(f64) -> f64) {
+        let scale = |count: u64| round(count as f64 * factor) as u64;
+        let mut bins = Bins::with_capacity(self.bins_capacity());
+        let (mut up_to_bin, mut previous) = (0, 0);
+        for bin in self.bins.iter() {
+            up_to_bin += bin.count();
+            let current = scale(up_to_bin);
+            if current > previous {
+                bins.push(Bin::new(bin.value(), current - previous));
+            }
+            previous = current;
+        }
+
+        let sum = self.sum.value() * factor;
+        self.sum = CompensatedSum::default();
+        self.sum.add(sum);
+        self.nan_count = scale(self.nan_count);
+        self.underflow_count = scale(self.underflow_count);
+        self.overflow_count = scale(self.overflow_count);
+        self.replace_bins(bins);
+    }
+
+    /// Transform all values of the histogram (the values of bins, the minimum and
+    /// the maximum values, and the sum) as `value * scale + offset`, e.g. to convert them
+    /// to different units. `scale` must be finite and non-zero, and `offset` must be finite,
+    /// or the function will panic. The function will also panic if any of the transformed
+    /// values is not finite.
+    ///
+    /// A negative `scale` reverses the order of bins, and swaps the underflow and the overflow
+    /// counts.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// // seconds to milliseconds
+    /// let mut h = Histogram::from_iter(5, vec![0.5, 1.5, 2.5]);
+    /// h.map_values(1000.0, 0.0);
+    /// assert_eq!(h.min(), Some(500.0));
+    /// assert_eq!(h.max(), Some(2500.0));
+    /// assert_eq!(h.sum(), 4500.0);
+    ///
+    /// // degrees Celsius to degrees Fahrenheit
+    /// let mut h = Histogram::from_iter(5, vec![-40.0, 0.0, 100.0]);
+    /// h.map_values(1.8, 32.0);
+    /// assert_eq!(h.min(), Some(-40.0));
+    /// assert_eq!(h.median(), Some(32.0));
+    /// assert_eq!(h.max(), Some(212.0));
+    /// ```
+    pub fn map_values(&mut self, scale: f64, offset: f64) {
+        assert!(
+            scale.is_finite() && scale != 0.0,
+            "scale must be finite and non-zero"
+        );
+        assert!(offset.is_finite(), "offset must be finite");
+
+        let map = |value: f64| value * scale + offset;
+        for bin in self.bins.iter_mut() {
+            *bin = Bin::new(map(bin.value()), bin.count());
+        }
+        let sum = self.sum.value() * scale + offset * self.count as f64;
+        self.sum = CompensatedSum::default();
+        self.sum.add(sum);
+
+        if let Extrema::Tracked(min_value, max_value) = self.extrema {
+            self.extrema = if scale > 0.0 {
+                Extrema::Tracked(map(min_value), map(max_value))
+            } else {
+                Extrema::Tracked(map(max_value), map(min_value))
+            };
+        }
+        if scale < 0.0 {
+            self.bins.reverse();
+            std::mem::swap(&mut self.underflow_count, &mut self.overflow_count);
+        }
+        self.gaps.invalidate();
+    }
+
+    /// Split the histogram into two: the one approximating the values less than or equal to
+    /// `value`, and the one approximating the values greater than `value`. `value` must not
+    /// be NaN, or the function will panic.
+    ///
+    /// The number of values below the threshold is estimated with the Sum procedure (see
+    /// [`Histogram::count_less_than_or_equal_to`]), and the counts of bins are apportioned
+    /// accordingly: a bin that straddles the threshold is split into two, with the parts
+    /// moved to the threshold if needed. The minimum and maximum values are clamped to
+    /// the threshold the same way. Underflows go to the first histogram, overflows
+    /// go to the second one, and the NaN count is dropped. Both histograms have the same
+    /// size and settings as this one.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(64, (1..=100).map(|v| v as f64));
+    /// let (fast, slow) = h.split_at(90.0);
+    ///
+    /// assert_eq!(fast.count(), 90);
+    /// assert_eq!(fast.max(), Some(90.0));
+    /// assert_eq!(slow.count(), 10);
+    /// assert_eq!(slow.max(), Some(100.0));
+    /// ```
+    pub fn split_at(&self, value: f64) -> (Histogram, Histogram) {
+        let below = self.count_less_than_or_equal_to(value);
+
+        let mut left = self.extract(0..below, f64::NEG_INFINITY, value);
+        left.underflow_count = self.underflow_count;
+        let mut right = self.extract(below..self.count, value, f64::INFINITY);
+        right.overflow_count = self.overflow_count;
+        if !right.bins.is_empty() {
+            // the sums of both parts must add up
+            right.sum = self.sum;
+            right.sum.subtract(&left.sum);
+        }
+
+        (left, right)
+    }
+
+    /// Returns a new histogram approximating the values of this histogram within the range
+    /// [low; high], e.g. to compute conditional quantiles. Neither `low` nor `high` may be
+    /// NaN, and `low` must not be greater than `high`, or the function will panic.
+    ///
+    /// The counts of values below `low` and above `high` are estimated with the Sum procedure
+    /// and cut off from the outermost bins, which are moved inside the range if needed (see
+    /// [`Histogram::split_at`]). The new histogram has the same size and settings.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(64, (1..=1000).map(|v| v as f64));
+    ///
+    /// // p99 of values above 500
+    /// let slow = h.restrict(500.5, f64::INFINITY);
+    /// assert_eq!(slow.count(), 500);
+    /// assert!((slow.quantile(0.99).unwrap() - 995.0).abs() < 5.0);
+    /// ```
+    pub fn restrict(&self, low: f64, high: f64) -> Histogram {
+        assert!(!low.is_nan() && !high.is_nan(), "value must not be NaN");
+        assert!(low <= high, "low must not be greater than high");
+
+        let below = self.count_up_to(low, false, None).round() as u64;
+        let up_to = (self.count_up_to(high, true, None).round() as u64).max(below);
+
+        let mut h = self.extract(below..up_to, low, high);
+        if low == f64::NEG_INFINITY {
+            h.underflow_count = self.underflow_count;
+        }
+        if high == f64::INFINITY {
+            h.overflow_count = self.overflow_count;
+        }
+
+        h
+    }
+
+    /// Returns a new histogram with the same size and settings, which holds the values
+    /// of this histogram with cumulative counts in the given range. Values of bins (and
+    /// the minimum and maximum values) are clamped to [low; high].
+    fn extract(&self, counts: Range<u64>, low: f64, high: f64) -> Histogram {
+        let mut h = self.empty_with_settings();
+
+        let mut up_to_bin = 0;
+        for bin in self.bins.iter() {
+            let clamp = |count: u64| count.clamp(counts.start, counts.end);
+            let count = clamp(up_to_bin + bin.count()) - clamp(up_to_bin);
+            if count > 0 {
+                let value = bin.value().clamp(low, high);
+                h.bins.push(Bin::new(value, count));
+                h.count += count;
+                h.sum.add(value * count as f64);
+            }
+            up_to_bin += bin.count();
+        }
+
+        if let Extrema::Tracked(min_value, max_value) = self.extrema {
+            if !h.bins.is_empty() {
+                h.extrema =
+                    Extrema::Tracked(min_value.clamp(low, high), max_value.clamp(low, high));
+            }
+        }
+
+        h
+    }
+
+    /// Returns a new empty histogram with the same size and settings.
+    fn empty_with_settings(&self) -> Histogram {
+        Histogram {
+            size: self.size,
+            bins: Bins::with_capacity(self.bins_capacity()),
+            extrema: self.extrema.cleared(),
+            min_gap: self.min_gap,
+            merge_policy: self.merge_policy,
+            duration_unit: self.duration_unit,
+            count: 0,
+            count_policy: self.count_policy,
+            sum: CompensatedSum::default(),
+            nan_policy: self.nan_policy,
+            nan_count: 0,
+            infinity_policy: self.infinity_policy,
+            underflow_count: 0,
+            overflow_count: 0,
+            gaps: Gaps::default(),
+        }
+    }
+
+    /// Create a Histogram from a sorted list of at most `size` bins and the matching
+    /// exact minimum and maximum values and the sum of values.
+    pub(crate) fn from_raw_parts(
+        size: usize,
+        bins: Bins,
+        min_value: Option<f64>,
         max_value: Option<f64>,
+        sum: CompensatedSum,
     ) -> Histogram {
-        assert!(size > 0, "histogram size must be greater than 0");
+        debug_assert!(size > 0 && bins.len() <= size);
+
+        Histogram {
+            size,
+            count: bins.iter().map(|bin| bin.count()).sum(),
+            bins,
+            extrema: Extrema::new(min_value, max_value),
+            min_gap: 0.0,
+            merge_policy: MergePolicy::default(),
+            duration_unit: DurationUnit::default(),
+            count_policy: CountPolicy::default(),
+            sum,
+            nan_policy: NanPolicy::default(),
+            nan_count: 0,
+            infinity_policy: InfinityPolicy::default(),
+            underflow_count: 0,
+            overflow_count: 0,
+            gaps: Gaps::default(),
+        }
+    }
+
+    /// Change the size of the histogram without reserving memory for its bins, e.g. when
+    /// the size comes from untrusted input. The storage of bins grows as they are added.
+    pub(crate) fn set_size_lazily(&mut self, size: usize) {
+        debug_assert!(size > 0 && self.bins.len() <= size);
+
+        self.size = size;
+    }
+
+    /// Set the unit of durations of a restored histogram, whose values are already recorded
+    /// in that unit (see [`Histogram::set_duration_unit`]).
+    pub(crate) fn restore_duration_unit(&mut self, unit: DurationUnit) {
+        self.duration_unit = unit;
+    }
+
+    /// Set the numbers of NaN, underflowed and overflowed values, which were counted (see
+    /// [`NanPolicy::Count`] and [`InfinityPolicy::Count`]).
+    pub(crate) fn set_special_counts(
+        &mut self,
+        nan_count: u64,
+        underflow_count: u64,
+        overflow_count: u64,
+    ) {
+        self.nan_count = nan_count;
+        self.underflow_count = underflow_count;
+        self.overflow_count = overflow_count;
+    }
+
+    /// Same as `count_in_range()`, but with optional precomputed cumulative counts of bins
+    /// (see `count_up_to()`).
+    pub(crate) fn estimate_count_in_range(
+        &self,
+        range: impl RangeBounds<f64>,
+        prefix_counts: Option<&[u64]>,
+    ) -> u64 {
+        // the number of values in the range is the difference between the number of values up
+        // to the upper bound and the number of values preceding the lower bound. Note, that an
+        // inclusive lower bound excludes the lower bound value from the subtracted count, and
+        // vice versa
+        let up_to_end = match range.end_bound() {
+            Bound::Included(&value) => self.checked_count_up_to(value, true, prefix_counts),
+            Bound::Excluded(&value) => self.checked_count_up_to(value, false, prefix_counts),
+            Bound::Unbounded => self.count() as f64,
+        };
+        let before_start = match range.start_bound() {
+            Bound::Included(&value) => self.checked_count_up_to(value, false, prefix_counts),
+            Bound::Excluded(&value) => self.checked_count_up_to(value, true, prefix_counts),
+            Bound::Unbounded => 0.0,
+        };
+
+        (up_to_end - before_start).max(0.0).round() as u64
+    }
+
+    /// Returns an estimate of the differential entropy (in nats) of the distribution of
+    /// values, or `None` if the histogram is empty. The entropy is computed for the density
+    /// of values estimated from the bins (see [`pdf_points`](Histogram::pdf_points)), so it's
+    /// a compact summary of the shape of the distribution: e.g. it's `ln(b - a)` for values
+    /// uniformly distributed in [a; b], and scaling all values by `k` adds `ln(k)` to it.
+    ///
+    /// Negative infinity is returned, if all values are equal.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(64, (0..=10000).map(|v| v as f64 / 100.0));
+    /// assert!((h.entropy().unwrap() - 100f64.ln()).abs() < 0.01);
+    ///
+    /// assert_eq!(Histogram::from_iter(5, [1.0, 1.0]).entropy(), Some(f64::NEG_INFINITY));
+    /// assert_eq!(Histogram::new(5).entropy(), None);
+    /// ```
+    pub fn entropy(&self) -> Option<f64> {
+        let (min_value, max_value) = match (self.min(), self.max()) {
+            (Some(min_value), Some(max_value)) => (min_value, max_value),
+            // histogram is empty
+            _ => return None,
+        };
+
+        // the density is linear between adjacent bins (and is zero at the minimum and
+        // the maximum values), so the integrals of f(x) and f(x) * ln(f(x)) are computed for
+        // every such interval analytically
+        let x_ln_x = |x: f64| if x > 0.0 { x * x.ln() } else { 0.0 };
+        let x2_ln_x = |x: f64| if x > 0.0 { x * x * x.ln() } else { 0.0 };
+
+        let total_count = self.count() as f64;
+        let points: Vec<Bin> = std::iter::once(Bin::empty(min_value))
+            .chain(self.bins.iter().copied())
+            .chain(std::iter::once(Bin::empty(max_value)))
+            .collect();
+        let (mut mass, mut integral) = (0.0, 0.0);
+        for pair in points.windows(2) {
+            let width = pair[1].value() - pair[0].value();
+            if width <= 0.0 {
+                continue;
+            }
+
+            let a = pair[0].count() as f64 / (width * total_count);
+            let b = pair[1].count() as f64 / (width * total_count);
+            mass += (a + b) / 2.0 * width;
+            integral += if a == b {
+                width * x_ln_x(a)
+            } else {
+                width / (b - a) * ((x2_ln_x(b) - x2_ln_x(a)) / 2.0 - (b * b - a * a) / 4.0)
+            };
+        }
+
+        // the mass of bins, which are equal to the minimum or the maximum value, is
+        // concentrated at a point, so the density is normalized to only account for the rest
+        if mass > 0.0 {
+            Some(-integral / mass + mass.ln())
+        } else {
+            Some(f64::NEG_INFINITY)
+        }
+    }
+
+    /// Returns `n` values evenly spaced across the range [min; max] (or no values, if the
+    /// histogram is empty).
+    fn evenly_spaced_values(&self, n: usize) -> Vec<f64> {
+        match (self.min(), self.max()) {
+            (Some(min_value), _) if n == 1 => vec![min_value],
+            (Some(min_value), Some(max_value)) => (0..n)
+                .map(|i| {
+                    // the last value must be exactly equal to the maximum one
+                    if i == n - 1 {
+                        max_value
+                    } else {
+                        min_value + (max_value - min_value) * (i as f64 / (n - 1) as f64)
+                    }
+                })
+                .collect(),
+            // histogram is empty
+            _ => Vec::new(),
+        }
+    }
+
+    /// Estimate the number of values per unit of length at `value`, i.e. the derivative of
+    /// `count_up_to()`. Between two adjacent bins it's the count linearly interpolated
+    /// between their counts divided by the distance between them.
+    fn density_at(&self, value: f64) -> f64 {
+        let (min_value, max_value) = match (self.min(), self.max()) {
+            (Some(min_value), Some(max_value)) => (min_value, max_value),
+            // histogram is empty
+            _ => return 0.0,
+        };
+        if value < min_value || value > max_value {
+            return 0.0;
+        }
+
+        // the density to the right of the value is used, unless the value is equal to the
+        // value of the last bin, as there are no values to the right of it then
+        let mut pos = self.bins.partition_point(|bin| bin.value() <= value);
+        if pos == self.bins.len() && self.bins.last().map(|bin| bin.value()) == Some(value) {
+            pos -= 1;
+        }
+
+        let (left_bin, right_bin) = get_bordering_bins(&self.bins, min_value, max_value, pos);
+        let (left_value, left_count) = (left_bin.value(), left_bin.count() as f64);
+        let (right_value, right_count) = (right_bin.value(), right_bin.count() as f64);
+        if right_value - left_value <= 0.0 {
+            0.0
+        } else {
+            let proximity_to_right = (value - left_value) / (right_value - left_value);
+            let count = left_count + (right_count - left_count) * proximity_to_right;
+
+            count / (right_value - left_value)
+        }
+    }
+
+    /// Same as `count_up_to()`, but panics if `value` is NaN.
+    fn checked_count_up_to(
+        &self,
+        value: f64,
+        inclusive: bool,
+        prefix_counts: Option<&[u64]>,
+    ) -> f64 {
+        assert!(!value.is_nan(), "value must not be NaN");
+
+        self.count_up_to(value, inclusive, prefix_counts)
+    }
+
+    /// Estimate the number of values in the interval (-inf; value] (or (-inf; value) if
+    /// `inclusive` is false). The result is not rounded.
+    ///
+    /// If `prefix_counts` is provided, its `i`'th element must be equal to the total count
+    /// of the first `i` bins. Otherwise, the counts of bins are summed up on every call.
+    pub(crate) fn count_up_to(
+        &self,
+        value: f64,
+        inclusive: bool,
+        prefix_counts: Option<&[u64]>,
+    ) -> f64 {
+        let (min_value, max_value) = match (self.min(), self.max()) {
+            (Some(min_value), Some(max_value)) => (min_value, max_value),
+            // histogram is empty
+            _ => return 0.0,
+        };
+
+        if value < min_value || (!inclusive && value == min_value) {
+            // the interval does not intersect with the interval [min; max]. The exact minimum
+            // value is known, so it can only be included by an inclusive bound
+            0.0
+        } else if value > max_value || (inclusive && value == max_value) {
+            // the interval includes all the values in the histogram
+            self.count() as f64
+        } else {
+            // Algorithm 3: Sum Procedure (from the paper mentioned in the description)
+            //
+            // In order to estimate the number of values in the histogram that are less than or
+            // equal to the given value we need to find a pair of bins, which would be adjacent to
+            // the (value, count) bin if we were to insert it to the histogram. The resulting count
+            // will be equal to the sum of the following components:
+            //
+            // 1) sum of counts of the bins preceding the left neighbour
+            // 2) one half of left neighbour's count
+            // 3) count of values between the left neighbour and the (value, count) bin
+
+            // find the position of the bin if we were to insert it to the histogram
+            let pos = self.bins.partition_point(|bin| bin.value() < value);
+
+            // calculate the sum of counts of the bins preceding the left neighbour of that bin
+            let left = pos.saturating_sub(1);
+            let count_up_to_left: u64 = match prefix_counts {
+                Some(prefix_counts) => prefix_counts[left],
+                None => self.bins[..left].iter().map(|bin| bin.count()).sum(),
+            };
+
+            // determine the bordering bins
+            let (left_bin, right_bin) = get_bordering_bins(&self.bins, min_value, max_value, pos);
+            let (left_value, left_count) = (left_bin.value(), left_bin.count() as f64);
+            let (right_value, right_count) = (right_bin.value(), right_bin.count() as f64);
+
+            // estimate the count of values between the left neighbour and the (value, count) bins
+            let count_left_to_value = if right_value - left_value <= 0.0 {
+                0.0
+            } else {
+                let proximity_to_right = (value - left_value) / (right_value - left_value);
+                let count = left_count + (right_count - left_count) * proximity_to_right;
+
+                (left_count + count) / 2.0 * proximity_to_right
+            };
+
+            // add up all partial counts. If the exact minimum and maximum values are known to be
+            // present in the histogram, the former is always included in the interval here, and
+            // the latter is always excluded from it
+            let count = count_up_to_left as f64 + left_count / 2.0 + count_left_to_value;
+            self.clamp_to_extrema(count)
+        }
+    }
+
+    /// Estimate the number of values in the interval [value; +inf) (or (value; +inf) if
+    /// `inclusive` is false). The result is not rounded.
+    ///
+    /// This is the same sum procedure as in `count_up_to()`, but applied from right to left,
+    /// i.e. the result is equal to the result of `count_up_to()` for `-value` in the mirror
+    /// image of the histogram. `prefix_counts` is the same as in `count_up_to()`.
+    pub(crate) fn count_down_to(
+        &self,
+        value: f64,
+        inclusive: bool,
+        prefix_counts: Option<&[u64]>,
+    ) -> f64 {
+        let (min_value, max_value) = match (self.min(), self.max()) {
+            (Some(min_value), Some(max_value)) => (min_value, max_value),
+            // histogram is empty
+            _ => return 0.0,
+        };
+
+        if value > max_value || (!inclusive && value == max_value) {
+            // the interval does not intersect with the interval [min; max]
+            0.0
+        } else if value < min_value || (inclusive && value == min_value) {
+            // the interval includes all the values in the histogram
+            self.count() as f64
+        } else {
+            // find the position of the first bin to the right of the (value, count) bin if we
+            // were to insert it to the histogram
+            let pos = self.bins.partition_point(|bin| bin.value() <= value);
+
+            // calculate the sum of counts of the bins following the right neighbour of that bin
+            let right = (pos + 1).min(self.bins.len());
+            let count_down_to_right: u64 = match prefix_counts {
+                Some(prefix_counts) => prefix_counts[self.bins.len()] - prefix_counts[right],
+                None => self.bins[right..].iter().map(|bin| bin.count()).sum(),
+            };
+
+            // determine the bordering bins
+            let (left_bin, right_bin) = get_bordering_bins(&self.bins, min_value, max_value, pos);
+            let (left_value, left_count) = (left_bin.value(), left_bin.count() as f64);
+            let (right_value, right_count) = (right_bin.value(), right_bin.count() as f64);
+
+            // estimate the count of values between the (value, count) bin and the right neighbour
+            let count_value_to_right = if right_value - left_value <= 0.0 {
+                0.0
+            } else {
+                let proximity_to_left = (right_value - value) / (right_value - left_value);
+                let count = right_count + (left_count - right_count) * proximity_to_left;
+
+                (right_count + count) / 2.0 * proximity_to_left
+            };
+
+            // add up all partial counts. If the exact minimum and maximum values are known to be
+            // present in the histogram, the latter is always included in the interval here, and
+            // the former is always excluded from it
+            let count = count_down_to_right as f64 + right_count / 2.0 + count_value_to_right;
+            self.clamp_to_extrema(count)
+        }
+    }
+
+    /// Clamp an estimate of the count of values in an interval, which includes exactly one of
+    /// the minimum and maximum values, so that it accounts for the exact one being included and
+    /// the other one being excluded. Estimates are returned as is, if the exact minimum and
+    /// maximum values are not tracked, as the outermost bins are used instead of them.
+    fn clamp_to_extrema(&self, count: f64) -> f64 {
+        if self.extrema == Extrema::Disabled {
+            count
+        } else {
+            count.min(self.count() as f64 - 1.0).max(1.0)
+        }
+    }
+
+    /// Replace the bins with a sorted subset of them (with possibly different counts). The exact
+    /// minimum and maximum values are only preserved if the outermost bins are still present.
+    fn replace_bins(&mut self, bins: Bins) {
+        if self.extrema != Extrema::Disabled {
+            let (min_value, max_value) = self.extrema.get().unzip();
+            let min_value = match (bins.first(), self.bins.first()) {
+                (Some(new), Some(old)) if new.value() == old.value() => min_value,
+                (new, _) => new.map(|bin| bin.value()),
+            };
+            let max_value = match (bins.last(), self.bins.last()) {
+                (Some(new), Some(old)) if new.value() == old.value() => max_value,
+                (new, _) => new.map(|bin| bin.value()),
+            };
+            self.extrema = Extrema::new(min_value, max_value);
+        }
+
+        self.count = bins.iter().map(|bin| bin.count()).sum();
+        if bins.is_empty() {
+            self.sum = CompensatedSum::default();
+        }
+        self.bins = bins;
+        self.gaps.invalidate();
+    }
+
+    /// Insert a new bin preserving the ascending order and shrink the histogram if needed.
+    fn insert_bin(&mut self, bin: Bin) {
+        let pos = self.bins.partition_point(|b| *b <= bin);
+        self.bins.insert(pos, bin);
+
+        if self.size < Self::GAPS_MIN_SIZE {
+            self.shrink();
+        } else {
+            // keep the gaps between bins up to date instead of scanning the bins
+            self.gaps.insert(&self.bins, pos);
+            self.merge_closest_bins();
+        }
+    }
+
+    /// Merge two closest bins until the histogram shrinks back to the fixed size (and no two
+    /// adjacent bins are closer to each other than the minimum gap). Only the gaps around
+    /// the merged bins are updated on every step, rather than all the bins being scanned.
+    fn merge_closest_bins(&mut self) {
+        while self.may_need_merge() {
+            let (left, right) = self.gaps.closest(&self.bins);
+            if !self.needs_merge(left, right) {
+                break;
+            }
+
+            self.bins[left] = Bin::merge(&self.bins[left], &self.bins[right]);
+            self.bins.remove(right);
+            self.gaps.merge(&self.bins, left);
+        }
+    }
+
+    /// Returns the part of the bin, which can be added without overflowing the total count
+    /// (see [`CountPolicy`]), or `None` if nothing can be added.
+    fn admit(&self, bin: Bin) -> Option<Bin> {
+        match self.count_policy.admit(self.count, bin.count()) {
+            0 => None,
+            count if count == bin.count() => Some(bin),
+            count => Some(Bin::new(bin.value(), count)),
+        }
+    }
+
+    /// Returns the bins of another histogram, which can be added without overflowing the
+    /// total count (see [`CountPolicy`]): either all of them, or their counts scaled down
+    /// proportionally to fit.
+    fn admit_all<'a>(&self, other: &'a Histogram) -> Cow<'a, [Bin]> {
+        let admitted = self.count_policy.admit(self.count, other.count);
+        if admitted == other.count {
+            return Cow::Borrowed(other.bins());
+        }
+
+        // scale the cumulative counts, so that the scaled counts add up to exactly the
+        // admitted number of values regardless of rounding
+        let factor = admitted as f64 / other.count as f64;
+        let (mut cumulative, mut previous) = (0u64, 0u64);
+        let bins = other
+            .bins()
+            .iter()
+            .filter_map(|bin| {
+                cumulative += bin.count();
+                let scaled = if cumulative == other.count {
+                    admitted
+                } else {
+                    ((cumulative as f64 * factor) as u64).clamp(previous, admitted)
+                };
+                let count = scaled - previous;
+                previous = scaled;
+                (count > 0).then(|| Bin::new(bin.value(), count))
+            })
+            .collect();
+
+        Cow::Owned(bins)
+    }
+
+    /// Append a bin, which must not be less than any of the existing bins, to the end of
+    /// the list. The histogram is compacted every time the number of buffered bins reaches
+    /// twice its size, which keeps the working set small. Call `shrink()` once all the bins
+    /// have been added to restore the invariant.
+    fn push_sorted_bin(&mut self, bin: Bin) {
+        if self.bins.len() >= 2 * self.size {
+            self.shrink();
+        }
+
+        self.gaps.invalidate();
+        self.bins.push(bin);
+    }
+
+    /// Add a sorted list of bins to the histogram preserving the ascending order of bins
+    /// (without shrinking the histogram).
+    fn union_bins(&mut self, other: &[Bin]) {
+        self.gaps.invalidate();
+
+        // merge the lists in-place starting from the end, so that each bin is moved at most once
+        let (mut i, mut j) = (self.bins.len(), other.len());
+        self.bins.resize(i + j, Bin::empty(0.0));
+        while j > 0 {
+            // bins of the other histogram go after the equal bins of this histogram, which
+            // is consistent with the behaviour of insert()
+            if i > 0 && self.bins[i - 1] > other[j - 1] {
+                self.bins[i + j - 1] = self.bins[i - 1];
+                i -= 1;
+            } else {
+                self.bins[i + j - 1] = other[j - 1];
+                j -= 1;
+            }
+        }
+    }
+
+    /// Increase the size of the histogram (if needed), so that it can hold at least `size` bins.
+    /// The storage of bins grows as bins are added, rather than up front.
+    fn grow(&mut self, size: usize) {
+        if size > self.size {
+            self.size = size;
+        }
+    }
+
+    /// Returns the initial capacity of a list of bins derived from the bins of this histogram.
+    /// It is bounded by the number of bins rather than by the size, as the latter may come
+    /// from untrusted input and be too large to be allocated.
+    fn bins_capacity(&self) -> usize {
+        self.bins.len().min(self.size) + 1
+    }
+
+    /// Release the memory held by bins beyond what is needed for regular updates.
+    fn release_excess_capacity(&mut self) {
+        if self.bins.capacity() > self.size + 1 {
+            let mut bins = Bins::with_capacity(self.size + 1);
+            bins.extend_from_slice(&self.bins);
+            self.bins = bins;
+        }
+    }
+
+    /// Keep track of the minimum and the maximum values (this will allow us to have more accurate quantile approximations).
+    fn track_min_max(&mut self, value: f64) {
+        match &mut self.extrema {
+            Extrema::Tracked(min_value, max_value) => {
+                if value < *min_value {
+                    *min_value = value;
+                }
+                if value > *max_value {
+                    *max_value = value;
+                }
+            }
+            Extrema::Empty => self.extrema = Extrema::Tracked(value, value),
+            Extrema::Disabled => {}
+        }
+    }
+
+    /// Returns `true` if there might be a pair of bins that needs to be merged: either the
+    /// histogram has grown beyond its size, or bins are allowed to be merged eagerly.
+    fn may_need_merge(&self) -> bool {
+        self.bins.len() > self.size || (self.min_gap > 0.0 && self.bins.len() > 1)
+    }
+
+    /// Returns `true` if the closest pair of bins needs to be merged.
+    fn needs_merge(&self, left: usize, right: usize) -> bool {
+        self.bins.len() > self.size
+            || self.bins[right].value() - self.bins[left].value() < self.min_gap
+    }
+
+    /// Merge two closest bins until the histogram shrinks back to the fixed size (and no two
+    /// adjacent bins are closer to each other than the minimum gap).
+    fn shrink(&mut self) {
+        // the bins might have been changed without updating the gaps between them
+        self.gaps.invalidate();
+
+        if self.bins.len() > self.size + 1 {
+            // scanning the bins for the closest pair on each step would take quadratic time
+            self.shrink_many();
+        }
+
+        if self.size >= Self::GAPS_MIN_SIZE {
+            // e.g. many bins may need to be merged to respect the minimum gap. The gaps
+            // are rebuilt once and are kept up to date for subsequent inserts
+            self.merge_closest_bins();
+            return;
+        }
+
+        while self.may_need_merge() {
+            let (left, right) = self.find_closest_bins();
+            if !self.needs_merge(left, right) {
+                break;
+            }
+
+            self.bins[left] = Bin::merge(&self.bins[left], &self.bins[right]);
+            self.bins.remove(right);
+        }
+    }
+
+    /// Merge two closest bins until the histogram shrinks back to the fixed size.
+    ///
+    /// The result is exactly the same as if `find_closest_bins()` was called repeatedly, but
+    /// pairs of adjacent bins are kept in a priority queue instead, so that shrinking the
+    /// histogram by `k` bins takes O(n log n) rather than O(n * k) time. Merged bins are
+    /// only marked as removed, and the list of bins is compacted once at the very end.
+    fn shrink_many(&mut self) {
+        let n = self.bins.len();
+        if n <= self.size {
+            return;
+        }
+
+        // a doubly linked list of bins that have not been merged yet (n stands for no bin)
+        let mut prev: Vec<usize> = (0..n).map(|i| if i == 0 { n } else { i - 1 }).collect();
+        let mut next: Vec<usize> = (1..=n).collect();
+        let mut removed = vec![false; n];
+        // a pair is identified by the index of its left bin. Whenever either of the two bins
+        // changes, the generation of the left one is incremented to invalidate the pair
+        let mut generation = vec![0u32; n];
+
+        // pairs are ordered the same way find_closest_bins() compares them: by the distance
+        // between bins, then by the total count, and then by the position in the list
+        let policy = self.merge_policy;
+        let pair = |bins: &[Bin], generation: &[u32], left: usize, right: usize| {
+            Reverse((
+                TotalF64(policy.distance(&bins[left], &bins[right])),
+                bins[left].count() + bins[right].count(),
+                left,
+                generation[left],
+            ))
+        };
+        let mut pairs: BinaryHeap<_> = (1..n)
+            .map(|right| pair(&self.bins, &generation, right - 1, right))
+            .collect();
+
+        let mut remaining = n;
+        while remaining > self.size {
+            let Reverse((_, _, left, left_generation)) = match pairs.pop() {
+                Some(pair) => pair,
+                None => break,
+            };
+            if removed[left] || generation[left] != left_generation {
+                // the pair is stale
+                continue;
+            }
+
+            let right = next[left];
+            self.bins[left] = Bin::merge(&self.bins[left], &self.bins[right]);
+            removed[right] = true;
+            next[left] = next[right];
+            if next[right] < n {
+                prev[next[right]] = left;
+            }
+            remaining -= 1;
+
+            generation[left] += 1;
+            if prev[left] < n {
+                generation[prev[left]] += 1;
+                pairs.push(pair(&self.bins, &generation, prev[left], left));
+            }
+            if next[left] < n {
+                pairs.push(pair(&self.bins, &generation, left, next[left]));
+            }
+        }
+
+        let mut i = 0;
+        self.bins.retain(|_| {
+            i += 1;
+            !removed[i - 1]
+        });
+    }
+
+    /// Find a pair of bins that are closest to each other.
+    fn find_closest_bins(&self) -> (usize, usize) {
+        #[cfg(feature = "simd")]
+        if self.merge_policy == MergePolicy::Absolute && self.bins.len() >= 2 {
+            return crate::simd::find_closest_bins(&self.bins);
+        }
+
+        let right_index = (1..self.bins.len())
+            .min_by_key(|i| {
+                (
+                    // distance between bins is considered first
+                    TotalF64(
+                        self.merge_policy
+                            .distance(&self.bins[*i - 1], &self.bins[*i]),
+                    ),
+                    // if distances are equal, a pair of bins with smaller total count is preferred
+                    self.bins[i - 1].count() + self.bins[*i].count(),
+                )
+            })
+            .unwrap_or(self.bins.len() - 1);
+
+        (right_index - 1, right_index)
+    }
+}
+
+/// Estimate the value of the `q`'th quantile from a non-empty sorted list of bins with
+/// the given total count and the exact minimum and maximum values (and, optionally,
+/// the cumulative counts of bins, see `Histogram::count_up_to()`). Bins may be stored in
+/// any representation, which can be converted to [`Bin`] (e.g. an archived one).
+pub(crate) fn uniform_quantile<B: Copy + Into<Bin>>(
+    bins: &[B],
+    total_count: u64,
+    min_value: f64,
+    max_value: f64,
+    q: f64,
+    prefix_counts: Option<&[u64]>,
+) -> f64 {
+    if q == 0.0 {
+        return min_value;
+    } else if q == 1.0 {
+        return max_value;
+    }
+
+    // Algorithm 4: Uniform procedure (from the paper mentioned in the description)
+    //
+    // To estimate the value of a given quantile we first find a pair of bins in
+    // the histogram that enclose the target cumulative count. Then we use the
+    // difference between the target count and the cumulative count up to the left
+    // bin in the pair to form a (quadratic) equation, that allows us to calculate
+    // the target value based on its proximity to the right bin.
+
+    let qth_count = total_count as f64 * q;
+    let (i, up_to_qth_count) = match prefix_counts {
+        Some(prefix_counts) => search_cumulative_count_less_than(prefix_counts, qth_count),
+        None => index_of_cumulative_count_less_than(bins, qth_count),
+    };
+
+    let (left_bin, right_bin) = get_bordering_bins(bins, min_value, max_value, i);
+    let (left_value, left_count) = (left_bin.value(), left_bin.count() as f64);
+    let (right_value, right_count) = (right_bin.value(), right_bin.count() as f64);
+
+    let d = qth_count - up_to_qth_count;
+    let a = right_count - left_count;
+    if a == 0.0 {
+        left_value + (right_value - left_value) * d / left_count
+    } else {
+        let b = 2.0 * left_count;
+        let c = -2.0 * d;
+        let z = (-b + (b.powi(2) - 4.0 * a * c).sqrt()) / (2.0 * a);
+
+        left_value + (right_value - left_value) * z
+    }
+}
+
+fn index_of_cumulative_count_less_than<B: Copy + Into<Bin>>(
+    bins: &[B],
+    target_count: f64,
+) -> (usize, f64) {
+    let counts = bins.iter().map(|&bin| bin.into().count());
+    counts
+        .clone()
+        .zip(std::iter::once(0).chain(counts))
+        .map(|(l, r)| (l + r) as f64 / 2.0)
+        .scan(0.0, |partial_count, next_count| {
+            *partial_count += next_count;
+            Some(*partial_count)
+        })
+        .enumerate()
+        .take_while(|(_, partial_count)| target_count > *partial_count)
+        .last()
+        .map_or((0, 0.0), |(i, sum)| (i + 1, sum))
+}
+
+/// Same as `index_of_cumulative_count_less_than()`, but finds the index in O(log n) time
+/// using precomputed cumulative counts of bins.
+fn search_cumulative_count_less_than(prefix_counts: &[u64], target_count: f64) -> (usize, f64) {
+    // the k'th partial count is the total count of the first k bins plus a half of the count
+    // of the next one. Partial counts increase, so the number of them that are less than
+    // the target count can be found by a binary search
+    let partial_count = |k: usize| (prefix_counts[k] + prefix_counts[k + 1]) as f64 / 2.0;
+    let (mut low, mut high) = (0, prefix_counts.len() - 1);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if partial_count(mid) < target_count {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    match low {
+        0 => (0, 0.0),
+        i => (i, partial_count(i - 1)),
+    }
+}
+
+fn get_bordering_bins<B: Copy + Into<Bin>>(
+    bins: &[B],
+    min_value: f64,
+    max_value: f64,
+    i: usize,
+) -> (Bin, Bin) {
+    if i == 0 {
+        (Bin::empty(min_value), bins[0].into())
+    } else if i == bins.len() {
+        (bins[i - 1].into(), Bin::empty(max_value))
+    } else {
+        (bins[i - 1].into(), bins[i].into())
+    }
+}
+
+impl PartialEq for Histogram {
+    /// Two histograms are equal, if they have the same size, bins, minimum and maximum
+    /// values, sum of values, and numbers of NaN, underflowed and overflowed values.
+    /// Settings of histograms (e.g. policies) are not compared.
+    ///
+    /// ```
+    /// use bhtt::{Histogram, NanPolicy};
+    ///
+    /// let h1 = Histogram::from_iter(5, [1.0, 2.0, 3.0]);
+    /// let mut h2 = Histogram::new(5);
+    /// h2.set_nan_policy(NanPolicy::Ignore);
+    /// h2.insert_many([1.0, 2.0, 3.0]);
+    /// assert_eq!(h1, h2);
+    ///
+    /// h2.insert(4.0);
+    /// assert_ne!(h1, h2);
+    /// ```
+    fn eq(&self, other: &Self) -> bool {
+        self.size == other.size
+            && self.bins == other.bins
+            && self.extrema.get() == other.extrema.get()
+            && self.sum() == other.sum()
+            && self.nan_count == other.nan_count
+            && self.underflow_count == other.underflow_count
+            && self.overflow_count == other.overflow_count
+    }
+}
+
+impl Default for Histogram {
+    /// Create a new Histogram of the default size (see [`Histogram::DEFAULT_SIZE`]).
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::default();
+    /// assert_eq!(h.size(), Histogram::DEFAULT_SIZE);
+    /// ```
+    fn default() -> Self {
+        Histogram::new(Histogram::DEFAULT_SIZE)
+    }
+}
+
+impl<T: Into<Bin>> FromIterator<T> for Histogram {
+    /// Create a new Histogram of the default size (see [`Histogram::DEFAULT_SIZE`]) from
+    /// an iterator. Use [`HistogramBuilder::collect_from`](crate::HistogramBuilder::collect_from)
+    /// to collect values into a histogram of a different size.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h: Histogram = (1..=100).map(|v| v as f64).collect();
+    /// assert_eq!(h.size(), Histogram::DEFAULT_SIZE);
+    /// assert_eq!(h.count(), 100);
+    /// ```
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut h = Histogram::default();
+        h.extend(iter);
+
+        h
+    }
+}
+
+impl<T: Into<Bin>> Extend<T> for Histogram {
+    /// Update the histogram by inserting all values (or bins) from an iterator.
+    ///
+    /// ```
+    /// use bhtt::{Bin, Histogram};
+    ///
+    /// let mut h = Histogram::new(5);
+    /// h.extend(vec![1.0, 0.0, -5.4, -2.1, 8.5]);
+    /// h.extend(vec![Bin::new(10.0, 2), Bin::new(-7.5, 3)]);
+    ///
+    /// assert_eq!(h.count(), 10);
+    /// assert_eq!(h.min(), Some(-7.5));
+    /// assert_eq!(h.max(), Some(10.0));
+    /// ```
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl AddAssign<&Histogram> for Histogram {
+    /// Merge another histogram into this one (in-place). The size of the histogram is
+    /// increased to the size of the other one, if the latter is larger, so that no accuracy
+    /// is lost.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::from_iter(5, &[1.0, 0.0, -5.4, -2.1, 8.5]);
+    /// h += &Histogram::from_iter(10, &[10.0, 8.6, 4.3, 7.8, 5.2]);
+    ///
+    /// assert_eq!(h.size(), 10);
+    /// assert_eq!(h.count(), 10);
+    /// assert_eq!(h.min(), Some(-5.4));
+    /// assert_eq!(h.max(), Some(10.0));
+    /// ```
+    fn add_assign(&mut self, other: &Histogram) {
+        self.grow(other.size());
+        self.merge(other);
+    }
+}
+
+impl AddAssign for Histogram {
+    /// Merge another histogram into this one (in-place). The size of the histogram is
+    /// increased to the size of the other one, if the latter is larger, so that no accuracy
+    /// is lost.
+    fn add_assign(&mut self, other: Histogram) {
+        *self += &other;
+    }
+}
+
+impl AddAssign<f64> for Histogram {
+    /// Update the histogram by inserting a new value.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let mut h = Histogram::new(5);
+    /// h += 42.0;
+    /// h += -7.5;
+    ///
+    /// assert_eq!(h.count(), 2);
+    /// ```
+    fn add_assign(&mut self, value: f64) {
+        self.insert(value);
+    }
+}
+
+impl AddAssign<Bin> for Histogram {
+    /// Update the histogram by inserting a new bin.
+    ///
+    /// ```
+    /// use bhtt::{Bin, Histogram};
+    ///
+    /// let mut h = Histogram::new(5);
+    /// h += Bin::new(42.0, 10);
+    ///
+    /// assert_eq!(h.count(), 10);
+    /// ```
+    fn add_assign(&mut self, bin: Bin) {
+        self.insert(bin);
+    }
+}
+
+impl Add<&Histogram> for Histogram {
+    type Output = Histogram;
+
+    /// Merge two histograms together. The size of the resulting histogram is equal to
+    /// the largest size of the two, so that no accuracy is lost.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h1 = Histogram::from_iter(5, &[1.0, 0.0, -5.4, -2.1, 8.5]);
+    /// let h2 = Histogram::from_iter(10, &[10.0, 8.6, 4.3, 7.8, 5.2]);
+    ///
+    /// let h = h1 + &h2;
+    /// assert_eq!(h.size(), 10);
+    /// assert_eq!(h.count(), 10);
+    /// ```
+    fn add(mut self, other: &Histogram) -> Histogram {
+        self += other;
+        self
+    }
+}
+
+impl Add for Histogram {
+    type Output = Histogram;
+
+    /// Merge two histograms together. The size of the resulting histogram is equal to
+    /// the largest size of the two, so that no accuracy is lost.
+    fn add(self, other: Histogram) -> Histogram {
+        self + &other
+    }
+}
+
+impl Add for &Histogram {
+    type Output = Histogram;
+
+    /// Merge two histograms together. The size of the resulting histogram is equal to
+    /// the largest size of the two, so that no accuracy is lost.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h1 = Histogram::from_iter(5, &[1.0, 0.0, -5.4, -2.1, 8.5]);
+    /// let h2 = Histogram::from_iter(10, &[10.0, 8.6, 4.3, 7.8, 5.2]);
+    ///
+    /// let h = &h1 + &h2;
+    /// assert_eq!(h.size(), 10);
+    /// assert_eq!(h.count(), 10);
+    /// ```
+    fn add(self, other: &Histogram) -> Histogram {
+        self.clone() + other
+    }
+}
+
+impl Sum for Histogram {
+    /// Merge all histograms from an iterator together (see [`Histogram::merge_all`]). The size
+    /// of the resulting histogram is equal to the largest size of the merged histograms, so
+    /// that no accuracy is lost. An empty iterator produces an empty histogram of the default
+    /// size (see [`Histogram::DEFAULT_SIZE`]).
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let histograms = vec![
+    ///     Histogram::from_iter(5, &[1.0, 0.0, -5.4, -2.1, 8.5]),
+    ///     Histogram::from_iter(10, &[10.0, 8.6, 4.3, 7.8, 5.2]),
+    /// ];
+    ///
+    /// let h: Histogram = histograms.into_iter().sum();
+    /// assert_eq!(h.size(), 10);
+    /// assert_eq!(h.count(), 10);
+    /// assert_eq!(h.min(), Some(-5.4));
+    /// assert_eq!(h.max(), Some(10.0));
+    /// ```
+    fn sum<I: Iterator<Item = Histogram>>(iter: I) -> Self {
+        let histograms: Vec<Histogram> = iter.collect();
+        histograms.iter().sum()
+    }
+}
+
+impl<'a> Sum<&'a Histogram> for Histogram {
+    /// Merge all histograms from an iterator together (see [`Histogram::merge_all`]). The size
+    /// of the resulting histogram is equal to the largest size of the merged histograms, so
+    /// that no accuracy is lost. An empty iterator produces an empty histogram of the default
+    /// size (see [`Histogram::DEFAULT_SIZE`]).
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let histograms = vec![
+    ///     Histogram::from_iter(5, &[1.0, 0.0, -5.4, -2.1, 8.5]),
+    ///     Histogram::from_iter(10, &[10.0, 8.6, 4.3, 7.8, 5.2]),
+    /// ];
+    ///
+    /// let h: Histogram = histograms.iter().sum();
+    /// assert_eq!(h.size(), 10);
+    /// assert_eq!(h.count(), 10);
+    /// assert_eq!(h.min(), Some(-5.4));
+    /// assert_eq!(h.max(), Some(10.0));
+    /// ```
+    fn sum<I: Iterator<Item = &'a Histogram>>(iter: I) -> Self {
+        let histograms: Vec<&Histogram> = iter.collect();
+        match histograms.iter().map(|h| h.size()).max() {
+            None => Histogram::default(),
+            Some(size) => Histogram::merge_all(size, histograms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utilities::{pseudo_random_integers, pseudo_random_values};
+
+    use super::*;
+
+    fn histogram_from_parts(
+        size: usize,
+        bins: Vec<Bin>,
+        min_value: Option<f64>,
+        max_value: Option<f64>,
+    ) -> Histogram {
+        Histogram::from_parts(size, bins, min_value, max_value).unwrap()
+    }
+
+    #[test]
+    fn new() {
+        let h = Histogram::new(5);
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.min(), None);
+        assert_eq!(h.max(), None);
+        assert_eq!(h.sum(), 0.0);
+        assert_eq!(h.mean(), None);
+        assert_eq!(h.bins(), &[]);
+    }
+
+    #[test]
+    #[cfg(any(feature = "ffi", feature = "python"))]
+    fn try_new() {
+        let h = Histogram::try_new(5).unwrap();
+        assert_eq!(h, Histogram::new(5));
+        assert!(h.bins.capacity() >= 6);
+
+        // sizes, whose bins can't be allocated
+        assert!(Histogram::try_new(crate::data::MAX_SIZE).is_none());
+        assert!(Histogram::try_new(1 << 50).is_none());
+        assert!(Histogram::try_new(usize::MAX).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram size must be greater than 0")]
+    fn new_invalid_size() {
+        Histogram::new(0);
+    }
+
+    #[test]
+    fn insert() {
+        let values = vec![
+            1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2, -6.0, -6.6, 0.5, 0.5, 2.625,
+        ];
+        let expected_bins = vec![
+            Bin::new(-6.0, 3),
+            Bin::new(-2.1, 1),
+            Bin::new(0.5, 4),
+            Bin::new(4.041666666666667, 3),
+            Bin::new(8.725, 4),
+        ];
+
+        let mut h = Histogram::new(5);
+        for v in &values {
+            h.insert(*v);
+        }
+
+        assert_eq!(h.count(), values.len() as u64);
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.min(), Some(-6.6));
+        assert_eq!(h.max(), Some(10.0));
+        assert_eq!(h.bins(), expected_bins.as_slice());
+    }
+
+    #[test]
+    fn insert_single_value() {
+        let mut h = Histogram::new(5);
+        h.insert(42.0);
+
+        assert_eq!(h.count(), 1);
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.min(), Some(42.0));
+        assert_eq!(h.max(), Some(42.0));
+        assert_eq!(h.bins(), &[Bin::new(42.0, 1)]);
+    }
+
+    #[test]
+    fn insert_bin() {
+        let bins = vec![
+            Bin::new(4.9, 6),
+            Bin::new(5.0, 8),
+            Bin::new(20.1, 7),
+            Bin::new(4.0, 8),
+            Bin::new(42.0, 14),
+            Bin::new(17.4, 4),
+            Bin::new(-10.0, 1),
+        ];
+        let expected_bins = vec![
+            Bin::new(-10.0, 1),
+            Bin::new(4.609090909090909, 22),
+            Bin::new(17.4, 4),
+            Bin::new(20.1, 7),
+            Bin::new(42.0, 14),
+        ];
+
+        let mut h = Histogram::new(5);
+        for bin in bins {
+            h.insert(bin);
+        }
+
+        assert_eq!(h.count(), 48);
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.min(), Some(-10.0));
+        assert_eq!(h.max(), Some(42.0));
+        assert_eq!(h.bins(), expected_bins.as_slice());
+    }
+
+    #[test]
+    fn insert_single_bin() {
+        let mut h = Histogram::new(5);
+        h.insert(Bin::new(42.0, 84));
+
+        assert_eq!(h.count(), 84);
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.min(), Some(42.0));
+        assert_eq!(h.max(), Some(42.0));
+        assert_eq!(h.bins(), &[Bin::new(42.0, 84)]);
+    }
+
+    #[test]
+    fn merge() {
+        let bins1 = vec![
+            Bin::new(-6.0, 3),
+            Bin::new(-2.1, 1),
+            Bin::new(0.5, 4),
+            Bin::new(4.041666666666667, 3),
+            Bin::new(8.725, 4),
+        ];
+        let mut h1 = histogram_from_parts(5, bins1, Some(-6.6), Some(10.0));
+
+        let bins2 = vec![
+            Bin::new(33.32588794226721, 9977),
+            Bin::new(1255.8137647058825, 17),
+            Bin::new(3364.983, 2),
+            Bin::new(5361.3435, 2),
+            Bin::new(7349.9465, 2),
+        ];
+        let h2 = histogram_from_parts(5, bins2, Some(9.48), Some(7829.851));
+
+        let expected_bins = vec![
+            Bin::new(33.27875390312249, 9992),
+            Bin::new(1255.8137647058825, 17),
+            Bin::new(3364.983, 2),
+            Bin::new(5361.3435, 2),
+            Bin::new(7349.9465, 2),
+        ];
+
+        h1.merge(&h2);
+
+        assert_eq!(h1.count(), 10015);
+        assert_eq!(h1.size(), 5);
+        assert_eq!(h1.min(), Some(-6.6));
+        assert_eq!(h1.max(), Some(7829.851));
+        assert_eq!(h1.bins(), expected_bins.as_slice());
+    }
+
+    #[test]
+    fn merge_empty() {
+        let mut h1 = Histogram::new(5);
+        let h2 = Histogram::new(10);
+
+        h1.merge(&h2);
+
+        assert_eq!(h1.count(), 0);
+        assert_eq!(h1.size(), 5);
+        assert_eq!(h1.min(), None);
+        assert_eq!(h1.max(), None);
+        assert_eq!(h1.bins(), &[]);
+    }
+
+    #[test]
+    fn from_iter() {
+        let values = vec![
+            1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2, -6.0, -6.6, 0.5, 0.5, 2.625,
+        ];
+        let expected_bins = vec![
+            Bin::new(-6.0, 3),
+            Bin::new(-2.1, 1),
+            Bin::new(0.5, 4),
+            Bin::new(4.041666666666667, 3),
+            Bin::new(8.725, 4),
+        ];
+
+        let h = Histogram::from_iter(5, &values);
+
+        assert_eq!(h.count(), values.len() as u64);
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.min(), Some(-6.6));
+        assert_eq!(h.max(), Some(10.0));
+        assert_eq!(h.bins(), expected_bins.as_slice());
+    }
+
+    #[test]
+    fn from_iter_values() {
+        let values = (1..=100).map(|v| v as f64);
+        let expected_bins = vec![
+            Bin::new(10.0, 19),
+            Bin::new(28.0, 17),
+            Bin::new(44.5, 16),
+            Bin::new(61.5, 18),
+            Bin::new(85.5, 30),
+        ];
+
+        let h = Histogram::from_iter(5, values);
+
+        assert_eq!(h.count(), 100);
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.min(), Some(1.0));
+        assert_eq!(h.max(), Some(100.0));
+        assert_eq!(h.bins(), expected_bins.as_slice());
+    }
+
+    #[test]
+    fn find_closest_bins_distance() {
+        // proximity of bins is defined by the absolute distance between their values
+        let bins = vec![
+            Bin::new(-10.0, 1),
+            Bin::new(4.9, 1),
+            Bin::new(5.0, 1),
+            Bin::new(17.4, 1),
+            Bin::new(20.1, 1),
+        ];
+        let h = histogram_from_parts(5, bins, Some(-10.0), Some(100.0));
+
+        assert_eq!(h.find_closest_bins(), (1, 2));
+    }
+
+    #[test]
+    fn find_closest_bins_count() {
+        // distances between bin values are equal. Therefore, the pair of bins
+        // with a smaller total count is considered to be the closest.
+        let bins = vec![
+            Bin::new(1.0, 1),
+            Bin::new(2.0, 2),
+            Bin::new(3.0, 3),
+            Bin::new(4.0, 4),
+            Bin::new(5.0, 5),
+        ];
+        let h = histogram_from_parts(5, bins, Some(-10.0), Some(100.0));
+
+        assert_eq!(h.find_closest_bins(), (0, 1));
+    }
+
+    #[test]
+    fn find_closest_bins_index() {
+        // distances between bin values are equal, and so are bin counts.
+        // Therefore, the leftmost pair of bins is considered to be the closest.
+        let bins = vec![
+            Bin::new(1.0, 1),
+            Bin::new(2.0, 1),
+            Bin::new(3.0, 1),
+            Bin::new(4.0, 1),
+            Bin::new(5.0, 1),
+        ];
+        let h = histogram_from_parts(5, bins, Some(-10.0), Some(100.0));
+
+        assert_eq!(h.find_closest_bins(), (0, 1));
+    }
+
+    #[test]
+    fn index_of_cumulative_count_less_than() {
+        let bins = vec![
+            Bin::new(1.0, 10),
+            Bin::new(2.0, 8),
+            Bin::new(3.0, 7),
+            Bin::new(4.0, 15),
+            Bin::new(5.0, 20),
+        ];
+        let h = histogram_from_parts(5, bins, Some(0.0), Some(6.0));
+
+        // [0] [ 5 : 5 ] [ 4 : 4 ] [ 3.5 : 3.5 ] [ 7.5 : 7.5 ] [ 10 : 10 ] [0]
+        //    0         1         2             3             4          5
+
+        let expected = vec![
+            // target count, expected index, expected cumulative count
+            (-10.0, 0, 0.0),
+            (0.0, 0, 0.0),
+            (1.0, 0, 0.0),
+            (5.0, 0, 0.0),
+            (6.0, 1, 5.0),
+            (11.0, 1, 5.0),
+            (14.0, 1, 5.0),
+            (15.0, 2, 14.0),
+            (18.0, 2, 14.0),
+            (21.5, 2, 14.0),
+            (22.0, 3, 21.5),
+            (60.0, 5, 50.0),
+            (70.0, 5, 50.0),
+        ];
+        for (target_count, index, cumulative_count) in expected {
+            assert_eq!(
+                super::index_of_cumulative_count_less_than(&h.bins, target_count),
+                (index, cumulative_count)
+            );
+        }
+    }
+
+    #[test]
+    fn quantile_empty() {
+        let h = Histogram::new(5);
+
+        assert_eq!(h.quantile(0.0), None);
+        assert_eq!(h.quantile(0.5), None);
+        assert_eq!(h.quantile(1.0), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "q must be in the range [0.0; 1.0]")]
+    fn quantile_nan() {
+        let h = Histogram::new(5);
+        h.quantile(f64::NAN);
+    }
+
+    #[test]
+    #[should_panic(expected = "q must be in the range [0.0; 1.0]")]
+    fn quantile_not_in_range_high() {
+        let h = Histogram::new(5);
+        h.quantile(1.1);
+    }
+
+    #[test]
+    #[should_panic(expected = "q must be in the range [0.0; 1.0]")]
+    fn quantile_not_in_range_low() {
+        let h = Histogram::new(5);
+        h.quantile(-0.1);
+    }
+
+    #[test]
+    fn quantile() {
+        let bins = vec![
+            Bin::new(2.0, 1),
+            Bin::new(9.5, 2),
+            Bin::new(19.33, 3),
+            Bin::new(32.67, 3),
+            Bin::new(45.0, 1),
+        ];
+        let h = histogram_from_parts(5, bins, Some(2.0), Some(45.0));
+
+        let expected = vec![
+            // quantile, expected value, max relative difference
+            (0.0, 2.0, 0.0),
+            (0.1, 8.3, 0.4),
+            (0.25, 11.5, 0.1),
+            (0.5, 21.0, 0.1),
+            (0.75, 31.5, 0.1),
+            (0.9, 36.9, 0.1),
+            (0.99, 44.19, 0.1),
+            (1.0, 45.0, 0.0),
+        ];
+        for (q, expected_value, max_relative) in expected {
+            assert_relative_eq!(
+                h.quantile(q).unwrap(),
+                expected_value,
+                max_relative = max_relative
+            );
+        }
+    }
+
+    #[test]
+    fn count_less_than_or_equal_to_empty() {
+        let h = Histogram::new(5);
+
+        assert_eq!(h.count_less_than_or_equal_to(-42.0), 0);
+        assert_eq!(h.count_less_than_or_equal_to(0.0), 0);
+        assert_eq!(h.count_less_than_or_equal_to(42.0), 0);
+        assert_eq!(h.count_less_than_or_equal_to(f64::NEG_INFINITY), 0);
+        assert_eq!(h.count_less_than_or_equal_to(f64::INFINITY), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "value must not be NaN")]
+    fn count_less_than_or_equal_to_nan() {
+        let h = Histogram::new(5);
+        h.count_less_than_or_equal_to(f64::NAN);
+    }
+
+    #[test]
+    fn count_less_than_or_equal_to() {
+        let bins = vec![
+            Bin::new(2.0, 1),
+            Bin::new(9.5, 2),
+            Bin::new(19.33, 3),
+            Bin::new(32.67, 3),
+            Bin::new(45.0, 1),
+        ];
+        let h = histogram_from_parts(5, bins, Some(2.0), Some(45.0));
+
+        assert_eq!(h.count_less_than_or_equal_to(f64::NEG_INFINITY), 0);
+        assert_eq!(h.count_less_than_or_equal_to(-42.0), 0);
+        assert_eq!(h.count_less_than_or_equal_to(0.0), 0);
+        assert_eq!(h.count_less_than_or_equal_to(2.1), 1);
+        assert_eq!(h.count_less_than_or_equal_to(10.0), 2);
+        assert_eq!(h.count_less_than_or_equal_to(15.0), 3);
+        assert_eq!(h.count_less_than_or_equal_to(25.0), 6);
+        assert_eq!(h.count_less_than_or_equal_to(38.0), 9);
+        assert_eq!(h.count_less_than_or_equal_to(45.0), 10);
+        assert_eq!(h.count_less_than_or_equal_to(f64::INFINITY), 10);
+    }
+
+    #[test]
+    fn count_less_than_or_equal_to_min_max() {
+        // the exact minimum and maximum values are known to be present in the histogram, so
+        // the minimum is always counted, and values below the maximum never include it
+        let h = Histogram::from_iter(1, [0.0, 10.0]);
+        assert_eq!(h.bins(), &[Bin::new(5.0, 2)]);
+
+        assert_eq!(h.count_less_than_or_equal_to(-0.01), 0);
+        assert_eq!(h.count_less_than_or_equal_to(0.0), 1);
+        assert_eq!(h.count_less_than_or_equal_to(0.01), 1);
+        assert_eq!(h.count_less_than_or_equal_to(9.99), 1);
+        assert_eq!(h.count_less_than_or_equal_to(10.0), 2);
+    }
+
+    #[test]
+    fn count_less_than_or_equal_to_min_max_disabled() {
+        // the outermost bins are used instead of the exact minimum and maximum values, so the
+        // estimate is not adjusted for the latter
+        let mut h = Histogram::new(3);
+        h.set_min_max_tracking(false);
+        h.insert_many([Bin::new(1.0, 4), Bin::new(2.0, 2), Bin::new(10.0, 4)]);
+        assert_eq!(h.min(), Some(1.0));
+
+        assert_eq!(h.count_less_than_or_equal_to(0.99), 0);
+        assert_eq!(h.count_less_than_or_equal_to(1.0), 0);
+        assert_eq!(h.count_less_than_or_equal_to(1.01), 2);
+        assert_eq!(h.count_less_than_or_equal_to(10.0), 10);
+
+        // whereas the exact minimum value is always counted
+        h.clear();
+        h.set_min_max_tracking(true);
+        h.insert_many([Bin::new(1.0, 4), Bin::new(2.0, 2), Bin::new(10.0, 4)]);
+        assert_eq!(h.count_less_than_or_equal_to(1.0), 1);
+    }
+
+    #[test]
+    fn count_in_range_empty() {
+        let h = Histogram::new(5);
+
+        assert_eq!(h.count_in_range(..), 0);
+        assert_eq!(h.count_in_range(-42.0..42.0), 0);
+        assert_eq!(h.count_in_range(0.0..=0.0), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "value must not be NaN")]
+    fn count_in_range_nan() {
+        let h = Histogram::new(5);
+        h.count_in_range(f64::NAN..42.0);
+    }
+
+    #[test]
+    fn count_in_range() {
+        let bins = vec![
+            Bin::new(2.0, 1),
+            Bin::new(9.5, 2),
+            Bin::new(19.33, 3),
+            Bin::new(32.67, 3),
+            Bin::new(45.0, 1),
+        ];
+        let h = histogram_from_parts(5, bins, Some(2.0), Some(45.0));
+
+        assert_eq!(h.count_in_range(..), 10);
+        assert_eq!(h.count_in_range(f64::NEG_INFINITY..f64::INFINITY), 10);
+        assert_eq!(h.count_in_range(2.0..=45.0), 10);
+        assert_eq!(h.count_in_range(..=15.0), 3);
+        assert_eq!(h.count_in_range(15.0..), 7);
+        assert_eq!(h.count_in_range(10.0..=38.0), 7);
+        assert_eq!(h.count_in_range(25.0..38.0), 3);
+        assert_eq!(h.count_in_range(46.0..), 0);
+        assert_eq!(h.count_in_range(..1.0), 0);
+
+        // inverted ranges are empty
+        assert_eq!(h.count_in_range(38.0..10.0), 0);
+    }
+
+    #[test]
+    fn count_in_range_bounds() {
+        // the exact minimum and maximum values are known, so they are only counted
+        // when the range includes them
+        let h = histogram_from_parts(5, vec![Bin::new(42.0, 10)], Some(42.0), Some(42.0));
+
+        assert_eq!(h.count_in_range(42.0..=42.0), 10);
+        assert_eq!(h.count_in_range(..=42.0), 10);
+        assert_eq!(h.count_in_range(42.0..), 10);
+        assert_eq!(h.count_in_range(42.0..42.0), 0);
+        assert_eq!(h.count_in_range(..42.0), 0);
+        assert_eq!(
+            h.count_in_range((Bound::Excluded(42.0), Bound::Unbounded)),
+            0
+        );
+        assert_eq!(h.count_in_range(0.0..=42.0), 10);
+        assert_eq!(h.count_in_range(0.0..42.0), 0);
+    }
+
+    #[test]
+    fn count_greater_than_empty() {
+        let h = Histogram::new(5);
+
+        assert_eq!(h.count_greater_than(-42.0), 0);
+        assert_eq!(h.count_greater_than(0.0), 0);
+        assert_eq!(h.count_greater_than(42.0), 0);
+        assert_eq!(h.count_greater_than(f64::NEG_INFINITY), 0);
+        assert_eq!(h.count_greater_than(f64::INFINITY), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "value must not be NaN")]
+    fn count_greater_than_nan() {
+        let h = Histogram::new(5);
+        h.count_greater_than(f64::NAN);
+    }
+
+    #[test]
+    fn count_greater_than() {
+        let bins = vec![
+            Bin::new(2.0, 1),
+            Bin::new(9.5, 2),
+            Bin::new(19.33, 3),
+            Bin::new(32.67, 3),
+            Bin::new(45.0, 1),
+        ];
+        let h = histogram_from_parts(5, bins, Some(2.0), Some(45.0));
+
+        assert_eq!(h.count_greater_than(f64::NEG_INFINITY), 10);
+        assert_eq!(h.count_greater_than(-42.0), 10);
+        assert_eq!(h.count_greater_than(1.9), 10);
+        assert_eq!(h.count_greater_than(2.0), 9);
+        assert_eq!(h.count_greater_than(10.0), 8);
+        assert_eq!(h.count_greater_than(15.0), 7);
+        assert_eq!(h.count_greater_than(25.0), 4);
+        assert_eq!(h.count_greater_than(38.0), 1);
+        assert_eq!(h.count_greater_than(44.9), 1);
+        assert_eq!(h.count_greater_than(45.0), 0);
+        assert_eq!(h.count_greater_than(f64::INFINITY), 0);
+    }
+
+    #[test]
+    fn count_greater_than_bin_center() {
+        // the estimated counts on both sides of the center of the second bin are 1.5, and
+        // both are rounded up the same way
+        let bins = vec![Bin::new(1.0, 1), Bin::new(2.0, 1), Bin::new(3.0, 1)];
+        let h = histogram_from_parts(5, bins, Some(1.0), Some(3.0));
+
+        assert_eq!(h.count_less_than_or_equal_to(2.0), 2);
+        assert_eq!(h.count_greater_than(2.0), 2);
+    }
+
+    #[test]
+    fn count_greater_than_mirrored() {
+        // the estimate is symmetric to the one of values less than the mirrored value in the
+        // mirror image of the histogram
+        for size in [1, 2, 5, 64] {
+            let h = Histogram::from_iter(size, pseudo_random_values(42, 1000));
+            let mirrored = histogram_from_parts(
+                size,
+                h.bins()
+                    .iter()
+                    .rev()
+                    .map(|bin| Bin::new(-bin.value(), bin.count()))
+                    .collect(),
+                h.max().map(|value| -value),
+                h.min().map(|value| -value),
+            );
+
+            for i in -10..=1010 {
+                let value = i as f64;
+                assert_eq!(
+                    h.count_greater_than(value),
+                    mirrored.count_up_to(-value, false, None).round() as u64,
+                    "size = {}, value = {}",
+                    size,
+                    value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rank_empty() {
+        let h = Histogram::new(5);
+
+        assert_eq!(h.rank(-42.0), None);
+        assert_eq!(h.rank(0.0), None);
+        assert_eq!(h.rank(42.0), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "value must not be NaN")]
+    fn rank_nan() {
+        let h = Histogram::new(5);
+        h.rank(f64::NAN);
+    }
+
+    #[test]
+    fn rank() {
+        let bins = vec![
+            Bin::new(2.0, 1),
+            Bin::new(9.5, 2),
+            Bin::new(19.33, 3),
+            Bin::new(32.67, 3),
+            Bin::new(45.0, 1),
+        ];
+        let h = histogram_from_parts(5, bins, Some(2.0), Some(45.0));
+
+        assert_eq!(h.rank(f64::NEG_INFINITY), Some(0.0));
+        assert_eq!(h.rank(1.9), Some(0.0));
+        assert_eq!(h.rank(45.0), Some(1.0));
+        assert_eq!(h.rank(f64::INFINITY), Some(1.0));
+
+        // rank is the inverse of quantile
+        for q in [0.1, 0.25, 0.5, 0.75, 0.9] {
+            assert_relative_eq!(
+                h.rank(h.quantile(q).unwrap()).unwrap(),
+                q,
+                max_relative = 1e-9
+            );
+        }
+    }
+
+    #[test]
+    fn sum_and_mean() {
+        let mut h = Histogram::new(5);
+        h.insert(0.1);
+        h.insert(Bin::new(0.2, 2));
+        h.insert(1e16);
+        h.insert(0.3);
+        h.insert(-1e16);
+
+        // bins have been merged together, but the sum is still exact
+        assert_eq!(h.sum(), 0.8);
+        assert_eq!(h.mean(), Some(0.8 / 6.0));
+    }
+
+    #[test]
+    fn sum_and_mean_merge() {
+        let mut h1 = Histogram::from_iter(2, [1.0, 1e100, 1.0]);
+        let h2 = Histogram::from_iter(2, [1.0, -1e100]);
+
+        h1.merge(&h2);
+        assert_eq!(h1.count(), 5);
+        assert_eq!(h1.sum(), 3.0);
+        assert_eq!(h1.mean(), Some(0.6));
+
+        h1.merge(&Histogram::new(5));
+        assert_eq!(h1.sum(), 3.0);
+    }
+
+    #[test]
+    fn sum_and_mean_overflow() {
+        let mut h = Histogram::new(5);
+        h.insert(Bin::new(1e308, 10));
+        assert_eq!(h.sum(), f64::INFINITY);
+        assert_eq!(h.mean(), Some(f64::INFINITY));
+
+        h.insert(1.0);
+        assert_eq!(h.sum(), f64::INFINITY);
+    }
+
+    #[test]
+    fn percentile_empty() {
+        let h = Histogram::new(5);
+
+        assert_eq!(h.percentile(0.0), None);
+        assert_eq!(h.percentile(50.0), None);
+        assert_eq!(h.percentile(100.0), None);
+        assert_eq!(h.median(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "p must be in the range [0.0; 100.0]")]
+    fn percentile_nan() {
+        let h = Histogram::new(5);
+        h.percentile(f64::NAN);
+    }
+
+    #[test]
+    #[should_panic(expected = "p must be in the range [0.0; 100.0]")]
+    fn percentile_not_in_range_high() {
+        let h = Histogram::new(5);
+        h.percentile(100.1);
+    }
+
+    #[test]
+    #[should_panic(expected = "p must be in the range [0.0; 100.0]")]
+    fn percentile_not_in_range_low() {
+        let h = Histogram::new(5);
+        h.percentile(-0.1);
+    }
+
+    #[test]
+    fn percentile() {
+        let bins = vec![
+            Bin::new(2.0, 1),
+            Bin::new(9.5, 2),
+            Bin::new(19.33, 3),
+            Bin::new(32.67, 3),
+            Bin::new(45.0, 1),
+        ];
+        let h = histogram_from_parts(5, bins, Some(2.0), Some(45.0));
+
+        for p in [0.0, 10.0, 25.0, 50.0, 75.0, 90.0, 99.0, 100.0] {
+            assert_eq!(h.percentile(p), h.quantile(p / 100.0));
+        }
+        assert_eq!(h.median(), h.quantile(0.5));
+    }
+
+    #[test]
+    fn mode_empty() {
+        let h = Histogram::new(5);
+        assert_eq!(h.mode(), None);
+    }
+
+    #[test]
+    fn mode_single_value() {
+        let h = Histogram::from_iter(5, [42.0, 42.0, 42.0]);
+        assert_eq!(h.mode(), Some(42.0));
+    }
+
+    #[test]
+    fn mode() {
+        // the bin with the largest count is wide, so the values in it are less
+        // probable than the ones in the narrow bin on the left
+        let bins = vec![
+            Bin::new(0.0, 5),
+            Bin::new(1.0, 5),
+            Bin::new(10.0, 6),
+            Bin::new(50.0, 1),
+        ];
+        let h = histogram_from_parts(5, bins, Some(0.0), Some(50.0));
+
+        assert_eq!(h.bin_edges(), vec![0.0, 0.5, 5.5, 30.0, 50.0]);
+        assert_eq!(h.mode(), Some(0.0));
+    }
+
+    #[test]
+    fn extend() {
+        let values = vec![
+            1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2, -6.0, -6.6, 0.5, 0.5, 2.625,
+        ];
+
+        let mut h = Histogram::new(5);
+        h.extend(values.iter().copied());
+
+        let expected = Histogram::from_iter(5, &values);
+        assert_eq!(h.count(), expected.count());
+        assert_eq!(h.min(), expected.min());
+        assert_eq!(h.max(), expected.max());
+        assert_eq!(h.bins(), expected.bins());
+    }
+
+    #[test]
+    fn extend_bins() {
+        let bins = vec![Bin::new(4.9, 6), Bin::new(5.0, 8), Bin::new(-10.0, 1)];
+
+        let mut h = Histogram::new(5);
+        h.extend(bins.clone());
+
+        assert_eq!(h.count(), 15);
+        assert_eq!(h.min(), Some(-10.0));
+        assert_eq!(h.max(), Some(5.0));
+        assert_eq!(h.bins(), &[bins[2], bins[0], bins[1]]);
+    }
+
+    #[test]
+    fn collect() {
+        let values = (1..=100).map(|v| v as f64);
+        let h: Histogram = values.clone().collect();
+        let expected = Histogram::from_iter(Histogram::DEFAULT_SIZE, values);
+
+        assert_eq!(h.size(), Histogram::DEFAULT_SIZE);
+        assert_eq!(h.count(), 100);
+        assert_eq!(h.bins(), expected.bins());
+    }
+
+    #[test]
+    fn sum_empty() {
+        let h: Histogram = Vec::<Histogram>::new().into_iter().sum();
+
+        assert_eq!(h.size(), Histogram::DEFAULT_SIZE);
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.bins(), &[]);
+    }
+
+    #[test]
+    fn sum() {
+        let values = vec![
+            1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2, -6.0, -6.6, 0.5, 0.5, 2.625,
+        ];
+        let histograms: Vec<Histogram> = values
+            .chunks(4)
+            .enumerate()
+            .map(|(i, chunk)| Histogram::from_iter(i + 1, chunk))
+            .collect();
+
+        let expected = Histogram::merge_all(4, &histograms);
+
+        let h1: Histogram = histograms.iter().sum();
+        let h2: Histogram = histograms.into_iter().sum();
+        for h in [h1, h2] {
+            // the largest size wins
+            assert_eq!(h.size(), 4);
+            assert_eq!(h.count(), values.len() as u64);
+            assert_eq!(h.min(), Some(-6.6));
+            assert_eq!(h.max(), Some(10.0));
+            assert_eq!(h.sum(), expected.sum());
+            assert_eq!(h.bins(), expected.bins());
+        }
+    }
+
+    #[test]
+    fn add() {
+        let h1 = Histogram::from_iter(5, [1.0, 0.0, -5.4, -2.1, 8.5, 10.0]);
+        let h2 = Histogram::from_iter(3, [8.6, 4.3, 7.8, 5.2]);
+
+        let mut expected = h1.clone();
+        expected.merge(&h2);
+
+        for h in [
+            &h1 + &h2,
+            h1.clone() + &h2,
+            h1.clone() + h2.clone(),
+            &h2 + &h1,
+        ] {
+            // the larger size wins regardless of the order of operands
+            assert_eq!(h.size(), 5);
+            assert_eq!(h.count(), 10);
+            assert_eq!(h.min(), Some(-5.4));
+            assert_eq!(h.max(), Some(10.0));
+            assert_eq!(h.sum(), expected.sum());
+        }
+        assert_eq!((&h1 + &h2).bins(), expected.bins());
+    }
+
+    #[test]
+    fn add_assign() {
+        let mut h = Histogram::new(2);
+        h += 42.0;
+        h += Bin::new(-7.5, 3);
+        h += Histogram::from_iter(3, [0.0, 1.0]);
+        h += &Histogram::from_iter(1, [100.0]);
+
+        assert_eq!(h.size(), 3);
+        assert_eq!(h.count(), 7);
+        assert_eq!(h.min(), Some(-7.5));
+        assert_eq!(h.max(), Some(100.0));
+        assert_eq!(h.bins().len(), 3);
+    }
+
+    #[test]
+    fn union_bins() {
+        let mut h = histogram_from_parts(
+            5,
+            vec![Bin::new(-1.0, 1), Bin::new(2.0, 2), Bin::new(5.0, 1)],
+            Some(-1.0),
+            Some(5.0),
+        );
+
+        h.union_bins(&[
+            Bin::new(-3.0, 1),
+            Bin::new(2.0, 2),
+            Bin::new(2.0, 3),
+            Bin::new(7.0, 1),
+        ]);
+        assert_eq!(
+            h.bins(),
+            &[
+                Bin::new(-3.0, 1),
+                Bin::new(-1.0, 1),
+                Bin::new(2.0, 2),
+                Bin::new(2.0, 2),
+                Bin::new(2.0, 3),
+                Bin::new(5.0, 1),
+                Bin::new(7.0, 1),
+            ]
+        );
+
+        h.union_bins(&[]);
+        assert_eq!(h.bins().len(), 7);
+    }
+
+    #[test]
+    fn shrink_many() {
+        // merging bins in bulk must give exactly the same result as merging the closest pair of
+        // bins one by one. Rounding values makes ties in distances and counts more likely
+        let policies = [
+            MergePolicy::Absolute,
+            MergePolicy::Relative,
+            MergePolicy::CountWeighted,
+        ];
+        for seed in 0..12 {
+            let policy = policies[seed as usize % policies.len()];
+            let bins: Vec<Bin> = pseudo_random_integers(seed, 300)
+                .into_iter()
+                .map(|v| Bin::new((v / 10.0).round(), 1 + v as u64 % 3))
+                .collect();
+            let mut bins_sorted: Bins = bins.into_iter().collect();
+            bins_sorted.sort();
+
+            let mut expected = Histogram::new(300);
+            expected.bins = bins_sorted.clone();
+            expected.size = 16;
+            expected.set_merge_policy(policy);
+            while expected.bins.len() > expected.size {
+                let (left, right) = expected.find_closest_bins();
+                expected.bins[left] = Bin::merge(&expected.bins[left], &expected.bins[right]);
+                expected.bins.remove(right);
+            }
+
+            let mut h = Histogram::new(300);
+            h.bins = bins_sorted;
+            h.size = 16;
+            h.set_merge_policy(policy);
+            h.shrink_many();
+
+            assert_eq!(h.bins(), expected.bins());
+        }
+    }
+
+    #[test]
+    fn merge_all_empty() {
+        let h = Histogram::merge_all(5, Vec::<Histogram>::new());
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.bins(), &[]);
+
+        let h = Histogram::merge_all(5, vec![Histogram::new(3), Histogram::new(10)]);
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.min(), None);
+        assert_eq!(h.max(), None);
+        assert_eq!(h.bins(), &[]);
+    }
+
+    #[test]
+    fn merge_all() {
+        let histograms: Vec<Histogram> = (0..20)
+            .map(|seed| Histogram::from_iter(32, pseudo_random_integers(seed, 100)))
+            .collect();
+
+        let h = Histogram::merge_all(16, &histograms);
+        assert_eq!(h.size(), 16);
+        assert_eq!(h.bins().len(), 16);
+        assert_eq!(h.count(), 2000);
+        assert_eq!(
+            h.min(),
+            histograms.iter().filter_map(|h| h.min()).reduce(f64::min)
+        );
+        assert_eq!(
+            h.max(),
+            histograms.iter().filter_map(|h| h.max()).reduce(f64::max)
+        );
+
+        // the result does not depend on the order of histograms
+        let reversed = Histogram::merge_all(16, histograms.into_iter().rev());
+        assert_eq!(reversed.bins(), h.bins());
+        assert_eq!(reversed.sum(), h.sum());
+    }
+
+    #[test]
+    fn merge_all_single() {
+        // bins are preserved as long as they fit into the new histogram
+        let h = Histogram::from_iter(32, pseudo_random_integers(42, 100));
+
+        let merged = Histogram::merge_all(64, [&h]);
+        assert_eq!(merged.size(), 64);
+        assert_eq!(merged.count(), h.count());
+        assert_eq!(merged.min(), h.min());
+        assert_eq!(merged.max(), h.max());
+        assert_eq!(merged.sum(), h.sum());
+        assert_eq!(merged.bins(), h.bins());
+    }
+
+    #[test]
+    fn insert_many() {
+        let values = pseudo_random_integers(42, 1000);
+
+        let mut h = Histogram::new(16);
+        h.insert_many(values.iter().copied());
+
+        // a single batch is merged with the bins of the histogram, and then the histogram
+        // is shrunk once
+        let mut bins: Vec<Bin> = values.iter().map(|v| Bin::from(*v)).collect();
+        bins.sort();
+        let (min_value, max_value) = (
+            bins.first().map(|bin| bin.value()),
+            bins.last().map(|bin| bin.value()),
+        );
+        let expected = histogram_from_parts(16, bins, min_value, max_value);
+
+        assert_eq!(h.size(), 16);
+        assert_eq!(h.count(), 1000);
+        assert_eq!(h.min(), values.iter().copied().reduce(f64::min));
+        assert_eq!(h.max(), values.iter().copied().reduce(f64::max));
+        assert_eq!(h.sum(), Histogram::from_iter(16, &values).sum());
+        assert_eq!(h.bins(), expected.bins());
+    }
+
+    #[test]
+    fn insert_many_batches() {
+        let values = pseudo_random_integers(42, 3 * Histogram::INSERT_MANY_BATCH_SIZE + 1);
+
+        let mut h = Histogram::new(16);
+        h.insert_many(values.iter().copied());
+
+        assert_eq!(h.bins().len(), 16);
+        assert_eq!(h.count(), values.len() as u64);
+        assert_eq!(h.min(), values.iter().copied().reduce(f64::min));
+        assert_eq!(h.max(), values.iter().copied().reduce(f64::max));
+    }
+
+    #[test]
+    fn insert_many_empty() {
+        let mut h = Histogram::from_iter(5, [1.0, 2.0]);
+        h.insert_many(Vec::<f64>::new());
+
+        assert_eq!(h.count(), 2);
+        assert_eq!(h.bins(), &[Bin::new(1.0, 1), Bin::new(2.0, 1)]);
+    }
+
+    #[test]
+    fn from_sorted_iter() {
+        let mut values = pseudo_random_integers(42, 1000);
+        values.sort_by(f64::total_cmp);
+
+        let h = Histogram::from_sorted_iter(16, &values);
+        let expected = Histogram::from_iter(16, &values);
+
+        assert_eq!(h.size(), 16);
+        assert_eq!(h.bins().len(), 16);
+        assert_eq!(h.count(), 1000);
+        assert_eq!(h.min(), expected.min());
+        assert_eq!(h.max(), expected.max());
+        assert_eq!(h.sum(), expected.sum());
+        for q in [0.1, 0.25, 0.5, 0.75, 0.9] {
+            assert_relative_eq!(
+                h.quantile(q).unwrap(),
+                expected.quantile(q).unwrap(),
+                max_relative = 0.1
+            );
+        }
+    }
+
+    #[test]
+    fn from_sorted_iter_small() {
+        // values are preserved as long as they fit into the histogram
+        let h = Histogram::from_sorted_iter(5, [-1.0, 0.0, 0.0, 2.5]);
+
+        assert_eq!(h.count(), 4);
+        assert_eq!(
+            h.bins(),
+            &[
+                Bin::new(-1.0, 1),
+                Bin::new(0.0, 1),
+                Bin::new(0.0, 1),
+                Bin::new(2.5, 1)
+            ]
+        );
+    }
 
-        let mut h = Histogram {
-            size,
-            bins,
-            min_value,
-            max_value,
-        };
-        h.shrink();
-        h.bins.shrink_to_fit();
+    #[test]
+    #[should_panic(expected = "values must be sorted in the ascending order")]
+    fn from_sorted_iter_unsorted() {
+        Histogram::from_sorted_iter(5, [1.0, 0.0]);
+    }
 
-        h
+    #[test]
+    #[cfg(feature = "smallvec")]
+    fn inline_bins() {
+        let h = Histogram::from_iter(32, pseudo_random_integers(0, 1000));
+        assert_eq!(h.bins().len(), 32);
+        assert!(!h.bins.spilled());
+
+        let h = Histogram::from_iter(64, pseudo_random_integers(0, 1000));
+        assert!(h.bins.spilled());
     }
 
     #[test]
-    fn new() {
-        let h = Histogram::new(5);
-        assert_eq!(h.size(), 5);
-        assert_eq!(h.count(), 0);
+    fn memory_usage() {
+        let struct_size = std::mem::size_of::<Histogram>();
+        let bin_size = std::mem::size_of::<Bin>();
+
+        let h = Histogram::new(64);
+        assert_eq!(h.memory_usage(), struct_size + 65 * bin_size);
+
+        // merge_all() releases the memory used for buffering bins
+        let h = Histogram::from_iter(64, pseudo_random_integers(0, 1000));
+        let h = Histogram::merge_all(64, vec![h.clone(), h.clone(), h]);
+        assert_eq!(h.memory_usage(), struct_size + 65 * bin_size);
+
+        let h = Histogram::new(1);
+        #[cfg(feature = "smallvec")]
+        assert_eq!(h.memory_usage(), struct_size);
+        #[cfg(not(feature = "smallvec"))]
+        assert_eq!(h.memory_usage(), struct_size + 2 * bin_size);
+    }
+
+    #[test]
+    fn insert_large() {
+        // keeping track of gaps between bins must give exactly the same result as scanning
+        // the bins. Rounding values makes ties in distances and counts more likely
+        let policies = [
+            MergePolicy::Absolute,
+            MergePolicy::Relative,
+            MergePolicy::CountWeighted,
+        ];
+        for seed in 0..6 {
+            let mut h = Histogram::new(Histogram::GAPS_MIN_SIZE);
+            h.set_merge_policy(policies[seed as usize % policies.len()]);
+            let mut expected = h.clone();
+            let values = pseudo_random_integers(seed, 5000)
+                .into_iter()
+                .map(|v| (v / 10.0).round())
+                // monotonic runs of values
+                .chain((0..1000).map(|v| v as f64))
+                .chain((0..1000).map(|v| -v as f64));
+            for (i, value) in values.enumerate() {
+                h.insert(value);
+
+                let bin = Bin::from(value);
+                expected
+                    .bins
+                    .insert(expected.bins.partition_point(|b| *b <= bin), bin);
+                while expected.bins.len() > expected.size {
+                    let (left, right) = expected.find_closest_bins();
+                    expected.bins[left] = Bin::merge(&expected.bins[left], &expected.bins[right]);
+                    expected.bins.remove(right);
+                }
+                assert_eq!(h.bins(), expected.bins(), "seed = {}, i = {}", seed, i);
+
+                if i % 1000 == 999 {
+                    // bulk updates invalidate the gaps
+                    let other =
+                        Histogram::from_iter(16, pseudo_random_integers(seed + i as u64, 100));
+                    h.merge(&other);
+                    expected.merge(&other);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn count_matches_bins() {
+        let bins_count = |h: &Histogram| h.bins().iter().map(|bin| bin.count()).sum::<u64>();
+
+        let mut h = Histogram::from_sorted_iter(8, (0..100).map(|v| v as f64));
+        assert_eq!(h.count(), 100);
+        assert_eq!(h.count(), bins_count(&h));
+
+        h.insert(Bin::new(5.0, 10));
+        h.insert_many(pseudo_random_integers(0, 100));
+        h.merge(&Histogram::from_iter(4, pseudo_random_integers(1, 50)));
+        assert_eq!(h.count(), 260);
+        assert_eq!(h.count(), bins_count(&h));
+
+        let h = Histogram::merge_all(3, vec![h.clone(), h]);
+        assert_eq!(h.count(), 520);
+        assert_eq!(h.count(), bins_count(&h));
+    }
+
+    #[test]
+    fn try_insert() {
+        let mut h = Histogram::new(5);
+        assert_eq!(h.nan_policy(), NanPolicy::Error);
+        assert_eq!(h.try_insert(1.0), Ok(()));
+        assert_eq!(h.try_insert(f64::NAN), Err(Error::NanValue));
+        assert_eq!(h.count(), 1);
+        assert_eq!(h.nan_count(), 0);
+
+        h.set_nan_policy(NanPolicy::Ignore);
+        assert_eq!(h.try_insert(f64::NAN), Ok(()));
+        assert_eq!(h.count(), 1);
+        assert_eq!(h.nan_count(), 0);
+
+        h.set_nan_policy(NanPolicy::Count);
+        assert_eq!(h.try_insert(f64::NAN), Ok(()));
+        assert_eq!(h.try_insert(2.0), Ok(()));
+        assert_eq!(h.count(), 2);
+        assert_eq!(h.nan_count(), 1);
+        assert_eq!(h.bins(), &[Bin::new(1.0, 1), Bin::new(2.0, 1)]);
+    }
+
+    #[test]
+    fn merge_nan_count() {
+        let mut h1 = Histogram::new(5);
+        h1.set_nan_policy(NanPolicy::Count);
+        h1.try_insert(f64::NAN).unwrap();
+
+        let mut h2 = h1.clone();
+        h2.try_insert(f64::NAN).unwrap();
+
+        assert_eq!(Histogram::merge_all(5, vec![&h1, &h2]).nan_count(), 3);
+        h1.merge(&h2);
+        assert_eq!(h1.nan_count(), 3);
+    }
+
+    #[test]
+    fn try_insert_infinity() {
+        let mut h = Histogram::new(5);
+        assert_eq!(h.infinity_policy(), InfinityPolicy::Error);
+        assert_eq!(h.try_insert(f64::INFINITY), Err(Error::InfiniteValue));
+        assert_eq!(h.try_insert(f64::NEG_INFINITY), Err(Error::InfiniteValue));
+
+        // there is nothing to clamp to yet
+        h.set_infinity_policy(InfinityPolicy::Clamp);
+        assert_eq!(h.try_insert(f64::INFINITY), Err(Error::InfiniteValue));
+
+        h.try_insert(1.0).unwrap();
+        h.try_insert(3.0).unwrap();
+        h.try_insert(f64::INFINITY).unwrap();
+        h.try_insert(f64::NEG_INFINITY).unwrap();
+        assert_eq!(
+            h.bins(),
+            &[
+                Bin::new(1.0, 1),
+                Bin::new(1.0, 1),
+                Bin::new(3.0, 1),
+                Bin::new(3.0, 1)
+            ]
+        );
+        assert_eq!(h.min(), Some(1.0));
+        assert_eq!(h.max(), Some(3.0));
+
+        h.set_infinity_policy(InfinityPolicy::Count);
+        h.try_insert(f64::INFINITY).unwrap();
+        h.try_insert(f64::INFINITY).unwrap();
+        h.try_insert(f64::NEG_INFINITY).unwrap();
+        assert_eq!(h.count(), 4);
+        assert_eq!(h.overflow_count(), 2);
+        assert_eq!(h.underflow_count(), 1);
+
+        let mut other = Histogram::new(5);
+        other.merge(&h);
+        other.merge(&h);
+        assert_eq!(other.overflow_count(), 4);
+        assert_eq!(other.underflow_count(), 2);
+        let other = Histogram::merge_all(5, vec![&h, &h, &h]);
+        assert_eq!(other.overflow_count(), 6);
+        assert_eq!(other.underflow_count(), 3);
+    }
+
+    #[test]
+    fn min_max_tracking() {
+        let mut h = Histogram::new(3);
+        assert!(h.min_max_tracking());
+
+        h.set_min_max_tracking(false);
+        assert!(!h.min_max_tracking());
         assert_eq!(h.min(), None);
         assert_eq!(h.max(), None);
-        assert_eq!(h.bins(), &[]);
+        assert_eq!(h.quantile(0.5), None);
+
+        h.set_min_max_tracking(true);
+        h.insert(1.0);
+        assert_eq!(h.min(), Some(1.0));
+
+        h.set_min_max_tracking(false);
+        for value in [0.0, 1.0, 2.0, 3.0, 4.0, 5.0] {
+            h.insert(value);
+        }
+        assert_eq!(h.count(), 7);
+        assert_relative_eq!(h.min().unwrap(), 2.0 / 3.0);
+        assert_eq!(h.max(), Some(4.5));
+        assert_eq!(h.quantile(0.0), h.min());
+        assert_eq!(h.quantile(1.0), h.max());
+        assert_eq!(h.count_less_than_or_equal_to(0.0), 0);
+        assert_eq!(h.count_less_than_or_equal_to(5.0), 7);
+
+        // the values of the outermost bins are used when merging histograms
+        let mut other = Histogram::new(3);
+        other.merge(&h);
+        assert_eq!(other.min(), h.min());
+        assert_eq!(other.max(), h.max());
+
+        // clearing the histogram keeps tracking disabled, after which it can be re-enabled
+        h.clear();
+        assert!(!h.min_max_tracking());
+        h.set_min_max_tracking(true);
+        h.insert(1.0);
+        assert_eq!(h.min(), Some(1.0));
     }
 
     #[test]
-    #[should_panic(expected = "histogram size must be greater than 0")]
-    fn new_invalid_size() {
-        Histogram::new(0);
+    #[should_panic(expected = "min/max tracking can only be enabled for an empty histogram")]
+    fn min_max_tracking_enable_non_empty() {
+        let mut h = Histogram::new(3);
+        h.set_min_max_tracking(false);
+        h.insert(1.0);
+        h.set_min_max_tracking(true);
     }
 
     #[test]
-    fn insert() {
-        let values = vec![
-            1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2, -6.0, -6.6, 0.5, 0.5, 2.625,
-        ];
-        let expected_bins = vec![
-            Bin::new(-6.0, 3),
-            Bin::new(-2.1, 1),
-            Bin::new(0.5, 4),
-            Bin::new(4.041666666666667, 3),
-            Bin::new(8.725, 4),
+    fn min_gap() {
+        let mut h = Histogram::new(5);
+        assert_eq!(h.min_gap(), 0.0);
+
+        // a long run of nearly identical values followed by outliers
+        h.set_min_gap(1.0);
+        for i in 0..100 {
+            h.insert(10.0 + i as f64 / 1000.0);
+        }
+        h.insert(100.0);
+        h.insert(1000.0);
+        assert_eq!(h.bins().len(), 3);
+        assert_eq!(h.count(), 102);
+        assert_eq!(h.bins()[1], Bin::new(100.0, 1));
+        assert_eq!(h.bins()[2], Bin::new(1000.0, 1));
+
+        // the histogram is still shrunk down to its size
+        for value in [20.0, 30.0, 40.0, 50.0] {
+            h.insert(value);
+        }
+        assert_eq!(h.bins().len(), 5);
+    }
+
+    #[test]
+    fn min_gap_large() {
+        let mut h = Histogram::new(256);
+        h.set_min_gap(10.0);
+        for value in pseudo_random_integers(42, 10_000) {
+            h.insert(value);
+        }
+
+        assert!(h.bins().len() < 256);
+        assert_eq!(h.count(), 10_000);
+        assert!(h
+            .bins()
+            .windows(2)
+            .all(|pair| pair[1].value() - pair[0].value() >= 10.0));
+    }
+
+    #[test]
+    fn set_min_gap() {
+        let mut h = Histogram::from_iter(5, [1.0, 1.5, 3.0, 10.0]);
+        h.set_min_gap(1.0);
+        assert_eq!(
+            h.bins(),
+            &[Bin::new(1.25, 2), Bin::new(3.0, 1), Bin::new(10.0, 1)]
+        );
+    }
+
+    #[test]
+    fn set_min_gap_large() {
+        // merging bins, which are closer than the minimum gap, must give exactly the same
+        // result as scanning the bins, and the gaps must be kept up to date afterwards
+        let mut h = Histogram::from_iter(256, pseudo_random_integers(42, 10_000));
+        let mut expected = h.clone();
+        h.set_min_gap(10.0);
+
+        let scan = |expected: &mut Histogram| {
+            while expected.may_need_merge() {
+                let (left, right) = expected.find_closest_bins();
+                if !expected.needs_merge(left, right) {
+                    break;
+                }
+                expected.bins[left] = Bin::merge(&expected.bins[left], &expected.bins[right]);
+                expected.bins.remove(right);
+            }
+        };
+        expected.min_gap = 10.0;
+        scan(&mut expected);
+        assert!(h.bins().len() < 256);
+        assert_eq!(h.bins(), expected.bins());
+
+        for value in pseudo_random_integers(43, 1000) {
+            h.insert(value);
+            let bin = Bin::from(value);
+            expected
+                .bins
+                .insert(expected.bins.partition_point(|b| *b <= bin), bin);
+            scan(&mut expected);
+            assert_eq!(h.bins(), expected.bins());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "minimum gap must be finite and non-negative")]
+    fn set_min_gap_invalid() {
+        Histogram::new(5).set_min_gap(-1.0);
+    }
+
+    #[test]
+    fn merge_policy() {
+        let values = [1.0, 2.0, 100.0, 120.0, 1000.0];
+        let mut h = Histogram::new(4);
+        assert_eq!(h.merge_policy(), MergePolicy::Absolute);
+        h.extend(values);
+        assert_eq!(h.bins()[0], Bin::new(1.5, 2));
+
+        h = Histogram::new(4);
+        h.set_merge_policy(MergePolicy::Relative);
+        h.extend(values);
+        assert_eq!(h.merge_policy(), MergePolicy::Relative);
+        assert_eq!(h.bins()[2], Bin::new(110.0, 2));
+
+        // densely populated bins are kept apart
+        let bins = [
+            Bin::new(0.0, 100),
+            Bin::new(1.0, 100),
+            Bin::new(10.0, 1),
+            Bin::new(12.0, 1),
+            Bin::new(100.0, 1),
         ];
+        h = Histogram::new(4);
+        h.extend(bins);
+        assert_eq!(h.bins()[0], Bin::new(0.5, 200));
 
-        let mut h = Histogram::new(5);
-        for v in &values {
-            h.insert(*v);
+        h = Histogram::new(4);
+        h.set_merge_policy(MergePolicy::CountWeighted);
+        h.extend(bins);
+        assert_eq!(h.bins()[2], Bin::new(11.0, 2));
+    }
+
+    #[test]
+    fn from_iter_pairs() {
+        let pairs = [(1.0, 10), (2.5, 40), (2.5, 5), (7.0, 1)];
+        let bins: Vec<Bin> = pairs.iter().map(Bin::from).collect();
+        let expected = Histogram::from_iter(3, &bins);
+
+        for h in [
+            Histogram::from_iter(3, pairs),
+            Histogram::from_iter(3, pairs.iter()),
+            Histogram::from_sorted_iter(3, pairs),
+            pairs.iter().collect(),
+        ] {
+            assert_eq!(h.count(), 56);
+            assert_eq!(h.sum(), expected.sum());
+            assert_eq!(h.min(), Some(1.0));
+            assert_eq!(h.max(), Some(7.0));
+        }
+        assert_eq!(Histogram::from_iter(3, pairs).bins(), expected.bins());
+    }
+
+    #[test]
+    fn from_iter_integers() {
+        let values = [1u64, 512, 4096, 17, 0, 100_000];
+        let expected = Histogram::from_iter(4, values.iter().map(|&v| v as f64));
+
+        let mut h = Histogram::from_iter(4, values);
+        assert_eq!(h.bins(), expected.bins());
+        assert_eq!(h.sum(), expected.sum());
+
+        h.insert(5u8);
+        h.extend([-1i32, -2]);
+        assert_eq!(h.count(), 9);
+        assert_eq!(h.min(), Some(-2.0));
+    }
+
+    #[test]
+    fn subtract() {
+        let values = pseudo_random_integers(42, 2000);
+        let mut h = Histogram::new(32);
+        h.extend(&values[..1000]);
+        let before = h.clone();
+        h.extend(&values[1000..]);
+
+        let delta = h.subtract(&before);
+        let expected = Histogram::from_iter(32, &values[1000..]);
+        assert_eq!(delta.size(), 32);
+        assert_eq!(delta.count(), 1000);
+        assert_eq!(
+            delta.bins().iter().map(|bin| bin.count()).sum::<u64>(),
+            1000
+        );
+        assert_relative_eq!(delta.sum(), expected.sum(), max_relative = 1e-12);
+        assert!(delta.bins().windows(2).all(|pair| pair[0] < pair[1]));
+        for q in [0.1, 0.25, 0.5, 0.75, 0.9] {
+            let (actual, expected) = (delta.quantile(q).unwrap(), expected.quantile(q).unwrap());
+            assert!(
+                (actual - expected).abs() < 50.0,
+                "{} != {}",
+                actual,
+                expected
+            );
         }
 
-        assert_eq!(h.count(), values.len() as u64);
-        assert_eq!(h.size(), 5);
-        assert_eq!(h.min(), Some(-6.6));
-        assert_eq!(h.max(), Some(10.0));
-        assert_eq!(h.bins(), expected_bins.as_slice());
+        // nothing is left after subtracting a histogram from itself
+        let empty = h.subtract(&h);
+        assert_eq!(empty.count(), 0);
+        assert_eq!(empty.bins(), &[]);
+        assert_eq!(empty.sum(), 0.0);
+        assert_eq!(empty.min(), None);
+        assert_eq!(empty.max(), None);
     }
 
     #[test]
-    fn insert_single_value() {
+    fn subtract_disjoint() {
         let mut h = Histogram::new(5);
-        h.insert(42.0);
+        h.extend([1.0, 2.0, 10.0, 11.0]);
+        let before = h.clone();
+        h.extend([100.0, 200.0]);
+        h.try_insert(f64::NAN).ok();
+
+        let delta = h.subtract(&before);
+        assert_eq!(delta.bins(), &[Bin::new(100.0, 1), Bin::new(200.0, 1)]);
+        assert_eq!(delta.min(), Some(100.0));
+        assert_eq!(delta.max(), Some(200.0));
+        assert_eq!(delta.sum(), 300.0);
+
+        // counts never go below zero
+        let reverse = before.subtract(&h);
+        assert_eq!(reverse.count(), 0);
+        assert_eq!(reverse.bins(), &[]);
+    }
+
+    #[test]
+    fn scale_counts() {
+        let mut h = Histogram::from_iter(5, [(1.0, 1), (2.0, 1), (3.0, 1), (4.0, 1), (5.0, 6)]);
+        h.try_insert(f64::NAN).ok();
+        h.scale_counts(3.0);
+        assert_eq!(h.count(), 30);
+        assert_eq!(h.sum(), 3.0 * 40.0);
+        assert_eq!(h.bins()[4], Bin::new(5.0, 18));
+
+        // rounding errors do not accumulate
+        h.scale_counts(0.5);
+        assert_eq!(h.count(), 15);
+        assert_eq!(
+            h.bins(),
+            &[
+                Bin::new(1.0, 2),
+                Bin::new(2.0, 1),
+                Bin::new(3.0, 2),
+                Bin::new(4.0, 1),
+                Bin::new(5.0, 9),
+            ]
+        );
+        assert_eq!(h.min(), Some(1.0));
+        assert_eq!(h.max(), Some(5.0));
+
+        // bins with small counts disappear
+        h.scale_counts(0.1);
+        assert_eq!(h.count(), 2);
+        assert_eq!(h.bins(), &[Bin::new(3.0, 1), Bin::new(5.0, 1)]);
+        assert_eq!(h.min(), Some(3.0));
+        assert_eq!(h.max(), Some(5.0));
+
+        h.scale_counts(0.0);
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.bins(), &[]);
+        assert_eq!(h.sum(), 0.0);
+        assert_eq!(h.min(), None);
+        assert_eq!(h.quantile(0.5), None);
 
+        // the histogram is usable after scaling
+        h.insert(1.0);
         assert_eq!(h.count(), 1);
-        assert_eq!(h.size(), 5);
-        assert_eq!(h.min(), Some(42.0));
-        assert_eq!(h.max(), Some(42.0));
-        assert_eq!(h.bins(), &[Bin::new(42.0, 1)]);
     }
 
     #[test]
-    fn insert_bin() {
-        let bins = vec![
-            Bin::new(4.9, 6),
-            Bin::new(5.0, 8),
-            Bin::new(20.1, 7),
-            Bin::new(4.0, 8),
-            Bin::new(42.0, 14),
-            Bin::new(17.4, 4),
-            Bin::new(-10.0, 1),
-        ];
-        let expected_bins = vec![
-            Bin::new(-10.0, 1),
-            Bin::new(4.609090909090909, 22),
-            Bin::new(17.4, 4),
-            Bin::new(20.1, 7),
-            Bin::new(42.0, 14),
-        ];
+    #[should_panic(expected = "scale factor must be finite and non-negative")]
+    fn scale_counts_negative() {
+        Histogram::new(5).scale_counts(-1.0);
+    }
 
-        let mut h = Histogram::new(5);
-        for bin in bins {
-            h.insert(bin);
+    #[test]
+    fn map_values() {
+        let values = [1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2];
+        let mut h = Histogram::from_iter(5, values);
+        h.map_values(2.0, -1.0);
+
+        let expected = Histogram::from_iter(5, values.iter().map(|v| v * 2.0 - 1.0));
+        assert_eq!(h.count(), expected.count());
+        assert_eq!(h.min(), expected.min());
+        assert_eq!(h.max(), expected.max());
+        assert_relative_eq!(h.sum(), expected.sum());
+        for (bin, expected) in h.bins().iter().zip(expected.bins()) {
+            assert_relative_eq!(bin.value(), expected.value());
+            assert_eq!(bin.count(), expected.count());
+        }
+
+        // the histogram is usable after the transformation
+        h.insert(0.5);
+        assert_eq!(h.count(), 11);
+    }
+
+    #[test]
+    fn map_values_negative_scale() {
+        let mut h = Histogram::from_iter(5, [(1.0, 1), (2.0, 2), (4.0, 3)]);
+        h.set_infinity_policy(InfinityPolicy::Count);
+        h.try_insert(f64::INFINITY).unwrap();
+        h.map_values(-1.0, 0.0);
+
+        assert_eq!(
+            h.bins(),
+            &[Bin::new(-4.0, 3), Bin::new(-2.0, 2), Bin::new(-1.0, 1)]
+        );
+        assert_eq!(h.min(), Some(-4.0));
+        assert_eq!(h.max(), Some(-1.0));
+        assert_eq!(h.sum(), -17.0);
+        assert_eq!(h.underflow_count(), 1);
+        assert_eq!(h.overflow_count(), 0);
+        assert_eq!(h.quantile(0.0), Some(-4.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "scale must be finite and non-zero")]
+    fn map_values_zero_scale() {
+        Histogram::new(5).map_values(0.0, 1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "value must be finite")]
+    fn map_values_overflow() {
+        Histogram::from_iter(5, [1e300]).map_values(1e300, 0.0);
+    }
+
+    #[test]
+    fn split_at() {
+        let values = pseudo_random_integers(42, 1000);
+        let h = Histogram::from_iter(16, &values);
+        for threshold in [-1.0, 0.0, 100.0, 500.0, 999.0, 1000.0, f64::INFINITY] {
+            let (left, right) = h.split_at(threshold);
+            assert_eq!(left.count(), h.count_less_than_or_equal_to(threshold));
+            assert_eq!(left.count() + right.count(), h.count());
+            assert_relative_eq!(left.sum() + right.sum(), h.sum(), max_relative = 1e-12);
+            assert!(left.bins().iter().all(|bin| bin.value() <= threshold));
+            assert!(right.bins().iter().all(|bin| bin.value() >= threshold));
+            assert!(left.bins().windows(2).all(|pair| pair[0] <= pair[1]));
+            assert!(right.bins().windows(2).all(|pair| pair[0] <= pair[1]));
+            assert_eq!(left.size(), 16);
+            assert_eq!(right.size(), 16);
+        }
+
+        let (left, right) = h.split_at(-1.0);
+        assert_eq!(left.count(), 0);
+        assert_eq!(left.min(), None);
+        assert_eq!(left.sum(), 0.0);
+        assert_eq!(right.bins(), h.bins());
+        assert_eq!(right.min(), h.min());
+        assert_eq!(right.max(), h.max());
+    }
+
+    #[test]
+    fn split_at_settings() {
+        let mut h = Histogram::from_iter(5, [1.0, 2.0, 3.0, 10.0]);
+        h.set_infinity_policy(InfinityPolicy::Count);
+        h.set_merge_policy(MergePolicy::Relative);
+        h.try_insert(f64::INFINITY).unwrap();
+        h.try_insert(f64::NEG_INFINITY).unwrap();
+
+        let (left, right) = h.split_at(5.0);
+        assert_eq!(
+            left.bins(),
+            &[Bin::new(1.0, 1), Bin::new(2.0, 1), Bin::new(3.0, 1)]
+        );
+        assert_eq!(left.min(), Some(1.0));
+        // the exact boundaries of both parts are unknown, so the threshold is used instead
+        assert_eq!(left.max(), Some(5.0));
+        assert_eq!(left.underflow_count(), 1);
+        assert_eq!(left.overflow_count(), 0);
+        assert_eq!(right.bins(), &[Bin::new(10.0, 1)]);
+        assert_eq!(right.min(), Some(5.0));
+        assert_eq!(right.max(), Some(10.0));
+        assert_eq!(right.underflow_count(), 0);
+        assert_eq!(right.overflow_count(), 1);
+        assert_eq!(right.merge_policy(), MergePolicy::Relative);
+        assert_eq!(right.infinity_policy(), InfinityPolicy::Count);
+    }
+
+    #[test]
+    #[should_panic(expected = "value must not be NaN")]
+    fn split_at_nan() {
+        Histogram::new(5).split_at(f64::NAN);
+    }
+
+    #[test]
+    fn restrict() {
+        let values = pseudo_random_integers(42, 1000);
+        let h = Histogram::from_iter(16, &values);
+        for (low, high) in [
+            (f64::NEG_INFINITY, f64::INFINITY),
+            (100.0, 200.0),
+            (500.0, 500.0),
+            (-10.0, 10.0),
+            (900.0, 2000.0),
+        ] {
+            let restricted = h.restrict(low, high);
+            let (_, above) = h.split_at(high);
+            let below = h.count_up_to(low, false, None).round() as u64;
+            assert_eq!(
+                restricted.count(),
+                h.count() - above.count() - below.min(h.count() - above.count())
+            );
+            assert!(restricted
+                .bins()
+                .iter()
+                .all(|bin| (low..=high).contains(&bin.value())));
+            if let (Some(min), Some(max)) = (restricted.min(), restricted.max()) {
+                assert!(low <= min && min <= max && max <= high);
+            }
+        }
+
+        let all = h.restrict(f64::NEG_INFINITY, f64::INFINITY);
+        assert_eq!(all.bins(), h.bins());
+        assert_eq!(all.min(), h.min());
+        assert_eq!(all.max(), h.max());
+
+        let none = h.restrict(-10.0, -1.0);
+        assert_eq!(none.count(), 0);
+        assert_eq!(none.min(), None);
+        assert_eq!(none.quantile(0.5), None);
+    }
+
+    #[test]
+    fn restrict_exact() {
+        let h = Histogram::from_iter(10, [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let restricted = h.restrict(1.5, 4.5);
+        assert_eq!(
+            restricted.bins(),
+            &[Bin::new(2.0, 1), Bin::new(3.0, 1), Bin::new(4.0, 1)]
+        );
+        assert_eq!(restricted.min(), Some(1.5));
+        assert_eq!(restricted.max(), Some(4.5));
+        assert_eq!(restricted.sum(), 9.0);
+
+        // half of the values of a bin are assumed to be to the left of its center
+        let restricted = h.restrict(2.0, 4.0);
+        assert_eq!(restricted.bins(), &[Bin::new(3.0, 1), Bin::new(4.0, 1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "low must not be greater than high")]
+    fn restrict_invalid_range() {
+        Histogram::new(5).restrict(2.0, 1.0);
+    }
+
+    #[test]
+    fn resize() {
+        let values = pseudo_random_integers(42, 10_000);
+        let mut h = Histogram::from_iter(128, &values);
+        let original = h.clone();
+
+        h.resize(16);
+        assert_eq!(h.size(), 16);
+        assert_eq!(h.bins().len(), 16);
+        assert_eq!(h.count(), original.count());
+        assert_eq!(h.sum(), original.sum());
+        assert_eq!(h.min(), original.min());
+        assert_eq!(h.max(), original.max());
+        for q in [0.1, 0.5, 0.9] {
+            let (actual, expected) = (h.quantile(q).unwrap(), original.quantile(q).unwrap());
+            assert!(
+                (actual - expected).abs() < 20.0,
+                "{} != {}",
+                actual,
+                expected
+            );
+        }
+
+        // the closest pair of bins is merged first
+        let mut expected = original.clone();
+        expected.size = 16;
+        while expected.bins.len() > expected.size {
+            let (left, right) = expected.find_closest_bins();
+            expected.bins[left] = Bin::merge(&expected.bins[left], &expected.bins[right]);
+            expected.bins.remove(right);
         }
+        assert_eq!(h.bins(), expected.bins());
+
+        // growing keeps the bins, but more of them are kept after future updates
+        h.resize(32);
+        assert_eq!(h.size(), 32);
+        assert_eq!(h.bins(), expected.bins());
+        h.extend(&values[..100]);
+        assert_eq!(h.bins().len(), 32);
+
+        let resized = original.resized(8);
+        assert_eq!(resized.bins().len(), 8);
+        assert_eq!(original.bins().len(), 128);
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram size must be greater than 0")]
+    fn resize_invalid_size() {
+        Histogram::new(5).resize(0);
+    }
+
+    #[test]
+    fn clear() {
+        let values = pseudo_random_integers(42, 1000);
+        let mut h = Histogram::new(Histogram::GAPS_MIN_SIZE);
+        h.set_nan_policy(NanPolicy::Count);
+        h.set_merge_policy(MergePolicy::Relative);
+        h.extend(&values);
+        h.try_insert(f64::NAN).unwrap();
+        assert!(!h.is_empty());
+
+        let capacity = h.bins.capacity();
+        h.clear();
+        assert!(h.is_empty());
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.nan_count(), 0);
+        assert_eq!(h.sum(), 0.0);
+        assert_eq!(h.min(), None);
+        assert_eq!(h.max(), None);
+        assert_eq!(h.bins(), &[]);
+        assert_eq!(h.bins.capacity(), capacity);
+        assert_eq!(h.size(), Histogram::GAPS_MIN_SIZE);
+        assert_eq!(h.nan_policy(), NanPolicy::Count);
+        assert_eq!(h.merge_policy(), MergePolicy::Relative);
+
+        // a cleared histogram behaves the same as a new one
+        h.extend(&values);
+        let mut expected = Histogram::new(Histogram::GAPS_MIN_SIZE);
+        expected.set_merge_policy(MergePolicy::Relative);
+        expected.extend(&values);
+        assert_eq!(h.bins(), expected.bins());
+        assert_eq!(h.count(), expected.count());
+        assert_eq!(h.min(), expected.min());
+        assert_eq!(h.max(), expected.max());
+    }
+
+    #[test]
+    fn decay() {
+        let mut h = Histogram::from_iter(5, [(1.0, 3), (2.0, 4), (3.0, 3)]);
+        h.set_nan_policy(NanPolicy::Count);
+        h.try_insert(f64::NAN).unwrap();
+        h.decay(1.0);
+        assert_eq!(h.count(), 10);
+        assert_eq!(h.nan_count(), 1);
+
+        // cumulative counts 1.5, 3.5, 5 are rounded down
+        h.decay(0.5);
+        assert_eq!(
+            h.bins(),
+            &[Bin::new(1.0, 1), Bin::new(2.0, 2), Bin::new(3.0, 2)]
+        );
+        assert_eq!(h.count(), 5);
+        assert_eq!(h.sum(), 11.0);
+        assert_eq!(h.nan_count(), 0);
+        assert_eq!(h.min(), Some(1.0));
+        assert_eq!(h.max(), Some(3.0));
 
-        assert_eq!(h.count(), 48);
-        assert_eq!(h.size(), 5);
-        assert_eq!(h.min(), Some(-10.0));
-        assert_eq!(h.max(), Some(42.0));
-        assert_eq!(h.bins(), expected_bins.as_slice());
+        // repeated decay makes progress even for small factors
+        let mut steps = 0;
+        while !h.is_empty() {
+            h.decay(0.99);
+            steps += 1;
+        }
+        assert_eq!(steps, 5);
+        assert_eq!(h.sum(), 0.0);
+        assert_eq!(h.min(), None);
     }
 
     #[test]
-    fn insert_single_bin() {
-        let mut h = Histogram::new(5);
-        h.insert(Bin::new(42.0, 84));
+    #[should_panic(expected = "decay factor must be in the range (0.0; 1.0]")]
+    fn decay_invalid_factor() {
+        Histogram::new(5).decay(1.5);
+    }
 
-        assert_eq!(h.count(), 84);
-        assert_eq!(h.size(), 5);
-        assert_eq!(h.min(), Some(42.0));
-        assert_eq!(h.max(), Some(42.0));
-        assert_eq!(h.bins(), &[Bin::new(42.0, 84)]);
+    #[test]
+    fn compressed() {
+        let h = Histogram::from_iter(32, pseudo_random_integers(42, 1000));
+        for n in [1, 2, 8, 31, 32, 100] {
+            let bins = h.compressed(n);
+            assert_eq!(bins.len(), n.min(32));
+            assert_eq!(bins.iter().map(|bin| bin.count()).sum::<u64>(), 1000);
+            assert_eq!(bins, h.resized(n).bins());
+        }
+        assert_eq!(h.bins().len(), 32);
     }
 
     #[test]
-    fn merge() {
-        let bins1 = vec![
-            Bin::new(-6.0, 3),
-            Bin::new(-2.1, 1),
-            Bin::new(0.5, 4),
-            Bin::new(4.041666666666667, 3),
-            Bin::new(8.725, 4),
-        ];
-        let mut h1 = histogram_from_parts(5, bins1, Some(-6.6), Some(10.0));
+    #[should_panic(expected = "number of bins must be greater than 0")]
+    fn compressed_invalid_size() {
+        Histogram::new(5).compressed(0);
+    }
 
-        let bins2 = vec![
-            Bin::new(33.32588794226721, 9977),
-            Bin::new(1255.8137647058825, 17),
-            Bin::new(3364.983, 2),
-            Bin::new(5361.3435, 2),
-            Bin::new(7349.9465, 2),
-        ];
-        let h2 = histogram_from_parts(5, bins2, Some(9.48), Some(7829.851));
+    #[test]
+    fn to_buckets() {
+        let h = Histogram::new(5);
+        assert_eq!(h.to_buckets(&[]), vec![0]);
+        assert_eq!(h.to_buckets(&[1.0, 2.0]), vec![0, 0, 0]);
 
-        let expected_bins = vec![
-            Bin::new(33.27875390312249, 9992),
-            Bin::new(1255.8137647058825, 17),
-            Bin::new(3364.983, 2),
-            Bin::new(5361.3435, 2),
-            Bin::new(7349.9465, 2),
-        ];
+        let values = pseudo_random_integers(42, 10000);
+        let h = Histogram::from_iter(64, values.iter().copied());
+        let boundaries = [f64::NEG_INFINITY, 100.5, 250.5, 250.5, 500.5, 900.5, 1000.0];
+        let buckets = h.to_buckets(&boundaries);
 
-        h1.merge(&h2);
+        assert_eq!(buckets.len(), boundaries.len() + 1);
+        assert_eq!(buckets.iter().sum::<u64>(), h.count());
+        assert_eq!(buckets[0], 0);
+        assert_eq!(buckets[3], 0);
+        assert_eq!(h.to_buckets(&[]), vec![h.count()]);
 
-        assert_eq!(h1.count(), 10015);
-        assert_eq!(h1.size(), 5);
-        assert_eq!(h1.min(), Some(-6.6));
-        assert_eq!(h1.max(), Some(7829.851));
-        assert_eq!(h1.bins(), expected_bins.as_slice());
+        let mut cumulative = 0;
+        for (&boundary, &count) in boundaries.iter().zip(buckets.iter()) {
+            cumulative += count;
+            let exact = values.iter().filter(|&&value| value <= boundary).count() as f64;
+            assert_relative_eq!(cumulative as f64, exact, epsilon = 100.0);
+        }
     }
 
     #[test]
-    fn merge_empty() {
-        let mut h1 = Histogram::new(5);
-        let h2 = Histogram::new(10);
-
-        h1.merge(&h2);
+    #[should_panic(expected = "boundaries must be sorted in ascending order")]
+    fn to_buckets_unsorted() {
+        Histogram::from_iter(5, [1.0, 2.0]).to_buckets(&[2.0, 1.0]);
+    }
 
-        assert_eq!(h1.count(), 0);
-        assert_eq!(h1.size(), 5);
-        assert_eq!(h1.min(), None);
-        assert_eq!(h1.max(), None);
-        assert_eq!(h1.bins(), &[]);
+    #[test]
+    #[should_panic(expected = "value must not be NaN")]
+    fn to_buckets_nan() {
+        Histogram::from_iter(5, [1.0, 2.0]).to_buckets(&[f64::NAN]);
     }
 
     #[test]
-    fn from_iter() {
-        let values = vec![
-            1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2, -6.0, -6.6, 0.5, 0.5, 2.625,
-        ];
-        let expected_bins = vec![
-            Bin::new(-6.0, 3),
-            Bin::new(-2.1, 1),
-            Bin::new(0.5, 4),
-            Bin::new(4.041666666666667, 3),
-            Bin::new(8.725, 4),
-        ];
+    fn pdf_points() {
+        assert_eq!(Histogram::new(5).pdf_points(10), vec![]);
+        assert_eq!(
+            Histogram::from_iter(5, [(1.0, 3)]).pdf_points(1),
+            vec![(1.0, 0.0)]
+        );
 
-        let h = Histogram::from_iter(5, &values);
+        let h = Histogram::from_iter(64, pseudo_random_integers(42, 10000));
+        let points = h.pdf_points(1001);
+        assert_eq!(points.len(), 1001);
+        assert_eq!(points.first().unwrap().0, h.min().unwrap());
+        assert_eq!(points.last().unwrap().0, h.max().unwrap());
+        assert!(points.iter().all(|&(_, density)| density >= 0.0));
 
-        assert_eq!(h.count(), values.len() as u64);
-        assert_eq!(h.size(), 5);
-        assert_eq!(h.min(), Some(-6.6));
-        assert_eq!(h.max(), Some(10.0));
-        assert_eq!(h.bins(), expected_bins.as_slice());
+        // the density of uniformly distributed values is roughly constant, and the area
+        // under the curve is roughly 1.0
+        let width = (h.max().unwrap() - h.min().unwrap()) / 1000.0;
+        let area: f64 = points
+            .windows(2)
+            .map(|pair| (pair[0].1 + pair[1].1) / 2.0 * width)
+            .sum();
+        assert_relative_eq!(area, 1.0, max_relative = 0.02);
+        assert_relative_eq!(points[500].1, 0.001, max_relative = 0.2);
     }
 
     #[test]
-    fn from_iter_values() {
-        let values = (1..=100).map(|v| v as f64);
-        let expected_bins = vec![
-            Bin::new(10.0, 19),
-            Bin::new(28.0, 17),
-            Bin::new(44.5, 16),
-            Bin::new(61.5, 18),
-            Bin::new(85.5, 30),
-        ];
-
-        let h = Histogram::from_iter(5, values);
+    fn cdf_points() {
+        assert_eq!(Histogram::new(5).cdf_points(10), vec![]);
 
-        assert_eq!(h.count(), 100);
-        assert_eq!(h.size(), 5);
-        assert_eq!(h.min(), Some(1.0));
-        assert_eq!(h.max(), Some(100.0));
-        assert_eq!(h.bins(), expected_bins.as_slice());
+        let h = Histogram::from_iter(64, pseudo_random_integers(42, 10000));
+        let points = h.cdf_points(101);
+        assert_eq!(points.len(), 101);
+        assert_eq!(points.last().unwrap(), &(h.max().unwrap(), 1.0));
+        assert!(points.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+        for &(value, fraction) in &points {
+            assert_eq!(Some(fraction), h.rank(value));
+        }
     }
 
     #[test]
-    fn find_closest_bins_distance() {
-        // proximity of bins is defined by the absolute distance between their values
-        let bins = vec![
-            Bin::new(-10.0, 1),
-            Bin::new(4.9, 1),
-            Bin::new(5.0, 1),
-            Bin::new(17.4, 1),
-            Bin::new(20.1, 1),
-        ];
-        let h = histogram_from_parts(5, bins, Some(-10.0), Some(100.0));
-
-        assert_eq!(h.find_closest_bins(), (1, 2));
+    #[should_panic(expected = "number of points must be greater than 0")]
+    fn pdf_points_zero() {
+        Histogram::from_iter(5, [1.0]).pdf_points(0);
     }
 
     #[test]
-    fn find_closest_bins_count() {
-        // distances between bin values are equal. Therefore, the pair of bins
-        // with a smaller total count is considered to be the closest.
-        let bins = vec![
-            Bin::new(1.0, 1),
-            Bin::new(2.0, 2),
-            Bin::new(3.0, 3),
-            Bin::new(4.0, 4),
-            Bin::new(5.0, 5),
-        ];
-        let h = histogram_from_parts(5, bins, Some(-10.0), Some(100.0));
+    fn entropy() {
+        // values are uniformly distributed in [0.0; 1000.0)
+        let h = Histogram::from_iter(64, pseudo_random_integers(42, 100000));
+        let entropy = h.entropy().unwrap();
+        assert_relative_eq!(entropy, 1000f64.ln(), max_relative = 0.01);
 
-        assert_eq!(h.find_closest_bins(), (0, 1));
+        // shifting values doesn't change the entropy, and scaling them adds the logarithm of
+        // the scale to it
+        let mut shifted = h.clone();
+        shifted.map_values(1.0, 500.0);
+        assert_relative_eq!(shifted.entropy().unwrap(), entropy, max_relative = 1e-9);
+        let mut scaled = h.clone();
+        scaled.map_values(0.5, 0.0);
+        assert_relative_eq!(
+            scaled.entropy().unwrap(),
+            entropy - 2f64.ln(),
+            max_relative = 1e-9
+        );
+
+        // squares of uniformly distributed values are more concentrated
+        let squares = Histogram::from_iter(
+            64,
+            pseudo_random_integers(42, 100000)
+                .iter()
+                .map(|v| v * v / 1000.0),
+        );
+        assert!(squares.entropy().unwrap() < entropy);
+
+        assert_eq!(Histogram::new(5).entropy(), None);
+        assert_eq!(
+            Histogram::from_iter(5, [(1.0, 3)]).entropy(),
+            Some(f64::NEG_INFINITY)
+        );
     }
 
     #[test]
-    fn find_closest_bins_index() {
-        // distances between bin values are equal, and so are bin counts.
-        // Therefore, the leftmost pair of bins is considered to be the closest.
-        let bins = vec![
-            Bin::new(1.0, 1),
-            Bin::new(2.0, 1),
-            Bin::new(3.0, 1),
-            Bin::new(4.0, 1),
-            Bin::new(5.0, 1),
-        ];
-        let h = histogram_from_parts(5, bins, Some(-10.0), Some(100.0));
+    fn iqr() {
+        assert_eq!(Histogram::new(5).iqr(), None);
+        assert_eq!(Histogram::from_iter(5, [(1.0, 3)]).iqr(), Some(0.0));
 
-        assert_eq!(h.find_closest_bins(), (0, 1));
+        let h = Histogram::from_iter(64, pseudo_random_integers(42, 10000));
+        assert_eq!(
+            h.iqr(),
+            Some(h.quantile(0.75).unwrap() - h.quantile(0.25).unwrap())
+        );
+        assert_relative_eq!(h.iqr().unwrap(), 500.0, max_relative = 0.05);
     }
 
     #[test]
-    fn index_of_cumulative_count_less_than() {
-        let bins = vec![
-            Bin::new(1.0, 10),
-            Bin::new(2.0, 8),
-            Bin::new(3.0, 7),
-            Bin::new(4.0, 15),
-            Bin::new(5.0, 20),
-        ];
-        let h = histogram_from_parts(5, bins, Some(0.0), Some(6.0));
+    fn outlier_fences() {
+        assert_eq!(Histogram::new(5).outlier_fences(1.5), None);
 
-        // [0] [ 5 : 5 ] [ 4 : 4 ] [ 3.5 : 3.5 ] [ 7.5 : 7.5 ] [ 10 : 10 ] [0]
-        //    0         1         2             3             4          5
+        let mut h = Histogram::from_iter(64, pseudo_random_integers(42, 10000));
+        h.insert(1e6);
+        let (low, high) = h.outlier_fences(1.5).unwrap();
+        let iqr = h.iqr().unwrap();
+        assert_relative_eq!(low, h.quantile(0.25).unwrap() - 1.5 * iqr);
+        assert_relative_eq!(high, h.quantile(0.75).unwrap() + 1.5 * iqr);
+        assert!(low < h.min().unwrap() && high < h.max().unwrap());
 
-        assert_eq!(h.index_of_cumulative_count_less_than(-10.0), (0, 0.0));
-        assert_eq!(h.index_of_cumulative_count_less_than(0.0), (0, 0.0));
-        assert_eq!(h.index_of_cumulative_count_less_than(1.0), (0, 0.0));
-        assert_eq!(h.index_of_cumulative_count_less_than(5.0), (0, 0.0));
-        assert_eq!(h.index_of_cumulative_count_less_than(6.0), (1, 5.0));
-        assert_eq!(h.index_of_cumulative_count_less_than(11.0), (1, 5.0));
-        assert_eq!(h.index_of_cumulative_count_less_than(14.0), (1, 5.0));
-        assert_eq!(h.index_of_cumulative_count_less_than(15.0), (2, 14.0));
-        assert_eq!(h.index_of_cumulative_count_less_than(18.0), (2, 14.0));
-        assert_eq!(h.index_of_cumulative_count_less_than(21.5), (2, 14.0));
-        assert_eq!(h.index_of_cumulative_count_less_than(22.0), (3, 21.5));
-        assert_eq!(h.index_of_cumulative_count_less_than(60.0), (5, 50.0));
-        assert_eq!(h.index_of_cumulative_count_less_than(70.0), (5, 50.0));
+        let (far_low, far_high) = h.outlier_fences(3.0).unwrap();
+        assert!(far_low < low && far_high > high);
     }
 
     #[test]
-    fn quantile_empty() {
-        let h = Histogram::new(5);
-
-        assert_eq!(h.quantile(0.0), None);
-        assert_eq!(h.quantile(0.5), None);
-        assert_eq!(h.quantile(1.0), None);
+    #[should_panic(expected = "k must be finite and non-negative")]
+    fn outlier_fences_invalid_k() {
+        Histogram::from_iter(5, [1.0]).outlier_fences(-1.0);
     }
 
     #[test]
-    #[should_panic(expected = "q must be in the range [0.0; 1.0]")]
-    fn quantile_nan() {
-        let h = Histogram::new(5);
-        h.quantile(std::f64::NAN);
+    fn quantile_bounds() {
+        assert_eq!(Histogram::new(5).quantile_bounds(0.5), None);
+        assert_eq!(
+            Histogram::from_iter(5, [(1.0, 3)]).quantile_bounds(0.5),
+            Some((1.0, 1.0))
+        );
+
+        for seed in 0..10 {
+            let mut values: Vec<f64> = pseudo_random_integers(seed, 1000)
+                .into_iter()
+                .map(|v| v * v)
+                .collect();
+            let h = Histogram::from_iter(16, values.iter().copied());
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            for q in [0.0, 0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99, 1.0] {
+                let (lower, upper) = h.quantile_bounds(q).unwrap();
+                let estimate = h.quantile(q).unwrap();
+                assert!(lower <= estimate && estimate <= upper, "{} {}", seed, q);
+
+                // the exact value is within the bounds too
+                let exact = values[((values.len() - 1) as f64 * q).round() as usize];
+                assert!(lower <= exact && exact <= upper, "{} {}", seed, q);
+            }
+        }
     }
 
     #[test]
     #[should_panic(expected = "q must be in the range [0.0; 1.0]")]
-    fn quantile_not_in_range_high() {
-        let h = Histogram::new(5);
-        h.quantile(1.1);
+    fn quantile_bounds_invalid_q() {
+        Histogram::from_iter(5, [1.0]).quantile_bounds(1.5);
     }
 
     #[test]
-    #[should_panic(expected = "q must be in the range [0.0; 1.0]")]
-    fn quantile_not_in_range_low() {
-        let h = Histogram::new(5);
-        h.quantile(-0.1);
+    fn bin_edges() {
+        assert!(Histogram::new(5).bin_edges().is_empty());
+        assert_eq!(
+            Histogram::from_iter(5, [(1.0, 3)]).bin_edges(),
+            vec![1.0, 1.0]
+        );
+
+        // the exact minimum and maximum values are the outermost boundaries
+        let h = Histogram::from_iter(3, [0.0, 1.0, 2.0, 9.0, 10.0]);
+        assert_eq!(
+            h.bins(),
+            &[Bin::new(0.5, 2), Bin::new(2.0, 1), Bin::new(9.5, 2)]
+        );
+        assert_eq!(h.bin_edges(), vec![0.0, 1.25, 5.75, 10.0]);
+
+        let h = Histogram::from_iter(64, pseudo_random_integers(42, 10000));
+        let edges = h.bin_edges();
+        assert_eq!(edges.len(), h.bins().len() + 1);
+        for (bin, edges) in h.bins().iter().zip(edges.windows(2)) {
+            assert!(edges[0] <= bin.value() && bin.value() <= edges[1]);
+        }
     }
 
     #[test]
-    fn quantile() {
+    fn from_parts() {
+        let h = Histogram::from_iter(16, pseudo_random_integers(42, 1000));
+        let (size, bins, min_value, max_value) = h.clone().into_parts();
+        let h2 = Histogram::from_parts(size, bins, min_value, max_value).unwrap();
+        assert_eq!(h2.size(), h.size());
+        assert_eq!(h2.bins(), h.bins());
+        assert_eq!(h2.count(), h.count());
+        assert_eq!(h2.min(), h.min());
+        assert_eq!(h2.max(), h.max());
+        assert_relative_eq!(h2.sum(), h.sum(), max_relative = 1e-12);
+
+        let h = Histogram::from_parts(3, vec![], None, None).unwrap();
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.into_parts(), (3, vec![], None, None));
+
+        // the closest bins are merged, if there are too many of them
         let bins = vec![
+            Bin::new(1.0, 1),
             Bin::new(2.0, 1),
-            Bin::new(9.5, 2),
-            Bin::new(19.33, 3),
-            Bin::new(32.67, 3),
-            Bin::new(45.0, 1),
+            Bin::new(2.5, 1),
+            Bin::new(8.0, 1),
         ];
-        let h = histogram_from_parts(5, bins, Some(2.0), Some(45.0));
+        let h = Histogram::from_parts(3, bins, Some(1.0), Some(8.0)).unwrap();
+        assert_eq!(
+            h.bins(),
+            &[Bin::new(1.0, 1), Bin::new(2.25, 2), Bin::new(8.0, 1)]
+        );
+        assert_eq!(h.count(), 4);
+    }
 
-        let expected = vec![
-            // quantile, expected value, max relative difference
-            (0.0, 2.0, 0.0),
-            (0.1, 8.3, 0.4),
-            (0.25, 11.5, 0.1),
-            (0.5, 21.0, 0.1),
-            (0.75, 31.5, 0.1),
-            (0.9, 36.9, 0.1),
-            (0.99, 44.19, 0.1),
-            (1.0, 45.0, 0.0),
+    #[test]
+    fn from_parts_invalid() {
+        let bins = vec![Bin::new(1.0, 1), Bin::new(2.0, 1)];
+        let cases = vec![
+            (0, bins.clone(), Some(1.0), Some(2.0)),
+            (
+                5,
+                vec![Bin::new(2.0, 1), Bin::new(1.0, 1)],
+                Some(1.0),
+                Some(2.0),
+            ),
+            (5, bins.clone(), None, None),
+            (5, bins.clone(), Some(1.0), None),
+            (5, bins.clone(), Some(1.5), Some(2.0)),
+            (5, bins.clone(), Some(1.0), Some(1.5)),
+            (5, bins.clone(), Some(f64::NEG_INFINITY), Some(2.0)),
+            (5, bins, Some(f64::NAN), Some(2.0)),
+            (5, vec![], Some(1.0), Some(1.0)),
+            (
+                5,
+                vec![Bin::new(1.0, u64::MAX), Bin::new(2.0, 1)],
+                Some(1.0),
+                Some(2.0),
+            ),
         ];
-        for (q, expected_value, max_relative) in expected {
-            assert_relative_eq!(
-                h.quantile(q).unwrap(),
-                expected_value,
-                max_relative = max_relative
-            );
+        for (size, bins, min_value, max_value) in cases {
+            assert!(matches!(
+                Histogram::from_parts(size, bins, min_value, max_value),
+                Err(Error::InvalidParts(_))
+            ));
         }
     }
 
     #[test]
-    fn count_less_than_or_equal_to_empty() {
-        let h = Histogram::new(5);
+    fn delta_since() {
+        let values = pseudo_random_integers(42, 2000);
+        let mut h = Histogram::new(32);
+        h.set_nan_policy(NanPolicy::Count);
+        h.extend(values[..1000].iter().copied());
+        let earlier = h.clone();
+        h.extend(values[1000..].iter().copied());
+        h.try_insert(f64::NAN).unwrap();
 
-        assert_eq!(h.count_less_than_or_equal_to(-42.0), 0);
-        assert_eq!(h.count_less_than_or_equal_to(0.0), 0);
-        assert_eq!(h.count_less_than_or_equal_to(42.0), 0);
-        assert_eq!(h.count_less_than_or_equal_to(std::f64::NEG_INFINITY), 0);
-        assert_eq!(h.count_less_than_or_equal_to(std::f64::INFINITY), 0);
+        let delta = h.delta_since(&earlier).unwrap();
+        assert_eq!(delta.count(), 1000);
+        assert_eq!(delta.nan_count(), 1);
+        assert_eq!(delta.bins(), h.subtract(&earlier).bins());
+
+        let delta = h.delta_since(&h).unwrap();
+        assert_eq!(delta.count(), 0);
+        assert_eq!(
+            h.delta_since(&Histogram::new(32)).unwrap().count(),
+            h.count()
+        );
     }
 
     #[test]
-    #[should_panic(expected = "value must not be NaN")]
-    fn count_less_than_or_equal_to_nan() {
-        let h = Histogram::new(5);
-        h.count_less_than_or_equal_to(std::f64::NAN);
+    fn delta_since_invalid() {
+        let mut earlier = Histogram::new(32);
+        earlier.set_nan_policy(NanPolicy::Count);
+        earlier.set_infinity_policy(InfinityPolicy::Count);
+        earlier.extend([1.0, 5.0, 10.0]);
+
+        let mut cases = Vec::new();
+        // fewer values
+        cases.push(Histogram::from_iter(32, [1.0, 10.0]));
+        // the minimum and maximum values can't increase and decrease
+        cases.push(Histogram::from_iter(32, [2.0, 5.0, 10.0, 11.0]));
+        cases.push(Histogram::from_iter(32, [0.0, 1.0, 5.0, 9.0]));
+        // special counts can't decrease
+        for value in [f64::NAN, f64::NEG_INFINITY, f64::INFINITY] {
+            let mut later = earlier.clone();
+            later.insert(2.0);
+            earlier.try_insert(value).unwrap();
+            cases.push(later);
+        }
+
+        for later in cases {
+            assert!(matches!(
+                later.delta_since(&earlier),
+                Err(Error::InvalidSnapshot(_))
+            ));
+        }
     }
 
     #[test]
-    fn count_less_than_or_equal_to() {
-        let bins = vec![
-            Bin::new(2.0, 1),
-            Bin::new(9.5, 2),
-            Bin::new(19.33, 3),
-            Bin::new(32.67, 3),
-            Bin::new(45.0, 1),
-        ];
-        let h = histogram_from_parts(5, bins, Some(2.0), Some(45.0));
+    fn count_overflow() {
+        let mut h = Histogram::new(3);
+        h.insert(Bin::new(1.0, u64::MAX - 10));
+        h.insert_many([Bin::new(2.0, 5), Bin::new(3.0, 10)]);
+        assert_eq!(h.count(), u64::MAX);
+        assert_eq!(h.bins().iter().map(|b| b.count()).sum::<u64>(), u64::MAX);
 
-        assert_eq!(h.count_less_than_or_equal_to(std::f64::NEG_INFINITY), 0);
-        assert_eq!(h.count_less_than_or_equal_to(-42.0), 0);
-        assert_eq!(h.count_less_than_or_equal_to(0.0), 0);
-        assert_eq!(h.count_less_than_or_equal_to(2.1), 1);
-        assert_eq!(h.count_less_than_or_equal_to(10.0), 2);
-        assert_eq!(h.count_less_than_or_equal_to(15.0), 3);
-        assert_eq!(h.count_less_than_or_equal_to(25.0), 6);
-        assert_eq!(h.count_less_than_or_equal_to(38.0), 9);
-        assert_eq!(h.count_less_than_or_equal_to(45.0), 10);
-        assert_eq!(h.count_less_than_or_equal_to(std::f64::INFINITY), 10);
+        // nothing fits anymore
+        h.insert(4.0);
+        assert_eq!(h.count(), u64::MAX);
+        assert_eq!(h.max(), Some(3.0));
+
+        // the bins of the other histogram are scaled down proportionally
+        let mut h = Histogram::from_iter(4, [Bin::new(1.0, u64::MAX / 2)]);
+        let other = Histogram::from_iter(
+            4,
+            [Bin::new(2.0, u64::MAX / 2), Bin::new(3.0, u64::MAX / 2)],
+        );
+        h.merge(&other);
+        assert_eq!(h.bins().len(), 3);
+        assert!(h.count() > u64::MAX - 4096);
+        assert_eq!(h.bins().iter().map(|b| b.count()).sum::<u64>(), h.count());
+        assert_relative_eq!(
+            h.bins()[1].count() as f64,
+            h.bins()[2].count() as f64,
+            max_relative = 1e-9
+        );
+
+        let merged = Histogram::merge_all(4, [&other, &other, &other]);
+        assert_eq!(merged.count(), u64::MAX);
+        assert_eq!(
+            merged.bins().iter().map(|b| b.count()).sum::<u64>(),
+            u64::MAX
+        );
+
+        // the counters of special values saturate the same way
+        let mut other = Histogram::new(4);
+        other.set_special_counts(u64::MAX / 2, u64::MAX / 2, u64::MAX / 2);
+        let merged = Histogram::merge_all(4, [&other, &other, &other]);
+        assert_eq!(merged.nan_count(), u64::MAX);
+        assert_eq!(merged.underflow_count(), u64::MAX);
+        assert_eq!(merged.overflow_count(), u64::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "count overflow")]
+    fn count_overflow_checked() {
+        let mut h = Histogram::new(3);
+        h.set_count_policy(CountPolicy::Checked);
+        h.insert(Bin::new(1.0, u64::MAX));
+
+        let other = h.clone();
+        h.merge(&other);
     }
 }
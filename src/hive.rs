@@ -0,0 +1,287 @@
+use crate::bin::Bin;
+use crate::data::MAX_SIZE;
+use crate::error::Error;
+use crate::histogram::Histogram;
+
+impl Histogram {
+    /// Returns the histogram in the format of partial aggregations of Hive's
+    /// `histogram_numeric` UDAF (which implements the same algorithm): the size of the
+    /// histogram followed by the `(value, count)` pairs of its bins.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(5, [(1.0, 2), (3.5, 1)]);
+    /// assert_eq!(h.to_hive(), vec![5.0, 1.0, 2.0, 3.5, 1.0]);
+    /// ```
+    pub fn to_hive(&self) -> Vec<f64> {
+        std::iter::once(self.size() as f64)
+            .chain(
+                self.bins()
+                    .iter()
+                    .flat_map(|bin| [bin.value(), bin.count() as f64]),
+            )
+            .collect()
+    }
+
+    /// Create a new Histogram from a partial aggregation of Hive's `histogram_numeric` UDAF
+    /// (see [`to_hive`](Histogram::to_hive)). Returns an error, if the size is not a positive
+    /// integer (or is too large to be allocated), if values are not paired with counts, or if
+    /// counts are not positive integers.
+    ///
+    /// As Hive doesn't keep the exact minimum and maximum values, the values of the outermost
+    /// bins are used instead.
+    ///
+    /// ```
+    /// use bhtt::{Bin, Histogram};
+    ///
+    /// let h = Histogram::from_hive(&[5.0, 1.0, 2.0, 3.5, 1.0]).unwrap();
+    /// assert_eq!(h.size(), 5);
+    /// assert_eq!(h.bins(), &[Bin::new(1.0, 2), Bin::new(3.5, 1)]);
+    ///
+    /// assert!(Histogram::from_hive(&[5.0, 1.0]).is_err());
+    /// ```
+    pub fn from_hive(values: &[f64]) -> Result<Histogram, Error> {
+        let (&size, pairs) = values
+            .split_first()
+            .ok_or_else(|| Error::InvalidFormat("the size of the histogram is missing".into()))?;
+        if !(size >= 1.0 && size.fract() == 0.0) {
+            return Err(Error::InvalidFormat(format!(
+                "the size of the histogram must be a positive integer, got {}",
+                size
+            )));
+        }
+        // the conversion saturates, so sizes beyond usize::MAX are rejected as well
+        let size = size as usize;
+        if size > MAX_SIZE {
+            return Err(Error::InvalidFormat(
+                "the histogram size is too large".into(),
+            ));
+        }
+        if pairs.len() % 2 != 0 {
+            return Err(Error::InvalidFormat(
+                "every value must be followed by a count".into(),
+            ));
+        }
+
+        let bins = pairs
+            .chunks_exact(2)
+            .map(|pair| parse_bin(pair[0], pair[1]))
+            .collect::<Result<Vec<Bin>, Error>>()?;
+
+        // memory is only allocated for the bins, which are present in the input, rather than
+        // for as many bins as the size taken from the input allows
+        let mut h = Histogram::from_iter(size.min(bins.len()).max(1), bins);
+        h.set_size_lazily(size);
+
+        Ok(h)
+    }
+
+    /// Returns the histogram in the format Hive and Spark display the results of the
+    /// `histogram_numeric` function in, i.e. an array of `{"x": value, "y": count}` structs.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(5, [(1.0, 2), (3.5, 1)]);
+    /// assert_eq!(h.to_hive_string(), r#"[{"x":1.0,"y":2.0},{"x":3.5,"y":1.0}]"#);
+    /// ```
+    pub fn to_hive_string(&self) -> String {
+        let pairs: Vec<String> = self
+            .bins()
+            .iter()
+            .map(|bin| format!(r#"{{"x":{:?},"y":{:?}}}"#, bin.value(), bin.count() as f64))
+            .collect();
+
+        format!("[{}]", pairs.join(","))
+    }
+
+    /// Create a new Histogram of the given size from the output of Hive's or Spark's
+    /// `histogram_numeric` function (see [`to_hive_string`](Histogram::to_hive_string)).
+    /// Whitespace between tokens is ignored, and both Rust and Java notations of
+    /// floating-point numbers are accepted. Returns an error, if the string can't be parsed.
+    ///
+    /// ```
+    /// use bhtt::{Bin, Histogram};
+    ///
+    /// let h = Histogram::from_hive_str(5, r#"[{"x":1.0,"y":2.0}, {"x":3.5E2,"y":1.0}]"#).unwrap();
+    /// assert_eq!(h.bins(), &[Bin::new(1.0, 2), Bin::new(350.0, 1)]);
+    ///
+    /// assert!(Histogram::from_hive_str(5, r#"[{"x":1.0}]"#).is_err());
+    /// ```
+    pub fn from_hive_str(size: usize, s: &str) -> Result<Histogram, Error> {
+        let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+        let structs = s
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .ok_or_else(|| Error::InvalidFormat("expected an array of structs".into()))?;
+
+        let mut bins = Vec::new();
+        if !structs.is_empty() {
+            let structs = structs
+                .strip_prefix('{')
+                .and_then(|s| s.strip_suffix('}'))
+                .ok_or_else(|| Error::InvalidFormat("expected a struct".into()))?;
+            for fields in structs.split("},{") {
+                let (mut x, mut y) = (None, None);
+                for field in fields.split(',') {
+                    match field.split_once(':') {
+                        Some((r#""x""#, value)) if x.is_none() => x = Some(parse_number(value)?),
+                        Some((r#""y""#, value)) if y.is_none() => y = Some(parse_number(value)?),
+                        _ => {
+                            return Err(Error::InvalidFormat(format!("unexpected field {}", field)))
+                        }
+                    }
+                }
+
+                match (x, y) {
+                    (Some(x), Some(y)) => bins.push(parse_bin(x, y)?),
+                    _ => {
+                        return Err(Error::InvalidFormat(
+                            "both x and y must be specified".into(),
+                        ))
+                    }
+                }
+            }
+        }
+
+        Ok(Histogram::from_iter(size, bins))
+    }
+}
+
+fn parse_number(s: &str) -> Result<f64, Error> {
+    s.parse()
+        .map_err(|_| Error::InvalidFormat(format!("{} is not a number", s)))
+}
+
+fn parse_bin(value: f64, count: f64) -> Result<Bin, Error> {
+    if !(count.fract() == 0.0 && count <= u64::MAX as f64) {
+        return Err(Error::InvalidFormat(format!(
+            "count must be an integer, got {}",
+            count
+        )));
+    }
+
+    // negative counts are rejected as zero ones
+    Bin::try_new(value, count.max(0.0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_hive() {
+        assert_eq!(Histogram::new(3).to_hive(), vec![3.0]);
+
+        let h = Histogram::from_iter(3, [1.0, 2.0, 4.0, 8.0]);
+        assert_eq!(h.to_hive(), vec![3.0, 1.5, 2.0, 4.0, 1.0, 8.0, 1.0]);
+    }
+
+    #[test]
+    fn from_hive() {
+        let h = Histogram::from_hive(&[3.0]).unwrap();
+        assert_eq!(h.size(), 3);
+        assert_eq!(h.count(), 0);
+
+        let h = Histogram::from_hive(&[3.0, 1.5, 2.0, 4.0, 1.0, 8.0, 1.0]).unwrap();
+        assert_eq!(h.size(), 3);
+        assert_eq!(h.count(), 4);
+        assert_eq!(h.min(), Some(1.5));
+        assert_eq!(h.max(), Some(8.0));
+        assert_eq!(
+            h.bins(),
+            &[Bin::new(1.5, 2), Bin::new(4.0, 1), Bin::new(8.0, 1)]
+        );
+        assert_eq!(Histogram::from_hive(&h.to_hive()).unwrap().bins(), h.bins());
+
+        // more bins than the size of the histogram
+        let h = Histogram::from_hive(&[1.0, 1.0, 1.0, 3.0, 1.0]).unwrap();
+        assert_eq!(h.bins(), &[Bin::new(2.0, 2)]);
+    }
+
+    #[test]
+    fn from_hive_invalid() {
+        let cases: Vec<&[f64]> = vec![
+            &[],
+            &[0.0],
+            &[2.5],
+            &[-1.0],
+            &[f64::NAN],
+            &[3.0, 1.0],
+            &[3.0, 1.0, 0.0],
+            &[3.0, 1.0, -1.0],
+            &[3.0, 1.0, 1.5],
+            &[3.0, 1.0, f64::INFINITY],
+            &[3.0, f64::NAN, 1.0],
+        ];
+        for case in cases {
+            assert!(Histogram::from_hive(case).is_err(), "{:?}", case);
+        }
+
+        assert_eq!(
+            Histogram::from_hive(&[3.0, f64::INFINITY, 1.0]).unwrap_err(),
+            Error::InfiniteValue
+        );
+    }
+
+    #[test]
+    fn from_hive_large_size() {
+        // histograms of these sizes can't be allocated
+        for size in [1e18, 2f64.powi(64), f64::MAX] {
+            assert_eq!(
+                Histogram::from_hive(&[size, 1.0, 1.0]).unwrap_err(),
+                Error::InvalidFormat("the histogram size is too large".into()),
+                "{}",
+                size
+            );
+        }
+
+        // memory is not reserved for bins up to the size of the histogram
+        let h = Histogram::from_hive(&[1e15, 1.0, 1.0, 2.0, 3.0]).unwrap();
+        assert_eq!(h.size(), 1_000_000_000_000_000);
+        assert_eq!(h.bins(), &[Bin::new(1.0, 1), Bin::new(2.0, 3)]);
+        assert!(h.memory_usage() < 1024);
+
+        let mut h = h;
+        h.extend([0.5, 3.5]);
+        assert_eq!(h.bins().len(), 4);
+    }
+
+    #[test]
+    fn hive_string() {
+        assert_eq!(Histogram::new(3).to_hive_string(), "[]");
+        assert_eq!(Histogram::from_hive_str(3, " [ ] ").unwrap().count(), 0);
+
+        let h = Histogram::from_iter(3, [(-1e20, 1), (0.25, 2)]);
+        let s = h.to_hive_string();
+        assert_eq!(s, r#"[{"x":-1e20,"y":1.0},{"x":0.25,"y":2.0}]"#);
+
+        let h2 = Histogram::from_hive_str(3, &s).unwrap();
+        assert_eq!(h2.bins(), h.bins());
+
+        let h3 =
+            Histogram::from_hive_str(3, r#"[{"y": 1.0, "x": -1.0E20}, {"x":0.25,"y":2}]"#).unwrap();
+        assert_eq!(h3.bins(), h.bins());
+    }
+
+    #[test]
+    fn from_hive_str_invalid() {
+        let cases = [
+            "",
+            "[",
+            r#"{"x":1.0,"y":1.0}"#,
+            r#"[{"x":1.0,"y":1.0]"#,
+            r#"[{"x":1.0}]"#,
+            r#"[{"x":1.0,"y":1.0,"z":1.0}]"#,
+            r#"[{"x":1.0,"x":2.0,"y":1.0}]"#,
+            r#"[{"x":"a","y":1.0}]"#,
+            r#"[{"x":1.0,"y":0.5}]"#,
+            r#"[{"x":NaN,"y":1.0}]"#,
+            r#"[{"x":1.0,"y":1.0},]"#,
+        ];
+        for case in cases {
+            assert!(Histogram::from_hive_str(3, case).is_err(), "{}", case);
+        }
+    }
+}
@@ -4,8 +4,114 @@
 #[macro_use]
 extern crate approx;
 
+#[cfg(feature = "rkyv")]
+mod archive;
+mod ascii;
 mod bin;
+mod builder;
+mod bytes;
+mod centroids;
+#[cfg(feature = "statrs")]
+mod chi_squared;
+#[cfg(feature = "prometheus")]
+mod collector;
+#[cfg(feature = "sync")]
+mod concurrent;
+mod data;
+mod ddsketch;
+mod decaying;
+mod distance;
+mod duration;
+mod error;
+mod exact;
+mod expose;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod fixed;
+mod frozen;
+mod gaps;
+#[cfg(any(feature = "arbitrary", feature = "proptest"))]
+mod generators;
+mod gk;
+#[cfg(feature = "hdrhistogram")]
+mod hdr;
 mod histogram;
+mod hive;
+mod logarithmic;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod persist;
+#[cfg(feature = "plot")]
+mod plot;
+mod policy;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "sync")]
+mod recorder;
+mod registry;
+mod rotating;
+#[cfg(feature = "rand")]
+mod sampling;
+#[cfg(feature = "serde")]
+mod serialization;
+#[cfg(feature = "simd")]
+mod simd;
+#[cfg(feature = "tokio")]
+mod sink;
+mod sketch;
+#[cfg(feature = "sync")]
+mod snapshot;
+#[cfg(feature = "statsd")]
+mod statsd;
+#[cfg(feature = "json")]
+mod streamhist;
+mod sum;
+mod tdigest;
+mod text;
+#[cfg(feature = "approx")]
+mod tolerance;
+mod variance;
+mod wal;
+mod weighted;
 
 pub use bin::Bin;
+pub use builder::HistogramBuilder;
+#[cfg(feature = "statrs")]
+pub use chi_squared::ChiSquaredTest;
+#[cfg(feature = "prometheus")]
+pub use collector::PrometheusCollector;
+#[cfg(feature = "sync")]
+pub use concurrent::ConcurrentHistogram;
+#[cfg(feature = "rkyv")]
+pub use data::ArchivedHistogram;
+pub use ddsketch::DDSketch;
+pub use decaying::DecayingHistogram;
+pub use duration::DurationUnit;
+pub use error::Error;
+pub use exact::ExactSample;
+pub use expose::PrometheusExport;
+#[cfg(feature = "ffi")]
+pub use ffi::{BhttHistogram, BhttStatus};
+pub use fixed::FixedHistogram;
+pub use frozen::FrozenHistogram;
+pub use gk::GKSketch;
 pub use histogram::Histogram;
+pub use logarithmic::LogHistogram;
+pub use policy::{CountPolicy, InfinityPolicy, MergePolicy, NanPolicy};
+#[cfg(feature = "python")]
+pub use python::PyHistogram;
+#[cfg(feature = "sync")]
+pub use recorder::BackgroundRecorder;
+pub use registry::{Key, Registry};
+pub use rotating::RotatingHistogram;
+#[cfg(feature = "tokio")]
+pub use sink::{HistogramSink, SinkSender, SnapshotHandle};
+pub use sketch::Sketch;
+#[cfg(feature = "sync")]
+pub use snapshot::{HistogramReader, HistogramWriter};
+#[cfg(feature = "statsd")]
+pub use statsd::{StatsdEmitter, StatsdFlavor, StatsdReporter};
+pub use tdigest::TDigest;
+pub use variance::{VarianceBin, VarianceHistogram};
+pub use wal::LoggedHistogram;
+pub use weighted::WeightedHistogram;
@@ -0,0 +1,508 @@
+use crate::ddsketch::DDSketch;
+use crate::sketch::Sketch;
+
+/// A histogram of values counted in buckets with logarithmically growing fixed boundaries,
+/// which guarantees the relative error of estimated quantiles, like DDSketch
+/// (<https://arxiv.org/abs/1908.10693>).
+///
+/// A positive value `v` is counted in the bucket with the index `ceil(log(v, base))`, i.e.
+/// the bucket with the index `i` covers the interval (`base^(i - 1)`; `base^i`]. Negative
+/// values are counted in a separate set of buckets by their absolute value, and zeros are
+/// counted separately too. Quantiles are estimated by the values representing buckets,
+/// whose relative difference from any value in the bucket does not exceed the relative
+/// accuracy `(base - 1) / (base + 1)`.
+///
+/// Unlike [`Histogram`](crate::Histogram), whose bins adapt to the data, the boundaries of
+/// buckets never change, so the memory usage grows with the logarithm of the range of
+/// values rather than being fixed, and the relative error holds no matter how values are
+/// distributed. Buckets are stored densely between the smallest and the largest index.
+/// Log histograms implement [`Sketch`], so that they can be used interchangeably with
+/// other sketches.
+///
+/// ```
+/// use bhtt::LogHistogram;
+///
+/// let mut h = LogHistogram::new(0.01);
+/// for value in 1..=10000 {
+///     h.insert(value as f64);
+/// }
+///
+/// assert_eq!(h.count(), 10000);
+/// let p99 = h.quantile(0.99).unwrap();
+/// assert!((p99 - 9900.0).abs() <= 9900.0 * 0.01);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogHistogram {
+    base: f64,
+    ln_base: f64,
+    positive: Buckets,
+    negative: Buckets,
+    zero_count: u64,
+    count: u64,
+    min_max: Option<(f64, f64)>,
+}
+
+/// Counts of values in a contiguous range of buckets.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Buckets {
+    offset: i32,
+    counts: Vec<u64>,
+}
+
+impl Buckets {
+    fn add(&mut self, index: i32, count: u64) {
+        if self.counts.is_empty() {
+            self.offset = index;
+        } else if index < self.offset {
+            let extra = (self.offset - index) as usize;
+            self.counts.splice(0..0, std::iter::repeat_n(0, extra));
+            self.offset = index;
+        }
+
+        let i = (index - self.offset) as usize;
+        if i >= self.counts.len() {
+            self.counts.resize(i + 1, 0);
+        }
+        self.counts[i] += count;
+    }
+
+    /// Returns the non-empty buckets as `(index, count)` pairs in the ascending order of
+    /// indices.
+    fn iter(&self) -> impl DoubleEndedIterator<Item = (i32, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(move |(i, &count)| (self.offset + i as i32, count))
+    }
+}
+
+impl LogHistogram {
+    /// Create a new empty LogHistogram with the given relative accuracy, e.g. 0.01 for
+    /// estimates within 1% of the true values. The relative accuracy must be in the range
+    /// (0.0; 1.0), or the function will panic.
+    ///
+    /// ```
+    /// use bhtt::LogHistogram;
+    ///
+    /// let h = LogHistogram::new(0.01);
+    /// assert!((h.base() - 1.01 / 0.99).abs() < 1e-12);
+    /// assert!((h.relative_accuracy() - 0.01).abs() < 1e-12);
+    /// ```
+    pub fn new(relative_accuracy: f64) -> LogHistogram {
+        assert!(
+            relative_accuracy > 0.0 && relative_accuracy < 1.0,
+            "relative accuracy must be in the range (0.0; 1.0)"
+        );
+
+        LogHistogram::with_base((1.0 + relative_accuracy) / (1.0 - relative_accuracy))
+    }
+
+    /// Create a new empty LogHistogram with the given base of bucket boundaries, e.g. 2.0
+    /// for buckets, whose boundaries are powers of two. The base must be finite and greater
+    /// than 1.0, or the function will panic.
+    ///
+    /// ```
+    /// use bhtt::LogHistogram;
+    ///
+    /// let mut h = LogHistogram::with_base(2.0);
+    /// h.insert(3.0);
+    /// h.insert(4.0);
+    ///
+    /// // both values are in the bucket (2; 4]
+    /// assert_eq!(h.buckets().collect::<Vec<_>>(), vec![(2.0, 4.0, 2)]);
+    /// ```
+    pub fn with_base(base: f64) -> LogHistogram {
+        assert!(
+            base.is_finite() && base > 1.0,
+            "base must be finite and greater than 1.0"
+        );
+
+        LogHistogram {
+            base,
+            ln_base: base.ln(),
+            positive: Buckets::default(),
+            negative: Buckets::default(),
+            zero_count: 0,
+            count: 0,
+            min_max: None,
+        }
+    }
+
+    /// Returns the base of bucket boundaries.
+    pub fn base(&self) -> f64 {
+        self.base
+    }
+
+    /// Returns the relative accuracy of estimated quantiles.
+    pub fn relative_accuracy(&self) -> f64 {
+        (self.base - 1.0) / (self.base + 1.0)
+    }
+
+    /// Returns the number of values in the histogram.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the minimum value in the histogram or `None` if the histogram is empty.
+    pub fn min(&self) -> Option<f64> {
+        self.min_max.map(|(min_value, _)| min_value)
+    }
+
+    /// Returns the maximum value in the histogram or `None` if the histogram is empty.
+    pub fn max(&self) -> Option<f64> {
+        self.min_max.map(|(_, max_value)| max_value)
+    }
+
+    /// Returns the non-empty buckets as `(lower, upper, count)` tuples in the ascending
+    /// order of values. Buckets of positive values cover the intervals (`lower`; `upper`],
+    /// buckets of negative values cover [`lower`; `upper`), and zeros are counted in
+    /// the bucket (0.0, 0.0, count).
+    pub fn buckets(&self) -> impl Iterator<Item = (f64, f64, u64)> + '_ {
+        let negative =
+            self.negative.iter().rev().map(move |(index, count)| {
+                (-self.base.powi(index), -self.base.powi(index - 1), count)
+            });
+        let zero = Some(self.zero_count)
+            .filter(|&count| count > 0)
+            .map(|count| (0.0, 0.0, count));
+        let positive = self
+            .positive
+            .iter()
+            .map(move |(index, count)| (self.base.powi(index - 1), self.base.powi(index), count));
+
+        negative.chain(zero).chain(positive)
+    }
+
+    /// Update the histogram by inserting a new value. The value must not be NaN or
+    /// infinite, or the function will panic.
+    pub fn insert(&mut self, value: f64) {
+        self.insert_many(value, 1);
+    }
+
+    /// Update the histogram by inserting a value repeated `count` times. The value must not
+    /// be NaN or infinite, or the function will panic.
+    pub fn insert_many(&mut self, value: f64, count: u64) {
+        assert!(!value.is_nan(), "value must not be NaN");
+        assert!(value.is_finite(), "value must be finite");
+        if count == 0 {
+            return;
+        }
+
+        if value > 0.0 {
+            self.positive.add(self.index(value), count);
+        } else if value < 0.0 {
+            self.negative.add(self.index(-value), count);
+        } else {
+            self.zero_count += count;
+        }
+        self.track_min_max(value, value);
+        self.count += count;
+    }
+
+    /// Merge another histogram into this one. Both histograms must have the same base, or
+    /// the function will panic.
+    ///
+    /// ```
+    /// use bhtt::LogHistogram;
+    ///
+    /// let mut h1 = LogHistogram::new(0.01);
+    /// let mut h2 = LogHistogram::new(0.01);
+    /// h1.insert(1.0);
+    /// h2.insert(-1.0);
+    /// h1.merge(&h2);
+    ///
+    /// assert_eq!(h1.count(), 2);
+    /// assert_eq!(h1.min(), Some(-1.0));
+    /// ```
+    pub fn merge(&mut self, other: &LogHistogram) {
+        assert!(self.base == other.base, "bases of histograms must be equal");
+
+        for (index, count) in other.positive.iter() {
+            self.positive.add(index, count);
+        }
+        for (index, count) in other.negative.iter() {
+            self.negative.add(index, count);
+        }
+        self.zero_count += other.zero_count;
+        self.count += other.count;
+        if let Some((min_value, max_value)) = other.min_max {
+            self.track_min_max(min_value, max_value);
+        }
+    }
+
+    /// Returns an estimate of the `q`'th quantile or `None` if the histogram is empty. `q`
+    /// must be in the range [0.0; 1.0], or the function will panic.
+    ///
+    /// The relative difference between the estimate and the value of the (lower) rank
+    /// `q * (count - 1)` does not exceed the relative accuracy. The exact minimum and
+    /// maximum values are returned for 0.0 and 1.0.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        assert!(
+            (0.0..=1.0).contains(&q),
+            "q must be in the range [0.0; 1.0]"
+        );
+
+        let (min_value, max_value) = self.min_max?;
+        if q == 0.0 {
+            return Some(min_value);
+        } else if q == 1.0 {
+            return Some(max_value);
+        }
+
+        let rank = (q * (self.count - 1) as f64).floor() as u64;
+        let mut cumulative = 0;
+        let negative = self
+            .negative
+            .iter()
+            .rev()
+            .map(|(index, count)| (-self.value(index), count));
+        let zero = Some((0.0, self.zero_count));
+        let positive = self
+            .positive
+            .iter()
+            .map(|(index, count)| (self.value(index), count));
+        for (value, count) in negative.chain(zero).chain(positive) {
+            cumulative += count;
+            if cumulative > rank {
+                return Some(value.clamp(min_value, max_value));
+            }
+        }
+
+        Some(max_value)
+    }
+
+    /// Returns an estimate of the fraction of values less than or equal to `value` or
+    /// `None` if the histogram is empty. All values in the bucket of `value` are counted.
+    /// The value must not be NaN, or the function will panic.
+    pub fn rank(&self, value: f64) -> Option<f64> {
+        assert!(!value.is_nan(), "value must not be NaN");
+
+        let (min_value, max_value) = self.min_max?;
+        if value < min_value {
+            return Some(0.0);
+        } else if value >= max_value {
+            return Some(1.0);
+        }
+
+        let negative: u64 = self.negative.counts.iter().sum();
+        let count = if value > 0.0 {
+            let index = self.index(value);
+            let positive: u64 = self
+                .positive
+                .iter()
+                .take_while(|&(i, _)| i <= index)
+                .map(|(_, count)| count)
+                .sum();
+            negative + self.zero_count + positive
+        } else if value < 0.0 {
+            // buckets of values greater than or equal to `value` have lower indices
+            let index = self.index(-value);
+            negative
+                - self
+                    .negative
+                    .iter()
+                    .take_while(|&(i, _)| i < index)
+                    .map(|(_, count)| count)
+                    .sum::<u64>()
+        } else {
+            negative + self.zero_count
+        };
+
+        Some(count as f64 / self.count as f64)
+    }
+
+    /// Returns the index of the bucket, which a positive value is counted in.
+    fn index(&self, value: f64) -> i32 {
+        (value.ln() / self.ln_base).ceil() as i32
+    }
+
+    /// Returns the value, which represents the bucket with the given index.
+    fn value(&self, index: i32) -> f64 {
+        2.0 * self.base.powi(index) / (1.0 + self.base)
+    }
+
+    fn track_min_max(&mut self, min_value: f64, max_value: f64) {
+        let (self_min, self_max) = self.min_max.unwrap_or((min_value, max_value));
+        self.min_max = Some((self_min.min(min_value), self_max.max(max_value)));
+    }
+}
+
+impl From<&LogHistogram> for DDSketch {
+    /// Returns a DDSketch with the same buckets, e.g. to send the histogram to a system,
+    /// which accepts DDSketches. The bucket indices of both are computed the same way, so
+    /// no accuracy is lost.
+    fn from(h: &LogHistogram) -> DDSketch {
+        let mut sketch = DDSketch::with_gamma(h.base);
+        for (index, count) in h.positive.iter() {
+            sketch.add_positive(index, count);
+        }
+        for (index, count) in h.negative.iter() {
+            sketch.add_negative(index, count);
+        }
+        sketch.add_zero(h.zero_count);
+
+        sketch
+    }
+}
+
+impl Sketch for LogHistogram {
+    fn insert(&mut self, value: f64) {
+        LogHistogram::insert(self, value);
+    }
+
+    fn merge(&mut self, other: &LogHistogram) {
+        LogHistogram::merge(self, other);
+    }
+
+    fn count(&self) -> u64 {
+        LogHistogram::count(self)
+    }
+
+    fn min(&self) -> Option<f64> {
+        LogHistogram::min(self)
+    }
+
+    fn max(&self) -> Option<f64> {
+        LogHistogram::max(self)
+    }
+
+    fn quantile(&self, q: f64) -> Option<f64> {
+        LogHistogram::quantile(self, q)
+    }
+
+    fn rank(&self, value: f64) -> Option<f64> {
+        LogHistogram::rank(self, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let h = LogHistogram::new(0.01);
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.min(), None);
+        assert_eq!(h.max(), None);
+        assert_eq!(h.quantile(0.5), None);
+        assert_eq!(h.rank(1.0), None);
+        assert_eq!(h.buckets().count(), 0);
+    }
+
+    #[test]
+    fn relative_error() {
+        // values span many orders of magnitude, and both signs
+        let mut values: Vec<f64> = (1..=10000)
+            .flat_map(|v| [v as f64 * 0.37, -(v as f64).powf(1.5), 0.0])
+            .collect();
+        let mut h = LogHistogram::new(0.02);
+        for &value in &values {
+            h.insert(value);
+        }
+        values.sort_by(f64::total_cmp);
+
+        for i in 1..100 {
+            let q = i as f64 / 100.0;
+            let expected = values[(q * (values.len() - 1) as f64).floor() as usize];
+            let actual = h.quantile(q).unwrap();
+            assert!(
+                (actual - expected).abs() <= expected.abs() * 0.02 + 1e-12,
+                "q = {}: {} != {}",
+                q,
+                actual,
+                expected
+            );
+        }
+        assert_eq!(h.quantile(0.0), Some(values[0]));
+        assert_eq!(h.quantile(1.0), Some(values[values.len() - 1]));
+    }
+
+    #[test]
+    fn buckets() {
+        let mut h = LogHistogram::with_base(2.0);
+        for value in [-3.0, -1.5, 0.0, 0.0, 1.0, 3.0, 4.0, 100.0] {
+            h.insert(value);
+        }
+
+        assert_eq!(
+            h.buckets().collect::<Vec<_>>(),
+            vec![
+                (-4.0, -2.0, 1),
+                (-2.0, -1.0, 1),
+                (0.0, 0.0, 2),
+                (0.5, 1.0, 1),
+                (2.0, 4.0, 2),
+                (64.0, 128.0, 1),
+            ]
+        );
+
+        // empty buckets between the smallest and the largest index are stored, but skipped
+        assert_eq!(h.positive.counts.len(), 8);
+    }
+
+    #[test]
+    fn rank() {
+        let mut h = LogHistogram::with_base(2.0);
+        for value in [-3.0, -1.5, 0.0, 0.0, 1.0, 3.0, 4.0, 100.0] {
+            h.insert(value);
+        }
+
+        assert_eq!(h.rank(-4.0), Some(0.0));
+        assert_eq!(h.rank(-2.5), Some(1.0 / 8.0));
+        assert_eq!(h.rank(-1.2), Some(2.0 / 8.0));
+        assert_eq!(h.rank(0.0), Some(4.0 / 8.0));
+        assert_eq!(h.rank(3.5), Some(7.0 / 8.0));
+        assert_eq!(h.rank(100.0), Some(1.0));
+    }
+
+    #[test]
+    fn merge() {
+        let mut h1 = LogHistogram::new(0.01);
+        let mut h2 = LogHistogram::new(0.01);
+        let mut expected = LogHistogram::new(0.01);
+        for value in 1..=1000 {
+            h1.insert(value as f64);
+            h2.insert_many(-(value as f64) * 1e6, 2);
+            expected.insert(value as f64);
+            expected.insert_many(-(value as f64) * 1e6, 2);
+        }
+
+        h1.merge(&h2);
+        assert_eq!(h1.count(), 3000);
+        for q in [0.0, 0.1, 0.5, 0.9, 1.0] {
+            assert_eq!(h1.quantile(q), expected.quantile(q));
+        }
+    }
+
+    #[test]
+    fn to_ddsketch() {
+        let mut h = LogHistogram::new(0.01);
+        let mut expected = DDSketch::new(0.01);
+        for value in [-5.0, 0.0, 0.5, 42.0, 42.0] {
+            h.insert(value);
+            expected.insert(value);
+        }
+
+        assert_eq!(DDSketch::from(&h), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "bases of histograms must be equal")]
+    fn merge_different_bases() {
+        LogHistogram::with_base(2.0).merge(&LogHistogram::with_base(1.5));
+    }
+
+    #[test]
+    #[should_panic(expected = "base must be finite and greater than 1.0")]
+    fn invalid_base() {
+        LogHistogram::with_base(1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "value must be finite")]
+    fn infinite_value() {
+        LogHistogram::new(0.01).insert(f64::INFINITY);
+    }
+}
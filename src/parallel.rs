@@ -0,0 +1,94 @@
+use rayon::prelude::*;
+
+use crate::bin::Bin;
+use crate::histogram::Histogram;
+
+impl Histogram {
+    /// Create a new Histogram of the given size from a parallel iterable of values (or bins,
+    /// or `(value, count)` pairs).
+    ///
+    /// Values are inserted into per-thread histograms, which are then merged together
+    /// using a parallel reduction tree. The resulting bins may slightly differ from the
+    /// ones produced by [`from_iter`](Histogram::from_iter), and may also vary between
+    /// runs depending on how rayon splits the work.
+    ///
+    /// This function is only available when the `rayon` feature is enabled.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    /// use rayon::prelude::*;
+    ///
+    /// let values: Vec<f64> = (1..=10_000).map(|v| v as f64).collect();
+    ///
+    /// let h = Histogram::par_from_iter(32, values.par_iter());
+    /// assert_eq!(h.size(), 32);
+    /// assert_eq!(h.count(), 10_000);
+    /// assert_eq!(h.min(), Some(1.0));
+    /// assert_eq!(h.max(), Some(10_000.0));
+    /// ```
+    pub fn par_from_iter<I>(size: usize, iter: I) -> Histogram
+    where
+        I: IntoParallelIterator,
+        I::Item: Into<Bin>,
+    {
+        assert!(size > 0, "histogram size must be greater than 0");
+
+        iter.into_par_iter()
+            .fold(
+                || Histogram::new(size),
+                |mut h, v| {
+                    h.insert(v);
+                    h
+                },
+            )
+            .reduce(
+                || Histogram::new(size),
+                |mut left, right| {
+                    left.merge(&right);
+                    left
+                },
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn par_from_iter() {
+        let values: Vec<f64> = (0..100_000).map(|v| ((v * 7919) % 1000) as f64).collect();
+
+        let h = Histogram::par_from_iter(64, values.par_iter());
+        let expected = Histogram::from_iter(64, &values);
+
+        assert_eq!(h.size(), 64);
+        assert!(h.bins().len() <= 64);
+        assert_eq!(h.count(), expected.count());
+        assert_eq!(h.min(), expected.min());
+        assert_eq!(h.max(), expected.max());
+        assert_eq!(h.sum(), expected.sum());
+        for q in [0.1, 0.25, 0.5, 0.75, 0.9] {
+            assert_relative_eq!(
+                h.quantile(q).unwrap(),
+                expected.quantile(q).unwrap(),
+                max_relative = 0.05
+            );
+        }
+    }
+
+    #[test]
+    fn par_from_iter_empty() {
+        let h = Histogram::par_from_iter(5, Vec::<f64>::new());
+
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.bins(), &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram size must be greater than 0")]
+    fn par_from_iter_invalid_size() {
+        Histogram::par_from_iter(0, vec![1.0]);
+    }
+}
@@ -0,0 +1,289 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::histogram::Histogram;
+
+// Versions of the checkpoint file format. Just like the binary format of histograms (see
+// `bytes.rs`), files written by older versions must always remain readable.
+//
+// Version 1: the magic bytes, the version, the length of the payload, the payload (the
+// histogram encoded in the binary format) and the CRC-32 checksum of the payload.
+
+/// The magic bytes at the start of every checkpoint file.
+const MAGIC: &[u8; 4] = b"BHTT";
+
+/// The version of the checkpoint file format, which is written by [`Histogram::save_to`].
+const VERSION: u8 = 1;
+
+/// The length of the header: the magic bytes, the version and the length of the payload.
+const HEADER_LEN: usize = MAGIC.len() + 1 + 8;
+
+impl Histogram {
+    /// Save a checkpoint of the histogram to a file, e.g. so that a long-running process
+    /// can restore its accumulated distributions after a restart (see
+    /// [`load_from`](Histogram::load_from)).
+    ///
+    /// The file starts with a header (the magic bytes `BHTT`, the version of the file
+    /// format and the length of the payload), which is followed by the histogram encoded
+    /// in the binary format (see [`to_bytes`](Histogram::to_bytes)) and its CRC-32
+    /// checksum. The file is replaced atomically: the checkpoint is written to a temporary
+    /// file in the same directory, which is then renamed, so a crash never leaves a
+    /// partially written checkpoint behind.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let path = std::env::temp_dir().join("bhtt-save-to-doctest.bin");
+    ///
+    /// let h = Histogram::from_iter(5, (1..=100).map(|v| v as f64));
+    /// h.save_to(&path).unwrap();
+    ///
+    /// let h2 = Histogram::load_from(&path).unwrap();
+    /// assert_eq!(h2, h);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn save_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let payload = self.to_bytes();
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len() + 4);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+        bytes.extend_from_slice(&crc32(&payload).to_le_bytes());
+
+        let temp_path = temp_path(path)?;
+        let result = File::create(&temp_path)
+            .and_then(|mut file| {
+                file.write_all(&bytes)?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&temp_path, path));
+        if result.is_err() {
+            // the temporary file may not exist, and the original error is more important
+            let _ = fs::remove_file(&temp_path);
+            return result;
+        }
+
+        // the rename itself is only durable once the directory is synced
+        #[cfg(unix)]
+        if let Some(parent) = path.parent() {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            File::open(parent)?.sync_all()?;
+        }
+
+        Ok(())
+    }
+
+    /// Create a new Histogram from a checkpoint file written by
+    /// [`save_to`](Histogram::save_to).
+    ///
+    /// Returns an error, if the file can't be read, or an error of the
+    /// [`InvalidData`](io::ErrorKind::InvalidData) kind, if the file is not a valid
+    /// checkpoint (e.g. it is truncated or its checksum doesn't match). The inner error of
+    /// the latter is an [`Error`].
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let path = std::env::temp_dir().join("bhtt-load-from-doctest.bin");
+    /// std::fs::write(&path, b"not a checkpoint").unwrap();
+    ///
+    /// let err = Histogram::load_from(&path).unwrap_err();
+    /// assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn load_from<P: AsRef<Path>>(path: P) -> io::Result<Histogram> {
+        let bytes = fs::read(path)?;
+        decode(&bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// Decode a checkpoint file.
+fn decode(bytes: &[u8]) -> Result<Histogram, Error> {
+    let invalid = |reason: &str| Error::InvalidFormat(reason.to_string());
+
+    if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(invalid("not a checkpoint file"));
+    }
+    let version = bytes[MAGIC.len()];
+    if version == 0 || version > VERSION {
+        return Err(Error::InvalidFormat(format!(
+            "unsupported checkpoint version {}",
+            version
+        )));
+    }
+
+    let len = u64::from_le_bytes(
+        bytes[MAGIC.len() + 1..HEADER_LEN]
+            .try_into()
+            .expect("the length is checked above"),
+    );
+    let rest = &bytes[HEADER_LEN..];
+    if (rest.len() as u64) < len.saturating_add(4) {
+        return Err(invalid("the checkpoint is truncated"));
+    }
+    if rest.len() as u64 > len + 4 {
+        return Err(invalid("unexpected trailing bytes"));
+    }
+
+    let (payload, checksum) = rest.split_at(len as usize);
+    let checksum = u32::from_le_bytes(checksum.try_into().expect("the length is checked above"));
+    if crc32(payload) != checksum {
+        return Err(invalid("checksum mismatch"));
+    }
+
+    Histogram::from_bytes(payload)
+}
+
+/// Returns the path of the temporary file, which is renamed to `path` once written.
+fn temp_path(path: &Path) -> io::Result<PathBuf> {
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the path of a checkpoint must be a file",
+        )
+    })?;
+
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+
+    Ok(path.with_file_name(temp_name))
+}
+
+/// Returns the CRC-32 checksum (the IEEE 802.3 polynomial, as used by zlib and gzip).
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::NanPolicy;
+
+    // A path of a test file, which is unique for every test and every run of the tests.
+    fn test_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("bhtt-{}-{}.bin", name, std::process::id()))
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = test_path("round-trip");
+
+        let mut h = Histogram::from_iter(8, (0..1000).map(|v| (v as f64).sqrt()));
+        h.set_nan_policy(NanPolicy::Count);
+        h.try_insert(f64::NAN).unwrap();
+        h.save_to(&path).unwrap();
+        assert_eq!(Histogram::load_from(&path).unwrap(), h);
+
+        // the checkpoint is replaced
+        let h = Histogram::new(8);
+        h.save_to(&path).unwrap();
+        assert_eq!(Histogram::load_from(&path).unwrap(), h);
+
+        // no temporary files are left behind
+        assert!(!temp_path(&path).unwrap().exists());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn stable_format() {
+        let h = Histogram::from_iter(5, [1.0]);
+        let path = test_path("stable-format");
+        h.save_to(&path).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        let payload = h.to_bytes();
+        assert_eq!(&bytes[..5], b"BHTT\x01");
+        assert_eq!(&bytes[5..13], &(payload.len() as u64).to_le_bytes());
+        assert_eq!(&bytes[13..13 + payload.len()], &payload[..]);
+        assert_eq!(&bytes[13 + payload.len()..], &crc32(&payload).to_le_bytes());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn load_from_invalid() {
+        let h = Histogram::from_iter(5, [1.0, 2.0, 3.0]);
+        let path = test_path("invalid");
+        h.save_to(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        let mut corrupted = bytes.clone();
+        corrupted[20] ^= 0xff;
+        let mut bad_version = bytes.clone();
+        bad_version[4] = 2;
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+
+        for (bytes, reason) in [
+            (b"".to_vec(), "not a checkpoint file"),
+            (
+                b"BHTX\x01\0\0\0\0\0\0\0\0".to_vec(),
+                "not a checkpoint file",
+            ),
+            (bad_version, "unsupported checkpoint version 2"),
+            (
+                bytes[..bytes.len() - 1].to_vec(),
+                "the checkpoint is truncated",
+            ),
+            (trailing, "unexpected trailing bytes"),
+            (corrupted, "checksum mismatch"),
+        ] {
+            assert_eq!(decode(&bytes), Err(Error::InvalidFormat(reason.into())));
+
+            fs::write(&path, &bytes).unwrap();
+            let err = Histogram::load_from(&path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(
+                err.into_inner().unwrap().downcast_ref::<Error>(),
+                Some(&Error::InvalidFormat(reason.into()))
+            );
+        }
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            Histogram::load_from(&path).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn save_to_invalid_path() {
+        let h = Histogram::new(5);
+        assert_eq!(
+            h.save_to("/").unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        let path = test_path("no-such-directory").join("checkpoint.bin");
+        assert_eq!(
+            h.save_to(&path).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use crate::histogram::Histogram;
+
+/// The number of density bars drawn by [`Histogram::plot`].
+const BARS: usize = 50;
+
+/// Quantiles marked by [`Histogram::plot`] and their colors.
+const QUANTILES: [(f64, &str, RGBColor); 3] = [
+    (0.5, "p50", RGBColor(0, 150, 0)),
+    (0.9, "p90", RGBColor(230, 140, 0)),
+    (0.99, "p99", RGBColor(200, 0, 0)),
+];
+
+impl Histogram {
+    /// Draw the distribution of values on a plotters drawing area: the estimated density of
+    /// values as bars over the range [min; max] of values, and vertical lines marking the
+    /// 50th, 90th and 99th percentiles. Nothing but axes is drawn, if the histogram is empty.
+    ///
+    /// The area can belong to any plotters backend, e.g. a `BitMapBackend` to render a PNG
+    /// image, if the corresponding features of plotters are enabled (note, that bitmap
+    /// backends also need a font feature to draw text). See [`to_svg`](Histogram::to_svg)
+    /// for a shortcut, which renders an SVG image.
+    ///
+    /// This is only available when the `plot` feature is enabled.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    /// use plotters::prelude::*;
+    ///
+    /// let h = Histogram::from_iter(64, (0..1000).map(|v| (v as f64).sqrt()));
+    ///
+    /// let mut svg = String::new();
+    /// {
+    ///     let root = SVGBackend::with_string(&mut svg, (640, 480)).into_drawing_area();
+    ///     root.fill(&WHITE).unwrap();
+    ///     let (left, right) = root.split_horizontally(320);
+    ///     h.plot(&left).unwrap();
+    ///     h.resized(8).plot(&right).unwrap();
+    ///     root.present().unwrap();
+    /// }
+    /// assert!(svg.contains("p99"));
+    /// ```
+    pub fn plot<DB: DrawingBackend>(
+        &self,
+        area: &DrawingArea<DB, Shift>,
+    ) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+        let (low, high) = match (self.min(), self.max()) {
+            (Some(min_value), Some(max_value)) if min_value < max_value => (min_value, max_value),
+            // all values are equal, so the range is extended to make the bar visible
+            (Some(value), Some(_)) => (value - 0.5, value + 0.5),
+            // histogram is empty
+            _ => (0.0, 1.0),
+        };
+        let bar_width = (high - low) / BARS as f64;
+
+        // the last boundary is the upper end of the range, so the extra bucket of greater
+        // values is always empty
+        let boundaries: Vec<f64> = (1..BARS)
+            .map(|i| low + (high - low) * (i as f64 / BARS as f64))
+            .chain(std::iter::once(high))
+            .collect();
+        let densities: Vec<f64> = self.to_buckets(&boundaries)[..BARS]
+            .iter()
+            .map(|&count| count as f64 / (self.count().max(1) as f64 * bar_width))
+            .collect();
+        let highest = densities.iter().copied().fold(0.0, f64::max);
+        let highest = if highest > 0.0 { highest * 1.1 } else { 1.0 };
+
+        let mut chart = ChartBuilder::on(area)
+            .margin(10)
+            .x_label_area_size(40)
+            .y_label_area_size(60)
+            .build_cartesian_2d(low..high, 0.0..highest)?;
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .x_desc("value")
+            .y_desc("density")
+            .draw()?;
+
+        chart.draw_series(densities.iter().enumerate().map(|(i, &density)| {
+            let x = low + bar_width * i as f64;
+            Rectangle::new([(x, 0.0), (x + bar_width, density)], BLUE.mix(0.5).filled())
+        }))?;
+
+        for (q, name, color) in QUANTILES {
+            if let Some(value) = self.quantile(q) {
+                chart
+                    .draw_series(std::iter::once(PathElement::new(
+                        vec![(value, 0.0), (value, highest)],
+                        color.stroke_width(2),
+                    )))?
+                    .label(format!("{} = {:.4}", name, value))
+                    .legend(move |(x, y)| {
+                        PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2))
+                    });
+            }
+        }
+        if !self.is_empty() {
+            chart
+                .configure_series_labels()
+                .background_style(WHITE.mix(0.8))
+                .border_style(BLACK)
+                .draw()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns an SVG image of the given size in pixels, which shows the distribution of
+    /// values (see [`plot`](Histogram::plot)).
+    ///
+    /// This is only available when the `plot` feature is enabled.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(64, (0..1000).map(|v| (v as f64).sqrt()));
+    /// let svg = h.to_svg(800, 600);
+    ///
+    /// assert!(svg.starts_with(r#"<svg width="800" height="600""#));
+    /// ```
+    pub fn to_svg(&self, width: u32, height: u32) -> String {
+        let mut svg = String::new();
+        {
+            let root = SVGBackend::with_string(&mut svg, (width, height)).into_drawing_area();
+            root.fill(&WHITE)
+                .and_then(|_| self.plot(&root))
+                .and_then(|_| root.present())
+                .expect("drawing to a string can't fail");
+        }
+
+        svg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_svg() {
+        let h = Histogram::from_iter(64, (0..1000).map(|v| (v as f64).sqrt()));
+        let svg = h.to_svg(640, 480);
+
+        assert!(svg.starts_with(r#"<svg width="640" height="480""#));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        for label in ["value", "density", "p50", "p90", "p99"] {
+            assert!(svg.contains(label), "{}", label);
+        }
+        // the background, the density bars and the legend
+        assert!(svg.matches("<rect").count() >= BARS);
+    }
+
+    #[test]
+    fn to_svg_special_cases() {
+        let svg = Histogram::new(5).to_svg(320, 240);
+        assert!(svg.contains("value"));
+        assert!(!svg.contains("p50"));
+
+        let svg = Histogram::from_iter(5, [(1.0, 10)]).to_svg(320, 240);
+        assert!(svg.contains("p50 = 1.0000"));
+    }
+}
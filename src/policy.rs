@@ -0,0 +1,193 @@
+use crate::bin::Bin;
+
+/// Defines how [`Histogram::try_insert`](crate::Histogram::try_insert) handles NaN values.
+///
+/// ```
+/// use bhtt::{Error, Histogram, NanPolicy};
+///
+/// let mut h = Histogram::new(5);
+/// assert_eq!(h.try_insert(f64::NAN), Err(Error::NanValue));
+///
+/// h.set_nan_policy(NanPolicy::Count);
+/// assert_eq!(h.try_insert(f64::NAN), Ok(()));
+/// assert_eq!(h.nan_count(), 1);
+/// assert_eq!(h.count(), 0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NanPolicy {
+    /// NaN values are rejected with [`Error::NanValue`](crate::Error::NanValue).
+    #[default]
+    Error,
+    /// NaN values are silently ignored.
+    Ignore,
+    /// NaN values are not inserted into the histogram, but their number is tracked
+    /// separately (see [`Histogram::nan_count`](crate::Histogram::nan_count)).
+    Count,
+}
+
+/// Defines how [`Histogram::try_insert`](crate::Histogram::try_insert) handles infinite
+/// values.
+///
+/// ```
+/// use bhtt::{Histogram, InfinityPolicy};
+///
+/// let mut h = Histogram::new(5);
+/// h.set_infinity_policy(InfinityPolicy::Clamp);
+/// h.try_insert(1.0).unwrap();
+/// h.try_insert(10.0).unwrap();
+///
+/// // a timeout is counted in the top bin
+/// h.try_insert(f64::INFINITY).unwrap();
+/// assert_eq!(h.count(), 3);
+/// assert_eq!(h.max(), Some(10.0));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InfinityPolicy {
+    /// Infinite values are rejected with
+    /// [`Error::InfiniteValue`](crate::Error::InfiniteValue).
+    #[default]
+    Error,
+    /// Infinite values are replaced with the current minimum (or maximum) value of
+    /// the histogram. If the histogram is empty, the values are rejected with
+    /// [`Error::InfiniteValue`](crate::Error::InfiniteValue).
+    Clamp,
+    /// Infinite values are not inserted into the histogram, but their numbers are tracked
+    /// separately (see [`Histogram::underflow_count`](crate::Histogram::underflow_count)
+    /// and [`Histogram::overflow_count`](crate::Histogram::overflow_count)).
+    Count,
+}
+
+/// Defines how the total count of a histogram is accumulated, if it would overflow `u64`
+/// (e.g. when many pre-aggregated bins with huge counts are merged), instead of silently
+/// wrapping around.
+///
+/// ```
+/// use bhtt::{Bin, CountPolicy, Histogram};
+///
+/// let mut h = Histogram::new(5);
+/// h.insert(Bin::new(1.0, u64::MAX - 1));
+/// h.insert(Bin::new(2.0, 10));
+///
+/// // only one of the last 10 values fits
+/// assert_eq!(h.count(), u64::MAX);
+/// assert_eq!(h.bins()[1], Bin::new(2.0, 1));
+///
+/// h.set_count_policy(CountPolicy::Checked);
+/// let result = std::panic::catch_unwind(move || h.insert(3.0));
+/// assert!(result.is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CountPolicy {
+    /// The total count saturates at `u64::MAX`: values, which do not fit, are dropped. When
+    /// histograms are merged, the counts of bins of the other histogram are scaled down
+    /// proportionally to make them fit.
+    #[default]
+    Saturating,
+    /// An overflow of the total count is treated as a bug: the update panics (in all builds).
+    Checked,
+}
+
+impl CountPolicy {
+    /// Returns how much of `count` can be added to `total` without overflowing it.
+    pub(crate) fn admit(self, total: u64, count: u64) -> u64 {
+        match self {
+            CountPolicy::Saturating => count.min(u64::MAX - total),
+            CountPolicy::Checked => {
+                assert!(total.checked_add(count).is_some(), "count overflow");
+                count
+            }
+        }
+    }
+
+    /// Returns `total + count` with the overflow handled according to the policy.
+    pub(crate) fn add(self, total: u64, count: u64) -> u64 {
+        total + self.admit(total, count)
+    }
+}
+
+/// Defines how the closeness of adjacent bins is measured, when a histogram is shrunk by
+/// merging the closest pair of bins. Ties are broken in favour of the pair with the smaller
+/// total count, and then in favour of the leftmost pair.
+///
+/// ```
+/// use bhtt::{Histogram, MergePolicy};
+///
+/// let mut h = Histogram::new(3);
+/// h.set_merge_policy(MergePolicy::Relative);
+/// h.insert_many(vec![1.0, 2.0, 100.0, 120.0]);
+///
+/// // 100.0 and 120.0 are farther apart in absolute terms, but closer in relative ones
+/// assert_eq!(h.bins()[0].value(), 1.0);
+/// assert_eq!(h.bins()[2].value(), 110.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergePolicy {
+    /// The absolute difference between values of bins.
+    #[default]
+    Absolute,
+    /// The difference between values of bins relative to the larger magnitude of the two,
+    /// which keeps the relative error of estimates bounded for heavy-tailed data.
+    Relative,
+    /// The absolute difference between values of bins multiplied by their total count, which
+    /// prefers merging sparsely populated bins.
+    CountWeighted,
+}
+
+impl MergePolicy {
+    /// Returns the distance between two adjacent bins (the smaller, the closer).
+    pub(crate) fn distance(self, left: &Bin, right: &Bin) -> f64 {
+        let distance = (right.value() - left.value()).abs();
+        match self {
+            MergePolicy::Absolute => distance,
+            MergePolicy::Relative => {
+                let magnitude = left.value().abs().max(right.value().abs());
+                if magnitude > 0.0 {
+                    distance / magnitude
+                } else {
+                    0.0
+                }
+            }
+            MergePolicy::CountWeighted => distance * (left.count() as f64 + right.count() as f64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_policy() {
+        assert_eq!(CountPolicy::Saturating.add(1, 2), 3);
+        assert_eq!(CountPolicy::Saturating.admit(u64::MAX - 5, 10), 5);
+        assert_eq!(CountPolicy::Saturating.add(u64::MAX - 5, 10), u64::MAX);
+        assert_eq!(CountPolicy::Saturating.admit(u64::MAX, 10), 0);
+        assert_eq!(CountPolicy::Checked.add(1, 2), 3);
+        assert_eq!(CountPolicy::Checked.add(u64::MAX - 5, 5), u64::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "count overflow")]
+    fn count_policy_checked() {
+        CountPolicy::Checked.add(u64::MAX - 5, 10);
+    }
+
+    #[test]
+    fn merge_policy_distance() {
+        let cases = [
+            (MergePolicy::Absolute, (1.0, 1), (3.0, 1), 2.0),
+            (MergePolicy::Absolute, (-3.0, 5), (-1.0, 5), 2.0),
+            (MergePolicy::Relative, (1.0, 1), (3.0, 1), 2.0 / 3.0),
+            (MergePolicy::Relative, (-4.0, 1), (-2.0, 1), 0.5),
+            (MergePolicy::Relative, (0.0, 1), (0.0, 1), 0.0),
+            (MergePolicy::CountWeighted, (1.0, 1), (3.0, 1), 4.0),
+            (MergePolicy::CountWeighted, (1.0, 2), (1.5, 6), 4.0),
+        ];
+
+        for (policy, (left_value, left_count), (right_value, right_count), expected) in cases {
+            let left = Bin::new(left_value, left_count);
+            let right = Bin::new(right_value, right_count);
+            assert_eq!(policy.distance(&left, &right), expected);
+        }
+    }
+}
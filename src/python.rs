@@ -0,0 +1,332 @@
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyType};
+
+use crate::bin::Bin;
+use crate::error::Error;
+use crate::histogram::Histogram;
+
+/// A histogram exposed to Python as `bhtt.Histogram`.
+///
+/// Histograms are serialized in the same binary format as [`Histogram::to_bytes`], so
+/// the sketches produced by Rust services can be loaded and merged in Python, and vice
+/// versa. Histograms can also be pickled.
+///
+/// ```python
+/// import bhtt
+///
+/// h = bhtt.Histogram(64)
+/// h.insert_many([1.0, 0.0, -5.4, -2.1, 8.5])
+/// h.insert(10.0, count=3)
+/// h.merge(bhtt.Histogram.from_bytes(payload))
+/// print(h.count, h.quantile(0.99))
+/// ```
+///
+/// This type is only available when the `python` feature is enabled.
+#[pyclass(name = "Histogram", module = "bhtt")]
+#[derive(Debug, Clone)]
+pub struct PyHistogram {
+    inner: Histogram,
+}
+
+#[pymethods]
+impl PyHistogram {
+    /// Create a new empty histogram with (up to) the given number of bins.
+    #[new]
+    fn new(size: usize) -> PyResult<PyHistogram> {
+        if size == 0 {
+            return Err(PyValueError::new_err(
+                "histogram size must be greater than 0",
+            ));
+        }
+
+        let inner = Histogram::try_new(size)
+            .ok_or_else(|| PyValueError::new_err("histogram size is too large to be allocated"))?;
+
+        Ok(PyHistogram { inner })
+    }
+
+    /// Decode a histogram serialized with `to_bytes` (or `Histogram::to_bytes` in Rust).
+    #[classmethod]
+    fn from_bytes(_cls: &Bound<'_, PyType>, data: &[u8]) -> PyResult<PyHistogram> {
+        let inner = Histogram::from_bytes(data).map_err(value_error)?;
+
+        Ok(PyHistogram { inner })
+    }
+
+    /// Returns the histogram serialized in the binary format.
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.inner.to_bytes())
+    }
+
+    /// Insert a value, which is repeated `count` times. NaN and infinite values are
+    /// rejected.
+    #[pyo3(signature = (value, count = 1))]
+    fn insert(&mut self, value: f64, count: u64) -> PyResult<()> {
+        if count == 1 {
+            return self.inner.try_insert(value).map_err(value_error);
+        }
+
+        let bin = Bin::try_new(value, count).map_err(value_error)?;
+        self.inner.insert(bin);
+        Ok(())
+    }
+
+    /// Insert many values at once, which is considerably faster than inserting them
+    /// one by one. NaN and infinite values are rejected, in which case no values are
+    /// inserted.
+    fn insert_many(&mut self, values: Vec<f64>) -> PyResult<()> {
+        if let Some(value) = values.iter().find(|value| !value.is_finite()) {
+            let err = match value.is_nan() {
+                true => Error::NanValue,
+                false => Error::InfiniteValue,
+            };
+            return Err(value_error(err));
+        }
+
+        self.inner.insert_many(values);
+        Ok(())
+    }
+
+    /// Merge another histogram into this one. Both histograms must have the same duration
+    /// unit.
+    fn merge(&mut self, other: &PyHistogram) -> PyResult<()> {
+        if self.inner.duration_unit() != other.inner.duration_unit() {
+            return Err(PyValueError::new_err(
+                "histograms must have the same duration unit",
+            ));
+        }
+
+        self.inner.merge(&other.inner);
+        Ok(())
+    }
+
+    /// Returns an estimate of the `q`'th quantile, or None, if the histogram is empty.
+    fn quantile(&self, q: f64) -> PyResult<Option<f64>> {
+        if !(0.0..=1.0).contains(&q) {
+            return Err(PyValueError::new_err("q must be in the range [0.0; 1.0]"));
+        }
+
+        Ok(self.inner.quantile(q))
+    }
+
+    /// Returns the bins of the histogram as a list of `(value, count)` tuples.
+    fn bins(&self) -> Vec<(f64, u64)> {
+        self.inner
+            .bins()
+            .iter()
+            .map(|bin| (bin.value(), bin.count()))
+            .collect()
+    }
+
+    #[getter]
+    fn size(&self) -> usize {
+        self.inner.size()
+    }
+
+    #[getter]
+    fn count(&self) -> u64 {
+        self.inner.count()
+    }
+
+    #[getter]
+    fn min(&self) -> Option<f64> {
+        self.inner.min()
+    }
+
+    #[getter]
+    fn max(&self) -> Option<f64> {
+        self.inner.max()
+    }
+
+    #[getter]
+    fn sum(&self) -> f64 {
+        self.inner.sum()
+    }
+
+    #[getter]
+    fn mean(&self) -> Option<f64> {
+        self.inner.mean()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.count() as usize
+    }
+
+    fn __eq__(&self, other: &Bound<'_, PyAny>) -> bool {
+        match other.downcast::<PyHistogram>() {
+            Ok(other) => self.inner == other.borrow().inner,
+            Err(_) => false,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Histogram(size={}, count={})",
+            self.inner.size(),
+            self.inner.count()
+        )
+    }
+
+    fn __copy__(&self) -> PyHistogram {
+        self.clone()
+    }
+
+    fn __deepcopy__(&self, _memo: &Bound<'_, PyAny>) -> PyHistogram {
+        self.clone()
+    }
+
+    // pickling restores the state of a histogram created with an arbitrary size
+    fn __getnewargs__(&self) -> (usize,) {
+        (self.inner.size(),)
+    }
+
+    fn __getstate__<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        self.to_bytes(py)
+    }
+
+    fn __setstate__(&mut self, state: &Bound<'_, PyAny>) -> PyResult<()> {
+        let state = state
+            .downcast::<PyBytes>()
+            .map_err(|_| PyTypeError::new_err("the state of a histogram must be bytes"))?;
+        self.inner = Histogram::from_bytes(state.as_bytes()).map_err(value_error)?;
+
+        Ok(())
+    }
+}
+
+impl From<Histogram> for PyHistogram {
+    fn from(inner: Histogram) -> PyHistogram {
+        PyHistogram { inner }
+    }
+}
+
+impl From<PyHistogram> for Histogram {
+    fn from(h: PyHistogram) -> Histogram {
+        h.inner
+    }
+}
+
+/// Returns a Python `ValueError` describing an error.
+fn value_error(err: Error) -> PyErr {
+    PyValueError::new_err(err.to_string())
+}
+
+/// The `bhtt` Python extension module. It is built with maturin, e.g.
+/// `maturin develop --features python,pyo3/extension-module`.
+#[pymodule]
+#[pyo3(name = "bhtt")]
+fn bhtt_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyHistogram>()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use pyo3::types::PyDict;
+
+    use super::*;
+    use crate::duration::DurationUnit;
+
+    // Run a Python script with the module importable as `bhtt` (e.g. by pickle) and
+    // the given bytes available as `payload`.
+    fn run(script: &str, payload: &[u8]) -> PyResult<()> {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "bhtt")?;
+            bhtt_module(&module)?;
+            py.import("sys")?
+                .getattr("modules")?
+                .set_item("bhtt", &module)?;
+            let globals = PyDict::new(py);
+            globals.set_item("bhtt", module)?;
+            globals.set_item("payload", PyBytes::new(py, payload))?;
+
+            py.run(&CString::new(script).unwrap(), Some(&globals), None)
+        })
+    }
+
+    #[test]
+    fn histogram() {
+        let script = r#"
+h = bhtt.Histogram(5)
+assert h.size == 5 and h.count == 0 and len(h) == 0
+assert h.min is None and h.quantile(0.5) is None
+
+h.insert_many([1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2])
+h.insert(42.0, count=3)
+assert h.count == 13
+assert h.min == -5.4 and h.max == 42.0
+assert len(h.bins()) == 5
+assert h.quantile(1.0) == 42.0
+
+other = bhtt.Histogram(5)
+other.insert(-10.0)
+h.merge(other)
+assert h.count == 14 and h.min == -10.0
+assert repr(h) == "Histogram(size=5, count=14)"
+"#;
+        run(script, &[]).unwrap();
+    }
+
+    #[test]
+    fn serialization() {
+        let h = Histogram::from_iter(8, [1.0, 2.0, 3.0]);
+        let script = r#"
+import copy, pickle
+
+h = bhtt.Histogram.from_bytes(payload)
+assert h.count == 3 and h.to_bytes() == payload
+assert copy.deepcopy(h) == h
+assert pickle.loads(pickle.dumps(h)) == h
+assert h != bhtt.Histogram(8) and h != 42
+"#;
+        run(script, &h.to_bytes()).unwrap();
+    }
+
+    #[test]
+    fn errors() {
+        let script = r#"
+h = bhtt.Histogram(5)
+for f, message in [
+    (lambda: bhtt.Histogram(0), "histogram size must be greater than 0"),
+    (lambda: bhtt.Histogram(1 << 50), "histogram size is too large to be allocated"),
+    (lambda: h.insert(float("nan")), "value must not be NaN"),
+    (lambda: h.insert(1.0, count=0), "count must be greater than zero"),
+    (lambda: h.insert_many([1.0, float("inf")]), "value must be finite"),
+    (lambda: h.quantile(1.5), "q must be in the range [0.0; 1.0]"),
+    (lambda: bhtt.Histogram.from_bytes(payload), "invalid format"),
+]:
+    try:
+        f()
+    except ValueError as e:
+        assert str(e).startswith(message), str(e)
+    else:
+        assert False, message
+assert h.count == 0
+"#;
+        run(script, b"abc").unwrap();
+    }
+
+    #[test]
+    fn merge_duration_units() {
+        let mut h = Histogram::new(5);
+        h.set_duration_unit(DurationUnit::Milliseconds);
+        h.insert_many([1.0, 2.0, 3.0]);
+        let script = r#"
+h = bhtt.Histogram(5)
+h.insert(42.0)
+try:
+    h.merge(bhtt.Histogram.from_bytes(payload))
+except ValueError as e:
+    assert str(e) == "histograms must have the same duration unit", str(e)
+else:
+    assert False
+assert h.count == 1
+"#;
+        run(script, &h.to_bytes()).unwrap();
+    }
+}
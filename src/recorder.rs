@@ -0,0 +1,273 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::bin::Bin;
+use crate::error::Error;
+use crate::histogram::Histogram;
+use crate::snapshot::{HistogramReader, HistogramWriter};
+
+/// A histogram owned by a dedicated background thread, which accepts values through
+/// a bounded queue and publishes immutable snapshots at a fixed interval.
+///
+/// Recording a value never blocks and never takes a lock: the value is put into the queue,
+/// or it is dropped, if the queue is full (e.g. if values are recorded faster than
+/// the background thread can insert them). Snapshots are read through
+/// [`HistogramReader`]s, so readers never block the background thread either.
+///
+/// The background thread stops, when the recorder is stopped (see
+/// [`stop`](BackgroundRecorder::stop)) or dropped. The values left in the queue are
+/// inserted and published before it stops.
+///
+/// This type is only available when the `sync` feature is enabled.
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use bhtt::BackgroundRecorder;
+///
+/// let recorder = Arc::new(BackgroundRecorder::new(64, 1024, Duration::from_millis(100)));
+/// let reader = recorder.reader();
+///
+/// let workers: Vec<_> = (0..4)
+///     .map(|_| {
+///         let recorder = Arc::clone(&recorder);
+///         std::thread::spawn(move || {
+///             for value in 0..100 {
+///                 // values are dropped, if the queue is full
+///                 let _ = recorder.record(value as f64);
+///             }
+///         })
+///     })
+///     .collect();
+/// for worker in workers {
+///     worker.join().unwrap();
+/// }
+///
+/// let h = Arc::try_unwrap(recorder).unwrap().stop();
+/// assert!(h.count() <= 400);
+/// assert_eq!(reader.snapshot().count(), h.count());
+/// ```
+#[derive(Debug)]
+pub struct BackgroundRecorder {
+    sender: Option<SyncSender<Bin>>,
+    reader: HistogramReader,
+    interval: Duration,
+    thread: Option<JoinHandle<Histogram>>,
+}
+
+impl BackgroundRecorder {
+    /// Create a new BackgroundRecorder of a histogram with the given number of bins, which
+    /// queues up to `capacity` values and publishes a snapshot every `interval`. Both
+    /// the capacity and the interval must be greater than 0, or the function will panic.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use bhtt::BackgroundRecorder;
+    ///
+    /// let recorder = BackgroundRecorder::new(64, 1024, Duration::from_secs(1));
+    /// assert_eq!(recorder.interval(), Duration::from_secs(1));
+    /// assert_eq!(recorder.snapshot().size(), 64);
+    /// ```
+    pub fn new(size: usize, capacity: usize, interval: Duration) -> BackgroundRecorder {
+        BackgroundRecorder::from_histogram(Histogram::new(size), capacity, interval)
+    }
+
+    /// Create a new BackgroundRecorder of an existing histogram (see
+    /// [`new`](BackgroundRecorder::new)). The histogram is published immediately.
+    pub fn from_histogram(
+        histogram: Histogram,
+        capacity: usize,
+        interval: Duration,
+    ) -> BackgroundRecorder {
+        assert!(capacity > 0, "capacity must be greater than 0");
+        assert!(!interval.is_zero(), "interval must be greater than 0");
+
+        let writer = HistogramWriter::from_histogram(histogram);
+        let reader = writer.reader();
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let thread = thread::Builder::new()
+            .name("bhtt-recorder".into())
+            .spawn(move || run(writer, receiver, interval))
+            .expect("failed to spawn the background thread");
+
+        BackgroundRecorder {
+            sender: Some(sender),
+            reader,
+            interval,
+            thread: Some(thread),
+        }
+    }
+
+    /// Returns the interval, at which snapshots are published.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Put a new value (or bin) into the queue without blocking.
+    ///
+    /// Returns [`Error::Full`], if the queue is full (the value is dropped), or
+    /// [`Error::Closed`], if the background thread has stopped (e.g. it panicked).
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use bhtt::{BackgroundRecorder, Bin};
+    ///
+    /// let recorder = BackgroundRecorder::new(64, 1024, Duration::from_secs(1));
+    /// recorder.record(42.0).unwrap();
+    /// recorder.record(Bin::new(-7.5, 10)).unwrap();
+    ///
+    /// assert_eq!(recorder.stop().count(), 11);
+    /// ```
+    pub fn record<T: Into<Bin>>(&self, value: T) -> Result<(), Error> {
+        self.sender
+            .as_ref()
+            .expect("the sender is only taken on stop")
+            .try_send(value.into())
+            .map_err(|err| match err {
+                TrySendError::Full(_) => Error::Full,
+                TrySendError::Disconnected(_) => Error::Closed,
+            })
+    }
+
+    /// Returns a new reader of the snapshots published by the background thread.
+    pub fn reader(&self) -> HistogramReader {
+        self.reader.clone()
+    }
+
+    /// Returns the last published snapshot of the histogram.
+    pub fn snapshot(&self) -> Arc<Histogram> {
+        self.reader.snapshot()
+    }
+
+    /// Stop the background thread, once it has inserted the values left in the queue, and
+    /// return the final state of the histogram, which is also published.
+    ///
+    /// If the background thread panicked, the panic is propagated.
+    pub fn stop(mut self) -> Histogram {
+        self.shutdown()
+            .expect("the background thread is only joined once")
+    }
+
+    fn shutdown(&mut self) -> Option<Histogram> {
+        // the background thread stops, once the queue is disconnected and empty
+        self.sender.take();
+        self.thread.take().map(|thread| match thread.join() {
+            Ok(histogram) => histogram,
+            Err(panic) => std::panic::resume_unwind(panic),
+        })
+    }
+}
+
+impl Drop for BackgroundRecorder {
+    fn drop(&mut self) {
+        // don't panic while panicking
+        if !thread::panicking() {
+            self.shutdown();
+        }
+    }
+}
+
+/// The loop of the background thread.
+fn run(mut writer: HistogramWriter, receiver: Receiver<Bin>, interval: Duration) -> Histogram {
+    let mut next_publish = Instant::now() + interval;
+    let mut changed = false;
+    loop {
+        let timeout = next_publish.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(timeout) {
+            Ok(bin) => {
+                writer.insert(bin);
+                changed = true;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let now = Instant::now();
+        if now >= next_publish {
+            // cloning an unchanged histogram would be a waste
+            if changed {
+                writer.publish();
+                changed = false;
+            }
+            next_publish = now + interval;
+        }
+    }
+
+    writer.publish();
+    writer.histogram().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn periodic_snapshots() {
+        let recorder = BackgroundRecorder::new(8, 1024, Duration::from_millis(10));
+        let reader = recorder.reader();
+        assert_eq!(reader.snapshot().count(), 0);
+
+        for value in 0..100 {
+            recorder.record(value as f64).unwrap();
+        }
+
+        // the snapshot is published within a few intervals
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while reader.snapshot().count() < 100 {
+            assert!(Instant::now() < deadline, "the snapshot was not published");
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(recorder.snapshot().count(), 100);
+
+        let h = recorder.stop();
+        assert_eq!(h, *reader.snapshot());
+        assert_eq!(h.min(), Some(0.0));
+        assert_eq!(h.max(), Some(99.0));
+    }
+
+    #[test]
+    fn stop_inserts_queued_values() {
+        // snapshots are never published before the recorder is stopped
+        let recorder = BackgroundRecorder::from_histogram(
+            Histogram::from_iter(8, [-1.0]),
+            1000,
+            Duration::from_secs(3600),
+        );
+        let reader = recorder.reader();
+        assert_eq!(reader.snapshot().count(), 1);
+
+        for value in 0..1000 {
+            recorder.record(value as f64).unwrap();
+        }
+        drop(recorder);
+        assert_eq!(reader.snapshot().count(), 1001);
+    }
+
+    #[test]
+    fn full_queue() {
+        let recorder = BackgroundRecorder::new(8, 1, Duration::from_secs(3600));
+
+        // the background thread may or may not have taken values from the queue yet
+        let results: Vec<_> = (0..1000).map(|v| recorder.record(v as f64)).collect();
+        assert!(results
+            .iter()
+            .all(|r| *r == Ok(()) || *r == Err(Error::Full)));
+
+        let recorded = results.iter().filter(|r| r.is_ok()).count() as u64;
+        assert_eq!(recorder.stop().count(), recorded);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity must be greater than 0")]
+    fn zero_capacity() {
+        BackgroundRecorder::new(8, 0, Duration::from_secs(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "interval must be greater than 0")]
+    fn zero_interval() {
+        BackgroundRecorder::new(8, 1, Duration::ZERO);
+    }
+}
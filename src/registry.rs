@@ -0,0 +1,409 @@
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Mutex, MutexGuard, RwLock};
+
+use crate::bin::Bin;
+use crate::histogram::Histogram;
+
+/// A key of a histogram in a [`Registry`]: a name and an optional set of labels.
+///
+/// Labels are kept sorted by their names, so the order in which they are added
+/// does not matter.
+///
+/// ```
+/// use bhtt::Key;
+///
+/// let k1 = Key::new("latency").with_label("method", "GET").with_label("code", "200");
+/// let k2 = Key::new("latency").with_label("code", "200").with_label("method", "GET");
+/// assert_eq!(k1, k2);
+/// assert_eq!(k1.name(), "latency");
+/// assert_eq!(k1.labels(), &[
+///     ("code".to_string(), "200".to_string()),
+///     ("method".to_string(), "GET".to_string()),
+/// ]);
+/// assert_eq!(k1.to_string(), r#"latency{code="200",method="GET"}"#);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Key {
+    name: String,
+    labels: Vec<(String, String)>,
+}
+
+impl Key {
+    /// Create a new Key with the given name and no labels.
+    pub fn new(name: impl Into<String>) -> Key {
+        Key {
+            name: name.into(),
+            labels: Vec::new(),
+        }
+    }
+
+    /// Returns the Key with a label added (or replaced, if a label with the same
+    /// name already exists).
+    pub fn with_label(mut self, name: impl Into<String>, value: impl Into<String>) -> Key {
+        let (name, value) = (name.into(), value.into());
+        match self.labels.binary_search_by(|(n, _)| n.cmp(&name)) {
+            Ok(i) => self.labels[i].1 = value,
+            Err(i) => self.labels.insert(i, (name, value)),
+        }
+
+        self
+    }
+
+    /// Returns the name of the key.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the labels of the key sorted by their names.
+    pub fn labels(&self) -> &[(String, String)] {
+        &self.labels
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.labels.is_empty() {
+            let labels: Vec<String> = self
+                .labels
+                .iter()
+                .map(|(name, value)| format!("{}={:?}", name, value))
+                .collect();
+            write!(f, "{{{}}}", labels.join(","))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&str> for Key {
+    fn from(name: &str) -> Self {
+        Key::new(name)
+    }
+}
+
+impl From<String> for Key {
+    fn from(name: String) -> Self {
+        Key::new(name)
+    }
+}
+
+/// A collection of named histograms of the same size, which can be updated concurrently
+/// from many threads. Histograms are created on first use.
+///
+/// ```
+/// use bhtt::{Key, Registry};
+///
+/// let registry = Registry::new(64);
+/// registry.record("latency", 12.5);
+/// registry.record("latency", 42.0);
+/// registry.record(Key::new("latency").with_label("method", "GET"), 7.0);
+///
+/// let snapshot = registry.snapshot_all();
+/// assert_eq!(snapshot.len(), 2);
+/// assert_eq!(snapshot[&Key::new("latency")].count(), 2);
+/// assert_eq!(registry.get("latency").unwrap().max(), Some(42.0));
+/// ```
+#[derive(Debug)]
+pub struct Registry {
+    size: usize,
+    histograms: RwLock<HashMap<Key, Mutex<Histogram>>>,
+}
+
+impl Registry {
+    /// Create a new empty Registry of histograms with the given number of bins.
+    ///
+    /// ```
+    /// use bhtt::Registry;
+    ///
+    /// let registry = Registry::new(64);
+    /// assert_eq!(registry.size(), 64);
+    /// assert!(registry.is_empty());
+    /// ```
+    pub fn new(size: usize) -> Registry {
+        assert!(size > 0, "histogram size must be greater than 0");
+
+        Registry {
+            size,
+            histograms: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the size of histograms in the registry.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the number of histograms in the registry.
+    pub fn len(&self) -> usize {
+        self.histograms
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+
+    /// Returns `true` if the registry contains no histograms.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Insert a new value (or bin) into the histogram with the given key.
+    ///
+    /// ```
+    /// use bhtt::{Bin, Registry};
+    ///
+    /// let registry = Registry::new(5);
+    /// registry.record("latency", 42.0);
+    /// registry.record("latency", Bin::new(-7.5, 10));
+    ///
+    /// assert_eq!(registry.get("latency").unwrap().count(), 11);
+    /// ```
+    pub fn record<T: Into<Bin>>(&self, key: impl Into<Key>, value: T) {
+        // convert the value before locking the histogram, so that invalid
+        // values can't poison it
+        let bin = value.into();
+        self.update(key.into(), |h| h.insert(bin));
+    }
+
+    /// Merge a histogram into the histogram with the given key.
+    ///
+    /// ```
+    /// use bhtt::{Histogram, Registry};
+    ///
+    /// let registry = Registry::new(5);
+    /// registry.merge("latency", &Histogram::from_iter(5, &[1.0, -7.6, 0.0]));
+    ///
+    /// assert_eq!(registry.get("latency").unwrap().count(), 3);
+    /// ```
+    pub fn merge(&self, key: impl Into<Key>, other: &Histogram) {
+        self.update(key.into(), |h| h.merge(other));
+    }
+
+    /// Merge many histograms into the registry matching them by their keys
+    /// (e.g. a snapshot of another registry).
+    ///
+    /// ```
+    /// use bhtt::Registry;
+    ///
+    /// let r1 = Registry::new(5);
+    /// r1.record("latency", 1.0);
+    ///
+    /// let r2 = Registry::new(5);
+    /// r2.record("latency", 2.0);
+    /// r2.record("size", 42.0);
+    ///
+    /// r1.merge_from(r2.snapshot_all());
+    /// assert_eq!(r1.len(), 2);
+    /// assert_eq!(r1.get("latency").unwrap().count(), 2);
+    /// assert_eq!(r1.get("size").unwrap().count(), 1);
+    /// ```
+    pub fn merge_from(
+        &self,
+        histograms: impl IntoIterator<Item = (impl Into<Key>, impl Borrow<Histogram>)>,
+    ) {
+        for (key, other) in histograms {
+            self.merge(key, other.borrow());
+        }
+    }
+
+    /// Returns a snapshot of the histogram with the given key or `None` if there is no
+    /// such histogram.
+    pub fn get(&self, key: impl Into<Key>) -> Option<Histogram> {
+        let histograms = self.histograms.read().unwrap_or_else(|e| e.into_inner());
+        histograms.get(&key.into()).map(|h| lock(h).clone())
+    }
+
+    /// Remove the histogram with the given key from the registry and return it.
+    ///
+    /// ```
+    /// use bhtt::Registry;
+    ///
+    /// let registry = Registry::new(5);
+    /// registry.record("latency", 42.0);
+    ///
+    /// assert_eq!(registry.remove("latency").unwrap().count(), 1);
+    /// assert!(registry.remove("latency").is_none());
+    /// ```
+    pub fn remove(&self, key: impl Into<Key>) -> Option<Histogram> {
+        let mut histograms = self.histograms.write().unwrap_or_else(|e| e.into_inner());
+        histograms
+            .remove(&key.into())
+            .map(|h| h.into_inner().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Returns snapshots of all histograms in the registry ordered by their keys.
+    pub fn snapshot_all(&self) -> BTreeMap<Key, Histogram> {
+        let histograms = self.histograms.read().unwrap_or_else(|e| e.into_inner());
+        histograms
+            .iter()
+            .map(|(key, h)| (key.clone(), lock(h).clone()))
+            .collect()
+    }
+
+    /// Returns snapshots of all histograms in the registry ordered by their keys and clears
+    /// the histograms, e.g. to periodically export the values recorded since the last
+    /// export. Every value is included in exactly one snapshot, even if values are recorded
+    /// concurrently.
+    ///
+    /// ```
+    /// use bhtt::{Key, Registry};
+    ///
+    /// let registry = Registry::new(5);
+    /// registry.record("latency", 42.0);
+    ///
+    /// assert_eq!(registry.take_all()[&Key::new("latency")].count(), 1);
+    /// assert_eq!(registry.get("latency").unwrap().count(), 0);
+    /// ```
+    pub fn take_all(&self) -> BTreeMap<Key, Histogram> {
+        let histograms = self.histograms.read().unwrap_or_else(|e| e.into_inner());
+        histograms
+            .iter()
+            .map(|(key, h)| {
+                let mut h = lock(h);
+                let snapshot = h.clone();
+                h.clear();
+                (key.clone(), snapshot)
+            })
+            .collect()
+    }
+
+    /// Apply an update to the histogram with the given key, creating it if needed.
+    fn update(&self, key: Key, f: impl FnOnce(&mut Histogram)) {
+        {
+            let histograms = self.histograms.read().unwrap_or_else(|e| e.into_inner());
+            if let Some(h) = histograms.get(&key) {
+                f(&mut lock(h));
+                return;
+            }
+        }
+
+        let mut histograms = self.histograms.write().unwrap_or_else(|e| e.into_inner());
+        let h = histograms
+            .entry(key)
+            .or_insert_with(|| Mutex::new(Histogram::new(self.size)));
+        f(&mut lock(h));
+    }
+}
+
+/// Lock a histogram. Histograms are never left in an inconsistent state by a panic, so
+/// poisoned locks are simply ignored.
+fn lock(h: &Mutex<Histogram>) -> MutexGuard<'_, Histogram> {
+    h.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key() {
+        let key = Key::new("latency");
+        assert_eq!(key.name(), "latency");
+        assert_eq!(key.labels(), &[]);
+        assert_eq!(key.to_string(), "latency");
+        assert_eq!(Key::from("latency"), key);
+        assert_eq!(Key::from("latency".to_string()), key);
+
+        let key = key.with_label("method", "GET").with_label("method", "POST");
+        assert_eq!(key.labels(), &[("method".to_string(), "POST".to_string())]);
+        assert_eq!(key.to_string(), r#"latency{method="POST"}"#);
+    }
+
+    #[test]
+    fn new() {
+        let registry = Registry::new(5);
+        assert_eq!(registry.size(), 5);
+        assert_eq!(registry.len(), 0);
+        assert!(registry.is_empty());
+        assert!(registry.get("latency").is_none());
+        assert!(registry.snapshot_all().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram size must be greater than 0")]
+    fn new_invalid_size() {
+        Registry::new(0);
+    }
+
+    #[test]
+    fn record() {
+        let registry = Registry::new(5);
+        let get = Key::new("latency").with_label("method", "GET");
+        let post = Key::new("latency").with_label("method", "POST");
+
+        registry.record(get.clone(), 1.0);
+        registry.record(get.clone(), 2.0);
+        registry.record(post.clone(), 3.0);
+        registry.record("latency", 4.0);
+
+        let snapshot = registry.snapshot_all();
+        assert_eq!(
+            snapshot.keys().collect::<Vec<_>>(),
+            vec![&Key::new("latency"), &get, &post]
+        );
+        assert_eq!(snapshot[&get].count(), 2);
+        assert_eq!(snapshot[&get].size(), 5);
+        assert_eq!(snapshot[&post].count(), 1);
+        assert_eq!(snapshot[&Key::new("latency")].count(), 1);
+    }
+
+    #[test]
+    fn record_concurrently() {
+        let registry = Registry::new(16);
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let registry = &registry;
+                s.spawn(move || {
+                    for i in 0..1000 {
+                        registry.record(format!("histogram{}", i % 3), (t * 1000 + i) as f64);
+                    }
+                });
+            }
+        });
+
+        let snapshot = registry.snapshot_all();
+        assert_eq!(snapshot.len(), 3);
+        assert_eq!(snapshot.values().map(|h| h.count()).sum::<u64>(), 4000);
+    }
+
+    #[test]
+    fn merge_from() {
+        let r1 = Registry::new(5);
+        r1.record("a", 1.0);
+        r1.record("b", 2.0);
+
+        let r2 = Registry::new(5);
+        r2.record("b", 3.0);
+        r2.record("c", 4.0);
+
+        r1.merge_from(r2.snapshot_all());
+        r1.merge_from(vec![("c", Histogram::from_iter(5, [5.0]))]);
+
+        let snapshot = r1.snapshot_all();
+        assert_eq!(snapshot.len(), 3);
+        assert_eq!(snapshot[&Key::new("a")].count(), 1);
+        assert_eq!(snapshot[&Key::new("b")].count(), 2);
+        assert_eq!(snapshot[&Key::new("c")].count(), 2);
+    }
+
+    #[test]
+    fn take_all() {
+        let registry = Registry::new(5);
+        registry.record("a", 1.0);
+        registry.record("b", 2.0);
+
+        let taken = registry.take_all();
+        assert_eq!(taken.len(), 2);
+        assert_eq!(taken[&Key::new("a")].count(), 1);
+        assert_eq!(taken[&Key::new("b")].max(), Some(2.0));
+
+        // histograms are cleared, but not removed
+        registry.record("a", 3.0);
+        let taken = registry.take_all();
+        assert_eq!(taken[&Key::new("a")].count(), 1);
+        assert_eq!(taken[&Key::new("a")].min(), Some(3.0));
+        assert!(taken[&Key::new("b")].is_empty());
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::bin::Bin;
+use crate::histogram::Histogram;
+
+/// A histogram over a sliding time window, which is split into a fixed number of
+/// fixed-duration sub-windows.
+///
+/// New values always go to the most recent sub-window. Once the current sub-window is
+/// over, the oldest one is discarded and an empty one is started, so that the memory
+/// usage stays bounded and every value is accounted for exactly once. Queries are
+/// answered over the union of all live sub-windows.
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use bhtt::RotatingHistogram;
+///
+/// // p99 over the last hour split into 12 windows of 5 minutes each
+/// let start = Instant::now();
+/// let mut h = RotatingHistogram::with_start(64, Duration::from_secs(300), 12, start);
+///
+/// h.insert_at(10.0, start);
+/// h.insert_at(20.0, start + Duration::from_secs(1800));
+/// assert_eq!(h.snapshot_at(start + Duration::from_secs(1800)).count(), 2);
+///
+/// // the first value expires once it is more than an hour old
+/// assert_eq!(h.snapshot_at(start + Duration::from_secs(3600)).count(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RotatingHistogram {
+    size: usize,
+    window: Duration,
+    windows: VecDeque<Histogram>,
+    current_start: Instant,
+}
+
+impl RotatingHistogram {
+    /// Create a new RotatingHistogram of the given size with `windows` sub-windows of
+    /// the given duration each. The first sub-window starts now.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use bhtt::RotatingHistogram;
+    ///
+    /// let h = RotatingHistogram::new(64, Duration::from_secs(60), 5);
+    /// assert_eq!(h.size(), 64);
+    /// assert_eq!(h.window(), Duration::from_secs(60));
+    /// assert_eq!(h.windows(), 5);
+    /// ```
+    pub fn new(size: usize, window: Duration, windows: usize) -> RotatingHistogram {
+        RotatingHistogram::with_start(size, window, windows, Instant::now())
+    }
+
+    /// Create a new RotatingHistogram like [`RotatingHistogram::new`], but with the first
+    /// sub-window starting at the given instant.
+    pub fn with_start(
+        size: usize,
+        window: Duration,
+        windows: usize,
+        start: Instant,
+    ) -> RotatingHistogram {
+        assert!(size > 0, "histogram size must be greater than 0");
+        assert!(
+            window > Duration::ZERO,
+            "window duration must be greater than 0"
+        );
+        assert!(windows > 0, "number of windows must be greater than 0");
+
+        RotatingHistogram {
+            size,
+            window,
+            windows: (0..windows).map(|_| Histogram::new(size)).collect(),
+            current_start: start,
+        }
+    }
+
+    /// Create a new RotatingHistogram of the given size, which tracks the values observed
+    /// during the last `window` units of time. The window is split into `precision`
+    /// sub-windows: the higher the precision, the more accurate the eviction of old values
+    /// is, but the more memory the histogram uses.
+    ///
+    /// Values are evicted a whole sub-window at a time, so queries cover between
+    /// `window - window / precision` and `window` units of time.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use bhtt::RotatingHistogram;
+    ///
+    /// // the last 10 minutes with the precision of 30 seconds
+    /// let h = RotatingHistogram::sliding(64, Duration::from_secs(600), 20);
+    /// assert_eq!(h.window(), Duration::from_secs(30));
+    /// assert_eq!(h.windows(), 20);
+    /// ```
+    pub fn sliding(size: usize, window: Duration, precision: usize) -> RotatingHistogram {
+        assert!(precision > 0, "precision must be greater than 0");
+
+        RotatingHistogram::new(size, window / precision as u32, precision)
+    }
+
+    /// Returns the size of the histogram.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the duration of a single sub-window.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Returns the number of sub-windows.
+    pub fn windows(&self) -> usize {
+        self.windows.len()
+    }
+
+    /// Insert a new value (or bin) into the current sub-window.
+    pub fn insert<T: Into<Bin>>(&mut self, value: T) {
+        self.insert_at(value, Instant::now());
+    }
+
+    /// Insert a new value (or bin) as observed at the given instant.
+    ///
+    /// Sub-windows are rotated first, if needed. Instants preceding the start of the
+    /// current sub-window are attributed to the current sub-window.
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use bhtt::RotatingHistogram;
+    ///
+    /// let start = Instant::now();
+    /// let mut h = RotatingHistogram::with_start(5, Duration::from_secs(1), 2, start);
+    /// h.insert_at(1.0, start);
+    /// h.insert_at(2.0, start + Duration::from_millis(1500));
+    ///
+    /// let snapshot = h.snapshot_at(start + Duration::from_millis(1500));
+    /// assert_eq!(snapshot.count(), 2);
+    /// assert_eq!(snapshot.min(), Some(1.0));
+    /// assert_eq!(snapshot.max(), Some(2.0));
+    /// ```
+    pub fn insert_at<T: Into<Bin>>(&mut self, value: T, now: Instant) {
+        self.rotate_to(now);
+        self.windows
+            .back_mut()
+            .expect("there is at least one window")
+            .insert(value);
+    }
+
+    /// Returns a histogram covering all live sub-windows.
+    pub fn snapshot(&mut self) -> Histogram {
+        self.snapshot_at(Instant::now())
+    }
+
+    /// Returns a histogram covering all sub-windows live at the given instant.
+    pub fn snapshot_at(&mut self, now: Instant) -> Histogram {
+        self.rotate_to(now);
+        Histogram::merge_all(self.size, &self.windows)
+    }
+
+    /// Returns an approximated value of the `q`'th quantile of the values in all live
+    /// sub-windows or `None` if there are none (see [`Histogram::quantile`]).
+    pub fn quantile(&mut self, q: f64) -> Option<f64> {
+        self.quantile_at(q, Instant::now())
+    }
+
+    /// Returns an approximated value of the `q`'th quantile of the values in all
+    /// sub-windows live at the given instant or `None` if there are none.
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use bhtt::RotatingHistogram;
+    ///
+    /// let start = Instant::now();
+    /// let mut h = RotatingHistogram::with_start(64, Duration::from_secs(60), 5, start);
+    /// for i in 0..100 {
+    ///     h.insert_at(1000.0 + i as f64, start);
+    /// }
+    /// for i in 0..100 {
+    ///     h.insert_at(i as f64, start + Duration::from_secs(240));
+    /// }
+    ///
+    /// assert!(h.quantile_at(0.5, start + Duration::from_secs(240)).unwrap() > 99.0);
+    /// assert!(h.quantile_at(0.5, start + Duration::from_secs(300)).unwrap() < 99.0);
+    /// ```
+    pub fn quantile_at(&mut self, q: f64, now: Instant) -> Option<f64> {
+        self.snapshot_at(now).quantile(q)
+    }
+
+    /// Discard sub-windows which are over by the given instant and start new ones.
+    pub fn rotate_to(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.current_start);
+        let steps = elapsed.as_nanos() / self.window.as_nanos();
+        if steps == 0 {
+            return;
+        }
+
+        let n = self.windows.len();
+        if steps >= n as u128 {
+            self.windows
+                .iter_mut()
+                .for_each(|h| *h = Histogram::new(self.size));
+        } else {
+            for _ in 0..steps {
+                self.windows.pop_front();
+                self.windows.push_back(Histogram::new(self.size));
+            }
+        }
+
+        // align the start of the current window to the grid of window boundaries
+        let remainder = elapsed.as_nanos() % self.window.as_nanos();
+        self.current_start = now - Duration::from_nanos(remainder as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn new() {
+        let h = RotatingHistogram::new(5, secs(10), 3);
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.window(), secs(10));
+        assert_eq!(h.windows(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram size must be greater than 0")]
+    fn new_invalid_size() {
+        RotatingHistogram::new(0, secs(10), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "window duration must be greater than 0")]
+    fn new_invalid_window() {
+        RotatingHistogram::new(5, Duration::ZERO, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "number of windows must be greater than 0")]
+    fn new_invalid_windows() {
+        RotatingHistogram::new(5, secs(10), 0);
+    }
+
+    #[test]
+    fn sliding() {
+        let h = RotatingHistogram::sliding(5, secs(60), 4);
+        assert_eq!(h.window(), secs(15));
+        assert_eq!(h.windows(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "precision must be greater than 0")]
+    fn sliding_invalid_precision() {
+        RotatingHistogram::sliding(5, secs(60), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "window duration must be greater than 0")]
+    fn sliding_too_precise() {
+        RotatingHistogram::sliding(5, Duration::from_nanos(10), 20);
+    }
+
+    #[test]
+    fn quantile() {
+        let start = Instant::now();
+        let mut h = RotatingHistogram::with_start(5, secs(10), 2, start);
+        assert_eq!(h.quantile_at(0.5, start), None);
+
+        h.insert_at(1.0, start);
+        h.insert_at(3.0, start + secs(10));
+        assert_eq!(h.quantile_at(0.0, start + secs(10)), Some(1.0));
+        assert_eq!(h.quantile_at(1.0, start + secs(10)), Some(3.0));
+        assert_eq!(h.quantile_at(0.0, start + secs(20)), Some(3.0));
+        assert_eq!(h.quantile_at(0.5, start + secs(30)), None);
+    }
+
+    #[test]
+    fn rotation() {
+        let start = Instant::now();
+        let mut h = RotatingHistogram::with_start(5, secs(10), 3, start);
+        assert_eq!(h.snapshot_at(start).count(), 0);
+
+        h.insert_at(1.0, start);
+        h.insert_at(2.0, start + secs(9));
+        h.insert_at(3.0, start + secs(10));
+        h.insert_at(4.0, start + secs(25));
+        assert_eq!(h.snapshot_at(start + secs(29)).count(), 4);
+
+        // the first window expires
+        let snapshot = h.snapshot_at(start + secs(30));
+        assert_eq!(snapshot.count(), 2);
+        assert_eq!(snapshot.min(), Some(3.0));
+        assert_eq!(snapshot.max(), Some(4.0));
+
+        // the second window expires
+        assert_eq!(h.snapshot_at(start + secs(40)).count(), 1);
+
+        // values observed in the past go to the current window
+        h.insert_at(5.0, start);
+        assert_eq!(h.snapshot_at(start + secs(49)).count(), 2);
+
+        // everything expires after a long pause
+        assert_eq!(h.snapshot_at(start + secs(1000)).count(), 0);
+        h.insert_at(6.0, start + secs(1000));
+        assert_eq!(h.snapshot_at(start + secs(1029)).count(), 1);
+        assert_eq!(h.snapshot_at(start + secs(1030)).count(), 0);
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut h = RotatingHistogram::new(5, secs(3600), 2);
+        h.insert(1.0);
+        h.insert(Bin::new(2.0, 3));
+
+        let snapshot = h.snapshot();
+        assert_eq!(snapshot.size(), 5);
+        assert_eq!(snapshot.count(), 4);
+    }
+}
@@ -0,0 +1,143 @@
+use rand::distributions::Distribution;
+use rand::Rng;
+
+use crate::histogram::{uniform_quantile, Histogram};
+
+/// Histograms approximate distributions of values, so they can be used to draw synthetic
+/// values, e.g. to generate load, which mimics the observed one. Values are drawn by inverse
+/// transform sampling: a quantile of a uniformly distributed random fraction is drawn
+/// (see [`Histogram::quantile`]).
+///
+/// The histogram must not be empty, or sampling will panic.
+///
+/// This is only available when the `rand` feature is enabled.
+///
+/// ```
+/// use bhtt::Histogram;
+/// use rand::distributions::Distribution;
+///
+/// let h = Histogram::from_iter(64, (1..=1000).map(|v| v as f64));
+///
+/// let mut rng = rand::thread_rng();
+/// let value = h.sample(&mut rng);
+/// assert!((1.0..=1000.0).contains(&value));
+/// ```
+impl Distribution<f64> for Histogram {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        assert!(!self.is_empty(), "histogram must not be empty");
+
+        self.quantile(rng.gen()).expect("histogram is not empty")
+    }
+}
+
+impl Histogram {
+    /// Returns `n` values drawn from the distribution of values approximated by the
+    /// histogram (see the implementation of [`Distribution`] for `Histogram`). This is
+    /// faster than drawing values one by one, as the cumulative counts of bins are only
+    /// computed once.
+    ///
+    /// The histogram must not be empty, or the function will panic.
+    ///
+    /// This is only available when the `rand` feature is enabled.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    /// use rand::SeedableRng;
+    ///
+    /// let h = Histogram::from_iter(64, (1..=1000).map(|v| v as f64));
+    ///
+    /// let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+    /// let values = h.sample_n(&mut rng, 100);
+    /// assert_eq!(values.len(), 100);
+    /// assert!(values.iter().all(|value| (1.0..=1000.0).contains(value)));
+    /// ```
+    pub fn sample_n<R: Rng + ?Sized>(&self, rng: &mut R, n: usize) -> Vec<f64> {
+        let (min_value, max_value) = match (self.min(), self.max()) {
+            (Some(min_value), Some(max_value)) => (min_value, max_value),
+            _ => panic!("histogram must not be empty"),
+        };
+
+        let prefix_counts: Vec<u64> = std::iter::once(0)
+            .chain(self.bins().iter().scan(0, |total, bin| {
+                *total += bin.count();
+                Some(*total)
+            }))
+            .collect();
+
+        (0..n)
+            .map(|_| {
+                uniform_quantile(
+                    self.bins(),
+                    self.count(),
+                    min_value,
+                    max_value,
+                    rng.gen(),
+                    Some(&prefix_counts),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn sample() {
+        let h = Histogram::from_iter(64, (0..10000).map(|v| (v as f64 / 100.0).powi(2)));
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let values: Vec<f64> = (0..10000).map(|_| h.sample(&mut rng)).collect();
+        assert!(values
+            .iter()
+            .all(|value| (h.min().unwrap()..=h.max().unwrap()).contains(value)));
+
+        // the distribution of drawn values is close to the original one (within 1% of
+        // the range of values)
+        let h2 = Histogram::from_iter(64, values);
+        for q in [0.1, 0.25, 0.5, 0.75, 0.9] {
+            assert_abs_diff_eq!(
+                h2.quantile(q).unwrap(),
+                h.quantile(q).unwrap(),
+                epsilon = 100.0
+            );
+        }
+        assert_relative_eq!(h2.mean().unwrap(), h.mean().unwrap(), max_relative = 0.05);
+    }
+
+    #[test]
+    fn sample_n() {
+        let h = Histogram::from_iter(64, (0..10000).map(|v| (v as f64 / 100.0).powi(2)));
+
+        // drawing values one by one and in a batch gives the same results
+        let values = h.sample_n(&mut StdRng::seed_from_u64(42), 1000);
+        let mut rng = StdRng::seed_from_u64(42);
+        let expected: Vec<f64> = (0..1000).map(|_| h.sample(&mut rng)).collect();
+        assert_eq!(values, expected);
+
+        assert!(h.sample_n(&mut rng, 0).is_empty());
+    }
+
+    #[test]
+    fn single_value() {
+        let h = Histogram::from_iter(5, [(2.5, 10)]);
+        let mut rng = StdRng::seed_from_u64(42);
+        assert!(h.sample_n(&mut rng, 100).iter().all(|&value| value == 2.5));
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram must not be empty")]
+    fn sample_empty() {
+        Histogram::new(5).sample(&mut StdRng::seed_from_u64(42));
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram must not be empty")]
+    fn sample_n_empty() {
+        Histogram::new(5).sample_n(&mut StdRng::seed_from_u64(42), 1);
+    }
+}
@@ -0,0 +1,153 @@
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::data::HistogramData;
+use crate::histogram::Histogram;
+
+/// Histograms are serialized as a struct with the following fields (in this exact order):
+/// `size: u64`, `count: u64`, `bins: Vec<(value: f64, count: u64)>`, `min_value: Option<f64>`,
+/// `max_value: Option<f64>`, `sum: f64`, `nan_count: u64`, `underflow_count: u64`,
+/// `overflow_count: u64`. Settings of histograms (e.g. policies) are not serialized.
+///
+/// This is only available when the `serde` feature is enabled.
+///
+/// ```
+/// use bhtt::Histogram;
+///
+/// let h = Histogram::from_iter(5, (1..=100).map(|v| v as f64));
+///
+/// let bytes = postcard::to_allocvec(&h).unwrap();
+/// let h2: Histogram = postcard::from_bytes(&bytes).unwrap();
+/// assert_eq!(h2.bins(), h.bins());
+/// assert_eq!(h2.min(), Some(1.0));
+/// assert_eq!(h2.max(), Some(100.0));
+/// ```
+impl Serialize for Histogram {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        HistogramData::from(self).serialize(serializer)
+    }
+}
+
+/// Deserialized data is validated (see [`Histogram::try_from`](TryFrom::try_from)), so
+/// corrupted data results in an error rather than a broken histogram.
+///
+/// This is only available when the `serde` feature is enabled.
+impl<'de> Deserialize<'de> for Histogram {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = HistogramData::deserialize(deserializer)?;
+
+        Histogram::try_from(data).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bin::Bin;
+    use crate::policy::{InfinityPolicy, NanPolicy};
+
+    fn histogram() -> Histogram {
+        let mut h = Histogram::new(4);
+        h.set_nan_policy(NanPolicy::Count);
+        h.set_infinity_policy(InfinityPolicy::Count);
+        h.insert_many([1.0, 2.0, 2.0, 0.5, -3.0]);
+        for value in [f64::NAN, f64::INFINITY, f64::INFINITY] {
+            h.try_insert(value).unwrap();
+        }
+
+        h
+    }
+
+    fn assert_same(h: &Histogram, expected: &Histogram) {
+        assert_eq!(h.size(), expected.size());
+        assert_eq!(h.count(), expected.count());
+        assert_eq!(h.bins(), expected.bins());
+        assert_eq!(h.min(), expected.min());
+        assert_eq!(h.max(), expected.max());
+        assert_eq!(h.sum(), expected.sum());
+        assert_eq!(h.nan_count(), expected.nan_count());
+        assert_eq!(h.underflow_count(), expected.underflow_count());
+        assert_eq!(h.overflow_count(), expected.overflow_count());
+    }
+
+    #[test]
+    fn postcard_round_trip() {
+        for h in [Histogram::new(3), histogram()] {
+            let bytes = postcard::to_allocvec(&h).unwrap();
+            let h2: Histogram = postcard::from_bytes(&bytes).unwrap();
+            assert_same(&h2, &h);
+        }
+    }
+
+    #[test]
+    fn bincode_round_trip() {
+        let config = bincode::config::standard();
+        for h in [Histogram::new(3), histogram()] {
+            let bytes = bincode::serde::encode_to_vec(&h, config).unwrap();
+            let (h2, read): (Histogram, usize) =
+                bincode::serde::decode_from_slice(&bytes, config).unwrap();
+            assert_eq!(read, bytes.len());
+            assert_same(&h2, &h);
+        }
+    }
+
+    // the serialized form must never change, as it's read by other programs and
+    // from persisted data
+    #[test]
+    fn postcard_stable_format() {
+        let h = Histogram::from_iter(3, [(1.0, 2), (2.5, 1)]);
+        let expected: Vec<u8> = [
+            vec![3, 3, 2],                 // size, count, the number of bins
+            1.0f64.to_le_bytes().to_vec(), // the first bin
+            vec![2],
+            2.5f64.to_le_bytes().to_vec(), // the second bin
+            vec![1],
+            vec![1], // min_value
+            1.0f64.to_le_bytes().to_vec(),
+            vec![1], // max_value
+            2.5f64.to_le_bytes().to_vec(),
+            4.5f64.to_le_bytes().to_vec(), // sum
+            vec![0, 0, 0],                 // NaN, underflow and overflow counts
+        ]
+        .concat();
+
+        assert_eq!(postcard::to_allocvec(&h).unwrap(), expected);
+        let h2: Histogram = postcard::from_bytes(&expected).unwrap();
+        assert_eq!(h2.bins(), &[Bin::new(1.0, 2), Bin::new(2.5, 1)]);
+    }
+
+    #[test]
+    fn bincode_stable_format() {
+        let h = Histogram::from_iter(3, [(1.0, 2)]);
+        let config = bincode::config::legacy();
+        let expected: Vec<u8> = [
+            3u64.to_le_bytes().to_vec(), // size
+            2u64.to_le_bytes().to_vec(), // count
+            1u64.to_le_bytes().to_vec(), // the number of bins
+            1.0f64.to_le_bytes().to_vec(),
+            2u64.to_le_bytes().to_vec(),
+            vec![1], // min_value
+            1.0f64.to_le_bytes().to_vec(),
+            vec![1], // max_value
+            1.0f64.to_le_bytes().to_vec(),
+            2.0f64.to_le_bytes().to_vec(), // sum
+            [0u64.to_le_bytes(); 3].concat(),
+        ]
+        .concat();
+
+        assert_eq!(bincode::serde::encode_to_vec(&h, config).unwrap(), expected);
+    }
+
+    #[test]
+    fn invalid_data() {
+        let h = Histogram::from_iter(3, [(1.0, 2), (2.5, 1)]);
+        let mut bytes = postcard::to_allocvec(&h).unwrap();
+
+        // the count no longer matches the counts of bins
+        bytes[1] = 4;
+        let err = postcard::from_bytes::<Histogram>(&bytes).unwrap_err();
+        assert!(matches!(err, postcard::Error::SerdeDeCustom));
+
+        assert!(postcard::from_bytes::<Histogram>(&bytes[..10]).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+//! A vectorized search for the closest pair of adjacent bins.
+//!
+//! This is only available when the `simd` feature is enabled.
+//!
+//! The differences between values of adjacent bins are computed in chunks with SSE2 on
+//! x86_64 (and with plain arrays, which the compiler is free to vectorize, elsewhere). The
+//! result is the same as that of the scalar search in `Histogram::find_closest_bins()`.
+
+use crate::bin::Bin;
+
+/// The number of differences between values of adjacent bins computed at once.
+const LANES: usize = 4;
+
+/// Find a pair of adjacent bins, whose values are closest to each other. If distances are
+/// equal, a pair of bins with smaller total count is preferred, and then the leftmost one.
+/// The bins must be sorted, and there must be at least two of them.
+pub(crate) fn find_closest_bins(bins: &[Bin]) -> (usize, usize) {
+    debug_assert!(bins.len() >= 2);
+
+    // find the minimum distance first, and then break the ties among the pairs of bins,
+    // which are that far apart. The latter are rare, unless values are e.g. integers
+    let distance = min_distance(bins);
+    let mut best: Option<(u64, usize)> = None;
+    for_each_match(bins, distance, |right| {
+        let count = bins[right - 1].count() + bins[right].count();
+        if best.is_none_or(|(best_count, _)| count < best_count) {
+            best = Some((count, right));
+        }
+    });
+
+    let right = best.map_or(bins.len() - 1, |(_, right)| right);
+    (right - 1, right)
+}
+
+/// Returns the differences between values of bins `[start + 1; start + LANES]` and their
+/// left neighbours.
+#[inline(always)]
+fn differences(bins: &[Bin], start: usize) -> [f64; LANES] {
+    let values: [f64; LANES + 1] = std::array::from_fn(|i| bins[start + i].value());
+    std::array::from_fn(|i| values[i + 1] - values[i])
+}
+
+/// Returns the minimum difference between values of adjacent bins.
+#[cfg(target_arch = "x86_64")]
+fn min_distance(bins: &[Bin]) -> f64 {
+    use std::arch::x86_64::*;
+
+    let chunks = (bins.len() - 1) / LANES;
+    let mut lanes = [f64::INFINITY; 2];
+    // SSE2 is a part of the x86_64 baseline, so the instructions are always available
+    unsafe {
+        let (mut low, mut high) = (_mm_set1_pd(f64::INFINITY), _mm_set1_pd(f64::INFINITY));
+        for chunk in 0..chunks {
+            let d = differences(bins, chunk * LANES);
+            low = _mm_min_pd(low, _mm_set_pd(d[1], d[0]));
+            high = _mm_min_pd(high, _mm_set_pd(d[3], d[2]));
+        }
+        _mm_storeu_pd(lanes.as_mut_ptr(), _mm_min_pd(low, high));
+    }
+
+    (chunks * LANES + 1..bins.len())
+        .map(|i| bins[i].value() - bins[i - 1].value())
+        .fold(lanes[0].min(lanes[1]), f64::min)
+}
+
+/// Returns the minimum difference between values of adjacent bins.
+#[cfg(not(target_arch = "x86_64"))]
+fn min_distance(bins: &[Bin]) -> f64 {
+    let chunks = (bins.len() - 1) / LANES;
+    let mut min = [f64::INFINITY; LANES];
+    for chunk in 0..chunks {
+        let d = differences(bins, chunk * LANES);
+        for (min, d) in min.iter_mut().zip(d) {
+            *min = min.min(d);
+        }
+    }
+
+    (chunks * LANES + 1..bins.len())
+        .map(|i| bins[i].value() - bins[i - 1].value())
+        .fold(min.into_iter().fold(f64::INFINITY, f64::min), f64::min)
+}
+
+/// Call `f` with the index of the right bin of every pair of adjacent bins, which are
+/// exactly `distance` apart, from left to right.
+#[cfg(target_arch = "x86_64")]
+fn for_each_match(bins: &[Bin], distance: f64, mut f: impl FnMut(usize)) {
+    use std::arch::x86_64::*;
+
+    let chunks = (bins.len() - 1) / LANES;
+    for chunk in 0..chunks {
+        let start = chunk * LANES;
+        let d = differences(bins, start);
+        // SSE2 is a part of the x86_64 baseline, so the instructions are always available
+        let mut mask = unsafe {
+            let target = _mm_set1_pd(distance);
+            let low = _mm_movemask_pd(_mm_cmpeq_pd(_mm_set_pd(d[1], d[0]), target));
+            let high = _mm_movemask_pd(_mm_cmpeq_pd(_mm_set_pd(d[3], d[2]), target));
+            low | high << 2
+        };
+        while mask != 0 {
+            f(start + 1 + mask.trailing_zeros() as usize);
+            mask &= mask - 1;
+        }
+    }
+
+    for i in chunks * LANES + 1..bins.len() {
+        if bins[i].value() - bins[i - 1].value() == distance {
+            f(i);
+        }
+    }
+}
+
+/// Call `f` with the index of the right bin of every pair of adjacent bins, which are
+/// exactly `distance` apart, from left to right.
+#[cfg(not(target_arch = "x86_64"))]
+fn for_each_match(bins: &[Bin], distance: f64, mut f: impl FnMut(usize)) {
+    for i in 1..bins.len() {
+        if bins[i].value() - bins[i - 1].value() == distance {
+            f(i);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utilities::pseudo_random_values;
+
+    use super::*;
+
+    // The reference implementation: the scalar search of Histogram.
+    fn find_closest_bins_by_scan(bins: &[Bin]) -> (usize, usize) {
+        let right = (1..bins.len())
+            .min_by(|&i, &j| {
+                let distance = |k: usize| bins[k].value() - bins[k - 1].value();
+                let count = |k: usize| bins[k - 1].count() + bins[k].count();
+                distance(i)
+                    .total_cmp(&distance(j))
+                    .then(count(i).cmp(&count(j)))
+            })
+            .unwrap();
+
+        (right - 1, right)
+    }
+
+    #[test]
+    fn closest_bins() {
+        // the number of bins is not a multiple of the number of lanes
+        let mut bins: Vec<Bin> = (0..11).map(|i| Bin::new((i * i) as f64, 1)).collect();
+        assert_eq!(find_closest_bins(&bins), (0, 1));
+
+        // the closest pair is in the tail
+        bins[10] = Bin::new(81.5, 1);
+        assert_eq!(find_closest_bins(&bins), (9, 10));
+
+        // ties are resolved by the total count, and then by the position
+        let bins: Vec<Bin> = (0..10).map(|i| Bin::new(i as f64, 10 - i / 3)).collect();
+        assert_eq!(find_closest_bins(&bins), (8, 9));
+        let bins: Vec<Bin> = (0..10).map(|i| Bin::new(i as f64, 1)).collect();
+        assert_eq!(find_closest_bins(&bins), (0, 1));
+
+        // the differences of extreme values overflow
+        let bins = [Bin::new(f64::MIN, 1), Bin::new(f64::MAX, 1)];
+        assert_eq!(find_closest_bins(&bins), (0, 1));
+    }
+
+    #[test]
+    fn same_as_scan() {
+        for len in 2..200 {
+            let mut bins: Vec<Bin> = pseudo_random_values(len as u64, len)
+                .into_iter()
+                .map(|value| {
+                    // few distinct values and counts, so that ties are common
+                    let count = 1 + (value.fract() * 1024.0) as u64 % 3;
+                    Bin::new((value / 16.0).floor(), count)
+                })
+                .collect();
+            bins.sort();
+
+            assert_eq!(
+                find_closest_bins(&bins),
+                find_closest_bins_by_scan(&bins),
+                "len = {}",
+                len
+            );
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, watch};
+
+use crate::bin::Bin;
+use crate::error::Error;
+use crate::histogram::Histogram;
+
+/// A message sent to a [`HistogramSink`].
+#[derive(Debug)]
+enum Message {
+    Value(Bin),
+    Batch(Vec<Bin>),
+}
+
+/// An asynchronous task, which owns a histogram and updates it with values received from
+/// a channel, so that tokio services can record values (e.g. latencies of requests)
+/// without locking the histogram on the hot path.
+///
+/// Values are sent through cloneable [`SinkSender`]s. The sink applies all received values
+/// and publishes a snapshot of the histogram whenever the channel is drained, so readers
+/// see updates with a small delay. Snapshots are read through cloneable
+/// [`SnapshotHandle`]s. The sink stops, once all senders are dropped.
+///
+/// This type is only available when the `tokio` feature is enabled.
+///
+/// ```
+/// use bhtt::HistogramSink;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let (sink, sender) = HistogramSink::new(64, 1024);
+/// let snapshots = sink.snapshots();
+/// let task = tokio::spawn(sink.run());
+///
+/// for value in [1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+///     sender.send(value).await.unwrap();
+/// }
+/// sender.send_batch(vec![42.0, 43.0]).await.unwrap();
+///
+/// // the sink stops once all senders are dropped
+/// drop(sender);
+/// let h = task.await.unwrap();
+/// assert_eq!(h.count(), 12);
+/// assert_eq!(snapshots.snapshot().count(), 12);
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct HistogramSink {
+    histogram: Histogram,
+    receiver: mpsc::Receiver<Message>,
+    published: watch::Sender<Arc<Histogram>>,
+}
+
+/// The sending half of a [`HistogramSink`].
+///
+/// Senders can be cloned and shared between tasks.
+///
+/// This type is only available when the `tokio` feature is enabled.
+#[derive(Debug, Clone)]
+pub struct SinkSender {
+    sender: mpsc::Sender<Message>,
+}
+
+/// A handle to read the snapshots published by a [`HistogramSink`].
+///
+/// Handles can be cloned and shared between tasks. Taking a snapshot is cheap and never
+/// blocks the sink.
+///
+/// This type is only available when the `tokio` feature is enabled.
+#[derive(Debug, Clone)]
+pub struct SnapshotHandle {
+    published: watch::Receiver<Arc<Histogram>>,
+}
+
+impl HistogramSink {
+    /// Create a new HistogramSink of a histogram with the given number of bins and a sender
+    /// of values to it. The channel buffers up to `buffer` messages: when it is full,
+    /// [`send`](SinkSender::send) waits and [`try_send`](SinkSender::try_send) fails.
+    /// The buffer must be greater than 0, or the function will panic.
+    pub fn new(size: usize, buffer: usize) -> (HistogramSink, SinkSender) {
+        HistogramSink::from_histogram(Histogram::new(size), buffer)
+    }
+
+    /// Create a new HistogramSink of an existing histogram (see
+    /// [`new`](HistogramSink::new)). The histogram is published immediately.
+    ///
+    /// ```
+    /// use bhtt::{Histogram, HistogramSink};
+    ///
+    /// let (sink, _sender) = HistogramSink::from_histogram(Histogram::from_iter(5, [1.0]), 16);
+    /// assert_eq!(sink.snapshots().snapshot().count(), 1);
+    /// ```
+    pub fn from_histogram(histogram: Histogram, buffer: usize) -> (HistogramSink, SinkSender) {
+        assert!(buffer > 0, "buffer must be greater than 0");
+
+        let (sender, receiver) = mpsc::channel(buffer);
+        let (published, _) = watch::channel(Arc::new(histogram.clone()));
+
+        let sink = HistogramSink {
+            histogram,
+            receiver,
+            published,
+        };
+        (sink, SinkSender { sender })
+    }
+
+    /// Returns a new handle to read the snapshots published by this sink.
+    pub fn snapshots(&self) -> SnapshotHandle {
+        SnapshotHandle {
+            published: self.published.subscribe(),
+        }
+    }
+
+    /// Receive values and update the histogram, until all senders are dropped. Returns
+    /// the final state of the histogram, which is also published.
+    ///
+    /// This is meant to be spawned as a separate task (e.g. with `tokio::spawn`).
+    pub async fn run(mut self) -> Histogram {
+        while let Some(message) = self.receiver.recv().await {
+            self.apply(message);
+            // apply all the values, which are already buffered, so that snapshots are not
+            // taken more often than necessary under load
+            while let Ok(message) = self.receiver.try_recv() {
+                self.apply(message);
+            }
+
+            self.published
+                .send_replace(Arc::new(self.histogram.clone()));
+        }
+
+        self.histogram
+    }
+
+    fn apply(&mut self, message: Message) {
+        match message {
+            Message::Value(bin) => self.histogram.insert(bin),
+            Message::Batch(bins) => self.histogram.insert_many(bins),
+        }
+    }
+}
+
+impl SinkSender {
+    /// Send a new value (or bin) to the sink, waiting for free space in the channel, if it
+    /// is full.
+    ///
+    /// Returns [`Error::Closed`], if the sink has stopped.
+    pub async fn send<T: Into<Bin>>(&self, value: T) -> Result<(), Error> {
+        self.sender
+            .send(Message::Value(value.into()))
+            .await
+            .map_err(|_| Error::Closed)
+    }
+
+    /// Send many values (or bins) to the sink as a single message, which is cheaper than
+    /// sending them one by one (see [`Histogram::insert_many`]).
+    ///
+    /// Returns [`Error::Closed`], if the sink has stopped.
+    pub async fn send_batch<T: Into<Bin>>(
+        &self,
+        values: impl IntoIterator<Item = T>,
+    ) -> Result<(), Error> {
+        let bins = values.into_iter().map(Into::into).collect();
+        self.sender
+            .send(Message::Batch(bins))
+            .await
+            .map_err(|_| Error::Closed)
+    }
+
+    /// Send a new value (or bin) to the sink without waiting, e.g. from synchronous code.
+    ///
+    /// Returns [`Error::Full`], if the channel is full (the value is dropped), or
+    /// [`Error::Closed`], if the sink has stopped.
+    ///
+    /// ```
+    /// use bhtt::{Error, HistogramSink};
+    ///
+    /// let (sink, sender) = HistogramSink::new(64, 1);
+    /// assert_eq!(sender.try_send(1.0), Ok(()));
+    /// assert_eq!(sender.try_send(2.0), Err(Error::Full));
+    ///
+    /// drop(sink);
+    /// assert_eq!(sender.try_send(3.0), Err(Error::Closed));
+    /// ```
+    pub fn try_send<T: Into<Bin>>(&self, value: T) -> Result<(), Error> {
+        self.sender
+            .try_send(Message::Value(value.into()))
+            .map_err(|err| match err {
+                mpsc::error::TrySendError::Full(_) => Error::Full,
+                mpsc::error::TrySendError::Closed(_) => Error::Closed,
+            })
+    }
+}
+
+impl SnapshotHandle {
+    /// Returns the last published snapshot of the histogram.
+    pub fn snapshot(&self) -> Arc<Histogram> {
+        Arc::clone(&self.published.borrow())
+    }
+
+    /// Wait until a new snapshot is published, and return it.
+    ///
+    /// Returns [`Error::Closed`], if the sink has stopped.
+    pub async fn changed(&mut self) -> Result<Arc<Histogram>, Error> {
+        self.published.changed().await.map_err(|_| Error::Closed)?;
+
+        Ok(Arc::clone(&self.published.borrow_and_update()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sink() {
+        let (sink, sender) = HistogramSink::new(8, 16);
+        let mut snapshots = sink.snapshots();
+        assert_eq!(snapshots.snapshot().count(), 0);
+        let task = tokio::spawn(sink.run());
+
+        let senders: Vec<_> = (0..4)
+            .map(|i| {
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    for v in 0..250 {
+                        sender.send((i * 250 + v) as f64).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for sender in senders {
+            sender.await.unwrap();
+        }
+
+        sender.send(Bin::new(-1.0, 10)).await.unwrap();
+        sender.send_batch([2000.0, 3000.0]).await.unwrap();
+        assert!(snapshots.changed().await.unwrap().count() > 0);
+
+        drop(sender);
+        let h = task.await.unwrap();
+        assert_eq!(h.count(), 1012);
+        assert_eq!(h.min(), Some(-1.0));
+        assert_eq!(h.max(), Some(3000.0));
+        assert_eq!(*snapshots.snapshot(), h);
+    }
+
+    #[tokio::test]
+    async fn closed() {
+        let (sink, sender) = HistogramSink::new(8, 16);
+        let mut snapshots = sink.snapshots();
+        drop(sink);
+
+        assert_eq!(snapshots.changed().await, Err(Error::Closed));
+
+        assert_eq!(sender.send(1.0).await, Err(Error::Closed));
+        assert_eq!(sender.send_batch([1.0]).await, Err(Error::Closed));
+        assert_eq!(sender.try_send(1.0), Err(Error::Closed));
+    }
+
+    #[test]
+    #[should_panic(expected = "buffer must be greater than 0")]
+    fn zero_buffer() {
+        HistogramSink::new(8, 0);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::histogram::Histogram;
+
+/// The common interface of quantile sketches, i.e. compact approximate representations of
+/// the distribution of values, which can be merged together.
+///
+/// Code written against this trait works with any implementation, so that the accuracy
+/// and the performance of different algorithms can be compared on the same data without
+/// changing call sites.
+///
+/// ```
+/// use bhtt::{Histogram, Sketch, TDigest};
+///
+/// fn p99<S: Sketch>(mut sketch: S, values: &[f64]) -> Option<f64> {
+///     for &value in values {
+///         sketch.insert(value);
+///     }
+///     sketch.quantile(0.99)
+/// }
+///
+/// let values: Vec<f64> = (1..=1000).map(|v| v as f64).collect();
+/// let bhtt = p99(Histogram::new(64), &values).unwrap();
+/// let tdigest = p99(TDigest::new(100.0), &values).unwrap();
+/// assert!((bhtt - 990.0).abs() < 5.0);
+/// assert!((tdigest - 990.0).abs() < 5.0);
+/// ```
+pub trait Sketch {
+    /// Insert a new value. The value must not be NaN or infinite, or the function will
+    /// panic.
+    fn insert(&mut self, value: f64);
+
+    /// Merge another sketch into this one.
+    fn merge(&mut self, other: &Self);
+
+    /// Returns the number of values in the sketch.
+    fn count(&self) -> u64;
+
+    /// Returns the minimum value in the sketch or `None` if the sketch is empty.
+    fn min(&self) -> Option<f64>;
+
+    /// Returns the maximum value in the sketch or `None` if the sketch is empty.
+    fn max(&self) -> Option<f64>;
+
+    /// Returns an estimate of the `q`'th quantile or `None` if the sketch is empty. `q` must
+    /// be in the range [0.0; 1.0], or the function will panic.
+    fn quantile(&self, q: f64) -> Option<f64>;
+
+    /// Returns an estimate of the fraction of values less than or equal to `value` or `None`
+    /// if the sketch is empty. The value must not be NaN, or the function will panic.
+    fn rank(&self, value: f64) -> Option<f64>;
+
+    /// Returns true, if the sketch is empty.
+    fn is_empty(&self) -> bool {
+        self.count() == 0
+    }
+
+    /// Insert many values one by one (see [`insert`](Sketch::insert)).
+    fn insert_many(&mut self, values: impl IntoIterator<Item = f64>)
+    where
+        Self: Sized,
+    {
+        for value in values {
+            self.insert(value);
+        }
+    }
+}
+
+impl Sketch for Histogram {
+    fn insert(&mut self, value: f64) {
+        Histogram::insert(self, value);
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        Histogram::merge(self, other);
+    }
+
+    fn count(&self) -> u64 {
+        Histogram::count(self)
+    }
+
+    fn min(&self) -> Option<f64> {
+        Histogram::min(self)
+    }
+
+    fn max(&self) -> Option<f64> {
+        Histogram::max(self)
+    }
+
+    fn quantile(&self, q: f64) -> Option<f64> {
+        Histogram::quantile(self, q)
+    }
+
+    fn rank(&self, value: f64) -> Option<f64> {
+        Histogram::rank(self, value)
+    }
+
+    fn insert_many(&mut self, values: impl IntoIterator<Item = f64>) {
+        Histogram::insert_many(self, values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exact::ExactSample;
+    use crate::gk::GKSketch;
+    use crate::logarithmic::LogHistogram;
+    use crate::tdigest::TDigest;
+
+    // Exercise a sketch only through the trait.
+    fn check<S: Sketch>(mut sketch: S, mut other: S) {
+        assert!(sketch.is_empty());
+        assert_eq!(sketch.quantile(0.5), None);
+        assert_eq!(sketch.rank(0.0), None);
+
+        sketch.insert_many((1..=500).map(|v| v as f64));
+        other.insert_many((501..=1000).map(|v| v as f64));
+        sketch.merge(&other);
+
+        assert!(!sketch.is_empty());
+        assert_eq!(sketch.count(), 1000);
+        assert_eq!(sketch.min(), Some(1.0));
+        assert_eq!(sketch.max(), Some(1000.0));
+        assert_eq!(sketch.quantile(0.0), Some(1.0));
+        assert_eq!(sketch.quantile(1.0), Some(1000.0));
+        assert_relative_eq!(sketch.quantile(0.5).unwrap(), 500.0, max_relative = 0.01);
+        assert_relative_eq!(sketch.rank(250.0).unwrap(), 0.25, max_relative = 0.01);
+        assert_eq!(sketch.rank(0.0), Some(0.0));
+        assert_eq!(sketch.rank(1000.0), Some(1.0));
+    }
+
+    #[test]
+    fn exact_sample() {
+        check(ExactSample::new(1000), ExactSample::new(1000));
+    }
+
+    #[test]
+    fn gk_sketch() {
+        check(GKSketch::new(0.001), GKSketch::new(0.001));
+    }
+
+    #[test]
+    fn histogram() {
+        check(Histogram::new(64), Histogram::new(64));
+    }
+
+    #[test]
+    fn tdigest() {
+        check(TDigest::new(100.0), TDigest::new(100.0));
+    }
+
+    #[test]
+    fn log_histogram() {
+        check(LogHistogram::new(0.001), LogHistogram::new(0.001));
+    }
+}
@@ -0,0 +1,216 @@
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use crate::bin::Bin;
+use crate::histogram::Histogram;
+
+/// The writing half of a histogram shared between a single writer thread and many
+/// reader threads.
+///
+/// The writer owns the histogram and updates it without any synchronization. Its state
+/// is made visible to readers by [`publish`](Self::publish), which atomically replaces
+/// the snapshot seen by readers. Readers never block the writer (and vice versa).
+///
+/// This type is only available when the `sync` feature is enabled.
+///
+/// ```
+/// use bhtt::HistogramWriter;
+///
+/// let mut writer = HistogramWriter::new(64);
+/// let reader = writer.reader();
+///
+/// let exporter = std::thread::spawn(move || {
+///     // readers see the state as of the last call to publish()
+///     reader.snapshot().count()
+/// });
+///
+/// for value in vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+///     writer.insert(value);
+/// }
+/// writer.publish();
+///
+/// let count = exporter.join().unwrap();
+/// assert!(count == 0 || count == 10);
+/// assert_eq!(writer.reader().snapshot().count(), 10);
+/// ```
+#[derive(Debug)]
+pub struct HistogramWriter {
+    histogram: Histogram,
+    published: Arc<ArcSwap<Histogram>>,
+}
+
+/// The reading half of a histogram shared between a single writer thread and many
+/// reader threads (see [`HistogramWriter`]).
+///
+/// Readers can be cloned and sent to other threads. Taking a snapshot is cheap and
+/// never blocks the writer.
+///
+/// This type is only available when the `sync` feature is enabled.
+#[derive(Debug, Clone)]
+pub struct HistogramReader {
+    published: Arc<ArcSwap<Histogram>>,
+}
+
+impl HistogramWriter {
+    /// Create a new HistogramWriter of a histogram with the given number of bins.
+    ///
+    /// ```
+    /// use bhtt::HistogramWriter;
+    ///
+    /// let writer = HistogramWriter::new(64);
+    /// assert_eq!(writer.histogram().size(), 64);
+    /// ```
+    pub fn new(size: usize) -> HistogramWriter {
+        HistogramWriter::from_histogram(Histogram::new(size))
+    }
+
+    /// Create a new HistogramWriter of an existing histogram. The histogram
+    /// is published immediately.
+    ///
+    /// ```
+    /// use bhtt::{Histogram, HistogramWriter};
+    ///
+    /// let writer = HistogramWriter::from_histogram(Histogram::from_iter(5, &[1.0, 2.0]));
+    /// assert_eq!(writer.reader().snapshot().count(), 2);
+    /// ```
+    pub fn from_histogram(histogram: Histogram) -> HistogramWriter {
+        HistogramWriter {
+            published: Arc::new(ArcSwap::from_pointee(histogram.clone())),
+            histogram,
+        }
+    }
+
+    /// Returns a new reader of the snapshots published by this writer.
+    pub fn reader(&self) -> HistogramReader {
+        HistogramReader {
+            published: Arc::clone(&self.published),
+        }
+    }
+
+    /// Returns the current (possibly not yet published) state of the histogram.
+    pub fn histogram(&self) -> &Histogram {
+        &self.histogram
+    }
+
+    /// Update the histogram by inserting a new value (or bin). The change is not
+    /// visible to readers until the histogram is published.
+    ///
+    /// ```
+    /// use bhtt::{Bin, HistogramWriter};
+    ///
+    /// let mut writer = HistogramWriter::new(5);
+    /// writer.insert(42.0);
+    /// writer.insert(Bin::new(-7.5, 10));
+    ///
+    /// assert_eq!(writer.histogram().count(), 11);
+    /// assert_eq!(writer.reader().snapshot().count(), 0);
+    /// ```
+    pub fn insert<T: Into<Bin>>(&mut self, value: T) {
+        self.histogram.insert(value);
+    }
+
+    /// Merge another histogram into this one. The change is not visible to readers
+    /// until the histogram is published.
+    pub fn merge(&mut self, other: &Histogram) {
+        self.histogram.merge(other);
+    }
+
+    /// Make the current state of the histogram visible to readers.
+    ///
+    /// ```
+    /// use bhtt::HistogramWriter;
+    ///
+    /// let mut writer = HistogramWriter::new(5);
+    /// writer.insert(42.0);
+    /// writer.publish();
+    ///
+    /// assert_eq!(writer.reader().snapshot().count(), 1);
+    /// ```
+    pub fn publish(&self) {
+        self.published.store(Arc::new(self.histogram.clone()));
+    }
+}
+
+impl HistogramReader {
+    /// Returns the last published snapshot of the histogram.
+    ///
+    /// ```
+    /// use bhtt::HistogramWriter;
+    ///
+    /// let mut writer = HistogramWriter::new(5);
+    /// let reader = writer.reader();
+    ///
+    /// writer.insert(42.0);
+    /// writer.publish();
+    /// assert_eq!(reader.snapshot().quantile(0.5), Some(42.0));
+    /// ```
+    pub fn snapshot(&self) -> Arc<Histogram> {
+        self.published.load_full()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new() {
+        let writer = HistogramWriter::new(5);
+        let snapshot = writer.reader().snapshot();
+
+        assert_eq!(snapshot.size(), 5);
+        assert_eq!(snapshot.count(), 0);
+        assert_eq!(snapshot.bins(), &[]);
+    }
+
+    #[test]
+    fn publish() {
+        let mut writer = HistogramWriter::new(5);
+        let reader = writer.reader();
+
+        writer.insert(1.0);
+        writer.merge(&Histogram::from_iter(5, [2.0, 3.0]));
+        let before = reader.snapshot();
+        writer.publish();
+        let after = reader.snapshot();
+
+        // snapshots are immutable
+        assert_eq!(before.count(), 0);
+        assert_eq!(after.count(), 3);
+        assert_eq!(after.bins(), writer.histogram().bins());
+        assert_eq!(reader.clone().snapshot().count(), 3);
+    }
+
+    #[test]
+    fn publish_concurrently() {
+        let mut writer = HistogramWriter::new(16);
+        let reader = writer.reader();
+
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                let reader = reader.clone();
+                s.spawn(move || {
+                    let mut last_count = 0;
+                    for _ in 0..1000 {
+                        // snapshots are consistent and published in order
+                        let snapshot = reader.snapshot();
+                        assert_eq!(
+                            snapshot.count(),
+                            snapshot.bins().iter().map(|b| b.count()).sum::<u64>()
+                        );
+                        assert!(snapshot.count() >= last_count);
+                        last_count = snapshot.count();
+                    }
+                });
+            }
+
+            for i in 0..1000 {
+                writer.insert(i as f64);
+                writer.publish();
+            }
+        });
+
+        assert_eq!(reader.snapshot().count(), 1000);
+    }
+}
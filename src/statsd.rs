@@ -0,0 +1,426 @@
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::histogram::Histogram;
+use crate::registry::{Key, Registry};
+
+/// The default maximum size of a datagram, which fits into a single Ethernet frame.
+const DEFAULT_MAX_PACKET_SIZE: usize = 1432;
+
+/// The dialect of the StatsD protocol spoken by a [`StatsdEmitter`].
+///
+/// This is only available when the `statsd` feature is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsdFlavor {
+    /// The original StatsD protocol: values are sent as timers (`name:value|ms|@rate`), and
+    /// labels are appended to the name (`name.label.value`), as there are no tags.
+    Statsd,
+    /// The DogStatsD protocol: values are sent as distributions
+    /// (`name:value|d|@rate|#label:value`), which are aggregated into sketches by
+    /// the Datadog agent.
+    DogStatsd,
+}
+
+/// An emitter of histograms to a StatsD (or DogStatsD) daemon over UDP.
+///
+/// Every bin of a histogram is sent as a single value with the sample rate of
+/// `1 / count`, so that the daemon counts it `count` times. The size of the payload is thus
+/// bounded by the size of the histogram, no matter how many values it has accumulated.
+/// Lines are packed into datagrams of up to 1432 bytes by default.
+///
+/// StatsD daemons expect values recorded since the last flush, so histograms are usually
+/// flushed from a [`Registry`] periodically (see [`flush`](StatsdEmitter::flush) and
+/// [`spawn`](StatsdEmitter::spawn)), which clears them.
+///
+/// This type is only available when the `statsd` feature is enabled.
+///
+/// ```
+/// use std::net::UdpSocket;
+/// use bhtt::{Key, Registry, StatsdEmitter, StatsdFlavor};
+///
+/// let daemon = UdpSocket::bind("127.0.0.1:0").unwrap();
+/// let emitter = StatsdEmitter::connect(daemon.local_addr().unwrap(), StatsdFlavor::DogStatsd)
+///     .unwrap();
+///
+/// let registry = Registry::new(64);
+/// registry.record(Key::new("latency").with_label("method", "GET"), 42.0);
+/// registry.record(Key::new("latency").with_label("method", "GET"), (12.5, 4));
+/// emitter.flush(&registry).unwrap();
+///
+/// let mut buf = [0; 1500];
+/// let len = daemon.recv(&mut buf).unwrap();
+/// assert_eq!(
+///     std::str::from_utf8(&buf[..len]).unwrap(),
+///     "latency:12.5|d|@0.25|#method:GET\nlatency:42|d|#method:GET"
+/// );
+/// ```
+#[derive(Debug)]
+pub struct StatsdEmitter {
+    socket: UdpSocket,
+    flavor: StatsdFlavor,
+    prefix: String,
+    max_packet_size: usize,
+}
+
+/// A background thread, which periodically flushes histograms of a [`Registry`] to
+/// a StatsD daemon (see [`StatsdEmitter::spawn`]).
+///
+/// The thread stops, when the reporter is stopped (see [`stop`](StatsdReporter::stop)) or
+/// dropped. Histograms are flushed one last time before it stops.
+///
+/// This type is only available when the `statsd` feature is enabled.
+#[derive(Debug)]
+pub struct StatsdReporter {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StatsdEmitter {
+    /// Create a new StatsdEmitter, which sends datagrams through a socket. The socket must
+    /// be connected to the address of the daemon.
+    pub fn new(socket: UdpSocket, flavor: StatsdFlavor) -> StatsdEmitter {
+        StatsdEmitter {
+            socket,
+            flavor,
+            prefix: String::new(),
+            max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+        }
+    }
+
+    /// Create a new StatsdEmitter, which sends datagrams to the daemon listening on
+    /// the given address, from a socket bound to an ephemeral port.
+    ///
+    /// Returns an error, if the address can't be resolved or the socket can't be created.
+    pub fn connect<A: ToSocketAddrs>(addr: A, flavor: StatsdFlavor) -> io::Result<StatsdEmitter> {
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no addresses to connect to")
+        })?;
+        let local: SocketAddr = match addr {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+        };
+
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(addr)?;
+
+        Ok(StatsdEmitter::new(socket, flavor))
+    }
+
+    /// Returns the prefix, which is prepended to names of all metrics.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Set the prefix, which is prepended to names of all metrics (e.g. `myservice.`).
+    /// The default is no prefix.
+    pub fn set_prefix(&mut self, prefix: impl Into<String>) {
+        self.prefix = prefix.into();
+    }
+
+    /// Returns the maximum size of a datagram in bytes.
+    pub fn max_packet_size(&self) -> usize {
+        self.max_packet_size
+    }
+
+    /// Set the maximum size of a datagram in bytes. The default is 1432 bytes, which fits
+    /// into a single Ethernet frame. A line, which is longer than that, is sent in its own
+    /// datagram. The size must be greater than 0, or the function will panic.
+    pub fn set_max_packet_size(&mut self, size: usize) {
+        assert!(size > 0, "maximum packet size must be greater than 0");
+
+        self.max_packet_size = size;
+    }
+
+    /// Send the bins of a histogram with the given key to the daemon. Empty histograms are
+    /// not sent.
+    ///
+    /// Returns an error, if a datagram can't be sent.
+    pub fn emit(&self, key: &Key, h: &Histogram) -> io::Result<()> {
+        self.send(self.lines(key, h))
+    }
+
+    /// Send the values recorded into the histograms of the registry since the last flush
+    /// to the daemon, and clear the histograms (see [`Registry::take_all`]).
+    ///
+    /// Returns an error, if a datagram can't be sent. The values, which were not sent, are
+    /// lost.
+    pub fn flush(&self, registry: &Registry) -> io::Result<()> {
+        let lines = registry
+            .take_all()
+            .iter()
+            .flat_map(|(key, h)| self.lines(key, h))
+            .collect();
+
+        self.send(lines)
+    }
+
+    /// Spawn a background thread, which flushes the histograms of the registry every
+    /// `interval` (see [`flush`](StatsdEmitter::flush)). Errors are ignored, as StatsD
+    /// metrics are delivered on a best-effort basis anyway. The interval must be greater
+    /// than 0, or the function will panic.
+    ///
+    /// ```
+    /// use std::net::UdpSocket;
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    /// use bhtt::{Registry, StatsdEmitter, StatsdFlavor};
+    ///
+    /// let daemon = UdpSocket::bind("127.0.0.1:0").unwrap();
+    /// let emitter = StatsdEmitter::connect(daemon.local_addr().unwrap(), StatsdFlavor::Statsd)
+    ///     .unwrap();
+    ///
+    /// let registry = Arc::new(Registry::new(64));
+    /// let reporter = emitter.spawn(Arc::clone(&registry), Duration::from_secs(10));
+    /// registry.record("latency", 42.0);
+    ///
+    /// // histograms are flushed one last time on stop
+    /// reporter.stop();
+    /// let mut buf = [0; 1500];
+    /// let len = daemon.recv(&mut buf).unwrap();
+    /// assert_eq!(&buf[..len], b"latency:42|ms");
+    /// ```
+    pub fn spawn(self, registry: Arc<Registry>, interval: Duration) -> StatsdReporter {
+        assert!(!interval.is_zero(), "interval must be greater than 0");
+
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::Builder::new()
+            .name("bhtt-statsd".into())
+            .spawn(move || loop {
+                let result = stopped.recv_timeout(interval);
+                let _ = self.flush(&registry);
+                if result != Err(RecvTimeoutError::Timeout) {
+                    break;
+                }
+            })
+            .expect("failed to spawn the background thread");
+
+        StatsdReporter {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Returns the lines of the protocol, which describe the bins of a histogram.
+    fn lines(&self, key: &Key, h: &Histogram) -> Vec<String> {
+        let mut name = format!("{}{}", self.prefix, sanitize(key.name()));
+        let mut tags = String::new();
+        match self.flavor {
+            StatsdFlavor::Statsd => {
+                for (label, value) in key.labels() {
+                    name = format!("{}.{}.{}", name, sanitize(label), sanitize(value));
+                }
+            }
+            StatsdFlavor::DogStatsd => {
+                let labels: Vec<String> = key
+                    .labels()
+                    .iter()
+                    .map(|(label, value)| format!("{}:{}", sanitize(label), sanitize_tag(value)))
+                    .collect();
+                if !labels.is_empty() {
+                    tags = format!("|#{}", labels.join(","));
+                }
+            }
+        }
+        let metric_type = match self.flavor {
+            StatsdFlavor::Statsd => "ms",
+            StatsdFlavor::DogStatsd => "d",
+        };
+
+        h.bins()
+            .iter()
+            .map(|bin| {
+                let rate = match bin.count() {
+                    1 => String::new(),
+                    count => format!("|@{}", 1.0 / count as f64),
+                };
+                format!("{}:{}|{}{}{}", name, bin.value(), metric_type, rate, tags)
+            })
+            .collect()
+    }
+
+    /// Send lines packed into as few datagrams as possible.
+    fn send(&self, lines: Vec<String>) -> io::Result<()> {
+        let mut packet = String::new();
+        for line in lines {
+            if !packet.is_empty() && packet.len() + 1 + line.len() > self.max_packet_size {
+                self.socket.send(packet.as_bytes())?;
+                packet.clear();
+            }
+            if !packet.is_empty() {
+                packet.push('\n');
+            }
+            packet.push_str(&line);
+        }
+        if !packet.is_empty() {
+            self.socket.send(packet.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl StatsdReporter {
+    /// Stop the background thread, once it has flushed the histograms one last time.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            // the thread only panics, if flushing panics, which is a bug
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for StatsdReporter {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Returns a name with the characters, which have a special meaning in the protocol,
+/// replaced with underscores.
+fn sanitize(name: &str) -> String {
+    name.replace([':', '|', '@', '#', ',', '\n', ' '], "_")
+}
+
+/// Returns a tag value with the characters, which have a special meaning in the protocol,
+/// replaced with underscores. Unlike names, tag values can contain colons.
+fn sanitize_tag(value: &str) -> String {
+    value.replace(['|', '@', '#', ',', '\n', ' '], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A daemon listening on the loopback interface and an emitter connected to it.
+    fn daemon(flavor: StatsdFlavor) -> (UdpSocket, StatsdEmitter) {
+        let daemon = UdpSocket::bind("127.0.0.1:0").unwrap();
+        daemon
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let emitter = StatsdEmitter::connect(daemon.local_addr().unwrap(), flavor).unwrap();
+
+        (daemon, emitter)
+    }
+
+    fn recv(daemon: &UdpSocket) -> String {
+        let mut buf = [0; 65536];
+        let len = daemon.recv(&mut buf).unwrap();
+        String::from_utf8(buf[..len].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn flavors() {
+        let key = Key::new("request latency")
+            .with_label("method", "GET")
+            .with_label("path", "/a:b|c");
+        let h = Histogram::from_iter(5, [(1.0, 1), (2.5, 3)]);
+
+        let (daemon, mut emitter) = daemon(StatsdFlavor::Statsd);
+        emitter.set_prefix("app.");
+        assert_eq!(emitter.prefix(), "app.");
+        emitter.emit(&key, &h).unwrap();
+        assert_eq!(
+            recv(&daemon),
+            [
+                "app.request_latency.method.GET.path./a_b_c:1|ms",
+                "app.request_latency.method.GET.path./a_b_c:2.5|ms|@0.3333333333333333",
+            ]
+            .join("\n")
+        );
+
+        let (daemon, emitter) = self::daemon(StatsdFlavor::DogStatsd);
+        emitter.emit(&key, &h).unwrap();
+        assert_eq!(
+            recv(&daemon),
+            [
+                "request_latency:1|d|#method:GET,path:/a:b_c",
+                "request_latency:2.5|d|@0.3333333333333333|#method:GET,path:/a:b_c",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn packets() {
+        let (daemon, mut emitter) = daemon(StatsdFlavor::DogStatsd);
+        assert_eq!(emitter.max_packet_size(), DEFAULT_MAX_PACKET_SIZE);
+        emitter.set_max_packet_size(20);
+
+        // "latency:1|d" is 11 bytes long, so only one line fits into a packet
+        let h = Histogram::from_iter(5, [1.0, 2.0, 3.0]);
+        emitter.emit(&Key::new("latency"), &h).unwrap();
+        for value in 1..=3 {
+            assert_eq!(recv(&daemon), format!("latency:{}|d", value));
+        }
+
+        // a line, which is too long, is sent on its own
+        let key = Key::new("a_very_long_name_of_a_metric");
+        emitter.emit(&key, &Histogram::from_iter(5, [1.0])).unwrap();
+        assert_eq!(recv(&daemon), "a_very_long_name_of_a_metric:1|d");
+
+        emitter.set_max_packet_size(DEFAULT_MAX_PACKET_SIZE);
+        let h = Histogram::from_iter(500, (0..500).map(|v| v as f64));
+        emitter.emit(&Key::new("latency"), &h).unwrap();
+        let mut values = 0;
+        while values < 500 {
+            let packet = recv(&daemon);
+            assert!(packet.len() <= DEFAULT_MAX_PACKET_SIZE);
+            values += packet.lines().count();
+        }
+        assert_eq!(values, 500);
+    }
+
+    #[test]
+    fn flush() {
+        let (daemon, emitter) = daemon(StatsdFlavor::Statsd);
+        let registry = Registry::new(5);
+        registry.record("a", 1.0);
+        registry.record("b", 2.0);
+
+        emitter.flush(&registry).unwrap();
+        assert_eq!(recv(&daemon), "a:1|ms\nb:2|ms");
+
+        // histograms are cleared, so only new values are sent
+        registry.record("b", 3.0);
+        emitter.flush(&registry).unwrap();
+        assert_eq!(recv(&daemon), "b:3|ms");
+
+        // nothing is sent, if there are no new values
+        emitter.flush(&registry).unwrap();
+        registry.record("a", 4.0);
+        emitter.flush(&registry).unwrap();
+        assert_eq!(recv(&daemon), "a:4|ms");
+    }
+
+    #[test]
+    fn reporter() {
+        let (daemon, emitter) = daemon(StatsdFlavor::Statsd);
+        let registry = Arc::new(Registry::new(5));
+        let reporter = emitter.spawn(Arc::clone(&registry), Duration::from_millis(10));
+
+        registry.record("a", 1.0);
+        assert_eq!(recv(&daemon), "a:1|ms");
+        registry.record("a", 2.0);
+        assert_eq!(recv(&daemon), "a:2|ms");
+
+        registry.record("a", 3.0);
+        drop(reporter);
+        assert_eq!(recv(&daemon), "a:3|ms");
+    }
+
+    #[test]
+    #[should_panic(expected = "maximum packet size must be greater than 0")]
+    fn zero_max_packet_size() {
+        let (_daemon, mut emitter) = daemon(StatsdFlavor::Statsd);
+        emitter.set_max_packet_size(0);
+    }
+}
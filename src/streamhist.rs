@@ -0,0 +1,308 @@
+use serde_json::{json, Map, Value};
+
+use crate::bin::Bin;
+use crate::data::MAX_SIZE;
+use crate::error::Error;
+use crate::histogram::{Bins, Histogram};
+use crate::policy::NanPolicy;
+use crate::sum::CompensatedSum;
+
+/// The default number of points used by `streamhist` to estimate the density of values.
+const MPD_POINTS: u64 = 10000;
+
+impl Histogram {
+    /// Returns the histogram as a JSON document in the format of `to_dict()` of the Python
+    /// `streamhist` package: the `(mean, count)` pairs of bins, the size of the histogram
+    /// (`maxbins`), the number of NaN values (`missing_count`), and the exact minimum and
+    /// maximum values.
+    ///
+    /// This is only available when the `json` feature is enabled.
+    ///
+    /// ```
+    /// use bhtt::Histogram;
+    ///
+    /// let h = Histogram::from_iter(5, [(1.0, 2), (3.5, 1)]);
+    /// assert_eq!(
+    ///     h.to_streamhist_json(),
+    ///     concat!(
+    ///         r#"{"bins":[{"count":2,"mean":1.0},{"count":1,"mean":3.5}],"#,
+    ///         r#""info":{"freeze":null,"maxbins":5,"missing_count":0,"mpdpoints":10000},"#,
+    ///         r#""stats":{"max":3.5,"min":1.0}}"#,
+    ///     )
+    /// );
+    /// ```
+    pub fn to_streamhist_json(&self) -> String {
+        let bins: Vec<Value> = self
+            .bins()
+            .iter()
+            .map(|bin| json!({"mean": bin.value(), "count": bin.count()}))
+            .collect();
+
+        json!({
+            "bins": bins,
+            "info": {
+                "missing_count": self.nan_count(),
+                "maxbins": self.size(),
+                "mpdpoints": MPD_POINTS,
+                "freeze": null,
+            },
+            "stats": {
+                "min": self.min(),
+                "max": self.max(),
+            },
+        })
+        .to_string()
+    }
+
+    /// Create a new Histogram from a JSON document in the format of `to_dict()` of the Python
+    /// `streamhist` package (see [`to_streamhist_json`](Histogram::to_streamhist_json)).
+    /// The NaN policy of the histogram is set to [`NanPolicy::Count`], as `streamhist` counts
+    /// missing values. If the minimum and maximum values are not specified, the values of
+    /// the outermost bins are used instead.
+    ///
+    /// Returns an error, if the document can't be parsed, if bins are not sorted, if there
+    /// are more bins than `maxbins`, or if `maxbins` is too large to be allocated.
+    ///
+    /// This is only available when the `json` feature is enabled.
+    ///
+    /// ```
+    /// use bhtt::{Bin, Histogram};
+    ///
+    /// let h = Histogram::from_streamhist_json(
+    ///     r#"{"bins": [{"mean": 1.0, "count": 2}, {"mean": 3.5, "count": 1}],
+    ///         "info": {"maxbins": 5, "missing_count": 3}}"#,
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(h.size(), 5);
+    /// assert_eq!(h.bins(), &[Bin::new(1.0, 2), Bin::new(3.5, 1)]);
+    /// assert_eq!(h.nan_count(), 3);
+    /// assert_eq!(h.min(), Some(1.0));
+    /// ```
+    pub fn from_streamhist_json(s: &str) -> Result<Histogram, Error> {
+        let document: Value =
+            serde_json::from_str(s).map_err(|err| Error::InvalidFormat(err.to_string()))?;
+        let document = as_object(&document, "the document")?;
+        let info = as_object(field(document, "info")?, "info")?;
+
+        let size = as_count(field(info, "maxbins")?, "maxbins")?;
+        if size == 0 {
+            return Err(Error::InvalidFormat(
+                "maxbins must be greater than 0".into(),
+            ));
+        }
+        let size = match usize::try_from(size) {
+            Ok(size) if size <= MAX_SIZE => size,
+            _ => return Err(Error::InvalidFormat("maxbins is too large".into())),
+        };
+        let missing_count = match info.get("missing_count") {
+            Some(value) => as_count(value, "missing_count")?,
+            None => 0,
+        };
+
+        let mut bins = Bins::new();
+        let mut sum = CompensatedSum::default();
+        for bin in as_array(field(document, "bins")?, "bins")? {
+            let bin = as_object(bin, "a bin")?;
+            let value = as_number(field(bin, "mean")?, "mean")?;
+            let bin = Bin::try_new(value, as_count(field(bin, "count")?, "count")?)?;
+
+            if bins.last().is_some_and(|last| last.value() > value) {
+                return Err(Error::InvalidFormat(
+                    "bins must be sorted in the ascending order".into(),
+                ));
+            }
+            bins.push(bin);
+            sum.add(bin.value() * bin.count() as f64);
+        }
+        if bins.len() > size {
+            return Err(Error::InvalidFormat(format!(
+                "the number of bins exceeds maxbins ({} > {})",
+                bins.len(),
+                size
+            )));
+        }
+
+        let (mut min_value, mut max_value) = (
+            bins.first().map(|bin| bin.value()),
+            bins.last().map(|bin| bin.value()),
+        );
+        if let (Some(first), Some(last)) = (min_value, max_value) {
+            if let Some(stats) = document.get("stats") {
+                let stats = as_object(stats, "stats")?;
+                if let Some(min) = optional_number(stats, "min")? {
+                    if min > first {
+                        return Err(Error::InvalidFormat(
+                            "min must not be greater than the value of the first bin".into(),
+                        ));
+                    }
+                    min_value = Some(min);
+                }
+                if let Some(max) = optional_number(stats, "max")? {
+                    if max < last {
+                        return Err(Error::InvalidFormat(
+                            "max must not be less than the value of the last bin".into(),
+                        ));
+                    }
+                    max_value = Some(max);
+                }
+            }
+        }
+
+        let mut h = Histogram::from_raw_parts(size, bins, min_value, max_value, sum);
+        h.set_nan_policy(NanPolicy::Count);
+        h.set_special_counts(missing_count, 0, 0);
+
+        Ok(h)
+    }
+}
+
+fn field<'a>(object: &'a Map<String, Value>, name: &str) -> Result<&'a Value, Error> {
+    object
+        .get(name)
+        .ok_or_else(|| Error::InvalidFormat(format!("{} is missing", name)))
+}
+
+fn as_object<'a>(value: &'a Value, name: &str) -> Result<&'a Map<String, Value>, Error> {
+    value
+        .as_object()
+        .ok_or_else(|| Error::InvalidFormat(format!("{} must be an object", name)))
+}
+
+fn as_array<'a>(value: &'a Value, name: &str) -> Result<&'a Vec<Value>, Error> {
+    value
+        .as_array()
+        .ok_or_else(|| Error::InvalidFormat(format!("{} must be an array", name)))
+}
+
+fn as_number(value: &Value, name: &str) -> Result<f64, Error> {
+    value
+        .as_f64()
+        .ok_or_else(|| Error::InvalidFormat(format!("{} must be a number", name)))
+}
+
+fn optional_number(object: &Map<String, Value>, name: &str) -> Result<Option<f64>, Error> {
+    match object.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => as_number(value, name).map(Some),
+    }
+}
+
+/// Counts are integers, but weighted histograms of `streamhist` store them as floats.
+fn as_count(value: &Value, name: &str) -> Result<u64, Error> {
+    match value.as_u64() {
+        Some(count) => Ok(count),
+        None => match value.as_f64() {
+            Some(count) if count >= 0.0 && count.fract() == 0.0 && count <= u64::MAX as f64 => {
+                Ok(count as u64)
+            }
+            _ => Err(Error::InvalidFormat(format!(
+                "{} must be a non-negative integer",
+                name
+            ))),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut h = Histogram::new(16);
+        h.set_nan_policy(NanPolicy::Count);
+        for v in 0..1000 {
+            h.insert((v as f64).sqrt() * 1.1);
+        }
+        h.try_insert(f64::NAN).unwrap();
+
+        let h2 = Histogram::from_streamhist_json(&h.to_streamhist_json()).unwrap();
+        assert_eq!(h2.size(), h.size());
+        assert_eq!(h2.bins(), h.bins());
+        assert_eq!(h2.count(), h.count());
+        assert_eq!(h2.min(), h.min());
+        assert_eq!(h2.max(), h.max());
+        assert_eq!(h2.nan_count(), 1);
+        assert_eq!(h2.nan_policy(), NanPolicy::Count);
+        assert_relative_eq!(h2.sum(), h.sum(), max_relative = 1e-12);
+        assert_eq!(h2.to_streamhist_json(), h.to_streamhist_json());
+    }
+
+    #[test]
+    fn empty() {
+        let h = Histogram::new(3);
+        let json = h.to_streamhist_json();
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"bins":[],"info":{"freeze":null,"maxbins":3,"missing_count":0,"mpdpoints":10000},"#,
+                r#""stats":{"max":null,"min":null}}"#
+            )
+        );
+
+        let h2 = Histogram::from_streamhist_json(&json).unwrap();
+        assert_eq!(h2.size(), 3);
+        assert_eq!(h2.count(), 0);
+        assert_eq!(h2.min(), None);
+        assert_eq!(h2.max(), None);
+    }
+
+    #[test]
+    fn float_counts() {
+        let h = Histogram::from_streamhist_json(
+            r#"{"bins": [{"mean": -1.5, "count": 2.0}, {"mean": 1e3, "count": 1}],
+                "info": {"maxbins": 4, "missing_count": 0.0, "weighted": true},
+                "stats": {"min": -2.0, "max": null}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(h.bins(), &[Bin::new(-1.5, 2), Bin::new(1000.0, 1)]);
+        assert_eq!(h.min(), Some(-2.0));
+        assert_eq!(h.max(), Some(1000.0));
+        assert_eq!(h.sum(), 997.0);
+    }
+
+    #[test]
+    fn invalid() {
+        let cases = [
+            "",
+            "[]",
+            r#"{"bins": []}"#,
+            r#"{"info": {"maxbins": 3}}"#,
+            r#"{"bins": {}, "info": {"maxbins": 3}}"#,
+            r#"{"bins": [], "info": {"maxbins": 0}}"#,
+            r#"{"bins": [], "info": {"maxbins": -1}}"#,
+            r#"{"bins": [], "info": {"maxbins": 1.5}}"#,
+            r#"{"bins": [], "info": {"maxbins": 3, "missing_count": "1"}}"#,
+            r#"{"bins": [{"mean": 1.0}], "info": {"maxbins": 3}}"#,
+            r#"{"bins": [{"mean": "1.0", "count": 1}], "info": {"maxbins": 3}}"#,
+            r#"{"bins": [{"mean": 1.0, "count": 0}], "info": {"maxbins": 3}}"#,
+            r#"{"bins": [{"mean": 1.0, "count": 0.5}], "info": {"maxbins": 3}}"#,
+            r#"{"bins": [{"mean": 2.0, "count": 1}, {"mean": 1.0, "count": 1}], "info": {"maxbins": 3}}"#,
+            r#"{"bins": [{"mean": 1.0, "count": 1}, {"mean": 2.0, "count": 1}], "info": {"maxbins": 1}}"#,
+            r#"{"bins": [{"mean": 1.0, "count": 1}], "info": {"maxbins": 3}, "stats": {"min": 1.5}}"#,
+            r#"{"bins": [{"mean": 1.0, "count": 1}], "info": {"maxbins": 3}, "stats": {"max": 0.5}}"#,
+            r#"{"bins": [{"mean": 1.0, "count": 1}], "info": {"maxbins": 3}, "stats": []}"#,
+        ];
+        for case in cases {
+            assert!(Histogram::from_streamhist_json(case).is_err(), "{}", case);
+        }
+    }
+
+    #[test]
+    fn maxbins_too_large() {
+        // histograms of this size can't be allocated, so e.g. merges would panic
+        let err = Histogram::from_streamhist_json(
+            r#"{"bins": [{"mean": 1.0, "count": 1}], "info": {"maxbins": 18446744073709551615}}"#,
+        )
+        .unwrap_err();
+        assert_eq!(err, Error::InvalidFormat("maxbins is too large".into()));
+
+        let json = format!(
+            r#"{{"bins": [], "info": {{"maxbins": {}}}}}"#,
+            MAX_SIZE as u64 + 1
+        );
+        assert!(Histogram::from_streamhist_json(&json).is_err());
+    }
+}
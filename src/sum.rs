@@ -0,0 +1,114 @@
+/// A running sum of floating point values that uses compensated (Kahan-Babuska-Neumaier)
+/// summation to keep track of the low-order bits lost in floating point additions.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub(crate) struct CompensatedSum {
+    sum: f64,
+    compensation: f64,
+}
+
+impl CompensatedSum {
+    /// Add a new value to the sum.
+    pub(crate) fn add(&mut self, value: f64) {
+        let t = self.sum + value;
+        if !t.is_finite() {
+            // the sum has overflowed (or a value is not finite), so the lost low-order bits
+            // no longer matter. Computing them would turn the compensation into NaN
+        } else if self.sum.abs() >= value.abs() {
+            self.compensation += (self.sum - t) + value;
+        } else {
+            self.compensation += (value - t) + self.sum;
+        }
+        self.sum = t;
+    }
+
+    /// Add another sum to this one, preserving its compensation.
+    pub(crate) fn merge(&mut self, other: &CompensatedSum) {
+        self.add(other.sum);
+        self.add(other.compensation);
+    }
+
+    /// Subtract another sum from this one, preserving its compensation.
+    pub(crate) fn subtract(&mut self, other: &CompensatedSum) {
+        self.add(-other.sum);
+        self.add(-other.compensation);
+    }
+
+    /// Returns the compensated value of the sum.
+    pub(crate) fn value(&self) -> f64 {
+        self.sum + self.compensation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add() {
+        let mut s = CompensatedSum::default();
+        assert_eq!(s.value(), 0.0);
+
+        s.add(1.5);
+        s.add(-0.25);
+        assert_eq!(s.value(), 1.25);
+    }
+
+    #[test]
+    fn add_compensated() {
+        // naive summation loses the small values completely
+        let values = [1.0, 1e100, 1.0, -1e100];
+        assert_eq!(values.iter().sum::<f64>(), 0.0);
+
+        let mut s = CompensatedSum::default();
+        for v in values {
+            s.add(v);
+        }
+        assert_eq!(s.value(), 2.0);
+    }
+
+    #[test]
+    fn add_overflow() {
+        let mut s = CompensatedSum::default();
+        s.add(1e308);
+        s.add(f64::MAX);
+        assert_eq!(s.value(), f64::INFINITY);
+
+        s.add(1.0);
+        assert_eq!(s.value(), f64::INFINITY);
+
+        let mut s = CompensatedSum::default();
+        s.add(-f64::MAX);
+        s.add(-f64::MAX);
+        assert_eq!(s.value(), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn merge() {
+        let mut s1 = CompensatedSum::default();
+        s1.add(1.0);
+        s1.add(1e100);
+
+        let mut s2 = CompensatedSum::default();
+        s2.add(1.0);
+        s2.add(-1e100);
+
+        s1.merge(&s2);
+        assert_eq!(s1.value(), 2.0);
+    }
+
+    #[test]
+    fn subtract() {
+        let mut s1 = CompensatedSum::default();
+        let mut s2 = CompensatedSum::default();
+        for _ in 0..10 {
+            s1.add(0.1);
+            s2.add(0.1);
+        }
+        s1.add(1e100);
+        s1.add(-1e100);
+        s1.add(2.5);
+
+        s1.subtract(&s2);
+        assert_eq!(s1.value(), 2.5);
+    }
+}
@@ -0,0 +1,411 @@
+use std::borrow::Cow;
+use std::f64::consts::PI;
+
+use crate::bin::Bin;
+use crate::histogram::Histogram;
+use crate::sketch::Sketch;
+
+/// The number of buffered values per unit of compression, before they are merged into
+/// centroids.
+const BUFFER_FACTOR: f64 = 5.0;
+
+/// A merging t-digest (<https://arxiv.org/abs/1902.04023>), i.e. a list of centroids, which
+/// are `(mean, weight)` pairs, whose weights are limited by a scale function, so that
+/// centroids near the tails of the distribution are small and quantiles near 0 and 1 are
+/// estimated accurately.
+///
+/// Unlike [`Histogram`], which merges the two closest bins on every insertion, a t-digest
+/// buffers new values and merges them with the centroids in one pass, once the buffer is
+/// full. The number of centroids is bounded by the `compression` parameter (roughly
+/// `compression / 2` centroids are kept). The k1 scale function (`asin`) is used.
+///
+/// Centroids are represented by [`Bin`]s, and t-digests implement [`Sketch`], so that they
+/// can be used interchangeably with histograms, e.g. to compare the accuracy of both on
+/// the same data.
+///
+/// ```
+/// use bhtt::TDigest;
+///
+/// let mut digest = TDigest::new(100.0);
+/// for value in 1..=10000 {
+///     digest.insert(value as f64);
+/// }
+///
+/// assert_eq!(digest.count(), 10000);
+/// assert!(digest.centroids().len() <= 100);
+/// assert!((digest.quantile(0.999).unwrap() - 9990.0).abs() < 5.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TDigest {
+    compression: f64,
+    centroids: Vec<Bin>,
+    buffer: Vec<Bin>,
+    buffer_capacity: usize,
+    count: u64,
+    min_max: Option<(f64, f64)>,
+}
+
+impl TDigest {
+    /// Create a new empty TDigest with the given compression (100 is a common choice).
+    /// The compression must be finite and at least 1, or the function will panic.
+    pub fn new(compression: f64) -> TDigest {
+        assert!(
+            compression.is_finite() && compression >= 1.0,
+            "compression must be finite and at least 1"
+        );
+
+        let buffer_capacity = (compression * BUFFER_FACTOR).ceil() as usize;
+        TDigest {
+            compression,
+            centroids: Vec::new(),
+            buffer: Vec::with_capacity(buffer_capacity),
+            buffer_capacity,
+            count: 0,
+            min_max: None,
+        }
+    }
+
+    /// Returns the compression of the digest.
+    pub fn compression(&self) -> f64 {
+        self.compression
+    }
+
+    /// Returns the number of values in the digest.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the minimum value in the digest or `None` if the digest is empty.
+    pub fn min(&self) -> Option<f64> {
+        self.min_max.map(|(min_value, _)| min_value)
+    }
+
+    /// Returns the maximum value in the digest or `None` if the digest is empty.
+    pub fn max(&self) -> Option<f64> {
+        self.min_max.map(|(_, max_value)| max_value)
+    }
+
+    /// Returns the centroids of the digest sorted by the mean. Buffered values are merged
+    /// into the result, but the digest itself is not changed (see
+    /// [`compress`](TDigest::compress)).
+    pub fn centroids(&self) -> Cow<'_, [Bin]> {
+        match self.buffer.is_empty() {
+            true => Cow::Borrowed(&self.centroids),
+            false => Cow::Owned(self.merged()),
+        }
+    }
+
+    /// Insert a new value (or bin, which is a value repeated `count` times).
+    pub fn insert<T: Into<Bin>>(&mut self, value: T) {
+        let bin = value.into();
+        let (min_value, max_value) = self.min_max.unwrap_or((bin.value(), bin.value()));
+        self.min_max = Some((min_value.min(bin.value()), max_value.max(bin.value())));
+        self.count += bin.count();
+
+        self.buffer.push(bin);
+        if self.buffer.len() >= self.buffer_capacity {
+            self.compress();
+        }
+    }
+
+    /// Merge another digest into this one. The compression of this digest is kept.
+    pub fn merge(&mut self, other: &TDigest) {
+        if let Some((min_value, max_value)) = other.min_max {
+            let (self_min, self_max) = self.min_max.unwrap_or((min_value, max_value));
+            self.min_max = Some((self_min.min(min_value), self_max.max(max_value)));
+        }
+        self.count += other.count;
+
+        self.buffer.extend_from_slice(&other.centroids);
+        self.buffer.extend_from_slice(&other.buffer);
+        self.compress();
+    }
+
+    /// Merge the buffered values into the centroids.
+    pub fn compress(&mut self) {
+        if !self.buffer.is_empty() {
+            self.centroids = self.merged();
+            self.buffer.clear();
+        }
+    }
+
+    /// Returns an estimate of the `q`'th quantile or `None` if the digest is empty. `q` must
+    /// be in the range [0.0; 1.0], or the function will panic.
+    ///
+    /// The cumulative count is interpolated linearly between the means of centroids, where
+    /// every centroid is assumed to have half of its weight on each side of its mean, and
+    /// the exact minimum and maximum values are used at the ends.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        assert!(
+            (0.0..=1.0).contains(&q),
+            "q must be in the range [0.0; 1.0]"
+        );
+
+        let points = self.cdf_points()?;
+        let target = q * self.count as f64;
+        let i = points
+            .iter()
+            .position(|&(_, cumulative)| cumulative >= target)
+            .unwrap_or(points.len() - 1);
+        if i == 0 {
+            return Some(points[0].0);
+        }
+
+        let ((x0, c0), (x1, c1)) = (points[i - 1], points[i]);
+        Some(x0 + (x1 - x0) * (target - c0) / (c1 - c0))
+    }
+
+    /// Returns an estimate of the fraction of values less than or equal to `value` or
+    /// `None` if the digest is empty (see [`quantile`](TDigest::quantile)). The value must
+    /// not be NaN, or the function will panic.
+    pub fn rank(&self, value: f64) -> Option<f64> {
+        assert!(!value.is_nan(), "value must not be NaN");
+
+        let points = self.cdf_points()?;
+        let total = self.count as f64;
+        let i = match points.iter().position(|&(x, _)| x > value) {
+            Some(0) => return Some(0.0),
+            Some(i) => i,
+            None => return Some(1.0),
+        };
+
+        let ((x0, c0), (x1, c1)) = (points[i - 1], points[i]);
+        Some((c0 + (c1 - c0) * (value - x0) / (x1 - x0)) / total)
+    }
+
+    /// Returns the points of the piecewise linear cumulative distribution function, i.e.
+    /// `(value, cumulative count)` pairs, or `None` if the digest is empty.
+    fn cdf_points(&self) -> Option<Vec<(f64, f64)>> {
+        let (min_value, max_value) = self.min_max?;
+
+        let centroids = self.centroids();
+        let mut points = Vec::with_capacity(centroids.len() + 2);
+        points.push((min_value, 0.0));
+        let mut cumulative = 0.0;
+        for centroid in centroids.iter() {
+            let weight = centroid.count() as f64;
+            points.push((centroid.value(), cumulative + weight / 2.0));
+            cumulative += weight;
+        }
+        points.push((max_value, cumulative));
+
+        // a centroid at the minimum or the maximum value would make the interpolation
+        // divide by zero
+        points.dedup_by(|next, prev| {
+            if next.0 == prev.0 {
+                prev.1 = next.1;
+                true
+            } else {
+                false
+            }
+        });
+
+        Some(points)
+    }
+
+    /// Returns the centroids with the buffered values merged into them.
+    fn merged(&self) -> Vec<Bin> {
+        let mut bins: Vec<Bin> = self.centroids.iter().chain(&self.buffer).copied().collect();
+        bins.sort_by(|a, b| a.value().total_cmp(&b.value()));
+
+        let total = self.count as f64;
+        let mut merged = Vec::new();
+        let mut bins = bins.into_iter();
+        let mut current = match bins.next() {
+            Some(bin) => bin,
+            None => return merged,
+        };
+        let mut weight_so_far = 0.0;
+        let mut k_left = self.k(0.0);
+        for bin in bins {
+            let q_right = (weight_so_far + (current.count() + bin.count()) as f64) / total;
+            if self.k(q_right) - k_left <= 1.0 {
+                current = Bin::merge(&current, &bin);
+            } else {
+                weight_so_far += current.count() as f64;
+                k_left = self.k(weight_so_far / total);
+                merged.push(current);
+                current = bin;
+            }
+        }
+        merged.push(current);
+
+        merged
+    }
+
+    /// The k1 scale function, which maps a quantile to the scale, in which every centroid
+    /// spans at most 1 unit.
+    fn k(&self, q: f64) -> f64 {
+        self.compression / (2.0 * PI) * (2.0 * q.min(1.0) - 1.0).asin()
+    }
+}
+
+impl Default for TDigest {
+    /// Returns a new empty TDigest with the compression of 100.
+    fn default() -> TDigest {
+        TDigest::new(100.0)
+    }
+}
+
+impl From<&TDigest> for Histogram {
+    /// Returns a histogram with as many bins as the digest has centroids, so that
+    /// the centroids are preserved as is. Like [`Histogram::from_centroids`], the means of
+    /// the outermost centroids become the minimum and maximum values.
+    fn from(digest: &TDigest) -> Histogram {
+        let centroids = digest.centroids();
+        let mut h = Histogram::new(centroids.len().max(1));
+        h.insert_many(centroids.iter().copied());
+
+        h
+    }
+}
+
+impl Sketch for TDigest {
+    fn insert(&mut self, value: f64) {
+        TDigest::insert(self, value);
+    }
+
+    fn merge(&mut self, other: &TDigest) {
+        TDigest::merge(self, other);
+    }
+
+    fn count(&self) -> u64 {
+        TDigest::count(self)
+    }
+
+    fn min(&self) -> Option<f64> {
+        TDigest::min(self)
+    }
+
+    fn max(&self) -> Option<f64> {
+        TDigest::max(self)
+    }
+
+    fn quantile(&self, q: f64) -> Option<f64> {
+        TDigest::quantile(self, q)
+    }
+
+    fn rank(&self, value: f64) -> Option<f64> {
+        TDigest::rank(self, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let digest = TDigest::default();
+        assert_eq!(digest.compression(), 100.0);
+        assert_eq!(digest.count(), 0);
+        assert_eq!(digest.min(), None);
+        assert_eq!(digest.quantile(0.5), None);
+        assert_eq!(digest.rank(1.0), None);
+        assert!(digest.centroids().is_empty());
+    }
+
+    #[test]
+    fn single_value() {
+        let mut digest = TDigest::new(100.0);
+        digest.insert(Bin::new(42.0, 3));
+
+        assert_eq!(digest.count(), 3);
+        assert_eq!(digest.quantile(0.0), Some(42.0));
+        assert_eq!(digest.quantile(0.5), Some(42.0));
+        assert_eq!(digest.quantile(1.0), Some(42.0));
+        assert_eq!(digest.rank(41.0), Some(0.0));
+        assert_eq!(digest.rank(42.0), Some(1.0));
+    }
+
+    #[test]
+    fn accuracy() {
+        let values: Vec<f64> = (0..100_000).map(|v| v as f64).collect();
+        let mut digest = TDigest::new(100.0);
+        for &value in values.iter().rev() {
+            digest.insert(value);
+        }
+
+        // the scale function bounds the number of centroids
+        digest.compress();
+        assert!(digest.centroids().len() <= 100);
+        assert!(digest
+            .centroids()
+            .windows(2)
+            .all(|w| w[0].value() < w[1].value()));
+        assert_eq!(
+            digest.centroids().iter().map(|c| c.count()).sum::<u64>(),
+            100_000
+        );
+
+        // the tails are estimated more accurately than the median
+        for (q, max_error) in [(0.001, 1.0), (0.01, 5.0), (0.5, 500.0), (0.999, 1.0)] {
+            let expected = q * 99_999.0;
+            let actual = digest.quantile(q).unwrap();
+            assert!(
+                (actual - expected).abs() <= max_error,
+                "q = {}: {} != {}",
+                q,
+                actual,
+                expected
+            );
+        }
+        assert_relative_eq!(digest.rank(25_000.0).unwrap(), 0.25, epsilon = 0.005);
+    }
+
+    #[test]
+    fn merge() {
+        let mut left = TDigest::new(50.0);
+        let mut right = TDigest::new(200.0);
+        for value in 0..1000 {
+            left.insert(value as f64);
+            right.insert((value + 1000) as f64);
+        }
+
+        let mut expected = TDigest::new(50.0);
+        for value in 0..2000 {
+            expected.insert(value as f64);
+        }
+
+        left.merge(&right);
+        assert_eq!(left.compression(), 50.0);
+        assert_eq!(left.count(), 2000);
+        assert_eq!(left.min(), Some(0.0));
+        assert_eq!(left.max(), Some(1999.0));
+        assert!(left.centroids().len() <= 50);
+        for q in [0.01, 0.25, 0.5, 0.75, 0.99] {
+            let error = (left.quantile(q).unwrap() - expected.quantile(q).unwrap()).abs();
+            assert!(error < 20.0, "q = {}: error {}", q, error);
+        }
+
+        // merging an empty digest only compresses the buffer
+        let count = left.count();
+        left.merge(&TDigest::default());
+        assert_eq!(left.count(), count);
+    }
+
+    #[test]
+    fn to_histogram() {
+        let mut digest = TDigest::new(20.0);
+        for value in 1..=1000 {
+            digest.insert(value as f64);
+        }
+
+        let h = Histogram::from(&digest);
+        assert_eq!(h.count(), 1000);
+        assert_eq!(h.size(), digest.centroids().len());
+        assert_eq!(h.bins(), &digest.centroids()[..]);
+    }
+
+    #[test]
+    #[should_panic(expected = "compression must be finite and at least 1")]
+    fn invalid_compression() {
+        TDigest::new(0.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "value must not be NaN")]
+    fn nan_value() {
+        TDigest::new(100.0).insert(f64::NAN);
+    }
+}
@@ -0,0 +1,263 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::data::{BinData, HistogramData};
+use crate::error::Error;
+use crate::histogram::Histogram;
+
+/// Formats the histogram in a human-readable text format, which can be parsed back with
+/// [`str::parse`]: the size of the histogram, the exact minimum and maximum values, the sum
+/// of values, the numbers of NaN, underflowed and overflowed values (only if they are not
+/// zero), and the `(value, count)` pairs of bins. Settings of the histogram (e.g. policies)
+/// are not formatted.
+///
+/// ```
+/// use bhtt::Histogram;
+///
+/// let h = Histogram::from_iter(5, [(1.0, 2), (3.5, 1)]);
+/// assert_eq!(
+///     h.to_string(),
+///     "size=5 min=1.0 max=3.5 sum=5.5 bins=[(1.0,2),(3.5,1)]"
+/// );
+/// assert_eq!(
+///     Histogram::new(5).to_string(),
+///     "size=5 min=none max=none sum=0.0 bins=[]"
+/// );
+/// ```
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let optional = |value: Option<f64>| match value {
+            Some(value) => format!("{:?}", value),
+            None => "none".into(),
+        };
+
+        write!(
+            f,
+            "size={} min={} max={} sum={:?}",
+            self.size(),
+            optional(self.min()),
+            optional(self.max()),
+            self.sum()
+        )?;
+        for (name, count) in [
+            ("nan", self.nan_count()),
+            ("underflow", self.underflow_count()),
+            ("overflow", self.overflow_count()),
+        ] {
+            if count > 0 {
+                write!(f, " {}={}", name, count)?;
+            }
+        }
+
+        write!(f, " bins=[")?;
+        for (i, bin) in self.bins().iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "({:?},{})", bin.value(), bin.count())?;
+        }
+        write!(f, "]")
+    }
+}
+
+/// Parses a histogram from the text format produced by its [`Display`](fmt::Display)
+/// implementation. Fields are separated by whitespace and can go in any order, but the
+/// bins must be the last field. Only the size and the bins are required: if the minimum
+/// and maximum values are omitted, the values of the outermost bins are used instead, and
+/// if the sum is omitted, it's estimated from the bins.
+///
+/// Returns an error, if the string can't be parsed or the parsed data is not consistent
+/// (e.g. bins are not sorted).
+///
+/// ```
+/// use bhtt::{Bin, Histogram};
+///
+/// let h: Histogram = "size=5 bins=[(1.0, 2), (3.5, 1)]".parse().unwrap();
+/// assert_eq!(h.size(), 5);
+/// assert_eq!(h.bins(), &[Bin::new(1.0, 2), Bin::new(3.5, 1)]);
+/// assert_eq!(h.min(), Some(1.0));
+/// assert_eq!(h.sum(), 5.5);
+///
+/// assert!("size=5".parse::<Histogram>().is_err());
+/// ```
+impl FromStr for Histogram {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (fields, bins) = s
+            .split_once("bins=")
+            .ok_or_else(|| Error::InvalidFormat("bins are missing".into()))?;
+        let bins = parse_bins(bins)?;
+
+        let (mut size, mut min_value, mut max_value, mut sum) = (None, None, None, None);
+        let (mut nan_count, mut underflow_count, mut overflow_count) = (0, 0, 0);
+        let mut seen = Vec::new();
+        for field in fields.split_whitespace() {
+            let (name, value) = field
+                .split_once('=')
+                .ok_or_else(|| Error::InvalidFormat(format!("unexpected field {}", field)))?;
+            if seen.contains(&name) {
+                return Err(Error::InvalidFormat(format!("duplicate field {}", name)));
+            }
+            seen.push(name);
+
+            match name {
+                "size" => size = Some(parse_integer(value)?),
+                "min" => min_value = Some(parse_optional_number(value)?),
+                "max" => max_value = Some(parse_optional_number(value)?),
+                "sum" => sum = Some(parse_number(value)?),
+                "nan" => nan_count = parse_integer(value)?,
+                "underflow" => underflow_count = parse_integer(value)?,
+                "overflow" => overflow_count = parse_integer(value)?,
+                _ => return Err(Error::InvalidFormat(format!("unexpected field {}", field))),
+            }
+        }
+
+        let data = HistogramData {
+            size: size.ok_or_else(|| Error::InvalidFormat("size is missing".into()))?,
+            count: bins
+                .iter()
+                .try_fold(0u64, |total, bin| total.checked_add(bin.count))
+                .ok_or_else(|| Error::InvalidFormat("the total count is too large".into()))?,
+            min_value: min_value.unwrap_or_else(|| bins.first().map(|bin| bin.value)),
+            max_value: max_value.unwrap_or_else(|| bins.last().map(|bin| bin.value)),
+            sum: sum.unwrap_or_else(|| bins.iter().map(|bin| bin.value * bin.count as f64).sum()),
+            bins,
+            nan_count,
+            underflow_count,
+            overflow_count,
+        };
+
+        Histogram::try_from(data)
+    }
+}
+
+fn parse_number(s: &str) -> Result<f64, Error> {
+    s.parse()
+        .map_err(|_| Error::InvalidFormat(format!("{} is not a number", s)))
+}
+
+fn parse_optional_number(s: &str) -> Result<Option<f64>, Error> {
+    match s {
+        "none" => Ok(None),
+        s => parse_number(s).map(Some),
+    }
+}
+
+fn parse_integer(s: &str) -> Result<u64, Error> {
+    s.parse()
+        .map_err(|_| Error::InvalidFormat(format!("{} is not a non-negative integer", s)))
+}
+
+fn parse_bins(s: &str) -> Result<Vec<BinData>, Error> {
+    let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    let pairs = s
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(|| Error::InvalidFormat("expected a list of bins".into()))?;
+    if pairs.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    pairs
+        .strip_prefix('(')
+        .and_then(|s| s.strip_suffix(')'))
+        .ok_or_else(|| Error::InvalidFormat("expected a (value,count) pair".into()))?
+        .split("),(")
+        .map(|pair| match pair.split_once(',') {
+            Some((value, count)) => Ok(BinData {
+                value: parse_number(value)?,
+                count: parse_integer(count)?,
+            }),
+            None => Err(Error::InvalidFormat(format!(
+                "expected a (value,count) pair, got ({})",
+                pair
+            ))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bin::Bin;
+    use crate::policy::{InfinityPolicy, NanPolicy};
+
+    #[test]
+    fn round_trip() {
+        let mut h = Histogram::new(16);
+        h.set_nan_policy(NanPolicy::Count);
+        h.set_infinity_policy(InfinityPolicy::Count);
+        for v in 0..1000 {
+            h.insert((v as f64).sqrt() * 1.1);
+        }
+        for value in [f64::NAN, f64::NEG_INFINITY, f64::INFINITY, f64::INFINITY] {
+            h.try_insert(value).unwrap();
+        }
+
+        let s = h.to_string();
+        assert!(s.contains(" nan=1 underflow=1 overflow=2 bins=["));
+
+        let h2: Histogram = s.parse().unwrap();
+        assert_eq!(h2.size(), h.size());
+        assert_eq!(h2.bins(), h.bins());
+        assert_eq!(h2.min(), h.min());
+        assert_eq!(h2.max(), h.max());
+        assert_eq!(h2.sum(), h.sum());
+        assert_eq!(h2.nan_count(), 1);
+        assert_eq!(h2.underflow_count(), 1);
+        assert_eq!(h2.overflow_count(), 2);
+        assert_eq!(h2.to_string(), s);
+    }
+
+    #[test]
+    fn empty() {
+        let h: Histogram = "size=3 min=none max=none sum=0.0 bins=[]".parse().unwrap();
+        assert_eq!(h.size(), 3);
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.min(), None);
+
+        let h: Histogram = "size=3\nbins=[ ]".parse().unwrap();
+        assert_eq!(h.size(), 3);
+    }
+
+    #[test]
+    fn any_order() {
+        let h: Histogram = "  sum=7.0\tmax=4.0 size=3 min=-1.0 bins=[(-1e0,1), (2.0 , 2)]"
+            .parse()
+            .unwrap();
+        assert_eq!(h.bins(), &[Bin::new(-1.0, 1), Bin::new(2.0, 2)]);
+        assert_eq!(h.min(), Some(-1.0));
+        assert_eq!(h.max(), Some(4.0));
+        assert_eq!(h.sum(), 7.0);
+    }
+
+    #[test]
+    fn invalid() {
+        let cases = [
+            "",
+            "size=3",
+            "bins=[]",
+            "size=0 bins=[]",
+            "size=-1 bins=[]",
+            "size=3 size=4 bins=[]",
+            "size=3 foo=1 bins=[]",
+            "size=3 min bins=[]",
+            "size=3 bins=[(1.0,1)",
+            "size=3 bins=[(1.0)]",
+            "size=3 bins=[(1.0,0)]",
+            "size=3 bins=[(1.0,-1)]",
+            "size=3 bins=[(NaN,1)]",
+            "size=3 bins=[(1.0,1),]",
+            "size=3 bins=[(2.0,1),(1.0,1)]",
+            "size=1 bins=[(1.0,1),(2.0,1)]",
+            "size=3 min=1.5 bins=[(1.0,1)]",
+            "size=3 min=none bins=[(1.0,1)]",
+            "size=3 sum=inf bins=[(1.0,1)]",
+            "size=3 nan=x bins=[]",
+        ];
+        for case in cases {
+            assert!(case.parse::<Histogram>().is_err(), "{}", case);
+        }
+    }
+}
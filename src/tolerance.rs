@@ -0,0 +1,179 @@
+use approx::{AbsDiffEq, RelativeEq};
+
+use crate::bin::Bin;
+use crate::histogram::Histogram;
+
+/// Bins are approximately equal, if their values are approximately equal, and their counts
+/// are equal.
+///
+/// This is only available when the `approx` feature is enabled.
+///
+/// ```
+/// use approx::assert_abs_diff_eq;
+/// use bhtt::Bin;
+///
+/// assert_abs_diff_eq!(Bin::new(1.0, 2), Bin::new(1.0 + 1e-12, 2), epsilon = 1e-9);
+/// ```
+impl AbsDiffEq for Bin {
+    type Epsilon = f64;
+
+    fn default_epsilon() -> Self::Epsilon {
+        f64::default_epsilon()
+    }
+
+    fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
+        self.count() == other.count() && self.value().abs_diff_eq(&other.value(), epsilon)
+    }
+}
+
+impl RelativeEq for Bin {
+    fn default_max_relative() -> Self::Epsilon {
+        f64::default_max_relative()
+    }
+
+    fn relative_eq(
+        &self,
+        other: &Self,
+        epsilon: Self::Epsilon,
+        max_relative: Self::Epsilon,
+    ) -> bool {
+        self.count() == other.count()
+            && self
+                .value()
+                .relative_eq(&other.value(), epsilon, max_relative)
+    }
+}
+
+/// Histograms are approximately equal, if they have the same size, the same number of bins
+/// with approximately equal values and equal counts (see the implementation for [`Bin`]),
+/// approximately equal minimum and maximum values and sums of values, and equal numbers of
+/// NaN, underflowed and overflowed values. Settings of histograms (e.g. policies) are not
+/// compared.
+///
+/// Unlike exact comparisons, approximate ones are not affected by rounding errors, e.g. when
+/// histograms are merged in a different order, or on different platforms.
+///
+/// This is only available when the `approx` feature is enabled.
+///
+/// ```
+/// use approx::{assert_relative_eq, assert_relative_ne};
+/// use bhtt::Histogram;
+///
+/// let h1 = Histogram::from_iter(3, [0.3, 0.6]);
+/// let h2 = Histogram::from_iter(3, [0.1 * 3.0, 0.2 * 3.0]);
+/// assert_ne!(h1, h2);
+/// assert_relative_eq!(h1, h2);
+///
+/// let h3 = Histogram::from_iter(3, [0.3, 0.7]);
+/// assert_relative_ne!(h1, h3);
+/// ```
+impl AbsDiffEq for Histogram {
+    type Epsilon = f64;
+
+    fn default_epsilon() -> Self::Epsilon {
+        f64::default_epsilon()
+    }
+
+    fn abs_diff_eq(&self, other: &Self, epsilon: Self::Epsilon) -> bool {
+        approx_eq(
+            self,
+            other,
+            |a, b| a.abs_diff_eq(b, epsilon),
+            |a, b| a.abs_diff_eq(&b, epsilon),
+        )
+    }
+}
+
+impl RelativeEq for Histogram {
+    fn default_max_relative() -> Self::Epsilon {
+        f64::default_max_relative()
+    }
+
+    fn relative_eq(
+        &self,
+        other: &Self,
+        epsilon: Self::Epsilon,
+        max_relative: Self::Epsilon,
+    ) -> bool {
+        approx_eq(
+            self,
+            other,
+            |a, b| a.relative_eq(b, epsilon, max_relative),
+            |a, b| a.relative_eq(&b, epsilon, max_relative),
+        )
+    }
+}
+
+/// Compare histograms using the given approximate comparisons of bins and values.
+fn approx_eq(
+    h1: &Histogram,
+    h2: &Histogram,
+    bins_eq: impl Fn(&Bin, &Bin) -> bool,
+    values_eq: impl Fn(f64, f64) -> bool,
+) -> bool {
+    let optional_values_eq = |a: Option<f64>, b: Option<f64>| match (a, b) {
+        (Some(a), Some(b)) => values_eq(a, b),
+        (a, b) => a == b,
+    };
+
+    h1.size() == h2.size()
+        && h1.bins().len() == h2.bins().len()
+        && h1.bins().iter().zip(h2.bins()).all(|(a, b)| bins_eq(a, b))
+        && optional_values_eq(h1.min(), h2.min())
+        && optional_values_eq(h1.max(), h2.max())
+        && values_eq(h1.sum(), h2.sum())
+        && h1.nan_count() == h2.nan_count()
+        && h1.underflow_count() == h2.underflow_count()
+        && h1.overflow_count() == h2.overflow_count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bins() {
+        assert_abs_diff_eq!(Bin::new(1.0, 2), Bin::new(1.0, 2));
+        assert_abs_diff_eq!(Bin::new(1.0, 2), Bin::new(1.05, 2), epsilon = 0.1);
+        assert_abs_diff_ne!(Bin::new(1.0, 2), Bin::new(1.05, 2), epsilon = 0.01);
+        assert_abs_diff_ne!(Bin::new(1.0, 2), Bin::new(1.0, 3), epsilon = 0.1);
+
+        assert_relative_eq!(
+            Bin::new(1e9, 2),
+            Bin::new(1e9 + 1.0, 2),
+            max_relative = 1e-6
+        );
+        assert_relative_ne!(Bin::new(1e9, 2), Bin::new(1e9 + 1.0, 2));
+        assert_relative_ne!(Bin::new(1e9, 2), Bin::new(1e9, 1), max_relative = 1e-6);
+    }
+
+    #[test]
+    fn histograms() {
+        let h1 = Histogram::from_iter(8, (0..1000).map(|v| (v as f64 * 0.1).sin()));
+        // rounding errors
+        let mut h2 = h1.clone();
+        h2.map_values(1.0, 0.1);
+        h2.map_values(1.0, -0.1);
+        assert_ne!(h1, h2);
+        assert_relative_eq!(h1, h1);
+        assert_relative_eq!(h1, h2, max_relative = 1e-9);
+        assert_abs_diff_eq!(h1, h2, epsilon = 1e-9);
+
+        let mut h3 = h1.clone();
+        h3.map_values(1.0, 1e-6);
+        // the sum of 1000 values is shifted by 1e-3
+        assert_abs_diff_eq!(h1, h3, epsilon = 1e-2);
+        assert_abs_diff_ne!(h1, h3, epsilon = 1e-7);
+
+        // counts must be equal
+        let mut h4 = h1.clone();
+        h4.insert(0.0);
+        assert_abs_diff_ne!(h1, h4, epsilon = 1.0);
+
+        // the size, the number of bins and special counts must be equal
+        assert_abs_diff_ne!(h1, h1.resized(9), epsilon = 1.0);
+        assert_abs_diff_ne!(h1, h1.resized(7), epsilon = 1.0);
+        assert_abs_diff_ne!(Histogram::new(3), Histogram::new(4));
+        assert_abs_diff_eq!(Histogram::new(3), Histogram::new(3));
+    }
+}
@@ -0,0 +1,498 @@
+use crate::bin::TotalF64;
+
+/// A bin of a [`VarianceHistogram`]: the mean and the number of values merged together,
+/// and the sum of squared deviations of those values from their mean.
+///
+/// ```
+/// use bhtt::VarianceBin;
+///
+/// let b1 = VarianceBin::new(1.0);
+/// let b2 = VarianceBin::new(3.0);
+///
+/// let b3 = VarianceBin::merge(&b1, &b2);
+/// assert_eq!(b3.value(), 2.0);
+/// assert_eq!(b3.count(), 2);
+/// assert_eq!(b3.variance(), 1.0);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct VarianceBin {
+    value: f64,
+    count: u64,
+    sum_of_squares: f64,
+}
+
+impl VarianceBin {
+    /// Returns a new VarianceBin holding a single value. `value` must be finite, or
+    /// the function will panic.
+    pub fn new(value: f64) -> VarianceBin {
+        assert!(!value.is_nan(), "value must not be NaN");
+        assert!(value.is_finite(), "value must be finite");
+
+        VarianceBin {
+            value,
+            count: 1,
+            sum_of_squares: 0.0,
+        }
+    }
+
+    /// Returns a new VarianceBin that holds the values of both bins. Unlike
+    /// [`Bin::merge`](crate::Bin::merge), no information about the spread of values
+    /// is lost.
+    pub fn merge(left: &VarianceBin, right: &VarianceBin) -> VarianceBin {
+        // the parallel algorithm by Chan et al.
+        let count = left.count + right.count;
+        let delta = right.value - left.value;
+        let ratio = right.count as f64 / count as f64;
+
+        VarianceBin {
+            value: left.value + delta * ratio,
+            count,
+            sum_of_squares: left.sum_of_squares
+                + right.sum_of_squares
+                + delta * delta * left.count as f64 * ratio,
+        }
+    }
+
+    /// Returns the mean of values in the bin.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Returns the number of values in the bin.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the (population) variance of values in the bin.
+    pub fn variance(&self) -> f64 {
+        self.sum_of_squares / self.count as f64
+    }
+
+    /// Returns the cumulative count of values less than or equal to `x`, assuming that
+    /// the values are uniformly distributed over an interval with the same mean and
+    /// variance, which is clipped to the given boundaries.
+    fn count_up_to(&self, x: f64, min_value: f64, max_value: f64) -> f64 {
+        // the variance of a uniform distribution over [a; b] is (b - a)^2 / 12
+        let half_width = (3.0 * self.variance()).sqrt();
+        let left = (self.value - half_width).max(min_value);
+        let right = (self.value + half_width).min(max_value);
+
+        if x >= right {
+            self.count as f64
+        } else if x < left {
+            0.0
+        } else {
+            self.count as f64 * (x - left) / (right - left)
+        }
+    }
+}
+
+/// A variant of [`Histogram`](crate::Histogram), in which bins also keep track of
+/// the spread of values merged into them.
+///
+/// This makes it possible to compute the exact variance of values, and improves
+/// the estimates of quantiles: values of every bin are assumed to be distributed
+/// uniformly over an interval of the matching width around the mean, rather than
+/// between the means of adjacent bins. Bins take 50% more memory than those of
+/// [`Histogram`](crate::Histogram).
+///
+/// ```
+/// use bhtt::VarianceHistogram;
+///
+/// let mut h = VarianceHistogram::new(2);
+/// for value in vec![1.0, 2.0, 3.0, 10.0, 11.0, 12.0] {
+///     h.insert(value);
+/// }
+///
+/// assert_eq!(h.mean(), Some(6.5));
+/// assert!((h.variance().unwrap() - 125.5 / 6.0).abs() < 1e-9);
+/// // the spread of values merged into a bin is preserved
+/// assert!((h.bins()[0].variance() - 2.0 / 3.0).abs() < 1e-9);
+/// ```
+#[derive(Debug, Clone)]
+pub struct VarianceHistogram {
+    size: usize,
+    bins: Vec<VarianceBin>,
+    min_value: Option<f64>,
+    max_value: Option<f64>,
+}
+
+impl VarianceHistogram {
+    /// The maximum number of bisection steps taken to find a quantile, which is enough to
+    /// narrow down any range of finite values to adjacent f64 numbers.
+    const QUANTILE_ITERATIONS: usize = 2100;
+
+    /// Create a new VarianceHistogram with the given number of bins.
+    ///
+    /// ```
+    /// use bhtt::VarianceHistogram;
+    ///
+    /// let h = VarianceHistogram::new(5);
+    /// assert_eq!(h.size(), 5);
+    /// assert_eq!(h.count(), 0);
+    /// ```
+    pub fn new(size: usize) -> VarianceHistogram {
+        assert!(size > 0, "histogram size must be greater than 0");
+
+        VarianceHistogram {
+            size,
+            bins: Vec::with_capacity(size + 1),
+            min_value: None,
+            max_value: None,
+        }
+    }
+
+    /// Returns the size of the histogram.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the bins of the histogram.
+    pub fn bins(&self) -> &[VarianceBin] {
+        &self.bins
+    }
+
+    /// Returns the total number of values in the histogram.
+    pub fn count(&self) -> u64 {
+        self.bins.iter().map(|bin| bin.count).sum()
+    }
+
+    /// Returns the (exact) minimum value or `None` if the histogram is empty.
+    pub fn min(&self) -> Option<f64> {
+        self.min_value
+    }
+
+    /// Returns the (exact) maximum value or `None` if the histogram is empty.
+    pub fn max(&self) -> Option<f64> {
+        self.max_value
+    }
+
+    /// Returns the mean of values or `None` if the histogram is empty.
+    pub fn mean(&self) -> Option<f64> {
+        match self.count() {
+            0 => None,
+            total => Some(
+                self.bins
+                    .iter()
+                    .map(|bin| bin.value * bin.count as f64)
+                    .sum::<f64>()
+                    / total as f64,
+            ),
+        }
+    }
+
+    /// Returns the (population) variance of values or `None` if the histogram is empty.
+    ///
+    /// ```
+    /// use bhtt::VarianceHistogram;
+    ///
+    /// let mut h = VarianceHistogram::new(1);
+    /// assert_eq!(h.variance(), None);
+    ///
+    /// h.insert(1.0);
+    /// h.insert(3.0);
+    /// assert_eq!(h.variance(), Some(1.0));
+    /// ```
+    pub fn variance(&self) -> Option<f64> {
+        let mean = self.mean()?;
+
+        // the law of total variance: the variance within bins plus the variance between them
+        let sum_of_squares: f64 = self
+            .bins
+            .iter()
+            .map(|bin| bin.sum_of_squares + bin.count as f64 * (bin.value - mean).powi(2))
+            .sum();
+        Some(sum_of_squares / self.count() as f64)
+    }
+
+    /// Returns the (population) standard deviation of values or `None` if the histogram
+    /// is empty.
+    pub fn std_dev(&self) -> Option<f64> {
+        self.variance().map(f64::sqrt)
+    }
+
+    /// Returns an approximated value of the `q`'th quantile of the values or `None`
+    /// if the histogram is empty. `q` must be in the range [0.0; 1.0], or the function
+    /// will panic.
+    ///
+    /// ```
+    /// use bhtt::VarianceHistogram;
+    ///
+    /// let mut h = VarianceHistogram::new(2);
+    /// for i in 0..=100 {
+    ///     h.insert(i as f64);
+    /// }
+    ///
+    /// assert_eq!(h.quantile(0.0), Some(0.0));
+    /// assert!((h.quantile(0.25).unwrap() - 25.0).abs() < 1.0);
+    /// assert_eq!(h.quantile(1.0), Some(100.0));
+    /// ```
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        assert!(
+            (0.0..=1.0).contains(&q),
+            "q must be in the range [0.0; 1.0]"
+        );
+
+        let (min_value, max_value) = match (self.min_value, self.max_value) {
+            (Some(min_value), Some(max_value)) => (min_value, max_value),
+            _ => return None,
+        };
+        if q == 0.0 {
+            return Some(min_value);
+        } else if q == 1.0 {
+            return Some(max_value);
+        }
+
+        // the cumulative count is monotonic, so the smallest value, at which it reaches
+        // the target, is found by bisection
+        let qth_count = self.count() as f64 * q;
+        let (mut low, mut high) = (min_value, max_value);
+        for _ in 0..Self::QUANTILE_ITERATIONS {
+            let mid = low + (high - low) / 2.0;
+            if mid <= low || mid >= high {
+                break;
+            }
+
+            let count: f64 = self
+                .bins
+                .iter()
+                .map(|bin| bin.count_up_to(mid, min_value, max_value))
+                .sum();
+            if count < qth_count {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+
+        Some(high)
+    }
+
+    /// Returns an approximated value of the median or `None` if the histogram is empty.
+    pub fn median(&self) -> Option<f64> {
+        self.quantile(0.5)
+    }
+
+    /// Insert a new value into the histogram. `value` must be finite, or the function
+    /// will panic.
+    pub fn insert(&mut self, value: f64) {
+        let bin = VarianceBin::new(value);
+        self.min_value = Some(self.min_value.map_or(value, |min| min.min(value)));
+        self.max_value = Some(self.max_value.map_or(value, |max| max.max(value)));
+
+        let pos = self.bins.partition_point(|b| b.value <= value);
+        self.bins.insert(pos, bin);
+        self.shrink();
+    }
+
+    /// Merge another histogram into this one.
+    ///
+    /// ```
+    /// use bhtt::VarianceHistogram;
+    ///
+    /// let mut h1 = VarianceHistogram::new(5);
+    /// h1.insert(1.0);
+    ///
+    /// let mut h2 = VarianceHistogram::new(5);
+    /// h2.insert(3.0);
+    ///
+    /// h1.merge(&h2);
+    /// assert_eq!(h1.count(), 2);
+    /// assert_eq!(h1.variance(), Some(1.0));
+    /// ```
+    pub fn merge(&mut self, other: &VarianceHistogram) {
+        for bin in other.bins.iter() {
+            let pos = self.bins.partition_point(|b| b.value <= bin.value);
+            self.bins.insert(pos, *bin);
+        }
+
+        for value in [other.min_value, other.max_value].into_iter().flatten() {
+            self.min_value = Some(self.min_value.map_or(value, |min| min.min(value)));
+            self.max_value = Some(self.max_value.map_or(value, |max| max.max(value)));
+        }
+        self.shrink();
+    }
+
+    /// Merge two closest bins until the histogram shrinks back to the fixed size.
+    fn shrink(&mut self) {
+        while self.bins.len() > self.size {
+            let right = (1..self.bins.len())
+                .min_by_key(|&i| {
+                    (
+                        TotalF64(self.bins[i].value - self.bins[i - 1].value),
+                        self.bins[i - 1].count + self.bins[i].count,
+                    )
+                })
+                .unwrap();
+
+            self.bins[right - 1] = VarianceBin::merge(&self.bins[right - 1], &self.bins[right]);
+            self.bins.remove(right);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utilities::pseudo_random_values;
+
+    use super::*;
+
+    fn exact_variance(values: &[f64]) -> f64 {
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64
+    }
+
+    #[test]
+    fn new() {
+        let h = VarianceHistogram::new(5);
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.count(), 0);
+        assert_eq!(h.bins(), &[]);
+        assert_eq!(h.min(), None);
+        assert_eq!(h.max(), None);
+        assert_eq!(h.mean(), None);
+        assert_eq!(h.variance(), None);
+        assert_eq!(h.std_dev(), None);
+        assert_eq!(h.quantile(0.5), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram size must be greater than 0")]
+    fn new_invalid_size() {
+        VarianceHistogram::new(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "value must not be NaN")]
+    fn insert_nan() {
+        VarianceHistogram::new(5).insert(f64::NAN);
+    }
+
+    #[test]
+    fn bin_merge() {
+        let values = [1.0, 0.0, -5.4, -2.1, 8.5];
+        let bin = values
+            .iter()
+            .map(|&value| VarianceBin::new(value))
+            .reduce(|left, right| VarianceBin::merge(&left, &right))
+            .unwrap();
+
+        assert_eq!(bin.count(), 5);
+        assert_relative_eq!(bin.value(), 0.4);
+        assert_relative_eq!(bin.variance(), exact_variance(&values));
+    }
+
+    #[test]
+    fn insert_matches_histogram() {
+        let values = [1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2];
+        let mut h = VarianceHistogram::new(5);
+        for value in values {
+            h.insert(value);
+        }
+
+        let expected = crate::Histogram::from_iter(5, values);
+        assert_eq!(h.bins().len(), expected.bins().len());
+        for (bin, expected) in h.bins().iter().zip(expected.bins()) {
+            assert_relative_eq!(bin.value(), expected.value());
+            assert_eq!(bin.count(), expected.count());
+        }
+        assert_eq!(h.count(), 10);
+        assert_eq!(h.min(), Some(-5.4));
+        assert_eq!(h.max(), Some(10.0));
+    }
+
+    #[test]
+    fn variance() {
+        let values = pseudo_random_values(42, 1000);
+        let mut h = VarianceHistogram::new(8);
+        for &value in &values {
+            h.insert(value);
+        }
+
+        assert_relative_eq!(
+            h.variance().unwrap(),
+            exact_variance(&values),
+            max_relative = 1e-9
+        );
+        assert_relative_eq!(
+            h.std_dev().unwrap(),
+            exact_variance(&values).sqrt(),
+            max_relative = 1e-9
+        );
+    }
+
+    #[test]
+    fn merge() {
+        let values = pseudo_random_values(7, 200);
+        let mut h1 = VarianceHistogram::new(4);
+        let mut h2 = VarianceHistogram::new(4);
+        for (i, &value) in values.iter().enumerate() {
+            if i % 2 == 0 {
+                h1.insert(value);
+            } else {
+                h2.insert(value);
+            }
+        }
+
+        h1.merge(&h2);
+        assert_eq!(h1.bins().len(), 4);
+        assert_eq!(h1.count(), 200);
+        assert_eq!(h1.min(), values.iter().copied().reduce(f64::min));
+        assert_eq!(h1.max(), values.iter().copied().reduce(f64::max));
+        assert_relative_eq!(
+            h1.variance().unwrap(),
+            exact_variance(&values),
+            max_relative = 1e-9
+        );
+    }
+
+    #[test]
+    fn quantile_single_value() {
+        let mut h = VarianceHistogram::new(5);
+        h.insert(42.0);
+        h.insert(42.0);
+
+        for q in [0.0, 0.25, 0.5, 0.75, 1.0] {
+            assert_eq!(h.quantile(q), Some(42.0));
+        }
+    }
+
+    #[test]
+    fn quantile_accuracy() {
+        // values of bins are spread better than by interpolating between adjacent bins
+        let values = pseudo_random_values(1, 10_000);
+        let mut sorted = values.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let mut h = VarianceHistogram::new(8);
+        for &value in &values {
+            h.insert(value);
+        }
+        let expected = crate::Histogram::from_iter(8, &values);
+
+        let (mut error, mut expected_error) = (0.0, 0.0);
+        for i in 1..100 {
+            let q = i as f64 / 100.0;
+            let exact = sorted[(q * sorted.len() as f64) as usize];
+            error += (h.quantile(q).unwrap() - exact).abs();
+            expected_error += (expected.quantile(q).unwrap() - exact).abs();
+        }
+        assert!(error < expected_error, "{} >= {}", error, expected_error);
+    }
+
+    #[test]
+    fn quantile_monotonic() {
+        let mut h = VarianceHistogram::new(5);
+        for value in [1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+            h.insert(value);
+        }
+
+        let mut previous = f64::NEG_INFINITY;
+        for i in 0..=100 {
+            let value = h.quantile(i as f64 / 100.0).unwrap();
+            assert!(value >= previous);
+            assert!((-5.4..=10.0).contains(&value));
+            previous = value;
+        }
+    }
+}
@@ -0,0 +1,482 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::bin::Bin;
+use crate::histogram::Histogram;
+use crate::persist::crc32;
+
+/// The number of records in the log, after which it is compacted by default.
+const DEFAULT_COMPACTION_THRESHOLD: usize = 100_000;
+
+/// The tag of a record of a single value.
+const VALUE_RECORD: u8 = 0;
+
+/// The tag of a record of a bin, i.e. of a value inserted with a count.
+const BIN_RECORD: u8 = 1;
+
+/// A histogram, which is made crash-consistent by a write-ahead log of inserts.
+///
+/// The state of the histogram is stored in a directory as a checkpoint (see
+/// [`Histogram::save_to`]) and an append-only log of values (and bins) inserted after
+/// the checkpoint was saved. Every insert is appended to the log before it is applied to
+/// the histogram. Once the log grows to the compaction threshold, it is compacted: a new
+/// checkpoint is saved and the log is started over. When a LoggedHistogram is opened, the
+/// latest checkpoint is loaded and the log is replayed into it.
+///
+/// Appended records are buffered, so only the records appended since the last call to
+/// [`sync`](LoggedHistogram::sync) (or the last compaction) can be lost in a crash. A
+/// record, which was only partially written before a crash, is detected by its checksum
+/// and discarded on replay.
+///
+/// ```
+/// use bhtt::LoggedHistogram;
+///
+/// let dir = std::env::temp_dir().join("bhtt-logged-histogram-doctest");
+/// # let _ = std::fs::remove_dir_all(&dir);
+///
+/// let mut h = LoggedHistogram::open(&dir, 64).unwrap();
+/// h.insert(1.0).unwrap();
+/// h.insert((2.0, 10)).unwrap();
+/// h.sync().unwrap();
+/// drop(h);
+///
+/// // e.g. after a restart
+/// let h = LoggedHistogram::open(&dir, 64).unwrap();
+/// assert_eq!(h.histogram().count(), 11);
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct LoggedHistogram {
+    dir: PathBuf,
+    histogram: Histogram,
+    generation: u64,
+    log: BufWriter<File>,
+    records: usize,
+    compaction_threshold: usize,
+}
+
+impl LoggedHistogram {
+    /// Open a LoggedHistogram stored in the given directory, which is created if it does
+    /// not exist. If the directory contains no checkpoint, the histogram starts from
+    /// an empty histogram of the given size.
+    ///
+    /// Returns an error, if the directory can't be accessed, or an error of the
+    /// [`InvalidData`](io::ErrorKind::InvalidData) kind, if the checkpoint is not valid
+    /// (see [`Histogram::load_from`]).
+    pub fn open<P: AsRef<Path>>(dir: P, size: usize) -> io::Result<LoggedHistogram> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        // files of older generations are only left behind, if a compaction was interrupted
+        let mut generation = None;
+        for entry in fs::read_dir(&dir)? {
+            if let Some(checkpoint) = parse_generation(&entry?.file_name(), CHECKPOINT) {
+                generation = generation.max(Some(checkpoint));
+            }
+        }
+        let (generation, mut histogram) = match generation {
+            Some(generation) => (
+                generation,
+                Histogram::load_from(dir.join(file_name(CHECKPOINT, generation)))?,
+            ),
+            None => (0, Histogram::new(size)),
+        };
+        remove_older_generations(&dir, generation)?;
+
+        let log_path = dir.join(file_name(LOG, generation));
+        let (records, valid_len) = match fs::read(&log_path) {
+            Ok(bytes) => replay(&bytes, &mut histogram),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (0, 0),
+            Err(err) => return Err(err),
+        };
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)?;
+        // discard a partially written record, so that new records are appended after
+        // the last valid one
+        log.set_len(valid_len as u64)?;
+
+        Ok(LoggedHistogram {
+            dir,
+            histogram,
+            generation,
+            log: BufWriter::new(log),
+            records,
+            compaction_threshold: DEFAULT_COMPACTION_THRESHOLD,
+        })
+    }
+
+    /// Returns the histogram.
+    pub fn histogram(&self) -> &Histogram {
+        &self.histogram
+    }
+
+    /// Returns the directory, where the histogram is stored.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the number of records in the log, i.e. the number of inserts since
+    /// the last compaction.
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    /// Returns the number of records in the log, after which the log is compacted.
+    pub fn compaction_threshold(&self) -> usize {
+        self.compaction_threshold
+    }
+
+    /// Set the number of records in the log, after which the log is compacted. The default
+    /// is 100 000 records. The threshold must be greater than 0, or the function will panic.
+    pub fn set_compaction_threshold(&mut self, records: usize) {
+        assert!(records > 0, "compaction threshold must be greater than 0");
+
+        self.compaction_threshold = records;
+    }
+
+    /// Append a new value (or bin) to the log and insert it into the histogram (see
+    /// [`Histogram::insert`]). The log is compacted, if it has grown to the compaction
+    /// threshold.
+    ///
+    /// Returns an error, if the record can't be written, in which case the value is not
+    /// inserted into the histogram.
+    pub fn insert<T: Into<Bin>>(&mut self, value: T) -> io::Result<()> {
+        let bin = value.into();
+
+        let mut record = Vec::with_capacity(21);
+        if bin.count() == 1 {
+            record.push(VALUE_RECORD);
+            record.extend_from_slice(&bin.value().to_le_bytes());
+        } else {
+            record.push(BIN_RECORD);
+            record.extend_from_slice(&bin.value().to_le_bytes());
+            record.extend_from_slice(&bin.count().to_le_bytes());
+        }
+        record.extend_from_slice(&crc32(&record).to_le_bytes());
+        self.log.write_all(&record)?;
+
+        self.histogram.insert(bin);
+        self.records += 1;
+        if self.records >= self.compaction_threshold {
+            self.compact()?;
+        }
+
+        Ok(())
+    }
+
+    /// Write all buffered records to the log and sync it to the disk, so that they can't be
+    /// lost in a crash.
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.log.flush()?;
+        self.log.get_ref().sync_data()
+    }
+
+    /// Compact the log: save the histogram as a new checkpoint and start a new empty log.
+    ///
+    /// A compaction is crash-safe: the checkpoint and the log of every compaction belong to
+    /// a new generation, and files of the previous generation are only removed after
+    /// the new checkpoint is saved. If a compaction is interrupted, the files of the latest
+    /// complete generation are used when the histogram is opened.
+    ///
+    /// ```
+    /// use bhtt::LoggedHistogram;
+    ///
+    /// let dir = std::env::temp_dir().join("bhtt-compact-doctest");
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    ///
+    /// let mut h = LoggedHistogram::open(&dir, 64).unwrap();
+    /// h.insert(1.0).unwrap();
+    /// assert_eq!(h.records(), 1);
+    ///
+    /// h.compact().unwrap();
+    /// assert_eq!(h.records(), 0);
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn compact(&mut self) -> io::Result<()> {
+        let generation = self.generation + 1;
+
+        // the new log is created first: once the new checkpoint is saved, records must
+        // not be appended to the old log anymore, as it is discarded on replay
+        let log_path = self.dir.join(file_name(LOG, generation));
+        let log = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&log_path)?;
+        if let Err(err) = self
+            .histogram
+            .save_to(self.dir.join(file_name(CHECKPOINT, generation)))
+        {
+            let _ = fs::remove_file(&log_path);
+            return Err(err);
+        }
+
+        // the records of the previous generation are included in the new checkpoint, so
+        // the buffered ones are flushed to the old log on a best-effort basis
+        self.log = BufWriter::new(log);
+        self.generation = generation;
+        self.records = 0;
+
+        remove_older_generations(&self.dir, generation)
+    }
+}
+
+/// The prefix of names of checkpoint files.
+const CHECKPOINT: &str = "checkpoint";
+
+/// The prefix of names of log files.
+const LOG: &str = "log";
+
+/// Returns the name of a file of the given kind and generation.
+fn file_name(prefix: &str, generation: u64) -> String {
+    format!("{}.{:020}", prefix, generation)
+}
+
+/// Returns the generation of a file of the given kind, or `None` if the name belongs to
+/// a file of a different kind (e.g. a temporary file).
+fn parse_generation(name: &std::ffi::OsStr, prefix: &str) -> Option<u64> {
+    let suffix = name.to_str()?.strip_prefix(prefix)?.strip_prefix('.')?;
+    if suffix.len() != 20 {
+        return None;
+    }
+
+    suffix.parse().ok()
+}
+
+/// Remove checkpoints and logs of generations older than the given one.
+fn remove_older_generations(dir: &Path, generation: u64) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let older = [CHECKPOINT, LOG]
+            .iter()
+            .filter_map(|prefix| parse_generation(&name, prefix))
+            .any(|g| g < generation);
+        if older {
+            fs::remove_file(entry.path())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Replay the records of a log into the histogram. Returns the number of replayed records
+/// and the length of the valid prefix of the log: replay stops at the first incomplete or
+/// corrupted record, which is left behind by a crash in the middle of a write.
+fn replay(bytes: &[u8], histogram: &mut Histogram) -> (usize, usize) {
+    let mut records = 0;
+    let mut offset = 0;
+    while let Some((bin, len)) = parse_record(&bytes[offset..]) {
+        histogram.insert(bin);
+        records += 1;
+        offset += len;
+    }
+
+    (records, offset)
+}
+
+/// Parse the record at the start of the bytes. Returns the bin and the length of
+/// the record, or `None` if the record is incomplete or corrupted.
+fn parse_record(bytes: &[u8]) -> Option<(Bin, usize)> {
+    let len = match *bytes.first()? {
+        VALUE_RECORD => 1 + 8,
+        BIN_RECORD => 1 + 8 + 8,
+        _ => return None,
+    };
+    let record = bytes.get(..len)?;
+    let checksum = u32::from_le_bytes(bytes.get(len..len + 4)?.try_into().ok()?);
+    if crc32(record) != checksum {
+        return None;
+    }
+
+    let value = f64::from_le_bytes(record[1..9].try_into().ok()?);
+    let count = match record.get(9..17) {
+        Some(count) => u64::from_le_bytes(count.try_into().ok()?),
+        None => 1,
+    };
+    if !value.is_finite() || count == 0 {
+        return None;
+    }
+
+    Some((Bin::new(value, count), len + 4))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A directory for test files, which is unique for every test and every run of the tests.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bhtt-wal-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        dir
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+
+        names
+    }
+
+    #[test]
+    fn replay_after_restart() {
+        let dir = test_dir("replay");
+
+        let mut h = LoggedHistogram::open(&dir, 8).unwrap();
+        let mut expected = Histogram::new(8);
+        for v in 0..100 {
+            let value = (v as f64).sqrt();
+            h.insert(value).unwrap();
+            expected.insert(value);
+        }
+        h.insert(Bin::new(-1.0, 5)).unwrap();
+        expected.insert(Bin::new(-1.0, 5));
+        assert_eq!(h.histogram(), &expected);
+        assert_eq!(h.records(), 101);
+        drop(h);
+
+        let mut h = LoggedHistogram::open(&dir, 8).unwrap();
+        assert_eq!(h.histogram(), &expected);
+        assert_eq!(h.records(), 101);
+
+        // new records are appended to the replayed ones
+        h.insert(42.0).unwrap();
+        expected.insert(42.0);
+        drop(h);
+        let h = LoggedHistogram::open(&dir, 8).unwrap();
+        assert_eq!(h.histogram(), &expected);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compaction() {
+        let dir = test_dir("compaction");
+
+        let mut h = LoggedHistogram::open(&dir, 8).unwrap();
+        assert_eq!(h.compaction_threshold(), DEFAULT_COMPACTION_THRESHOLD);
+        h.set_compaction_threshold(10);
+        let mut expected = Histogram::new(8);
+        for v in 0..25 {
+            h.insert(v as f64).unwrap();
+            expected.insert(v as f64);
+        }
+        assert_eq!(h.records(), 5);
+        assert_eq!(
+            file_names(&dir),
+            vec![file_name(CHECKPOINT, 2), file_name(LOG, 2)]
+        );
+        drop(h);
+
+        let h = LoggedHistogram::open(&dir, 8).unwrap();
+        assert_eq!(h.histogram(), &expected);
+        assert_eq!(h.records(), 5);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn interrupted_compaction() {
+        let dir = test_dir("interrupted-compaction");
+
+        let mut h = LoggedHistogram::open(&dir, 8).unwrap();
+        h.insert(1.0).unwrap();
+        h.compact().unwrap();
+        h.insert(2.0).unwrap();
+        h.sync().unwrap();
+        let expected = h.histogram().clone();
+
+        // a new checkpoint was saved, but the files of the previous generation were not
+        // removed yet
+        let stale_checkpoint = dir.join(file_name(CHECKPOINT, 1));
+        let stale_log = dir.join(file_name(LOG, 1));
+        h.histogram
+            .save_to(dir.join(file_name(CHECKPOINT, 2)))
+            .unwrap();
+        drop(h);
+        assert!(stale_checkpoint.exists() && stale_log.exists());
+
+        let h = LoggedHistogram::open(&dir, 8).unwrap();
+        assert_eq!(h.histogram(), &expected);
+        assert_eq!(h.records(), 0);
+        assert!(!stale_checkpoint.exists() && !stale_log.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn partially_written_record() {
+        let dir = test_dir("partial");
+
+        let mut h = LoggedHistogram::open(&dir, 8).unwrap();
+        h.insert(1.0).unwrap();
+        h.insert(Bin::new(2.0, 3)).unwrap();
+        drop(h);
+
+        // a crash in the middle of writing the last record
+        let log_path = dir.join(file_name(LOG, 0));
+        let mut bytes = fs::read(&log_path).unwrap();
+        let len = bytes.len();
+        bytes.truncate(len - 3);
+        fs::write(&log_path, &bytes).unwrap();
+
+        let mut h = LoggedHistogram::open(&dir, 8).unwrap();
+        assert_eq!(h.histogram().count(), 1);
+        assert_eq!(h.records(), 1);
+        // the partial record is discarded, so that new records can be replayed
+        h.insert(3.0).unwrap();
+        drop(h);
+        let h = LoggedHistogram::open(&dir, 8).unwrap();
+        assert_eq!(h.histogram().bins(), &[Bin::new(1.0, 1), Bin::new(3.0, 1)]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupted_record() {
+        let mut record = vec![VALUE_RECORD];
+        record.extend_from_slice(&1.0f64.to_le_bytes());
+        record.extend_from_slice(&crc32(&record).to_le_bytes());
+        assert_eq!(parse_record(&record), Some((Bin::new(1.0, 1), 13)));
+
+        let mut corrupted = record.clone();
+        corrupted[3] ^= 1;
+        assert_eq!(parse_record(&corrupted), None);
+
+        let mut unknown = record.clone();
+        unknown[0] = 7;
+        assert_eq!(parse_record(&unknown), None);
+
+        assert_eq!(parse_record(&[]), None);
+    }
+
+    #[test]
+    fn invalid_checkpoint() {
+        let dir = test_dir("invalid-checkpoint");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(file_name(CHECKPOINT, 3)), b"garbage").unwrap();
+
+        assert_eq!(
+            LoggedHistogram::open(&dir, 8).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "compaction threshold must be greater than 0")]
+    fn zero_compaction_threshold() {
+        let dir = test_dir("zero-threshold");
+        let mut h = LoggedHistogram::open(&dir, 8).unwrap();
+        h.set_compaction_threshold(0);
+    }
+}
@@ -0,0 +1,429 @@
+use crate::bin::TotalF64;
+
+/// A bin of a [`WeightedHistogram`]: a value and its (fractional) weight.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct WeightedBin {
+    value: f64,
+    weight: f64,
+}
+
+impl WeightedBin {
+    /// Merge two bins into one. The value of the new bin is the weighted average of values.
+    fn merge(left: &WeightedBin, right: &WeightedBin) -> WeightedBin {
+        let weight = left.weight + right.weight;
+        // the difference of values of opposite signs may overflow, so the weights are
+        // normalized instead. Clamping keeps the result exact when both values are equal
+        let value = left.value * (left.weight / weight) + right.value * (right.weight / weight);
+        WeightedBin {
+            value: value.clamp(left.value.min(right.value), left.value.max(right.value)),
+            weight,
+        }
+    }
+}
+
+/// A variant of [`Histogram`](crate::Histogram), in which every value carries
+/// a fractional weight (e.g. the inverse of the sampling rate of a downsampled
+/// stream) rather than an integer count.
+///
+/// The exact minimum and maximum values are not tracked. Instead, the values of
+/// the outermost bins are used as the boundaries of the distribution.
+///
+/// ```
+/// use bhtt::WeightedHistogram;
+///
+/// let mut h = WeightedHistogram::new(64);
+/// // a value sampled at the rate of 1% stands for 100 values
+/// h.insert_weighted(250.0, 100.0);
+/// h.insert_weighted(10.0, 1.0);
+/// h.insert_weighted(11.0, 1.0);
+///
+/// assert_eq!(h.count(), 102.0);
+/// assert!(h.median().unwrap() > 200.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct WeightedHistogram {
+    size: usize,
+    bins: Vec<WeightedBin>,
+}
+
+impl WeightedHistogram {
+    /// Create a new WeightedHistogram with the given number of bins.
+    ///
+    /// ```
+    /// use bhtt::WeightedHistogram;
+    ///
+    /// let h = WeightedHistogram::new(5);
+    /// assert_eq!(h.size(), 5);
+    /// assert_eq!(h.count(), 0.0);
+    /// ```
+    pub fn new(size: usize) -> WeightedHistogram {
+        assert!(size > 0, "histogram size must be greater than 0");
+
+        WeightedHistogram {
+            size,
+            bins: Vec::with_capacity(size + 1),
+        }
+    }
+
+    /// Returns the size of the histogram.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the total weight of values in the histogram.
+    pub fn count(&self) -> f64 {
+        self.bins.iter().map(|bin| bin.weight).sum()
+    }
+
+    /// Returns the value of the leftmost bin or `None` if the histogram is empty.
+    pub fn min(&self) -> Option<f64> {
+        self.bins.first().map(|bin| bin.value)
+    }
+
+    /// Returns the value of the rightmost bin or `None` if the histogram is empty.
+    pub fn max(&self) -> Option<f64> {
+        self.bins.last().map(|bin| bin.value)
+    }
+
+    /// Returns the weighted mean of values or `None` if the histogram is empty.
+    ///
+    /// ```
+    /// use bhtt::WeightedHistogram;
+    ///
+    /// let mut h = WeightedHistogram::new(5);
+    /// assert_eq!(h.mean(), None);
+    ///
+    /// h.insert_weighted(1.0, 0.5);
+    /// h.insert_weighted(4.0, 1.0);
+    /// assert_eq!(h.mean(), Some(3.0));
+    /// ```
+    pub fn mean(&self) -> Option<f64> {
+        match self.count() {
+            total if total > 0.0 => Some(
+                self.bins
+                    .iter()
+                    .map(|bin| bin.value * bin.weight)
+                    .sum::<f64>()
+                    / total,
+            ),
+            _ => None,
+        }
+    }
+
+    /// Returns an approximated value of the `q`'th quantile of the values or `None`
+    /// if the histogram is empty. `q` must be in the range [0.0; 1.0], or the function
+    /// will panic.
+    ///
+    /// ```
+    /// use bhtt::WeightedHistogram;
+    ///
+    /// let mut h = WeightedHistogram::new(5);
+    /// for value in vec![1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2] {
+    ///     h.insert(value);
+    /// }
+    ///
+    /// assert_eq!(h.quantile(0.0), Some(-5.4));
+    /// assert_eq!(h.quantile(0.5), Some(4.75));
+    /// // the value of the rightmost bin rather than the exact maximum
+    /// assert_eq!(h.quantile(1.0), Some(8.725));
+    /// ```
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        assert!(
+            (0.0..=1.0).contains(&q),
+            "q must be in the range [0.0; 1.0]"
+        );
+
+        if self.bins.is_empty() {
+            return None;
+        } else if q == 0.0 {
+            return self.min();
+        } else if q == 1.0 {
+            return self.max();
+        }
+
+        // Algorithm 4: Uniform procedure (see Histogram::quantile()), where the outermost
+        // bins are used in place of the exact minimum and maximum values
+        let qth_weight = self.count() * q;
+        let mut i = 0;
+        let mut up_to_qth_weight = 0.0;
+        while i <= self.bins.len() {
+            let (left, right) = self.get_bordering_bins(i);
+            let next = up_to_qth_weight + (left.weight + right.weight) / 2.0;
+            if qth_weight <= next {
+                break;
+            }
+            up_to_qth_weight = next;
+            i += 1;
+        }
+
+        let (left, right) = self.get_bordering_bins(i.min(self.bins.len()));
+        let d = qth_weight - up_to_qth_weight;
+        let a = right.weight - left.weight;
+        let z = if a == 0.0 {
+            if left.weight == 0.0 {
+                0.0
+            } else {
+                d / left.weight
+            }
+        } else {
+            let b = 2.0 * left.weight;
+            let c = -2.0 * d;
+            (-b + (b.powi(2) - 4.0 * a * c).max(0.0).sqrt()) / (2.0 * a)
+        };
+
+        Some(left.value + (right.value - left.value) * z.clamp(0.0, 1.0))
+    }
+
+    /// Returns an approximated value of the median or `None` if the histogram is empty.
+    pub fn median(&self) -> Option<f64> {
+        self.quantile(0.5)
+    }
+
+    /// Insert a new value with the weight of 1.0 into the histogram.
+    pub fn insert(&mut self, value: f64) {
+        self.insert_weighted(value, 1.0);
+    }
+
+    /// Insert a new value with the given weight into the histogram. `value` must be
+    /// finite, and `weight` must be finite and greater than zero, or the function
+    /// will panic.
+    ///
+    /// ```
+    /// use bhtt::WeightedHistogram;
+    ///
+    /// let mut h = WeightedHistogram::new(5);
+    /// h.insert_weighted(1.0, 0.25);
+    /// h.insert_weighted(2.0, 2.5);
+    /// assert_eq!(h.count(), 2.75);
+    /// ```
+    pub fn insert_weighted(&mut self, value: f64, weight: f64) {
+        assert!(!value.is_nan(), "value must not be NaN");
+        assert!(value.is_finite(), "value must be finite");
+        assert!(
+            weight.is_finite() && weight > 0.0,
+            "weight must be finite and greater than zero"
+        );
+
+        let pos = self.bins.partition_point(|bin| bin.value <= value);
+        self.bins.insert(pos, WeightedBin { value, weight });
+        self.shrink();
+    }
+
+    /// Merge another histogram into this one.
+    ///
+    /// ```
+    /// use bhtt::WeightedHistogram;
+    ///
+    /// let mut h1 = WeightedHistogram::new(5);
+    /// h1.insert_weighted(1.0, 0.5);
+    ///
+    /// let mut h2 = WeightedHistogram::new(5);
+    /// h2.insert_weighted(2.0, 1.5);
+    ///
+    /// h1.merge(&h2);
+    /// assert_eq!(h1.count(), 2.0);
+    /// ```
+    pub fn merge(&mut self, other: &WeightedHistogram) {
+        for bin in other.bins.iter() {
+            let pos = self.bins.partition_point(|b| b.value <= bin.value);
+            self.bins.insert(pos, *bin);
+        }
+        self.shrink();
+    }
+
+    /// Multiply the weights of all bins by `factor`, which must be in the range
+    /// (0.0; 1.0]. Bins, whose weights underflow to zero, are removed.
+    pub(crate) fn scale(&mut self, factor: f64) {
+        for bin in self.bins.iter_mut() {
+            bin.weight *= factor;
+        }
+
+        self.bins.retain(|bin| bin.weight > 0.0);
+    }
+
+    /// Remove all bins from the histogram.
+    pub(crate) fn clear(&mut self) {
+        self.bins.clear();
+    }
+
+    /// Merge two closest bins until the histogram shrinks back to the fixed size.
+    fn shrink(&mut self) {
+        while self.bins.len() > self.size {
+            let right = (1..self.bins.len())
+                .min_by_key(|&i| {
+                    (
+                        TotalF64(self.bins[i].value - self.bins[i - 1].value),
+                        TotalF64(self.bins[i - 1].weight + self.bins[i].weight),
+                    )
+                })
+                .unwrap();
+
+            self.bins[right - 1] = WeightedBin::merge(&self.bins[right - 1], &self.bins[right]);
+            self.bins.remove(right);
+        }
+    }
+
+    fn get_bordering_bins(&self, i: usize) -> (WeightedBin, WeightedBin) {
+        let empty = |value| WeightedBin { value, weight: 0.0 };
+        if i == 0 {
+            (empty(self.bins[0].value), self.bins[0])
+        } else if i == self.bins.len() {
+            (self.bins[i - 1], empty(self.bins[i - 1].value))
+        } else {
+            (self.bins[i - 1], self.bins[i])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new() {
+        let h = WeightedHistogram::new(5);
+        assert_eq!(h.size(), 5);
+        assert_eq!(h.count(), 0.0);
+        assert_eq!(h.min(), None);
+        assert_eq!(h.max(), None);
+        assert_eq!(h.mean(), None);
+        assert_eq!(h.quantile(0.5), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram size must be greater than 0")]
+    fn new_invalid_size() {
+        WeightedHistogram::new(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "value must not be NaN")]
+    fn insert_nan() {
+        WeightedHistogram::new(5).insert(f64::NAN);
+    }
+
+    #[test]
+    #[should_panic(expected = "weight must be finite and greater than zero")]
+    fn insert_weighted_zero() {
+        WeightedHistogram::new(5).insert_weighted(1.0, 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "weight must be finite and greater than zero")]
+    fn insert_weighted_infinite() {
+        WeightedHistogram::new(5).insert_weighted(1.0, f64::INFINITY);
+    }
+
+    #[test]
+    fn insert_matches_histogram() {
+        let values = [1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2];
+        let mut h = WeightedHistogram::new(5);
+        for value in values {
+            h.insert(value);
+        }
+
+        let expected = crate::Histogram::from_iter(5, values);
+        assert_eq!(h.bins.len(), expected.bins().len());
+        for (bin, expected) in h.bins.iter().zip(expected.bins()) {
+            assert_relative_eq!(bin.value, expected.value());
+            assert_relative_eq!(bin.weight, expected.count() as f64);
+        }
+        assert_eq!(h.count(), 10.0);
+    }
+
+    #[test]
+    fn insert_weighted() {
+        let mut h = WeightedHistogram::new(3);
+        h.insert_weighted(1.0, 0.5);
+        h.insert_weighted(2.0, 1.5);
+        h.insert_weighted(10.0, 3.0);
+        h.insert_weighted(11.0, 1.0);
+
+        // the pair of bins with the smaller total weight is merged first
+        assert_eq!(
+            h.bins,
+            vec![
+                WeightedBin {
+                    value: 1.75,
+                    weight: 2.0
+                },
+                WeightedBin {
+                    value: 10.0,
+                    weight: 3.0
+                },
+                WeightedBin {
+                    value: 11.0,
+                    weight: 1.0
+                },
+            ]
+        );
+        assert_eq!(h.count(), 6.0);
+        assert_eq!(h.min(), Some(1.75));
+        assert_eq!(h.max(), Some(11.0));
+    }
+
+    #[test]
+    fn merge() {
+        let mut h1 = WeightedHistogram::new(2);
+        h1.insert_weighted(1.0, 1.0);
+        h1.insert_weighted(3.0, 1.0);
+
+        let mut h2 = WeightedHistogram::new(2);
+        h2.insert_weighted(2.0, 2.0);
+        h2.insert_weighted(100.0, 0.5);
+
+        h1.merge(&h2);
+        assert_eq!(h1.bins.len(), 2);
+        assert_eq!(h1.count(), 4.5);
+        assert_relative_eq!(h1.bins[0].value, 2.0);
+        assert_relative_eq!(h1.bins[1].value, 100.0);
+    }
+
+    #[test]
+    fn merge_bins() {
+        let bin = |value, weight| WeightedBin { value, weight };
+
+        let merged = WeightedBin::merge(&bin(1.0, 1.0), &bin(4.0, 2.0));
+        assert_eq!(merged, bin(3.0, 3.0));
+
+        // equal values are preserved exactly
+        let merged = WeightedBin::merge(&bin(0.1, 0.3), &bin(0.1, 0.7));
+        assert_eq!(merged.value, 0.1);
+
+        // the difference of values overflows
+        let merged = WeightedBin::merge(&bin(-f64::MAX, 1.0), &bin(f64::MAX, 1.0));
+        assert_eq!(merged, bin(0.0, 2.0));
+        let merged = WeightedBin::merge(&bin(-f64::MAX, 1.0), &bin(f64::MAX, 3.0));
+        assert!(merged.value.is_finite() && merged.value > 0.0);
+    }
+
+    #[test]
+    fn quantile_weights() {
+        let mut h = WeightedHistogram::new(16);
+        h.insert_weighted(10.0, 1.0);
+        h.insert_weighted(20.0, 98.0);
+        h.insert_weighted(30.0, 1.0);
+
+        assert_relative_eq!(h.median().unwrap(), 20.0);
+        assert!(h.quantile(0.1).unwrap() > 10.0);
+        assert!(h.quantile(0.9).unwrap() < 30.0);
+    }
+
+    #[test]
+    fn quantile_monotonic() {
+        let mut h = WeightedHistogram::new(5);
+        for (i, value) in [1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2]
+            .iter()
+            .enumerate()
+        {
+            h.insert_weighted(*value, 0.1 * (i + 1) as f64);
+        }
+
+        let mut previous = f64::NEG_INFINITY;
+        for i in 0..=100 {
+            let value = h.quantile(i as f64 / 100.0).unwrap();
+            assert!(value >= previous);
+            previous = value;
+        }
+    }
+}
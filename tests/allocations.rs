@@ -0,0 +1,147 @@
+// Heap allocations made by histograms are counted by a global allocator, so that the memory
+// footprint of histograms (see Histogram::memory_usage()) and the absence of allocations on
+// the hot path of updates are checked for regressions.
+//
+// A report of allocations per insert and bytes per histogram across sizes is printed with
+// `cargo test --test allocations -- --nocapture`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::mem::size_of;
+
+use bhtt::Histogram;
+use utilities::pseudo_random_values;
+
+const HISTOGRAM_SIZES: [usize; 8] = [1, 8, 16, 32, 64, 128, 256, 1024];
+
+/// The number of allocations and the number of bytes allocated (minus the number of bytes
+/// deallocated) by the current thread.
+#[derive(Debug, Clone, Copy)]
+struct Stats {
+    allocations: usize,
+    bytes: isize,
+}
+
+thread_local! {
+    // tests run in parallel, so allocations are counted per thread
+    static STATS: Cell<Stats> = const { Cell::new(Stats { allocations: 0, bytes: 0 }) };
+}
+
+fn record(allocations: usize, bytes: isize) {
+    // the thread local might have been destroyed already, if the thread is exiting
+    let _ = STATS.try_with(|stats| {
+        let Stats {
+            allocations: a,
+            bytes: b,
+        } = stats.get();
+        stats.set(Stats {
+            allocations: a + allocations,
+            bytes: b + bytes,
+        });
+    });
+}
+
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record(1, layout.size() as isize);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        record(0, -(layout.size() as isize));
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record(1, new_size as isize - layout.size() as isize);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the result of `f` and the allocations made by it. Memory held by the result is
+/// included in the number of bytes, and temporary allocations are not.
+fn measure<T>(f: impl FnOnce() -> T) -> (T, Stats) {
+    let before = STATS.with(Cell::get);
+    let result = f();
+    let after = STATS.with(Cell::get);
+
+    let stats = Stats {
+        allocations: after.allocations - before.allocations,
+        bytes: after.bytes - before.bytes,
+    };
+    (result, stats)
+}
+
+/// Returns the number of bytes of heap memory used by the histogram.
+fn heap_usage(h: &Histogram) -> isize {
+    (h.memory_usage() - size_of::<Histogram>()) as isize
+}
+
+#[test]
+fn new() {
+    for size in HISTOGRAM_SIZES {
+        let (h, stats) = measure(|| Histogram::new(size));
+
+        // a single allocation of size + 1 bins (unless they are stored inline)
+        assert!(stats.allocations <= 1, "size = {}: {:?}", size, stats);
+        assert_eq!(stats.bytes, heap_usage(&h), "size = {}", size);
+        #[cfg(not(feature = "smallvec"))]
+        assert_eq!(stats.bytes, ((size + 1) * size_of::<bhtt::Bin>()) as isize);
+    }
+}
+
+#[test]
+fn insert() {
+    println!("size  allocations/insert  bytes/histogram");
+    for size in HISTOGRAM_SIZES {
+        let values = pseudo_random_values(size as u64, 20_000);
+        let (mut h, stats) = measure(|| {
+            let mut h = Histogram::new(size);
+            h.extend(&values[..10_000]);
+            h
+        });
+        assert_eq!(stats.bytes, heap_usage(&h), "size = {}", size);
+
+        // once the histogram is full, updates don't allocate memory
+        let (_, stats) = measure(|| h.extend(&values[10_000..]));
+        println!(
+            "{:>4}  {:>18}  {:>15}",
+            size,
+            stats.allocations as f64 / 10_000.0,
+            h.memory_usage()
+        );
+        assert_eq!(stats.allocations, 0, "size = {}", size);
+        assert_eq!(stats.bytes, 0, "size = {}", size);
+        assert_eq!(h.bins().len(), size);
+    }
+}
+
+#[test]
+fn merge() {
+    for size in HISTOGRAM_SIZES {
+        let others: Vec<Histogram> = (0..10)
+            .map(|seed| Histogram::from_iter(size, pseudo_random_values(seed, 1000)))
+            .collect();
+
+        // bins of the other histogram are buffered in place, so the storage of bins may grow
+        // beyond size + 1 bins, but it is reused by subsequent merges
+        let mut h = Histogram::new(size);
+        for other in &others {
+            h.merge(other);
+        }
+        let (_, stats) = measure(|| {
+            for other in &others {
+                h.merge(other);
+            }
+        });
+
+        // only temporary buffers are allocated to shrink the histogram
+        assert_eq!(stats.bytes, 0, "size = {}", size);
+        assert!(stats.allocations <= 10 * others.len(), "size = {}", size);
+    }
+}
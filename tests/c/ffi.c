@@ -0,0 +1,54 @@
+/*
+ * Checks that the C API can be used from C code: the program is compiled against
+ * include/bhtt.h and linked with the library built with the `ffi` feature, e.g.
+ *
+ *   cargo build --features ffi
+ *   cc -Wall -Werror -Iinclude tests/c/ffi.c target/debug/libbhtt.a -lpthread -ldl -lm
+ */
+#include <stdio.h>
+
+#include "bhtt.h"
+
+#define CHECK(cond)                                                     \
+  do {                                                                  \
+    if (!(cond)) {                                                      \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+      return 1;                                                         \
+    }                                                                   \
+  } while (0)
+
+int main(void) {
+  BhttHistogram *h = bhtt_histogram_new(5);
+  CHECK(h != NULL);
+  CHECK(bhtt_histogram_new(0) == NULL);
+
+  const double values[] = {1.0, 0.0, -5.4, -2.1, 8.5, 10.0, 8.6, 4.3, 7.8, 5.2};
+  for (size_t i = 0; i < sizeof(values) / sizeof(values[0]); i++) {
+    CHECK(bhtt_histogram_insert(h, values[i], 1) == BHTT_STATUS_OK);
+  }
+  CHECK(bhtt_histogram_insert(h, 42.0, 0) == BHTT_STATUS_INVALID_ARGUMENT);
+  CHECK(bhtt_histogram_count(h) == 10);
+
+  double value = 0.0;
+  CHECK(bhtt_histogram_quantile(h, 0.0, &value) == BHTT_STATUS_OK);
+  CHECK(value == -5.4);
+  CHECK(bhtt_histogram_quantile(h, 1.0, &value) == BHTT_STATUS_OK);
+  CHECK(value == 10.0);
+
+  size_t len = 0;
+  CHECK(bhtt_histogram_to_bytes(h, NULL, 0, &len) == BHTT_STATUS_BUFFER_TOO_SMALL);
+  uint8_t buf[1024];
+  CHECK(len <= sizeof(buf));
+  CHECK(bhtt_histogram_to_bytes(h, buf, sizeof(buf), &len) == BHTT_STATUS_OK);
+
+  BhttHistogram *decoded = bhtt_histogram_from_bytes(buf, len);
+  CHECK(decoded != NULL);
+  CHECK(bhtt_histogram_merge(h, decoded) == BHTT_STATUS_OK);
+  CHECK(bhtt_histogram_count(h) == 20);
+
+  bhtt_histogram_free(decoded);
+  bhtt_histogram_free(h);
+
+  printf("ok\n");
+  return 0;
+}
@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
 
 use ordered_float::NotNan;
 use statrs::statistics::OrderStatistics;
@@ -9,34 +9,135 @@ const QUANTILES: [f64; 14] = [
     0.0, 0.05, 0.1, 0.2, 0.25, 0.3, 0.4, 0.5, 0.7, 0.75, 0.9, 0.95, 0.99, 1.0,
 ];
 
+/// A column of a delimited (e.g. CSV) file: either its zero-based index or its name in
+/// the header.
+#[derive(Debug, Clone, Copy)]
+pub enum Column<'a> {
+    Index(usize),
+    Name(&'a str),
+}
+
 pub struct Dataset {
     values: Vec<f64>,
     quantiles: BTreeMap<NotNan<f64>, f64>,
 }
 
 impl Dataset {
-    pub fn from_file(filename: &str) -> std::io::Result<Dataset> {
+    /// Read values from a file (see [`Dataset::from_reader`]).
+    pub fn from_file(filename: &str) -> io::Result<Dataset> {
         let file = File::open(filename)?;
 
-        let values: Vec<f64> = BufReader::new(file)
+        Dataset::from_reader(BufReader::new(file))
+    }
+
+    /// Read values from stdin (see [`Dataset::from_reader`]).
+    pub fn from_stdin() -> io::Result<Dataset> {
+        Dataset::from_reader(io::stdin().lock())
+    }
+
+    /// Read values from any source, one value per line. Empty lines and lines starting
+    /// with # are skipped. Values, which can't be parsed, are reported as
+    /// `io::ErrorKind::InvalidData` errors.
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<Dataset> {
+        let mut values = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            values.push(parse(i + 1, line)?);
+        }
+
+        Ok(Dataset::from_values(values))
+    }
+
+    /// Read values from a column of a delimited file (see [`Dataset::from_csv_reader`]).
+    pub fn from_csv_file(filename: &str, delimiter: char, column: Column) -> io::Result<Dataset> {
+        let file = File::open(filename)?;
+
+        Dataset::from_csv_reader(BufReader::new(file), delimiter, column)
+    }
+
+    /// Read values from a column of a delimited file (e.g. a CSV export of request
+    /// latencies). Empty lines and lines starting with # are skipped.
+    ///
+    /// A column selected by name is looked up in the header (the first line). A column
+    /// selected by index is read from all lines, except for the first one, if it is not a
+    /// number (i.e. a header). Fields may be enclosed in double quotes, but delimiters within
+    /// quoted fields are not supported. Empty fields (missing values) are skipped.
+    pub fn from_csv_reader<R: BufRead>(
+        reader: R,
+        delimiter: char,
+        column: Column,
+    ) -> io::Result<Dataset> {
+        let mut lines = reader
             .lines()
-            .map(|line| line.unwrap())
-            .filter(|line| !line.starts_with("#"))
-            .map(|line| line.parse::<f64>().unwrap())
-            .collect();
+            .enumerate()
+            .map(|(i, line)| line.map(|line| (i + 1, line)))
+            .filter(|line| match line {
+                Ok((_, line)) => !line.trim().is_empty() && !line.starts_with('#'),
+                Err(_) => true,
+            })
+            .peekable();
+
+        let index = match column {
+            Column::Index(index) => {
+                if let Some(Ok((_, header))) = lines.peek() {
+                    let is_header = field(header, delimiter, index)
+                        .is_none_or(|field| field.parse::<f64>().is_err());
+                    if is_header {
+                        lines.next();
+                    }
+                }
+                index
+            }
+            Column::Name(name) => {
+                let header = match lines.next() {
+                    Some(line) => line?.1,
+                    None => return Ok(Dataset::from_values(Vec::new())),
+                };
+                header
+                    .split(delimiter)
+                    .position(|field| unquote(field) == name)
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("column {:?} is not found in the header", name),
+                        )
+                    })?
+            }
+        };
+
+        let mut values = Vec::new();
+        for line in lines {
+            let (i, line) = line?;
+            let value = field(&line, delimiter, index).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: column {} is missing", i, index),
+                )
+            })?;
+            if !value.is_empty() {
+                values.push(parse(i, value)?);
+            }
+        }
+
+        Ok(Dataset::from_values(values))
+    }
 
+    /// Create a dataset from values generated in memory.
+    pub fn from_values(values: Vec<f64>) -> Dataset {
         // quantile() sorts the vector internally, so we create a copy to
         // preserve the original order of values
         let mut values_copy = values.clone();
         let quantiles = QUANTILES
             .iter()
-            .map(|q| (NotNan::new(*q).unwrap(), *(&mut values_copy.quantile(*q))))
+            .map(|q| (NotNan::new(*q).unwrap(), values_copy.quantile(*q)))
             .collect();
 
-        Ok(Dataset {
-            values: values,
-            quantiles: quantiles,
-        })
+        Dataset { values, quantiles }
     }
 
     pub fn values(&self) -> &[f64] {
@@ -47,3 +148,130 @@ impl Dataset {
         &self.quantiles
     }
 }
+
+/// Returns `n` pseudo-random values in the range [0.0; 1000.0). The values are produced by
+/// a simple deterministic generator (a 64-bit LCG), so that tests are reproducible. Different
+/// seeds produce different sequences of values.
+pub fn pseudo_random_values(seed: u64, n: usize) -> Vec<f64> {
+    let mut state = seed;
+    (0..n)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 11) as f64 / (1u64 << 53) as f64 * 1000.0
+        })
+        .collect()
+}
+
+/// Same as [`pseudo_random_values`], but the values are rounded down to integers, so that
+/// many of them are repeated.
+pub fn pseudo_random_integers(seed: u64, n: usize) -> Vec<f64> {
+    pseudo_random_values(seed, n)
+        .into_iter()
+        .map(f64::floor)
+        .collect()
+}
+
+/// Returns the field of a delimited line at the given index (without quotes).
+fn field(line: &str, delimiter: char, index: usize) -> Option<&str> {
+    line.split(delimiter).nth(index).map(unquote)
+}
+
+fn unquote(field: &str) -> &str {
+    let field = field.trim();
+    field
+        .strip_prefix('"')
+        .and_then(|field| field.strip_suffix('"'))
+        .unwrap_or(field)
+}
+
+/// Parse a value at the given line (for error reporting).
+fn parse(line_number: usize, value: &str) -> io::Result<f64> {
+    value.parse::<f64>().map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("line {}: {}: {:?}", line_number, err, value),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pseudo_random() {
+        let values = pseudo_random_values(42, 1000);
+        assert_eq!(values.len(), 1000);
+        assert!(values.iter().all(|&v| (0.0..1000.0).contains(&v)));
+        assert_eq!(values, pseudo_random_values(42, 1000));
+        assert_ne!(values, pseudo_random_values(43, 1000));
+
+        let integers = pseudo_random_integers(42, 1000);
+        assert!(integers.iter().zip(&values).all(|(i, v)| *i == v.floor()));
+    }
+
+    #[test]
+    fn from_reader() {
+        let input = "# ping times\n1.5\n\n 2.5 \n3\n";
+        let dataset = Dataset::from_reader(input.as_bytes()).unwrap();
+        assert_eq!(dataset.values(), &[1.5, 2.5, 3.0]);
+        assert_eq!(dataset.quantiles()[&NotNan::new(0.0).unwrap()], 1.5);
+        assert_eq!(dataset.quantiles()[&NotNan::new(1.0).unwrap()], 3.0);
+    }
+
+    #[test]
+    fn from_reader_invalid() {
+        let err = Dataset::from_reader("1.0\nabc\n".as_bytes()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("line 2: "), "{}", err);
+    }
+
+    #[test]
+    fn from_file() {
+        let dataset = Dataset::from_file("testdata/pings.txt").unwrap();
+        assert_eq!(dataset.values().len(), 10000);
+    }
+
+    #[test]
+    fn from_csv_reader() {
+        let input = "timestamp,endpoint,\"latency_ms\"\n\
+                     1700000000,/api,12.5\n\
+                     1700000001,/health,\"3\"\n\
+                     \n\
+                     1700000002,/api,\n\
+                     1700000003,/api,7.25\n";
+
+        let dataset =
+            Dataset::from_csv_reader(input.as_bytes(), ',', Column::Name("latency_ms")).unwrap();
+        assert_eq!(dataset.values(), &[12.5, 3.0, 7.25]);
+
+        // the header is skipped, as it's not a number
+        let dataset = Dataset::from_csv_reader(input.as_bytes(), ',', Column::Index(2)).unwrap();
+        assert_eq!(dataset.values(), &[12.5, 3.0, 7.25]);
+
+        // there is no header
+        let input = "1.5\t2\n3.5\t4\n";
+        let dataset = Dataset::from_csv_reader(input.as_bytes(), '\t', Column::Index(1)).unwrap();
+        assert_eq!(dataset.values(), &[2.0, 4.0]);
+    }
+
+    #[test]
+    fn from_csv_reader_invalid() {
+        let input = "a,b\n1,2\n3\n";
+        let error = |column| {
+            Dataset::from_csv_reader(input.as_bytes(), ',', column)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(
+            error(Column::Name("c")),
+            "column \"c\" is not found in the header"
+        );
+        assert_eq!(error(Column::Name("b")), "line 3: column 1 is missing");
+        assert!(Dataset::from_csv_reader("a\n1\nx\n".as_bytes(), ',', Column::Index(0)).is_err());
+    }
+}